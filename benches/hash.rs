@@ -0,0 +1,59 @@
+//! Manifest hashing by number of threads. Every parallel manifest is checked
+//! against the serial one before it is measured. Run with
+//! `cargo bench --bench hash`, set `FDIR_BENCH_DIR` to measure another disk
+//! than the temporary directory.
+
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fdir::{Action, Algorithm, DirectoryInfo};
+
+const KIB: usize = 1024;
+const MIB: usize = 1024 * KIB;
+
+/// A few large files and many small ones, spread over nested directories
+fn tree() -> (DirectoryInfo, PathBuf, u64) {
+    let root = std::env::var_os("FDIR_BENCH_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("fdir_bench_hash");
+    let _ = std::fs::remove_dir_all(&root);
+    let mut total = 0;
+    for i in 0..200 {
+        let len = if i % 50 == 0 { 8 * MIB } else { 32 * KIB };
+        let dir = root.join(format!("tree/{}/{}", i % 7, i % 3));
+        std::fs::create_dir_all(&dir).unwrap();
+        let content: Vec<u8> = (0..len).map(|byte| ((byte + i) % 251) as u8).collect();
+        std::fs::write(dir.join(format!("file{}", i)), content).unwrap();
+        total += len as u64;
+    }
+    let dir = DirectoryInfo::open(root.join("tree")).unwrap();
+    (dir, root, total)
+}
+
+fn manifest(c: &mut Criterion) {
+    let (dir, root, total) = tree();
+    let serial = root.join("serial.sha256");
+    dir.write_manifest(&serial, Algorithm::Sha256).unwrap();
+    let serial = std::fs::read(serial).unwrap();
+
+    let mut group = c.benchmark_group("manifest");
+    group.throughput(Throughput::Bytes(total));
+    group.sample_size(10);
+    for threads in [1, 2, 4, 8] {
+        let dest = root.join(format!("parallel{}.sha256", threads));
+        dir.write_manifest_parallel(&dest, Algorithm::Sha256, threads)
+            .unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), serial, "{} threads", threads);
+        group.bench_function(BenchmarkId::from_parameter(threads), |b| {
+            b.iter(|| {
+                dir.write_manifest_parallel(&dest, Algorithm::Sha256, threads)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, manifest);
+criterion_main!(benches);
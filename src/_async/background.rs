@@ -0,0 +1,305 @@
+use std::future::Future;
+use std::io::Error;
+use std::panic::resume_unwind;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use tokio::{io::Result, task::JoinHandle};
+
+use crate::error::cancelled;
+use crate::sync::background::{EventSender, ProgressSnapshot, Tracker};
+use crate::{CopyReport, Options, ProgressEvent, ProgressEvents};
+
+use super::scheduler::{Job, JobRef};
+use super::{dir::AsyncDirectoryInfo, file::AsyncFileInfo, AsyncInfo};
+
+/// An operation running as its own task, see `ProgressHandle`. The
+/// `_background` methods must be called from within a Tokio runtime.
+///
+/// Dropping the handle detaches the task, the operation still completes.
+#[derive(Debug)]
+pub struct AsyncProgressHandle {
+    tracker: Arc<Tracker>,
+    task: JoinHandle<Result<CopyReport>>,
+    /// Set when queued by a `CopyScheduler`
+    job: Option<JobRef>,
+}
+
+impl AsyncProgressHandle {
+    /// Spawn `op` after `totals` gave the number of files and bytes to
+    /// write, `op` runs with options attached to `tracker`. A `job` waits
+    /// for its turn first and leaves its scheduler once over.
+    fn spawn<T, O>(
+        tracker: Arc<Tracker>,
+        source: PathBuf,
+        job: Option<Job>,
+        totals: T,
+        op: O,
+    ) -> Self
+    where
+        T: Future<Output = Result<(u64, u64)>> + Send + 'static,
+        O: Future<Output = Result<CopyReport>> + Send + 'static,
+    {
+        let this = Arc::clone(&tracker);
+        let job_ref = job.as_ref().map(Job::to_ref);
+        let task = tokio::spawn(async move {
+            let result = async {
+                if let Some(job) = &job {
+                    if !job.started().await {
+                        return Err(cancelled());
+                    }
+                }
+                let (files, bytes) = totals.await?;
+                this.start(files, bytes);
+                op.await
+            }
+            .await;
+            drop(job);
+            this.finish(&source, &result);
+            result
+        });
+        AsyncProgressHandle {
+            tracker,
+            task,
+            job: job_ref,
+        }
+    }
+
+    /// See `ProgressHandle::snapshot`
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        self.tracker.snapshot()
+    }
+
+    /// See `ProgressHandle::cancel`, a queued job is not started
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::_async::{dir::AsyncDirectoryInfo, file::AsyncFileInfo, AsyncAction};
+    /// use fdir::{Options, PauseToken};
+    /// tokio::runtime::Runtime::new().unwrap().block_on(async {
+    ///     let root = std::env::temp_dir().join("fdir_async_cancel_paused");
+    ///     let _ = std::fs::remove_dir_all(&root);
+    ///     AsyncFileInfo::create(root.join("src/a.txt")).await.unwrap();
+    ///     let pause = PauseToken::new();
+    ///     pause.pause();
+    ///     let src = AsyncDirectoryInfo::open(root.join("src")).await.unwrap();
+    ///     let options = Options::new().pause(pause.clone());
+    ///     let handle = src.copy_new_background(root.join("dest"), options);
+    ///     tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    ///     handle.cancel();
+    ///     let error = handle.join().await.unwrap_err();
+    ///     assert_eq!(error.kind(), std::io::ErrorKind::Other);
+    ///     assert!(pause.is_paused());
+    ///     assert!(!root.join("dest/a.txt").exists());
+    ///     std::fs::remove_dir_all(root).unwrap();
+    /// });
+    /// ```
+    pub fn cancel(&self) {
+        self.tracker.cancel();
+        if let Some(job) = &self.job {
+            job.cancel();
+        }
+    }
+
+    /// Wait for the end of the operation. A panic of the task is resumed,
+    /// a task aborted by the shutdown of the runtime is an error.
+    pub async fn join(self) -> Result<CopyReport> {
+        match self.task.await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => resume_unwind(e.into_panic()),
+            Err(e) => Err(Error::other(e)),
+        }
+    }
+}
+
+impl AsyncDirectoryInfo {
+    /// See `DirectoryInfo::copy_new_background`
+    pub fn copy_new_background<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: Options,
+    ) -> AsyncProgressHandle {
+        self.copy_new_queued(path, options, None)
+    }
+
+    /// [`AsyncDirectoryInfo::copy_new_background`] waiting for `job` to
+    /// start
+    pub(crate) fn copy_new_queued<P: AsRef<Path>>(
+        &self,
+        path: P,
+        mut options: Options,
+        job: Option<Job>,
+    ) -> AsyncProgressHandle {
+        let tracker = Tracker::attach(&mut options);
+        let (dir, source) = (self.clone(), self.clone());
+        let path = path.as_ref().to_path_buf();
+        AsyncProgressHandle::spawn(
+            tracker,
+            self.to_path_buf(),
+            job,
+            async move { dir.stats().await.map(|stats| (stats.files, stats.bytes.0)) },
+            async move { source.copy_new_with(path, &options).await },
+        )
+    }
+
+    /// See `DirectoryInfo::copy_new_events`. Nothing is copied until the
+    /// returned future is polled, poll it along with the events, e.g. with
+    /// `tokio::join!` or by spawning it.
+    pub fn copy_new_events<P: AsRef<Path>>(
+        &self,
+        path: P,
+        mut options: Options,
+    ) -> (
+        impl Future<Output = Result<CopyReport>> + Send + 'static,
+        ProgressEvents,
+    ) {
+        let (sender, events) = EventSender::attach(&mut options);
+        let source = self.clone();
+        let path = path.as_ref().to_path_buf();
+        let copy = async move {
+            let stats = source.stats().await?;
+            sender.send(ProgressEvent::Started {
+                files: stats.files,
+                bytes: stats.bytes,
+            });
+            drop(sender);
+            source.copy_new_with(path, &options).await
+        };
+        (copy, events)
+    }
+
+    /// See `DirectoryInfo::move_new_background`
+    pub fn move_new_background<P: AsRef<Path>>(
+        self,
+        path: P,
+        mut options: Options,
+    ) -> AsyncProgressHandle {
+        let tracker = Tracker::attach(&mut options);
+        let (dir, mut source) = (self.clone(), self);
+        let path = path.as_ref().to_path_buf();
+        AsyncProgressHandle::spawn(
+            tracker,
+            source.to_path_buf(),
+            None,
+            async move { dir.stats().await.map(|stats| (stats.files, stats.bytes.0)) },
+            async move { source.move_new_with(path, &options).await },
+        )
+    }
+
+    /// See [`AsyncFileInfo::move_new_detached`]
+    pub fn move_new_detached<P: AsRef<Path>>(
+        self,
+        path: P,
+        options: Options,
+    ) -> JoinHandle<Result<(AsyncDirectoryInfo, CopyReport)>> {
+        let path = path.as_ref().to_path_buf();
+        let mut source = self;
+        tokio::spawn(async move {
+            let report = source.move_new_with(path, &options).await?;
+            Ok((source, report))
+        })
+    }
+}
+
+impl AsyncFileInfo {
+    /// See `FileInfo::copy_new_background`
+    pub fn copy_new_background<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: Options,
+    ) -> AsyncProgressHandle {
+        self.copy_new_queued(path, options, None)
+    }
+
+    /// [`AsyncFileInfo::copy_new_background`] waiting for `job` to start
+    pub(crate) fn copy_new_queued<P: AsRef<Path>>(
+        &self,
+        path: P,
+        mut options: Options,
+        job: Option<Job>,
+    ) -> AsyncProgressHandle {
+        let tracker = Tracker::attach(&mut options);
+        let (file, source) = (self.clone(), self.clone());
+        let path = path.as_ref().to_path_buf();
+        AsyncProgressHandle::spawn(
+            tracker,
+            self.to_path_buf(),
+            job,
+            async move { file_totals(&file).await },
+            async move { source.copy_new_with(path, &options).await },
+        )
+    }
+
+    /// See `FileInfo::move_new_background`
+    pub fn move_new_background<P: AsRef<Path>>(
+        self,
+        path: P,
+        mut options: Options,
+    ) -> AsyncProgressHandle {
+        let tracker = Tracker::attach(&mut options);
+        let (file, mut source) = (self.clone(), self);
+        let path = path.as_ref().to_path_buf();
+        AsyncProgressHandle::spawn(
+            tracker,
+            source.to_path_buf(),
+            None,
+            async move { file_totals(&file).await },
+            async move { source.move_new_with(path, &options).await },
+        )
+    }
+
+    /// [`AsyncFileInfo::move_new_with`] in its own task, which completes
+    /// the move even when the returned handle or the caller is dropped,
+    /// e.g. a request handler whose client disconnected. Unlike
+    /// [`AsyncFileInfo::move_new_background`] the source is not walked
+    /// first and no progress is kept. Must be called from within a Tokio
+    /// runtime. The task returns the file at its new location.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::_async::{file::AsyncFileInfo, AsyncAction, AsyncInfo};
+    /// use fdir::Options;
+    /// tokio::runtime::Runtime::new().unwrap().block_on(async {
+    ///     let root = std::env::temp_dir().join("fdir_async_move_detached");
+    ///     let _ = std::fs::remove_dir_all(&root);
+    ///     let file = AsyncFileInfo::create(root.join("upload.part")).await.unwrap();
+    ///     file.write("content").await.unwrap();
+    ///     let task = file.move_new_detached(root.join("done/upload.bin"), Options::new());
+    ///     let (file, report) = task.await.unwrap().unwrap();
+    ///     assert_eq!(file.as_path(), root.join("done/upload.bin"));
+    ///     assert_eq!(report.files, [root.join("done/upload.bin")]);
+    ///     assert_eq!(file.read().await.unwrap(), b"content");
+    ///     assert!(!root.join("upload.part").exists());
+    ///     std::fs::remove_dir_all(root).unwrap();
+    /// });
+    /// ```
+    pub fn move_new_detached<P: AsRef<Path>>(
+        self,
+        path: P,
+        options: Options,
+    ) -> JoinHandle<Result<(AsyncFileInfo, CopyReport)>> {
+        let path = path.as_ref().to_path_buf();
+        let mut source = self;
+        tokio::spawn(async move {
+            let report = source.move_new_with(path, &options).await?;
+            Ok((source, report))
+        })
+    }
+}
+
+async fn file_totals(file: &AsyncFileInfo) -> Result<(u64, u64)> {
+    file.validate().await?;
+    Ok((1, file.metadata().await?.len()))
+}
+
+/// See `ProgressEvents`, ends after `Finished`
+impl Stream for ProgressEvents {
+    type Item = ProgressEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ProgressEvent>> {
+        self.get_mut().poll_next_event(cx)
+    }
+}
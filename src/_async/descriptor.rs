@@ -0,0 +1,41 @@
+use std::io::Result;
+
+use crate::descriptor::{OpDescriptor, OpOutcome};
+
+use super::{
+    dir::AsyncDirectoryInfo, entry::AsyncEntry, file::AsyncFileInfo, AsyncAction, AsyncInfo,
+};
+
+impl OpDescriptor {
+    /// See `OpDescriptor::execute`
+    pub async fn execute_async(&self) -> Result<OpOutcome> {
+        let options = self.options().to_options()?;
+        Ok(match self {
+            OpDescriptor::CopyFile { from, to, .. } => {
+                let file = AsyncFileInfo::open(from).await?;
+                OpOutcome::Copied(file.copy_new_with(to, &options).await?)
+            }
+            OpDescriptor::CopyTree { from, to, .. } => {
+                let dir = AsyncDirectoryInfo::open(from).await?;
+                OpOutcome::Copied(dir.copy_new_with(to, &options).await?)
+            }
+            OpDescriptor::MoveFile { from, to, .. } => {
+                let mut file = AsyncFileInfo::open(from).await?;
+                OpOutcome::Moved(file.move_new_with(to, &options).await?)
+            }
+            OpDescriptor::MoveTree { from, to, .. } => {
+                let mut dir = AsyncDirectoryInfo::open(from).await?;
+                OpOutcome::Moved(dir.move_new_with(to, &options).await?)
+            }
+            OpDescriptor::Delete { path, .. } => {
+                let entry = AsyncEntry::open(path).await?;
+                let path = entry.to_path_buf();
+                match entry {
+                    AsyncEntry::File(file) => file.delete_with(&options).await?,
+                    AsyncEntry::Dir(dir) => dir.delete_with(&options).await?,
+                }
+                OpOutcome::Deleted(path)
+            }
+        })
+    }
+}
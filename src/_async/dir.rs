@@ -1,21 +1,45 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashSet, VecDeque},
     ffi::OsStr,
     fmt::Display,
     fs::Metadata,
-    io::{Error, ErrorKind},
+    io::ErrorKind,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-use crate::{error::already_exist, fix_path, replace};
+use crate::{
+    error::{
+        already_exist, check_kind, into_own_subtree, is_source_not_removed, link_cycle,
+        no_longer_exists, same_file, source_not_removed, wrong_kind,
+    },
+    fix_path, fmt_path,
+    fs_id::same_filesystem,
+    io::keep_metadata,
+    is_ancestor_of, is_dry_run, is_same_file,
+    journal::Operation,
+    numbered_paths, relation, renamed, safe_join,
+    sync::{
+        batch::OpenMany,
+        dir::{extension_matcher, StatsVisitor},
+        listing::ListingLimit,
+        walk::Ancestors,
+        EntryFilter,
+    },
+    CopyReport, CyclePolicy, DirStats, DirectoryInfo, ErrorDecision, Numbering, Options, ReadOrder,
+    Relation, WalkOptions,
+};
 
 use super::{
+    _delete,
+    entry::AsyncEntry,
     file::AsyncFileInfo,
+    hash::blocking,
+    journaled,
     recover::{Status, TryRecover, TryRecoverResult},
-    AsyncAction, AsyncInfo,
+    retry, AsyncAction, AsyncInfo, AsyncTransform,
 };
-use async_recursion::async_recursion;
-use async_trait::async_trait;
+use std::future::{poll_fn, ready};
 use std::io::Result;
 use tokio::fs::{self, create_dir_all, metadata, rename};
 
@@ -26,14 +50,135 @@ pub struct AsyncDirectoryInfo {
 
 impl Display for AsyncDirectoryInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}", self.path.display()))
+        fmt_path(&self.path, f)
+    }
+}
+
+impl From<AsyncDirectoryInfo> for PathBuf {
+    fn from(value: AsyncDirectoryInfo) -> Self {
+        value.path
+    }
+}
+
+impl AsRef<Path> for AsyncDirectoryInfo {
+    fn as_ref(&self) -> &Path {
+        &self.path
     }
 }
 
 impl AsyncDirectoryInfo {
+    /// The path, without cloning it
+    pub fn into_path_buf(self) -> PathBuf {
+        self.path
+    }
+
+    /// Open and validate every path, see `DirectoryInfo::open_many`
+    pub async fn open_many<I>(paths: I) -> OpenMany<AsyncDirectoryInfo>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        super::open_many(paths).await
+    }
+
+    /// See `DirectoryInfo::create_unique`
+    pub async fn create_unique<P: AsRef<Path>>(path: P) -> Result<AsyncDirectoryInfo> {
+        Self::create_unique_with(path, Numbering::default()).await
+    }
+
+    /// See `DirectoryInfo::create_unique_with`
+    pub async fn create_unique_with<P: AsRef<Path>>(
+        path: P,
+        numbering: Numbering,
+    ) -> Result<AsyncDirectoryInfo> {
+        let path = fix_path(path)?;
+        let mut candidates =
+            std::iter::once(path.clone()).chain(numbered_paths(&path, numbering, true));
+        if is_dry_run() {
+            let candidate = candidates.find(|candidate| !candidate.exists());
+            let path = candidate.expect("ran out of candidate names");
+            return Ok(AsyncDirectoryInfo { path });
+        }
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).await?;
+        }
+        loop {
+            let candidate = candidates.next().expect("ran out of candidate names");
+            match fs::create_dir(&candidate).await {
+                Ok(()) => return Ok(AsyncDirectoryInfo { path: candidate }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// See `DirectoryInfo::home`
+    pub async fn home() -> Result<AsyncDirectoryInfo> {
+        from_sync(DirectoryInfo::home).await
+    }
+
+    /// See `DirectoryInfo::config_dir`, created on a blocking thread
+    pub async fn config_dir(app_name: impl AsRef<Path>) -> Result<AsyncDirectoryInfo> {
+        let app_name = app_name.as_ref().to_path_buf();
+        from_sync(move || DirectoryInfo::config_dir(app_name)).await
+    }
+
+    /// See `DirectoryInfo::data_dir`, created on a blocking thread
+    pub async fn data_dir(app_name: impl AsRef<Path>) -> Result<AsyncDirectoryInfo> {
+        let app_name = app_name.as_ref().to_path_buf();
+        from_sync(move || DirectoryInfo::data_dir(app_name)).await
+    }
+
+    /// See `DirectoryInfo::cache_dir`, created on a blocking thread
+    pub async fn cache_dir(app_name: impl AsRef<Path>) -> Result<AsyncDirectoryInfo> {
+        let app_name = app_name.as_ref().to_path_buf();
+        from_sync(move || DirectoryInfo::cache_dir(app_name)).await
+    }
+
+    /// See `DirectoryInfo::executable_dir`
+    pub async fn executable_dir() -> Result<AsyncDirectoryInfo> {
+        from_sync(DirectoryInfo::executable_dir).await
+    }
+
+    /// See `DirectoryInfo::child`
+    pub fn child(&self, name: impl AsRef<OsStr>) -> Result<PathBuf> {
+        Ok(self.as_path().join(crate::checked_name(name.as_ref())?))
+    }
+
+    /// See `DirectoryInfo::child_file`
+    pub async fn child_file(&self, name: impl AsRef<OsStr>) -> Result<AsyncFileInfo> {
+        AsyncFileInfo::open(self.child(name)?).await
+    }
+
+    /// See `DirectoryInfo::child_dir`
+    pub async fn child_dir(&self, name: impl AsRef<OsStr>) -> Result<AsyncDirectoryInfo> {
+        AsyncDirectoryInfo::open(self.child(name)?).await
+    }
+
     pub async fn children(&self) -> Result<Vec<PathBuf>> {
         read_dir(self.as_path(), |_| true).await
     }
+    /// Direct children as [`AsyncEntry`], see `DirectoryInfo::entries`
+    pub async fn entries(&self) -> Result<Vec<AsyncEntry>> {
+        let mut entries = Vec::new();
+        let mut limit = ListingLimit::new(self.as_path());
+        let mut read_dir = fs::read_dir(self.as_path()).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            limit.count()?;
+            let file_type = match entry.file_type().await {
+                Ok(file_type) if file_type.is_symlink() => match fs::metadata(entry.path()).await {
+                    Ok(metadata) => metadata.file_type(),
+                    Err(_) => continue,
+                },
+                Ok(file_type) => file_type,
+                Err(_) => continue,
+            };
+            if let Ok(entry) = unsafe { AsyncEntry::classify(entry.path(), file_type) } {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
     pub async fn files(&self) -> Result<Vec<AsyncFileInfo>> {
         read_dir(self.as_path(), |path| path.is_file())
             .await
@@ -52,15 +197,290 @@ impl AsyncDirectoryInfo {
                     .collect()
             })
     }
+
+    /// See `DirectoryInfo::find_file_normalized`
+    #[cfg(feature = "unicode-normalization")]
+    pub async fn find_file_normalized(
+        &self,
+        name: impl AsRef<OsStr>,
+    ) -> Result<Option<AsyncFileInfo>> {
+        use crate::convert::{normalize_file_name, Form};
+        let name = normalize_file_name(name.as_ref(), Form::Nfc);
+        Ok(self.files().await?.into_iter().find(|file| {
+            file.file_name()
+                .is_some_and(|file_name| normalize_file_name(file_name, Form::Nfc) == name)
+        }))
+    }
+
+    /// Count the entries matching `filter`, see `DirectoryInfo::count_entries`
+    pub async fn count_entries(&self, filter: EntryFilter, recursive: bool) -> Result<u64> {
+        let mut count = 0;
+        let mut pending = vec![self.as_path().to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let mut read_dir = fs::read_dir(dir).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                let file_type = entry.file_type().await?;
+                if filter.matches(file_type) {
+                    count += 1;
+                }
+                if recursive && file_type.is_dir() {
+                    pending.push(entry.path());
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Whether the directory has no entries, stops at the first one found
+    pub async fn is_empty(&self) -> Result<bool> {
+        Ok(fs::read_dir(self.as_path())
+            .await?
+            .next_entry()
+            .await?
+            .is_none())
+    }
+
+    /// Locate the directory relative to `path`, see `fdir::relation`
+    pub fn relation_to(&self, path: impl AsRef<Path>) -> Relation {
+        relation(self.as_path(), path)
+    }
+
+    /// Same as [`AsyncAction::copy_new`], configured by `options`
+    ///
+    /// Directories are always traversed breadth-first, `options.walk` is not used yet.
+    pub async fn copy_new_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &Options,
+    ) -> Result<CopyReport> {
+        let transform = |relative: PathBuf| ready(options.transform_of(&relative));
+        self.copy_new_transformed(path, options, &transform).await
+    }
+
+    /// Same as [`AsyncDirectoryInfo::copy_new_with`], with `transform`
+    /// deciding for each file instead of `options.transform`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # async fn run() -> std::io::Result<()> {
+    /// use std::path::PathBuf;
+    /// use fdir::_async::{dir::AsyncDirectoryInfo, AsyncAction};
+    /// use fdir::hooks::Transform;
+    /// let skeleton = AsyncDirectoryInfo::open("skeleton").await?;
+    /// let transform = |relative: PathBuf| async move {
+    ///     let name = relative.to_str()?.strip_suffix(".tmpl")?;
+    ///     Some(Transform::new().dest(name))
+    /// };
+    /// skeleton
+    ///     .copy_new_transformed("project", &Default::default(), &transform)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn copy_new_transformed<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &Options,
+        transform: &impl AsyncTransform,
+    ) -> Result<CopyReport> {
+        self.validate().await?;
+        let path = fix_path(path)?;
+        if is_same_file(self.as_path(), &path) {
+            return Err(same_file(self.as_path(), &path));
+        }
+        match options.resolve_conflict(self.as_path(), path)? {
+            Some(path) => _write_dir(self, &path, true, transform, options).await,
+            None => Ok(CopyReport {
+                skipped: 1,
+                ..CopyReport::new()
+            }),
+        }
+    }
+
+    /// Same as [`AsyncAction::move_new`], configured by `options`, see
+    /// `DirectoryInfo::move_new_with`
+    pub async fn move_new_with<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &Options,
+    ) -> Result<CopyReport> {
+        self.validate().await?;
+        let path = fix_path(path)?;
+        if is_same_file(self.as_path(), &path) {
+            return Ok(CopyReport::default());
+        }
+        let mut report = CopyReport::new();
+        let Some(path) = options.resolve_conflict(self.as_path(), path)? else {
+            report.skipped += 1;
+            return Ok(report);
+        };
+        // an existing destination is merged into rather than renamed over
+        report.renamed = !options.has_filters()
+            && !path.exists()
+            && if report.dry_run {
+                path.parent().is_some_and(Path::is_dir) && same_filesystem(self.as_path(), &path)
+            } else {
+                rename(self.as_path(), path.as_path()).await.is_ok()
+            };
+        if report.renamed {
+            options.journal_done(Operation::Move, self.as_path(), Some(&path))?;
+        } else {
+            let keep = |_: PathBuf| ready(None);
+            report = match _write_dir(self, &path, false, &keep, options).await {
+                Err(e) if is_source_not_removed(&e, self.as_path()) => {
+                    self.path = path;
+                    return Err(e);
+                }
+                result => result?,
+            };
+        }
+        if report.is_complete() && !report.dry_run {
+            self.path = path;
+        }
+        Ok(report)
+    }
+
+    /// Collect a [`DirStats`] summary in a single walk, see `DirectoryInfo::stats`
+    pub async fn stats(&self) -> Result<DirStats> {
+        self.stats_with(&WalkOptions::default()).await
+    }
+
+    /// See `DirectoryInfo::stats_with`, traversing with
+    /// [`AsyncDirectoryInfo::visit`]
+    pub async fn stats_with(&self, options: &WalkOptions) -> Result<DirStats> {
+        // only an unreadable root is an error
+        drop(fs::read_dir(self.as_path()).await?);
+        let mut visitor = StatsVisitor::new(options);
+        self.visit(options, &mut visitor).await?;
+        Ok(visitor.stats)
+    }
+
+    /// The most recently modified file, see `DirectoryInfo::newest_file`
+    pub async fn newest_file(&self, recursive: bool) -> Result<Option<AsyncFileInfo>> {
+        self.select_file(recursive, |candidate, best| candidate > best)
+            .await
+    }
+
+    /// The least recently modified file, see `DirectoryInfo::newest_file`
+    pub async fn oldest_file(&self, recursive: bool) -> Result<Option<AsyncFileInfo>> {
+        self.select_file(recursive, |candidate, best| candidate < best)
+            .await
+    }
+
+    /// Every file modified after `since`, see `DirectoryInfo::modified_since`
+    pub async fn modified_since(
+        &self,
+        since: SystemTime,
+        recursive: bool,
+    ) -> Result<Vec<AsyncFileInfo>> {
+        let mut files = Vec::new();
+        self.for_each_file(recursive, |path, modified| {
+            if modified > since {
+                files.push(unsafe { AsyncAction::open_uncheck(path) });
+            }
+        })
+        .await?;
+        Ok(files)
+    }
+
+    /// See `DirectoryInfo::files_with_extension`
+    pub async fn files_with_extension(
+        &self,
+        extension: impl AsRef<OsStr>,
+        recursive: bool,
+    ) -> Result<Vec<AsyncFileInfo>> {
+        self.files_with_extensions(&[extension], recursive).await
+    }
+
+    /// See `DirectoryInfo::files_with_extensions`
+    pub async fn files_with_extensions<E: AsRef<OsStr>>(
+        &self,
+        extensions: &[E],
+        recursive: bool,
+    ) -> Result<Vec<AsyncFileInfo>> {
+        self.files_matching(extension_matcher(extensions), recursive)
+            .await
+    }
+
+    /// Files whose name is accepted by `pred`, breadth-first. See
+    /// `DirectoryInfo::files_matching`.
+    pub async fn files_matching(
+        &self,
+        pred: impl Fn(&OsStr) -> bool,
+        recursive: bool,
+    ) -> Result<Vec<AsyncFileInfo>> {
+        let mut files = Vec::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(self.as_path().to_path_buf());
+        while let Some(dir) = queue.pop_front() {
+            let mut read_dir = fs::read_dir(dir).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                let accepted = pred(&entry.file_name());
+                if !accepted && !recursive {
+                    continue;
+                }
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() && recursive {
+                    queue.push_back(entry.path());
+                } else if file_type.is_file() && accepted {
+                    files.push(unsafe { AsyncAction::open_uncheck(entry.path()) });
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    async fn select_file(
+        &self,
+        recursive: bool,
+        better: fn(SystemTime, SystemTime) -> bool,
+    ) -> Result<Option<AsyncFileInfo>> {
+        let mut best: Option<(PathBuf, SystemTime)> = None;
+        self.for_each_file(recursive, |path, modified| {
+            if best
+                .as_ref()
+                .is_none_or(|(_, time)| better(modified, *time))
+            {
+                best = Some((path, modified));
+            }
+        })
+        .await?;
+        Ok(best.map(|(path, _)| unsafe { AsyncAction::open_uncheck(path) }))
+    }
+
+    /// Breadth-first over the files below the directory with their
+    /// modification time, symlinks are not followed
+    async fn for_each_file<F>(&self, recursive: bool, mut f: F) -> Result<()>
+    where
+        F: FnMut(PathBuf, SystemTime),
+    {
+        let mut queue = VecDeque::new();
+        queue.push_back(self.as_path().to_path_buf());
+        while let Some(dir) = queue.pop_front() {
+            let mut read_dir = fs::read_dir(dir).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() && recursive {
+                    queue.push_back(entry.path());
+                } else if file_type.is_file() {
+                    f(entry.path(), entry.metadata().await?.modified()?);
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 pub async fn read_dir<F>(path: impl AsRef<Path>, f: F) -> Result<Vec<PathBuf>>
 where
     F: Fn(&PathBuf) -> bool,
 {
+    let path = path.as_ref();
+    let mut limit = ListingLimit::new(path);
     let mut read_dir = fs::read_dir(path).await?;
     let mut children = Vec::new();
     while let Ok(Some(entry)) = read_dir.next_entry().await {
+        limit.count()?;
         let path = entry.path();
         if f(&path) {
             children.push(path)
@@ -69,7 +489,6 @@ where
     Ok(children)
 }
 
-#[async_trait]
 impl AsyncInfo for AsyncDirectoryInfo {
     fn as_path(&self) -> &Path {
         self.path.as_path()
@@ -80,23 +499,32 @@ impl AsyncInfo for AsyncDirectoryInfo {
     async fn size(&self) -> u64 {
         self.metadata().await.map_or(0, |f| f.len())
     }
+
+    async fn validate(&self) -> Result<()> {
+        match self.metadata().await {
+            Ok(data) if data.is_dir() => Ok(()),
+            Ok(_) => Err(wrong_kind(self.as_path(), "directory")),
+            Err(_) => Err(no_longer_exists(self.as_path())),
+        }
+    }
+}
+
+/// Run a constructor of `DirectoryInfo` on a blocking thread
+async fn from_sync<F>(open: F) -> Result<AsyncDirectoryInfo>
+where
+    F: FnOnce() -> Result<DirectoryInfo> + Send + 'static,
+{
+    let dir = blocking(open).await?;
+    Ok(AsyncDirectoryInfo {
+        path: dir.into_path_buf(),
+    })
 }
 
-#[async_trait]
 impl AsyncAction for AsyncDirectoryInfo {
     async fn open<P: AsRef<Path> + Send + Sync>(path: P) -> Result<Self> {
         let path = fix_path(path)?;
-        if path.is_dir() {
-            Ok(Self { path })
-        } else {
-            Err(Error::new(
-                ErrorKind::NotFound,
-                format!(
-                    "The path '{}' is not a directory or does not exist",
-                    path.display()
-                ),
-            ))
-        }
+        check_kind(&path, fs::metadata(&path).await, true)?;
+        Ok(Self { path })
     }
     unsafe fn open_uncheck<P: AsRef<Path>>(path: P) -> Self {
         Self {
@@ -105,63 +533,302 @@ impl AsyncAction for AsyncDirectoryInfo {
     }
     /// Rename a file or directory
     async fn rename<T: AsRef<OsStr> + Send + Sync>(&mut self, name: T) -> Result<()> {
-        let mut new_path = self.path.clone();
-        new_path.set_file_name(name.as_ref());
+        let new_path = renamed(self.as_path(), name.as_ref())?;
+        if is_dry_run() {
+            return self.validate().await;
+        }
         rename(self.as_path(), &new_path).await?;
         self.path = new_path;
         Ok(())
     }
-    async fn copy_new<P: AsRef<Path> + Send + Sync>(&self, path: P) -> TryRecoverResult<()> {
+    async fn copy_new<P: AsRef<Path> + Send + Sync>(&self, path: P) -> TryRecoverResult<'_, ()> {
+        self.validate().await?;
         let path = fix_path(path)?;
-        if path.try_exists()? {
+        if !is_same_file(self.as_path(), &path) && path.try_exists()? {
             return Err(TryRecover::new(
                 already_exist(&path),
                 Status::CopyDirectory(self, path),
             ));
         }
-        _write_dir(self, &path, true).await?;
+        self.copy_new_with(path, &Options::default()).await?;
         Ok(())
     }
-    async fn move_new<P: AsRef<Path> + Send + Sync>(&mut self, path: P) -> TryRecoverResult<()> {
+    async fn move_new<P: AsRef<Path> + Send + Sync>(
+        &mut self,
+        path: P,
+    ) -> TryRecoverResult<'_, ()> {
+        self.validate().await?;
         let path = fix_path(path)?;
-        if path.try_exists()? {
+        if !is_same_file(self.as_path(), &path) && path.try_exists()? {
             return Err(TryRecover::new(
                 already_exist(&path),
                 Status::MoveDirectory(self, path),
             ));
         }
-        if rename(self.as_path(), path.as_path()).await.is_err() {
-            _write_dir(self, &path, false).await?;
-        }
-        self.path = path;
+        self.move_new_with(path, &Options::default()).await?;
         Ok(())
     }
 }
-#[async_recursion]
-pub(crate) async fn _write_dir(dir: &AsyncDirectoryInfo, to: &Path, is_copy: bool) -> Result<()> {
+/// Sort the files of a directory in `order`, see `ReadOrder`. The inode
+/// numbers are read with a `stat` each as the listing does not keep them.
+fn sort_files(files: &mut [AsyncFileInfo], order: ReadOrder) {
+    match order.effective() {
+        #[cfg(unix)]
+        ReadOrder::ByInode => {
+            use std::os::unix::fs::MetadataExt;
+            files.sort_by_cached_key(|file| {
+                std::fs::symlink_metadata(file.as_path()).map_or(u64::MAX, |data| data.ino())
+            })
+        }
+        ReadOrder::ByName => files.sort_unstable_by(|a, b| a.file_name().cmp(&b.file_name())),
+        _ => {}
+    }
+}
+
+/// `transform` is only asked by copies
+pub(crate) async fn _write_dir(
+    dir: &AsyncDirectoryInfo,
+    to: &Path,
+    is_copy: bool,
+    transform: &impl AsyncTransform,
+    options: &Options,
+) -> Result<CopyReport> {
+    dir.validate().await?;
+    if is_same_file(dir.as_path(), to) {
+        return if is_copy {
+            Err(same_file(dir.as_path(), to))
+        } else {
+            Ok(CopyReport::default())
+        };
+    }
+    if is_ancestor_of(dir.as_path(), to) {
+        return Err(into_own_subtree(dir.as_path(), to));
+    }
+    let mut report = CopyReport {
+        read_order: Some(options.read_order.effective()),
+        ..CopyReport::new()
+    };
+    let mut sources = Vec::new();
+    // directories created by a move with the metadata of their source, read
+    // before moving their content changes it
+    let mut new_dirs = Vec::new();
+    // links to directories are always followed, cycles are cut by id
+    let mut visited = HashSet::new();
+    let mut ancestors = Ancestors::default();
+    if let Ok(id) = dir.file_id() {
+        visited.insert(id);
+        ancestors = ancestors.push(id, dir.as_path());
+    }
+    // directories are paired with their destination, renamed ones included
     let mut queue = VecDeque::new();
-    let path = dir.path.clone();
-    queue.push_back(dir.clone());
-    while let Some(dir) = queue.pop_front() {
-        queue.append(&mut dir.directories().await?.into());
-        let dir_path = replace(dir.as_path(), path.as_path(), to);
-        if !dir_path.is_dir() {
-            create_dir_all(dir_path.as_path()).await?;
-        }
-        for mut file in dir.files().await? {
-            let result = if is_copy {
-                file.copy_to(dir_path.as_path()).await
-            } else {
-                file.move_to(dir_path.as_path()).await
+    queue.push_back((dir.clone(), to.to_path_buf(), ancestors));
+    while let Some((current, dir_path, ancestors)) = queue.pop_front() {
+        // `on_error` is asked between attempts, never while one is pending,
+        // `Some(false)` when `current` was removed meanwhile
+        let created = loop {
+            let created = create_dest(current.as_path(), &dir_path, is_copy, options).await;
+            match options.missing(current.as_path(), created) {
+                Ok(Some(data)) => {
+                    if let Some(data) = data {
+                        new_dirs.push((current.clone(), dir_path.clone(), data));
+                    }
+                    break Some(true);
+                }
+                Ok(None) => break Some(false),
+                Err(e) => match options.decide(current.as_path(), &e) {
+                    ErrorDecision::Retry => continue,
+                    ErrorDecision::Skip => break None,
+                    ErrorDecision::Abort => return Err(e),
+                },
+            }
+        };
+        match created {
+            Some(true) => {}
+            Some(false) => {
+                report.skipped_missing.push(current.to_path_buf());
+                continue;
+            }
+            None => {
+                report.skipped += 1;
+                continue;
+            }
+        }
+        report.directories.push(dir_path.clone());
+        let listed = match current.directories().await {
+            Ok(directories) => current.files().await.map(|files| (directories, files)),
+            Err(e) => Err(e),
+        };
+        let Some((directories, mut files)) = options.missing(current.as_path(), listed)? else {
+            report.skipped_missing.push(current.to_path_buf());
+            continue;
+        };
+        for child in directories {
+            if !accepts(options, dir, &child, true) {
+                report.skipped += 1;
+                continue;
+            }
+            let mut ancestors = ancestors.clone();
+            if let Ok(id) = child.file_id() {
+                if let Some(mut chain) = ancestors.chain_from(id) {
+                    if options.walk.on_cycle == CyclePolicy::Fail {
+                        chain.push(child.as_path().to_path_buf());
+                        return Err(link_cycle(chain));
+                    }
+                    report.skipped += 1;
+                    continue;
+                }
+                if options.walk.visit_once && !visited.insert(id) {
+                    report.skipped += 1;
+                    continue;
+                }
+                ancestors = ancestors.push(id, child.as_path());
+            }
+            let dest = dir_path.join(child.file_name().unwrap_or_default());
+            match options.resolve_conflict(child.as_path(), dest)? {
+                Some(dest) => queue.push_back((child, dest, ancestors)),
+                None => report.skipped += 1,
+            }
+        }
+        sort_files(&mut files, options.read_order);
+        for mut file in files {
+            if !accepts(options, dir, &file, false) {
+                report.skipped += 1;
+                continue;
+            }
+            let mut dest = dir_path.join(file.file_name().unwrap_or_default());
+            let mut filter = None;
+            if is_copy {
+                let relative = file.as_path().strip_prefix(dir.as_path());
+                let relative = relative.unwrap_or(file.as_path());
+                if let Some(transform) = transform.transform(relative).await {
+                    if let Some(relative) = transform.dest {
+                        dest = safe_join(to, relative)?;
+                        if let Some(parent) = dest.parent().filter(|_| !is_dry_run()) {
+                            create_dir_all(parent).await?;
+                        }
+                    }
+                    filter = transform.filter;
+                }
+            }
+            if let Some(pause) = &options.pause {
+                let cancel = options.stream.cancel.as_deref();
+                poll_fn(|cx| pause.poll_resumed_unless(cx, cancel)).await;
+            }
+            options.check_cancelled()?;
+            options.file_started(file.as_path());
+            let written = loop {
+                let result = if is_copy {
+                    file.copy_filtered(&dest, options, filter.as_ref()).await
+                } else {
+                    file.move_new_with(&dest, options).await
+                };
+                match options.missing(file.as_path(), result) {
+                    Ok(Some(written)) => break Some(Some(written)),
+                    Ok(None) => break Some(None),
+                    Err(e) => match options.decide(file.as_path(), &e) {
+                        ErrorDecision::Retry => continue,
+                        ErrorDecision::Skip => break None,
+                        ErrorDecision::Abort => return Err(e),
+                    },
+                }
             };
-            match result {
-                Ok(_) => (),
-                Err(e) => e.try_recover().await?,
+            match written {
+                Some(Some(written)) if written.files.is_empty() => report.append(written),
+                Some(Some(written)) => {
+                    report.append(written);
+                    options.progress(file.as_path(), &report);
+                }
+                Some(None) => report.skipped_missing.push(file.to_path_buf()),
+                None => report.skipped += 1,
             }
         }
+        sources.push(current);
     }
     if !is_copy {
-        dir.clone().delete().await?;
+        // after their content, which changes their modification time, and
+        // deepest first, as the permissions may deny writing
+        for (source, dest, data) in new_dirs.iter().rev() {
+            if !report.dry_run {
+                keep_metadata(source.as_path(), dest, data)?;
+            }
+        }
+        if report.is_complete() {
+            let delete = dir.clone().delete();
+            journaled(options, Operation::Delete, dir.as_path(), None, delete)
+                .await
+                .map_err(|e| source_not_removed(dir.as_path(), to, e))?;
+        } else {
+            // only remove the directories that were emptied, deepest first
+            for source in sources.iter().rev() {
+                if !report.dry_run && fs::remove_dir(source.as_path()).await.is_ok() {
+                    options.journal_done(Operation::Delete, source.as_path(), None)?;
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Create `dest` unless it exists. When a move creates it, the metadata of
+/// `source` is read first and returned.
+async fn create_dest(
+    source: &Path,
+    dest: &Path,
+    is_copy: bool,
+    options: &Options,
+) -> Result<Option<Metadata>> {
+    if dest.is_dir() {
+        return Ok(None);
+    }
+    let data = if is_copy {
+        None
+    } else {
+        Some(metadata(source).await?)
+    };
+    journaled(options, Operation::CreateDir, source, Some(dest), create_dir_all(dest)).await?;
+    Ok(data)
+}
+
+/// Async twin of `sync::dir::_delete_filtered`, `options.walk` is not used yet
+pub(crate) async fn _delete_filtered(dir: &Path, options: &Options) -> Result<()> {
+    let root = unsafe { AsyncDirectoryInfo::open_uncheck(dir) };
+    let mut directories = Vec::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(root.clone());
+    while let Some(current) = queue.pop_front() {
+        for child in current.directories().await? {
+            if accepts(options, &root, &child, true) {
+                queue.push_back(child);
+            }
+        }
+        for file in current.files().await? {
+            if accepts(options, &root, &file, false) {
+                let delete = retry(options, || _delete(file.as_path()));
+                journaled(options, Operation::Delete, file.as_path(), None, delete).await?;
+            }
+        }
+        directories.push(current);
+    }
+    for directory in directories.iter().rev() {
+        if !is_dry_run() && fs::remove_dir(directory.as_path()).await.is_ok() {
+            options.journal_done(Operation::Delete, directory.as_path(), None)?;
+        }
     }
     Ok(())
 }
+
+fn accepts(
+    options: &Options,
+    root: &AsyncDirectoryInfo,
+    entry: &impl AsyncInfo,
+    is_dir: bool,
+) -> bool {
+    if !options.has_filters() {
+        return true;
+    }
+    let relative = entry
+        .as_path()
+        .strip_prefix(root.as_path())
+        .unwrap_or(entry.as_path());
+    options.accepts(relative, is_dir)
+}
@@ -0,0 +1,153 @@
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::fs::{FileType, Metadata};
+use std::io::Result;
+use std::path::Path;
+
+use tokio::fs;
+
+use crate::error::wrong_kind;
+use crate::fix_path;
+
+use super::dir::AsyncDirectoryInfo;
+use super::file::AsyncFileInfo;
+use super::recover::TryRecoverResult;
+use super::{AsyncAction, AsyncInfo};
+
+/// A file or a directory, classified once when opened, see `fdir::Entry`
+#[derive(Debug, Clone)]
+pub enum AsyncEntry {
+    File(AsyncFileInfo),
+    Dir(AsyncDirectoryInfo),
+}
+
+impl Display for AsyncEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsyncEntry::File(file) => file.fmt(f),
+            AsyncEntry::Dir(dir) => dir.fmt(f),
+        }
+    }
+}
+
+impl From<AsyncFileInfo> for AsyncEntry {
+    fn from(value: AsyncFileInfo) -> Self {
+        AsyncEntry::File(value)
+    }
+}
+
+impl From<AsyncDirectoryInfo> for AsyncEntry {
+    fn from(value: AsyncDirectoryInfo) -> Self {
+        AsyncEntry::Dir(value)
+    }
+}
+
+impl AsyncEntry {
+    /// Open `path` as whatever it is, with a single metadata call
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = fix_path(path)?;
+        let metadata = fs::metadata(&path).await?;
+        unsafe { Self::classify(path, metadata.file_type()) }
+    }
+
+    /// # Safety
+    /// `path` is not checked or fixed, see [`AsyncAction::open_uncheck`]
+    pub(crate) unsafe fn classify(path: impl AsRef<Path>, file_type: FileType) -> Result<Self> {
+        if file_type.is_dir() {
+            Ok(AsyncEntry::Dir(AsyncAction::open_uncheck(path)))
+        } else if file_type.is_file() {
+            Ok(AsyncEntry::File(AsyncAction::open_uncheck(path)))
+        } else {
+            Err(wrong_kind(path, "file or directory"))
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        matches!(self, AsyncEntry::File(_))
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self, AsyncEntry::Dir(_))
+    }
+
+    pub async fn rename<T: AsRef<OsStr> + Send + Sync>(&mut self, name: T) -> Result<()> {
+        match self {
+            AsyncEntry::File(file) => file.rename(name).await,
+            AsyncEntry::Dir(dir) => dir.rename(name).await,
+        }
+    }
+
+    pub async fn delete(self) -> Result<()> {
+        match self {
+            AsyncEntry::File(file) => file.delete().await,
+            AsyncEntry::Dir(dir) => dir.delete().await,
+        }
+    }
+
+    pub async fn copy_to<P: AsRef<Path> + Send + Sync>(&self, path: P) -> TryRecoverResult<'_, ()> {
+        match self {
+            AsyncEntry::File(file) => file.copy_to(path).await,
+            AsyncEntry::Dir(dir) => dir.copy_to(path).await,
+        }
+    }
+
+    pub async fn copy_new<P: AsRef<Path> + Send + Sync>(
+        &self,
+        path: P,
+    ) -> TryRecoverResult<'_, ()> {
+        match self {
+            AsyncEntry::File(file) => file.copy_new(path).await,
+            AsyncEntry::Dir(dir) => dir.copy_new(path).await,
+        }
+    }
+
+    pub async fn move_to<P: AsRef<Path> + Send + Sync>(
+        &mut self,
+        path: P,
+    ) -> TryRecoverResult<'_, ()> {
+        match self {
+            AsyncEntry::File(file) => file.move_to(path).await,
+            AsyncEntry::Dir(dir) => dir.move_to(path).await,
+        }
+    }
+
+    pub async fn move_new<P: AsRef<Path> + Send + Sync>(
+        &mut self,
+        path: P,
+    ) -> TryRecoverResult<'_, ()> {
+        match self {
+            AsyncEntry::File(file) => file.move_new(path).await,
+            AsyncEntry::Dir(dir) => dir.move_new(path).await,
+        }
+    }
+}
+
+impl AsyncInfo for AsyncEntry {
+    fn as_path(&self) -> &Path {
+        match self {
+            AsyncEntry::File(file) => file.as_path(),
+            AsyncEntry::Dir(dir) => dir.as_path(),
+        }
+    }
+
+    async fn metadata(&self) -> Result<Metadata> {
+        match self {
+            AsyncEntry::File(file) => file.metadata().await,
+            AsyncEntry::Dir(dir) => dir.metadata().await,
+        }
+    }
+
+    async fn size(&self) -> u64 {
+        match self {
+            AsyncEntry::File(file) => file.size().await,
+            AsyncEntry::Dir(dir) => dir.size().await,
+        }
+    }
+
+    async fn validate(&self) -> Result<()> {
+        match self {
+            AsyncEntry::File(file) => file.validate().await,
+            AsyncEntry::Dir(dir) => dir.validate().await,
+        }
+    }
+}
@@ -1,15 +1,32 @@
+use super::io::{copy_file, copy_stream, PartialFile};
 use super::recover::{Status, TryRecover, TryRecoverResult};
-use super::{remove_file_any, AsyncAction, AsyncInfo};
-use crate::error::{already_exist, INVALID_PATH};
-use crate::web::content_type;
-use crate::{fix_path, get_file_path, is_same_root};
-use async_trait::async_trait;
+use super::{_delete_file, journaled, remove_file_any, retry, AsyncAction, AsyncInfo};
+use crate::convert::{portable_issues, sanitize_file_name, SanitizeOptions};
+use crate::error::{
+    already_exist, check_kind, invalid_path, is_source_not_removed, modified_during_copy,
+    no_longer_exists, path_issues, same_file, source_not_removed, wrong_kind,
+};
+use crate::fs_id::same_filesystem;
+use crate::hooks::FilterHook;
+use crate::io::{keep_metadata, StreamOptions, StreamReport};
+use crate::journal::Operation;
+use crate::options::Snapshot;
+use crate::sync::batch::OpenMany;
+use crate::sync::file::suffixed_name;
+#[cfg(feature = "hyper")]
+use crate::web::{content_disposition, content_type, detected_type};
+use crate::{
+    fix_path, fmt_path, get_file_path, is_dry_run, is_same_file, push_file_name, renamed,
+    renamed_extension, set_file_times, CopyReport, KeptExtension, ModifiedPolicy, Options, Size,
+};
+use futures::future::join_all;
 use std::ffi::OsStr;
 use std::fmt::{Debug, Display};
 use std::fs::Metadata;
-use std::io::Result;
+use std::io::{ErrorKind, Result};
 use std::path::{Path, PathBuf};
-use tokio::fs::{copy, create_dir_all, metadata, rename, File};
+use tokio::fs::{self, create_dir_all, metadata, rename, File};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 
 #[derive(Debug, Clone)]
 pub struct AsyncFileInfo {
@@ -29,24 +46,475 @@ impl TryFrom<File> for AsyncFileInfo {
 }
 impl Display for AsyncFileInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}", self.path.display()))
+        fmt_path(&self.path, f)
+    }
+}
+
+impl From<AsyncFileInfo> for PathBuf {
+    fn from(value: AsyncFileInfo) -> Self {
+        value.path
+    }
+}
+
+impl AsRef<Path> for AsyncFileInfo {
+    fn as_ref(&self) -> &Path {
+        &self.path
     }
 }
 
 impl AsyncFileInfo {
+    /// The path, without cloning it
+    pub fn into_path_buf(self) -> PathBuf {
+        self.path
+    }
+
+    /// See `FileInfo::with_suffix`
+    pub fn with_suffix(&self, suffix: impl AsRef<OsStr>) -> Result<PathBuf> {
+        self.sibling(suffixed_name(self.as_path(), suffix.as_ref()))
+    }
+
+    /// Create the file and its missing parent directories, an existing file
+    /// is truncated, see `FileInfo::create`
+    pub async fn create<P: AsRef<Path>>(path: P) -> Result<AsyncFileInfo> {
+        let path = fix_path(path)?;
+        if is_dry_run() {
+            return Ok(Self { path });
+        }
+        create_parent(&path).await?;
+        File::create(&path).await?;
+        Ok(Self { path })
+    }
+
+    /// Same as [`AsyncFileInfo::create`], but fails if the file already exists
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::_async::{file::AsyncFileInfo, AsyncInfo};
+    /// let root = std::env::temp_dir().join("fdir_async_create_new");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let runtime = tokio::runtime::Runtime::new().unwrap();
+    /// runtime.block_on(async {
+    ///     let file = AsyncFileInfo::create_new(root.join("a/log.txt")).await.unwrap();
+    ///     let error = AsyncFileInfo::create_new(file.as_path()).await.unwrap_err();
+    ///     assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+    ///     file.write("one\n").await.unwrap();
+    ///     file.append(b"two\n").await.unwrap();
+    ///     assert_eq!(file.read().await.unwrap(), b"one\ntwo\n");
+    ///     file.write("three").await.unwrap();
+    ///     assert_eq!(file.read_to_string().await.unwrap(), "three");
+    /// });
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub async fn create_new<P: AsRef<Path>>(path: P) -> Result<AsyncFileInfo> {
+        let path = fix_path(path)?;
+        if is_dry_run() {
+            if path.try_exists()? {
+                return Err(already_exist(&path));
+            }
+            return Ok(Self { path });
+        }
+        create_parent(&path).await?;
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+            .map_err(|e| match e.kind() {
+                ErrorKind::AlreadyExists => already_exist(&path),
+                _ => e,
+            })?;
+        Ok(Self { path })
+    }
+
+    /// See `FileInfo::create_sanitized`
+    pub async fn create_sanitized<P: AsRef<Path>>(
+        path: P,
+        options: &SanitizeOptions,
+    ) -> Result<AsyncFileInfo> {
+        let path = fix_path(path)?;
+        let name = match path.file_name() {
+            Some(name) => sanitize_file_name(&name.to_string_lossy(), options),
+            _ => return Err(invalid_path(&path, "has no file name")),
+        };
+        AsyncFileInfo::create(path.with_file_name(name)).await
+    }
+
+    /// See `FileInfo::create_portable`
+    pub async fn create_portable<P: AsRef<Path>>(path: P) -> Result<AsyncFileInfo> {
+        let issues = portable_issues(path.as_ref());
+        if !issues.is_empty() {
+            return Err(path_issues(path, issues));
+        }
+        AsyncFileInfo::create(path).await
+    }
+
+    /// Replace the content of the file with `bytes`
+    pub async fn write(&self, bytes: impl AsRef<[u8]>) -> Result<()> {
+        self.validate().await?;
+        if is_dry_run() {
+            return Ok(());
+        }
+        fs::write(self.as_path(), bytes).await
+    }
+
+    /// Add `bytes` at the end of the file
+    pub async fn append(&self, bytes: impl AsRef<[u8]>) -> Result<()> {
+        self.validate().await?;
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(self.as_path())
+            .await?;
+        file.write_all(bytes.as_ref()).await?;
+        file.flush().await
+    }
+
+    /// See `FileInfo::write_from`
+    pub async fn write_from(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        options: &StreamOptions,
+    ) -> Result<StreamReport> {
+        self.validate().await?;
+        copy_stream(reader, File::create(self.as_path()).await?, options).await
+    }
+
+    /// The whole content of the file, see `FileInfo::read`
+    pub async fn read(&self) -> Result<Vec<u8>> {
+        fs::read(self.as_path()).await
+    }
+
+    /// The whole content of the file, see `FileInfo::read_to_string`
+    pub async fn read_to_string(&self) -> Result<String> {
+        fs::read_to_string(self.as_path()).await
+    }
+
+    /// Open and validate every path, see `FileInfo::open_many`
+    pub async fn open_many<I>(paths: I) -> OpenMany<AsyncFileInfo>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        super::open_many(paths).await
+    }
+
+    /// Copy the file into every directory of `dests`, see `FileInfo::copy_to_many`.
+    /// Each chunk read from the source is written to all destinations
+    /// concurrently before the next one is read.
+    pub async fn copy_to_many<P: AsRef<Path>>(
+        &self,
+        dests: &[P],
+    ) -> Result<Vec<Result<AsyncFileInfo>>> {
+        self.validate().await?;
+        let mut source = File::open(self.as_path()).await?;
+        let permissions = source.metadata().await?.permissions();
+        let mut targets = Vec::with_capacity(dests.len());
+        for dest in dests {
+            targets.push(create_target(self.as_path(), dest.as_ref()).await);
+        }
+        let mut buf = vec![0; 64 * 1024];
+        let read = loop {
+            let len = match source.read(&mut buf).await {
+                Ok(0) => break Ok(()),
+                Ok(len) => len,
+                Err(e) => break Err(e),
+            };
+            let chunk = &buf[..len];
+            let writes = targets.iter_mut().map(|target| async move {
+                if let Ok((path, file)) = target {
+                    if let Err(e) = file.write_all(chunk).await {
+                        let _ = fs::remove_file(&path).await;
+                        *target = Err(e);
+                    }
+                }
+            });
+            join_all(writes).await;
+        };
+        if let Err(e) = read {
+            for (path, _) in targets.iter().flatten() {
+                let _ = fs::remove_file(path).await;
+            }
+            return Err(e);
+        }
+        let mut files = Vec::with_capacity(targets.len());
+        for target in targets {
+            files.push(match target {
+                Ok((path, mut file)) => {
+                    async {
+                        file.flush().await?;
+                        file.set_permissions(permissions.clone()).await?;
+                        Ok(AsyncFileInfo { path })
+                    }
+                    .await
+                }
+                Err(e) => Err(e),
+            });
+        }
+        Ok(files)
+    }
+
+    /// See `FileInfo::copy_new_with`
+    pub async fn copy_new_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &Options,
+    ) -> Result<CopyReport> {
+        let path = options.sanitized(fix_path(path)?);
+        self.copy_filtered(path, options, None).await
+    }
+
+    /// [`AsyncFileInfo::copy_new_with`] writing the content through `filter`
+    pub(crate) async fn copy_filtered<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &Options,
+        filter: Option<&FilterHook>,
+    ) -> Result<CopyReport> {
+        self.validate().await?;
+        let path = fix_path(path)?;
+        if is_same_file(self.as_path(), &path) {
+            return Err(same_file(self.as_path(), &path));
+        }
+        let mut report = CopyReport::new();
+        if options.up_to_date(self.as_path(), &path)? {
+            report.up_to_date += 1;
+            return Ok(report);
+        }
+        let Some(path) = options.resolve_conflict(self.as_path(), path)? else {
+            report.skipped += 1;
+            return Ok(report);
+        };
+        let dest = path.clone();
+        let copy = self.copy_resolved(path, options, filter, report);
+        journaled(options, Operation::Copy, self.as_path(), Some(&dest), copy).await
+    }
+
+    /// Copy to `path` once its conflict is resolved, see
+    /// `FileInfo::copy_resolved`
+    async fn copy_resolved(
+        &self,
+        path: PathBuf,
+        options: &Options,
+        filter: Option<&FilterHook>,
+        mut report: CopyReport,
+    ) -> Result<CopyReport> {
+        let mut attempt = 0;
+        loop {
+            let metadata = self.metadata().await?;
+            let times = options.times_of(&metadata);
+            // the conflict was resolved by replacing the destination, or an
+            // earlier attempt wrote it
+            let overwrite = path.try_exists()?;
+            let (source, dest) = (self.as_path(), path.as_path());
+            let written = retry(options, || async move {
+                let prepare = |written: &Path| match times {
+                    Some(times) => set_file_times(written, times),
+                    None => Ok(()),
+                };
+                copy_staged(source, dest, options, filter, overwrite, prepare).await
+            })
+            .await?;
+            report.bytes = Size(written);
+            // see `FileInfo::copy_filtered`
+            let after = self.metadata().await.ok().map(|data| Snapshot::of(&data));
+            if after == Some(Snapshot::of(&metadata)) {
+                break;
+            }
+            attempt += 1;
+            match options.modified_during_copy {
+                policy if policy.retries(attempt) => continue,
+                ModifiedPolicy::Fail => {
+                    let _ = remove_file_any(&path).await;
+                    return Err(modified_during_copy(self.as_path()));
+                }
+                _ => {
+                    report.modified_during_copy.push(path.clone());
+                    break;
+                }
+            }
+        }
+        #[cfg(all(windows, feature = "ads"))]
+        if options.preserve_ads && !crate::sync::ads::copy_streams(self.as_path(), &path)? {
+            report.streams_skipped.push(path.clone());
+        }
+        #[cfg(all(windows, feature = "acl"))]
+        if options.preserve_acl {
+            crate::sync::acl::copy_acl(self.as_path(), &path, &options.acl, &mut report)?;
+        }
+        report.files.push(path);
+        Ok(report)
+    }
+
+    /// See `FileInfo::rename_keeping_extension`
+    pub async fn rename_keeping_extension(
+        &mut self,
+        stem: impl AsRef<OsStr>,
+        kept: KeptExtension,
+        options: &Options,
+    ) -> Result<bool> {
+        let name = kept.name(stem.as_ref(), self.as_path());
+        self.rename_resolved(renamed(self.as_path(), &name)?, options)
+            .await
+    }
+
+    /// See `FileInfo::change_extension`
+    pub async fn change_extension(
+        &mut self,
+        extension: impl AsRef<OsStr>,
+        options: &Options,
+    ) -> Result<bool> {
+        let dest = renamed_extension(self.as_path(), extension.as_ref())?;
+        self.rename_resolved(dest, options).await
+    }
+
+    /// See `FileInfo::rename_resolved`
+    async fn rename_resolved(&mut self, dest: PathBuf, options: &Options) -> Result<bool> {
+        self.validate().await?;
+        let dest = if is_same_file(self.as_path(), &dest) {
+            dest
+        } else {
+            match options.resolve_conflict(self.as_path(), dest)? {
+                Some(dest) => dest,
+                None => return Ok(false),
+            }
+        };
+        let (source, renamed) = (self.as_path(), rename(self.as_path(), &dest));
+        journaled(options, Operation::Rename, source, Some(&dest), renamed).await?;
+        if !is_dry_run() {
+            self.path = dest;
+        }
+        Ok(true)
+    }
+
+    /// See `FileInfo::move_new_with`
+    ///
+    /// Across filesystems the file is copied then the source removed. When
+    /// the future is dropped during the copy the partial destination is
+    /// removed and the source left in place, see
+    /// [`AsyncFileInfo::move_new_detached`] to finish the move anyway.
+    pub async fn move_new_with<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &Options,
+    ) -> Result<CopyReport> {
+        self.validate().await?;
+        let path = fix_path(path)?;
+        let mut report = CopyReport::new();
+        if is_same_file(self.as_path(), &path) {
+            return Ok(report);
+        }
+        if options.up_to_date(self.as_path(), &path)? {
+            report.up_to_date += 1;
+            return Ok(report);
+        }
+        let Some(path) = options.resolve_conflict(self.as_path(), path)? else {
+            report.skipped += 1;
+            return Ok(report);
+        };
+        let (source, dest) = (self.to_path_buf(), path.clone());
+        let moved = self.move_resolved(path, options, report);
+        journaled(options, Operation::Move, &source, Some(&dest), moved).await
+    }
+
+    /// Move to `path` once its conflict is resolved
+    async fn move_resolved(
+        &mut self,
+        path: PathBuf,
+        options: &Options,
+        mut report: CopyReport,
+    ) -> Result<CopyReport> {
+        match path.parent() {
+            Some(parent) => create_dir_all(parent).await?,
+            None => return Err(invalid_path(&path, "has no parent directory")),
+        }
+        let metadata = self.metadata().await?;
+        let times = options.times_of(&metadata);
+        let overwrite = path.try_exists()?;
+        let (source, dest) = (self.as_path(), path.as_path());
+        let metadata = &metadata;
+        let result = retry(options, || async move {
+            if dest.try_exists()? {
+                remove_file_any(dest).await?;
+            }
+            if same_filesystem(source, dest) && rename(source, dest).await.is_ok() {
+                return Ok(true);
+            }
+            let prepare = |written: &Path| keep_metadata(source, written, metadata);
+            copy_staged(source, dest, options, None, overwrite, prepare).await?;
+            _delete_file(source)
+                .await
+                .map_err(|e| source_not_removed(source, dest, e))?;
+            Ok(false)
+        })
+        .await;
+        report.renamed = match result {
+            Err(e) if is_source_not_removed(&e, self.as_path()) => {
+                self.path = path;
+                return Err(e);
+            }
+            result => result?,
+        };
+        if let Some(times) = times {
+            set_file_times(&path, times)?;
+        }
+        self.path = path.clone();
+        report.bytes = Size(metadata.len());
+        report.files.push(path);
+        Ok(report)
+    }
+
+    /// Serve the file as an attachment named `name`, see
+    /// [`AsyncFileInfo::response`]
+    #[cfg(feature = "hyper")]
     pub async fn response_with_name(&self, name: impl AsRef<str>) -> hyper::Response<hyper::Body> {
-        response(self, name).await
+        response(self, OsStr::new(name.as_ref())).await
     }
+    /// Serve the file as an attachment under its own name. The name is
+    /// sent percent-encoded in `filename*` (RFC 5987), a name that is not
+    /// UTF-8 as its raw bytes, and with its non-ASCII characters replaced
+    /// by `_` in `filename`. The content type follows the extension, or
+    /// the content when the extension is unknown and the `infer` feature is
+    /// on.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::_async::{file::AsyncFileInfo, AsyncAction};
+    /// use hyper::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+    /// tokio::runtime::Runtime::new().unwrap().block_on(async {
+    ///     let root = std::env::temp_dir().join("fdir_async_response");
+    ///     let _ = std::fs::remove_dir_all(&root);
+    ///     let file = AsyncFileInfo::create(root.join("résumé 1.txt")).await.unwrap();
+    ///     let response = file.response().await;
+    ///     assert_eq!(
+    ///         response.headers()[CONTENT_DISPOSITION],
+    ///         "attachment; filename=\"r_sum_ 1.txt\"; filename*=UTF-8''r%C3%A9sum%C3%A9%201.txt"
+    ///     );
+    ///     assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+    ///     #[cfg(feature = "infer")]
+    ///     {
+    ///         let image = AsyncFileInfo::create(root.join("image")).await.unwrap();
+    ///         image.write(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").await.unwrap();
+    ///         assert_eq!(image.response().await.headers()[CONTENT_TYPE], "image/png");
+    ///     }
+    ///     #[cfg(unix)]
+    ///     {
+    ///         use std::os::unix::ffi::OsStrExt;
+    ///         let name = std::ffi::OsStr::from_bytes(b"caf\xe9.txt");
+    ///         let file = AsyncFileInfo::create(root.join(name)).await.unwrap();
+    ///         assert_eq!(
+    ///             file.response().await.headers()[CONTENT_DISPOSITION],
+    ///             "attachment; filename=\"caf_.txt\"; filename*=UTF-8''caf%E9.txt"
+    ///         );
+    ///     }
+    ///     std::fs::remove_dir_all(root).unwrap();
+    /// });
+    /// ```
+    #[cfg(feature = "hyper")]
     pub async fn response(&self) -> hyper::Response<hyper::Body> {
-        let name = self
-            .file_name()
-            .and_then(|name| name.to_str())
-            .unwrap_or("unknown_name");
+        let name = self.file_name().unwrap_or(OsStr::new("unknown_name"));
         response(self, name).await
     }
 }
 
-#[async_trait]
 impl AsyncInfo for AsyncFileInfo {
     fn as_path(&self) -> &Path {
         &self.path
@@ -59,11 +527,21 @@ impl AsyncInfo for AsyncFileInfo {
     async fn size(&self) -> u64 {
         self.metadata().await.map_or(0, |f| f.len())
     }
+
+    async fn validate(&self) -> Result<()> {
+        match self.metadata().await {
+            Ok(data) if data.is_file() => Ok(()),
+            Ok(_) => Err(wrong_kind(self.as_path(), "file")),
+            Err(_) => Err(no_longer_exists(self.as_path())),
+        }
+    }
 }
 
-#[async_trait]
 impl AsyncAction for AsyncFileInfo {
     async fn open<P: AsRef<Path> + Send + Sync>(path: P) -> Result<Self> {
+        let path = fix_path(path)?;
+        // see `FileInfo::open`
+        check_kind(&path, fs::metadata(&path).await, false)?;
         AsyncFileInfo::try_from(File::open(path).await?)
     }
     unsafe fn open_uncheck<P: AsRef<Path>>(path: P) -> Self {
@@ -71,64 +549,139 @@ impl AsyncAction for AsyncFileInfo {
             path: path.as_ref().to_path_buf(),
         }
     }
+    /// See `FileInfo::rename`
     async fn rename<T: AsRef<OsStr> + Send + Sync>(&mut self, name: T) -> Result<()> {
-        let mut new_path = self.path.clone();
-        new_path.set_file_name(name.as_ref());
-        if let Some(ext) = self.as_path().extension() {
-            new_path.set_extension(ext);
+        let new_path = renamed(self.as_path(), name.as_ref())?;
+        if is_dry_run() {
+            return self.validate().await;
         }
         rename(self.as_path(), &new_path).await?;
         self.path = new_path;
         Ok(())
     }
-    async fn copy_new<P: AsRef<Path> + Send + Sync>(&self, path: P) -> TryRecoverResult<()> {
+    async fn copy_new<P: AsRef<Path> + Send + Sync>(&self, path: P) -> TryRecoverResult<'_, ()> {
+        self.validate().await?;
         let path = fix_path(path)?;
-        if path.try_exists()? {
+        if !is_same_file(self.as_path(), &path) && path.try_exists()? {
             return Err(TryRecover::new(
                 already_exist(&path),
                 Status::CopyFile(self, path),
             ));
         }
-        copy(self.as_path(), &path).await?;
+        self.copy_new_with(path, &Options::default()).await?;
         Ok(())
     }
-    async fn move_new<P: AsRef<Path> + Send + Sync>(&mut self, path: P) -> TryRecoverResult<()> {
+    async fn move_new<P: AsRef<Path> + Send + Sync>(
+        &mut self,
+        path: P,
+    ) -> TryRecoverResult<'_, ()> {
+        self.validate().await?;
         let path = fix_path(path)?;
-        if path.try_exists()? {
+        if !is_same_file(self.as_path(), &path) && path.try_exists()? {
             return Err(TryRecover::new(
                 already_exist(&path),
                 Status::MoveFile(self, path),
             ));
         }
-        if let Some(parent) = path.parent() {
-            create_dir_all(parent).await?;
-        } else {
-            INVALID_PATH()?;
+        self.move_new_with(path, &Options::default()).await?;
+        Ok(())
+    }
+}
+
+/// See `fdir::sync::file::copy_staged`
+async fn copy_staged(
+    source: &Path,
+    dest: &Path,
+    options: &Options,
+    filter: Option<&FilterHook>,
+    overwrite: bool,
+    prepare: impl Fn(&Path) -> Result<()>,
+) -> Result<u64> {
+    let Some(temp) = options.staging.temp_path(dest)? else {
+        if dest.try_exists()? {
+            remove_file_any(dest).await?;
         }
-        if is_same_root(self.as_path(), &path) {
-            rename(self.as_path(), &path).await?;
-        } else {
-            copy(self.as_path(), &path).await?;
-            remove_file_any(self.as_path()).await?;
+        let copied = copy_file(source, dest, &options.stream, filter).await?;
+        prepare(dest)?;
+        return Ok(copied.bytes);
+    };
+    // also covers the commit, the temporary file is left when the future
+    // is dropped between the copy and the rename
+    let mut partial = PartialFile::new(&temp);
+    partial.arm();
+    let result = match copy_file(source, &temp, &options.stream, filter).await {
+        Ok(copied) => match prepare(&temp) {
+            Ok(()) => commit_staged(&temp, dest, overwrite)
+                .await
+                .map(|()| copied.bytes),
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+    if result.is_ok() {
+        partial.disarm();
+    }
+    result
+}
+
+/// See `fdir::sync::file::commit_staged`
+async fn commit_staged(temp: &Path, dest: &Path, overwrite: bool) -> Result<()> {
+    if overwrite {
+        if rename(temp, dest).await.is_ok() {
+            return Ok(());
         }
-        self.path = path;
-        Ok(())
+        if dest.try_exists()? {
+            remove_file_any(dest).await?;
+        }
+        return rename(temp, dest).await;
+    }
+    match fs::hard_link(temp, dest).await {
+        Ok(()) => remove_file_any(temp).await,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Err(already_exist(dest)),
+        Err(_) => {
+            if dest.try_exists()? {
+                return Err(already_exist(dest));
+            }
+            rename(temp, dest).await
+        }
+    }
+}
+
+async fn create_parent(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.is_dir() => create_dir_all(parent).await,
+        Some(_) => Ok(()),
+        None => Err(invalid_path(path, "has no parent directory")),
     }
 }
 
-async fn response(f: &AsyncFileInfo, file_name: impl AsRef<str>) -> hyper::Response<hyper::Body> {
+async fn create_target(source: &Path, dest: &Path) -> Result<(PathBuf, File)> {
+    let path = fix_path(push_file_name(source, dest)?)?;
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .await
+        .map_err(|e| match e.kind() {
+            ErrorKind::AlreadyExists => already_exist(&path),
+            _ => e,
+        })?;
+    Ok((path, file))
+}
+
+#[cfg(feature = "hyper")]
+async fn response(f: &AsyncFileInfo, file_name: &OsStr) -> hyper::Response<hyper::Body> {
     use hyper::{
         header::{HeaderValue, ACCESS_CONTROL_EXPOSE_HEADERS, CONTENT_DISPOSITION, CONTENT_TYPE},
         Body, Response, StatusCode,
     };
     match tokio::fs::read(f.as_path()).await {
         Ok(buf) => {
-            let file_name = file_name.as_ref().to_string();
-            let content_type = content_type(Some(file_name.as_ref())).unwrap_or("text/plain");
+            let content_type = content_type(Some(file_name.to_string_lossy().as_ref()))
+                .or_else(|| detected_type(&buf))
+                .unwrap_or("text/plain");
 
-            let file_name: String =
-                url::form_urlencoded::byte_serialize(file_name.as_bytes()).collect();
-            let content_disposition = format!("attachment; filename={}", file_name);
+            let content_disposition = content_disposition(file_name);
             Response::builder()
                 .status(StatusCode::OK)
                 .header(CONTENT_TYPE, HeaderValue::from_static(content_type))
@@ -0,0 +1,121 @@
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+use tokio::{io::Result, task};
+
+use crate::sync::hash::{Algorithm, VerifyReport};
+use crate::sync::{Action, DirectoryInfo, FileInfo, Info};
+
+use super::{dir::AsyncDirectoryInfo, file::AsyncFileInfo, AsyncAction, AsyncInfo};
+
+impl AsyncFileInfo {
+    /// See `FileInfo::hash`, the file is hashed on a blocking thread
+    pub async fn hash(&self, algorithm: Algorithm) -> Result<String> {
+        let file = unsafe { FileInfo::open_uncheck(self.as_path()) };
+        blocking(move || file.hash(algorithm)).await
+    }
+}
+
+impl AsyncDirectoryInfo {
+    /// See `DirectoryInfo::write_manifest`
+    pub async fn write_manifest(
+        &self,
+        dest: impl AsRef<Path>,
+        algorithm: Algorithm,
+    ) -> Result<AsyncFileInfo> {
+        self.write_manifest_parallel(dest, algorithm, 1).await
+    }
+
+    /// See `DirectoryInfo::write_manifest_parallel`. The hashing runs on
+    /// blocking threads, never on the runtime threads.
+    pub async fn write_manifest_parallel(
+        &self,
+        dest: impl AsRef<Path>,
+        algorithm: Algorithm,
+        threads: usize,
+    ) -> Result<AsyncFileInfo> {
+        let dir = unsafe { DirectoryInfo::open_uncheck(self.as_path()) };
+        let dest = dest.as_ref().to_path_buf();
+        let manifest =
+            blocking(move || dir.write_manifest_parallel(dest, algorithm, threads)).await?;
+        Ok(unsafe { AsyncFileInfo::open_uncheck(manifest.as_path()) })
+    }
+
+    /// See `DirectoryInfo::verify_manifest`
+    pub async fn verify_manifest(&self, manifest: impl AsRef<Path>) -> Result<VerifyReport> {
+        self.verify_manifest_parallel(manifest, 1).await
+    }
+
+    /// See `DirectoryInfo::verify_manifest_parallel`
+    pub async fn verify_manifest_parallel(
+        &self,
+        manifest: impl AsRef<Path>,
+        threads: usize,
+    ) -> Result<VerifyReport> {
+        let dir = unsafe { DirectoryInfo::open_uncheck(self.as_path()) };
+        let manifest = manifest.as_ref().to_path_buf();
+        blocking(move || dir.verify_manifest_parallel(manifest, threads)).await
+    }
+
+    /// See `DirectoryInfo::find_duplicates`
+    pub async fn find_duplicates(&self, algorithm: Algorithm) -> Result<Vec<Vec<PathBuf>>> {
+        self.find_duplicates_parallel(algorithm, 1).await
+    }
+
+    /// See `DirectoryInfo::find_duplicates_parallel`, the hashing runs on
+    /// blocking threads
+    pub async fn find_duplicates_parallel(
+        &self,
+        algorithm: Algorithm,
+        threads: usize,
+    ) -> Result<Vec<Vec<PathBuf>>> {
+        let dir = unsafe { DirectoryInfo::open_uncheck(self.as_path()) };
+        blocking(move || dir.find_duplicates_parallel(algorithm, threads)).await
+    }
+}
+
+impl AsyncDirectoryInfo {
+    /// See `DirectoryInfo::store_by_hash`, the file is hashed and copied on
+    /// a blocking thread
+    pub async fn store_by_hash(
+        &self,
+        source: &AsyncFileInfo,
+        algorithm: Algorithm,
+    ) -> Result<(String, AsyncFileInfo)> {
+        self.store_by_hash_with(source, algorithm, 1).await
+    }
+
+    /// See `DirectoryInfo::store_by_hash_with`
+    pub async fn store_by_hash_with(
+        &self,
+        source: &AsyncFileInfo,
+        algorithm: Algorithm,
+        depth: usize,
+    ) -> Result<(String, AsyncFileInfo)> {
+        let dir = unsafe { DirectoryInfo::open_uncheck(self.as_path()) };
+        let source = unsafe { FileInfo::open_uncheck(source.as_path()) };
+        let (digest, object) =
+            blocking(move || dir.store_by_hash_with(&source, algorithm, depth)).await?;
+        Ok((digest, unsafe { AsyncFileInfo::open_uncheck(object.as_path()) }))
+    }
+
+    /// See `DirectoryInfo::retrieve_by_hash`
+    pub async fn retrieve_by_hash(&self, digest: &str) -> Result<AsyncFileInfo> {
+        self.retrieve_by_hash_with(digest, 1).await
+    }
+
+    /// See `DirectoryInfo::retrieve_by_hash_with`
+    pub async fn retrieve_by_hash_with(&self, digest: &str, depth: usize) -> Result<AsyncFileInfo> {
+        let dir = unsafe { DirectoryInfo::open_uncheck(self.as_path()) };
+        let object = dir.retrieve_by_hash_with(digest, depth)?;
+        Ok(unsafe { AsyncFileInfo::open_uncheck(object.as_path()) })
+    }
+}
+
+pub(crate) async fn blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    task::spawn_blocking(f).await.map_err(Error::other)?
+}
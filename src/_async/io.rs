@@ -0,0 +1,114 @@
+//! Async twin of `fdir::io`
+
+use std::path::Path;
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Result};
+
+use crate::hooks::FilterHook;
+use crate::io::{Pump, StreamOptions, StreamReport};
+
+/// See `fdir::io::copy_stream`, the progress hook is called between chunks
+/// and never locked across an `.await`. The next chunk is read while the
+/// current one is written, with two buffers of `buffer_size`.
+pub async fn copy_stream(
+    reader: impl AsyncRead + Unpin,
+    writer: impl AsyncWrite + Unpin,
+    options: &StreamOptions,
+) -> Result<StreamReport> {
+    pump_stream(reader, writer, Pump::new(options)).await
+}
+
+async fn pump_stream(
+    mut reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    mut pump: Pump<'_>,
+) -> Result<StreamReport> {
+    let (mut buf, mut next) = (pump.buffer(), pump.buffer());
+    pump.check()?;
+    let mut len = reader.read(&mut buf).await?;
+    while len > 0 {
+        pump.check()?;
+        let chunk = pump.filter(&buf[..len]);
+        let (read, written) = tokio::join!(reader.read(&mut next), writer.write_all(&chunk));
+        written?;
+        // accounted once written, the rate limit may then hold back the
+        // chunk already read
+        if let Some(pause) = pump.advance(&chunk) {
+            tokio::time::sleep(pause).await;
+        }
+        len = read?;
+        std::mem::swap(&mut buf, &mut next);
+    }
+    let tail = pump.filter(&[]);
+    if !tail.is_empty() {
+        writer.write_all(&tail).await?;
+        pump.advance(&tail);
+    }
+    writer.flush().await?;
+    Ok(pump.finish())
+}
+
+/// See `fdir::io::copy_file`
+pub(crate) async fn copy_file(
+    source: &Path,
+    dest: &Path,
+    options: &StreamOptions,
+    filter: Option<&FilterHook>,
+) -> Result<StreamReport> {
+    let mut reader = File::open(source).await?;
+    let metadata = reader.metadata().await?;
+    // dropped after the writer, which must be closed first on Windows
+    let mut partial = PartialFile::new(dest);
+    let mut writer = File::create(dest).await?;
+    partial.arm();
+    let pump = Pump::new(options)
+        .with_filter(filter)
+        .with_len(metadata.len());
+    let result = match pump_stream(&mut reader, &mut writer, pump).await {
+        Ok(report) => writer
+            .set_permissions(metadata.permissions())
+            .await
+            .map(|()| report),
+        Err(e) => Err(e),
+    };
+    if result.is_ok() {
+        partial.disarm();
+    }
+    result
+}
+
+/// Removes a file being written when dropped armed: when the write failed,
+/// or when the future writing it was dropped, e.g. by a cancelled request
+/// or a timeout. The file is removed without waiting on the runtime, drop
+/// cannot await.
+#[derive(Debug)]
+pub(crate) struct PartialFile<'a> {
+    path: &'a Path,
+    armed: bool,
+}
+
+impl<'a> PartialFile<'a> {
+    /// Not armed until [`PartialFile::arm`], so that a file the write did
+    /// not create is never removed
+    pub(crate) fn new(path: &'a Path) -> Self {
+        Self { path, armed: false }
+    }
+
+    pub(crate) fn arm(&mut self) {
+        self.armed = true;
+    }
+
+    /// The file is complete and stays
+    pub(crate) fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for PartialFile<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            let _ = crate::sync::remove_file_any(self.path);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs::FileType;
+use std::path::Path;
+
+use tokio::{fs, io::Result};
+
+use crate::error::into_own_subtree;
+use crate::sync::merge::{plan, remove_emptied, Step};
+use crate::{is_ancestor_of, is_same_file, ConflictPolicy, MergeReport, Options};
+
+use super::{dir::AsyncDirectoryInfo, file::AsyncFileInfo, AsyncAction, AsyncInfo};
+
+impl AsyncDirectoryInfo {
+    /// See `DirectoryInfo::merge_into`
+    pub async fn merge_into(
+        &mut self,
+        dest: &AsyncDirectoryInfo,
+        conflict: ConflictPolicy,
+    ) -> Result<MergeReport> {
+        self.validate().await?;
+        dest.validate().await?;
+        let mut report = MergeReport::default();
+        if is_same_file(self.as_path(), dest.as_path()) {
+            return Ok(report);
+        }
+        if is_ancestor_of(self.as_path(), dest.as_path()) {
+            return Err(into_own_subtree(self.as_path(), dest.as_path()));
+        }
+        let mut sources = vec![self.as_path().to_path_buf()];
+        let mut pending = vec![(self.as_path().to_path_buf(), dest.as_path().to_path_buf())];
+        while let Some((dir, to)) = pending.pop() {
+            let mut entries = Vec::new();
+            let mut read_dir = fs::read_dir(&dir).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                entries.push((entry.path(), entry.file_type().await?));
+            }
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (source, file_type) in entries {
+                let target = to.join(source.file_name().unwrap_or_default());
+                match plan(&source, file_type, target, conflict)? {
+                    Step::Merge(target) => {
+                        report.merged.push(target.clone());
+                        sources.push(source.clone());
+                        pending.push((source, target));
+                    }
+                    Step::Move(target) => {
+                        move_entry(&source, file_type, &target, false).await?;
+                        report.moved.push(target);
+                    }
+                    Step::Replace(target) => {
+                        move_entry(&source, file_type, &target, true).await?;
+                        report.overwritten.push(target);
+                    }
+                    Step::Skip => report.skipped.push(source),
+                }
+            }
+        }
+        report.sort();
+        remove_emptied(sources);
+        if !self.as_path().exists() {
+            *self = dest.clone();
+        }
+        Ok(report)
+    }
+}
+
+async fn move_entry(
+    source: &Path,
+    file_type: FileType,
+    target: &Path,
+    overwrite: bool,
+) -> Result<()> {
+    let options = Options::new().overwrite(overwrite);
+    if file_type.is_dir() {
+        let mut dir = unsafe { AsyncDirectoryInfo::open_uncheck(source) };
+        dir.move_new_with(target, &options).await?;
+    } else if file_type.is_symlink() {
+        fs::rename(source, target).await?;
+    } else {
+        let mut file = unsafe { AsyncFileInfo::open_uncheck(source) };
+        file.move_new_with(target, &options).await?;
+    }
+    Ok(())
+}
@@ -1,89 +1,422 @@
+pub mod background;
+pub mod descriptor;
 pub mod dir;
+pub mod entry;
 pub mod file;
+pub mod hash;
+pub mod io;
+pub mod merge;
+pub mod permissions;
 pub mod recover;
-use async_trait::async_trait;
+pub mod sample;
+pub mod scheduler;
+pub mod swap;
+pub mod upload;
+pub mod visit;
+use std::borrow::Cow;
 use std::ffi::OsStr;
-use std::fs::{Metadata, Permissions};
-use std::path::Path;
+use std::fs::{FileType, Metadata, Permissions};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use tokio::{
     fs::{self, remove_dir_all, remove_file},
     io::Result,
 };
 
-use crate::push_file_name;
+use crate::convert::{QuotedDisplay, RelativeDisplay};
+use crate::error::not_under_base;
+use crate::hooks::Transform;
+use crate::journal::{planned, record, Operation, Written};
+use crate::options::RETRY_DELAY;
+use crate::sync::batch::{fix_paths, OpenMany};
+use crate::{fix_path, push_file_name, Options, PathPolicy};
 
 use self::dir::AsyncDirectoryInfo;
 use self::file::AsyncFileInfo;
 use self::recover::TryRecoverResult;
-#[async_trait]
+/// Methods are `async` with `Send` futures, without the boxing of
+/// `async_trait`. Implementations can use plain `async fn`.
 pub trait AsyncInfo: Sized + Send + Sync {
     fn as_path(&self) -> &Path;
+    /// See `Info::to_path_buf`
+    fn to_path_buf(&self) -> PathBuf {
+        self.as_path().to_path_buf()
+    }
+    /// See `Info::parent_path`
+    fn parent_path(&self) -> Option<&Path> {
+        self.as_path().parent()
+    }
     fn file_name(&self) -> Option<&OsStr> {
         self.as_path().file_name()
     }
-    async fn metadata(&self) -> Result<Metadata>;
-    async fn size(&self) -> u64;
-    /// Return None if the path is a root directory
-    async fn parent(&self) -> Option<AsyncDirectoryInfo> {
-        let parent = self.as_path().parent()?;
+    /// See `Info::file_name_str`
+    fn file_name_str(&self) -> Result<&str> {
+        crate::file_name_str(self.as_path())
+    }
+    /// See `Info::path_str`
+    fn path_str(&self) -> Result<&str> {
+        crate::path_str(self.as_path())
+    }
+    /// See `Info::to_portable_string`
+    fn to_portable_string(&self) -> Result<String> {
+        self.path_str().map(str::to_owned)
+    }
+    /// See `Info::to_encoded_string`
+    fn to_encoded_string(&self) -> String {
+        crate::convert::encode_path(self.as_path())
+    }
+    /// See `Info::file_name_lossy`
+    fn file_name_lossy(&self) -> Cow<'_, str> {
+        self.file_name()
+            .map_or(Cow::Borrowed(""), |name| name.to_string_lossy())
+    }
+    /// See `Info::stem`
+    fn stem(&self) -> Option<&OsStr> {
+        self.as_path().file_stem()
+    }
+    /// See `Info::extension`
+    fn extension(&self) -> Option<&OsStr> {
+        self.as_path().extension().filter(|ext| !ext.is_empty())
+    }
+    fn extension_lower(&self) -> Option<String> {
+        self.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+    }
+    /// See `Info::full_extension`
+    fn full_extension(&self) -> Option<&OsStr> {
+        crate::full_extension(self.file_name()?)
+    }
+    /// See `Info::has_extension`
+    fn has_extension(&self, extension: impl AsRef<str>) -> bool {
+        self.file_name()
+            .is_some_and(|name| crate::has_extension(name, extension.as_ref()))
+    }
+    fn with_extension(&self, extension: impl AsRef<OsStr>) -> PathBuf {
+        self.as_path().with_extension(extension)
+    }
+    fn metadata(&self) -> impl Future<Output = Result<Metadata>> + Send;
+    /// See `Info::file_id`
+    fn file_id(&self) -> Result<crate::FileId> {
+        crate::FileId::of(self.as_path())
+    }
+    /// See `Info::filesystem_id`
+    fn filesystem_id(&self) -> Result<crate::FsId> {
+        crate::FsId::of(self.as_path())
+    }
+    /// See `Info::mount_point`, read on a blocking thread
+    fn mount_point(&self) -> impl Future<Output = Result<PathBuf>> + Send {
+        let path = self.to_path_buf();
+        hash::blocking(move || crate::mount_point(path))
+    }
+    fn size(&self) -> impl Future<Output = u64> + Send;
+    /// See `Info::size_typed`
+    fn size_typed(&self) -> impl Future<Output = crate::Size> + Send {
+        async move { crate::Size(self.size().await) }
+    }
+    fn exists(&self) -> bool {
+        self.as_path().exists()
+    }
+    /// Check that the path still exists and is still the expected kind
+    /// (file or directory)
+    fn validate(&self) -> impl Future<Output = Result<()>> + Send;
+    /// See `Info::sibling`
+    fn sibling(&self, name: impl AsRef<OsStr>) -> Result<PathBuf> {
+        let name = crate::checked_name(name.as_ref())?;
+        Ok(self.as_path().with_file_name(name))
+    }
+    /// See `Info::try_parent`
+    fn try_parent(&self) -> impl Future<Output = Result<Option<AsyncDirectoryInfo>>> + Send {
+        async move {
+            match self.parent_path() {
+                Some(parent) => AsyncDirectoryInfo::open(parent).await.map(Some),
+                None => Ok(None),
+            }
+        }
+    }
+    /// Return None if the path is a root directory, or if the parent
+    /// cannot be opened
+    #[deprecated(note = "use `try_parent`, which reports why the parent cannot be opened")]
+    fn parent(&self) -> impl Future<Output = Option<AsyncDirectoryInfo>> + Send {
+        async move { self.try_parent().await.ok().flatten() }
+    }
+
+    fn permissions(&self) -> impl Future<Output = Result<Permissions>> + Send {
+        async move { self.metadata().await.map(|data| data.permissions()) }
+    }
+
+    fn read_only(&self) -> impl Future<Output = Result<bool>> + Send {
+        async move {
+            self.metadata()
+                .await
+                .map(|data| data.permissions().readonly())
+        }
+    }
 
-        AsyncDirectoryInfo::open(parent).await.ok()
+    /// Return the path relative to `base`, or an error if the path is not under `base`
+    fn relative_to<P: AsRef<Path>>(&self, base: P) -> Result<PathBuf> {
+        let base = fix_path(base)?;
+        self.as_path()
+            .strip_prefix(&base)
+            .map(|path| path.to_path_buf())
+            .map_err(|_| not_under_base(self.as_path(), base))
     }
 
-    async fn permissions(&self) -> Result<Permissions> {
-        self.metadata().await.map(|data| data.permissions())
+    /// See `Info::display_relative_to`
+    fn display_relative_to<P: AsRef<Path>>(&self, base: P) -> RelativeDisplay<'_> {
+        RelativeDisplay::new(self.as_path(), base.as_ref())
     }
 
-    async fn read_only(&self) -> Result<bool> {
-        self.metadata()
-            .await
-            .map(|data| data.permissions().readonly())
+    /// See `Info::display_quoted`
+    fn display_quoted(&self) -> QuotedDisplay<'_> {
+        QuotedDisplay::new(self.as_path())
     }
 }
-#[async_trait]
+
 pub trait AsyncAction: AsyncInfo {
-    async fn open<P: AsRef<Path> + Send + Sync>(path: P) -> Result<Self>;
+    fn open<P: AsRef<Path> + Send + Sync>(path: P) -> impl Future<Output = Result<Self>> + Send;
+
+    /// Same as [`AsyncAction::open`], after checking the path against
+    /// `policy` with [`fix_path_strict`](crate::fix_path_strict)
+    fn open_with_policy<P: AsRef<Path>>(
+        path: P,
+        policy: &PathPolicy,
+    ) -> impl Future<Output = Result<Self>> + Send {
+        let path = crate::fix_path_strict(path, policy);
+        async move { Self::open(path?).await }
+    }
 
     /// # Safety
     /// This function is unsafe as it does not check or fix the path.
-    /// please make sure the path is correct absolute path 
-    /// 
+    /// please make sure the path is correct absolute path
+    ///
     /// # Example
     unsafe fn open_uncheck<P: AsRef<Path>>(path: P) -> Self;
+    /// See `Action::from_portable_string`
+    fn from_portable_string(path: &str) -> impl Future<Output = Result<Self>> + Send {
+        let path = PathBuf::from(path);
+        async move { Self::open(path).await }
+    }
+    /// See `Action::from_encoded_string`
+    fn from_encoded_string(encoded: &str) -> impl Future<Output = Result<Self>> + Send {
+        let path = crate::convert::decode_path(encoded);
+        async move { Self::open(path?).await }
+    }
     /// Rename a file or directory
-    async fn rename<T: AsRef<OsStr> + Send + Sync>(&mut self, name: T) -> Result<()>;
-    async fn set_readonly(&self, readonly: bool) -> Result<()> {
-        let mut perm = self.metadata().await?.permissions();
-        perm.set_readonly(readonly);
-        self.set_permissions(perm).await
-    }
-    async fn set_permissions(&self, perm: Permissions) -> Result<()> {
-        fs::set_permissions(self.as_path(), perm).await
-    }
-    async fn delete(self) -> Result<()> {
-        if self.read_only().await? {
-            self.set_readonly(false).await?;
+    fn rename<T: AsRef<OsStr> + Send + Sync>(
+        &mut self,
+        name: T,
+    ) -> impl Future<Output = Result<()>> + Send;
+    /// Resolve symlinks and relative components, and store the resulting path
+    fn canonicalize(&mut self) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let path = fs::canonicalize(self.as_path()).await?;
+            *self = unsafe { Self::open_uncheck(path) };
+            Ok(())
         }
-        if self.as_path().is_dir() {
-            remove_dir_all(self.as_path()).await
-        } else {
-            remove_file(self.as_path()).await
+    }
+    fn set_readonly(&self, readonly: bool) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let mut perm = self.metadata().await?.permissions();
+            perm.set_readonly(readonly);
+            self.set_permissions(perm).await
+        }
+    }
+    fn set_permissions(&self, perm: Permissions) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            if crate::is_dry_run() {
+                return self.metadata().await.map(drop);
+            }
+            fs::set_permissions(self.as_path(), perm).await
         }
     }
-    async fn copy_to<P: AsRef<Path> + Send + Sync>(&self, path: P) -> TryRecoverResult<()> {
-        let path = push_file_name(self.file_name(), path)?;
-        self.copy_new(path).await
+    fn delete(self) -> impl Future<Output = Result<()>> + Send {
+        async move { self.delete_with(&Options::default()).await }
     }
-    async fn copy_new<P: AsRef<Path> + Send + Sync>(&self, path: P) -> TryRecoverResult<()>;
-    async fn move_to<P: AsRef<Path> + Send + Sync>(&mut self, path: P) -> TryRecoverResult<()> {
-        let path = push_file_name(self.file_name(), path)?;
-        self.move_new(path).await
+    /// See `Action::delete_with`
+    fn delete_with(self, options: &Options) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let metadata = fs::symlink_metadata(self.as_path()).await;
+            if options.has_filters() && metadata.is_ok_and(|data| data.is_dir()) {
+                return dir::_delete_filtered(self.as_path(), options).await;
+            }
+            let op = retry(options, || _delete(self.as_path()));
+            journaled(options, Operation::Delete, self.as_path(), None, op).await
+        }
+    }
+    /// Copy into the existing directory `dest`, see `Action::copy_into`
+    fn copy_into(&self, dest: &AsyncDirectoryInfo) -> impl Future<Output = Result<Self>> + Send {
+        async move {
+            dest.validate().await?;
+            let path = push_file_name(self.as_path(), dest.as_path())?;
+            self.copy_new(&path).await?;
+            Ok(unsafe { Self::open_uncheck(path) })
+        }
+    }
+    /// Move into the existing directory `dest`, see `Action::move_into`
+    fn move_into(
+        &mut self,
+        dest: &AsyncDirectoryInfo,
+    ) -> impl Future<Output = Result<Self>> + Send {
+        async move {
+            dest.validate().await?;
+            let path = push_file_name(self.as_path(), dest.as_path())?;
+            self.move_new(&path).await?;
+            Ok(unsafe { Self::open_uncheck(self.as_path()) })
+        }
+    }
+    fn copy_to<P: AsRef<Path> + Send + Sync>(
+        &self,
+        path: P,
+    ) -> impl Future<Output = TryRecoverResult<'_, ()>> + Send {
+        async move {
+            let path = push_file_name(self.as_path(), path)?;
+            self.copy_new(path).await
+        }
+    }
+    fn copy_new<P: AsRef<Path> + Send + Sync>(
+        &self,
+        path: P,
+    ) -> impl Future<Output = TryRecoverResult<'_, ()>> + Send;
+    fn move_to<P: AsRef<Path> + Send + Sync>(
+        &mut self,
+        path: P,
+    ) -> impl Future<Output = TryRecoverResult<'_, ()>> + Send {
+        async move {
+            let path = push_file_name(self.as_path(), path)?;
+            self.move_new(path).await
+        }
+    }
+    fn move_new<P: AsRef<Path> + Send + Sync>(
+        &mut self,
+        path: P,
+    ) -> impl Future<Output = TryRecoverResult<'_, ()>> + Send;
+}
+
+/// Async counterpart of `Options::transform`, see
+/// [`AsyncDirectoryInfo::copy_new_transformed`]. Closures taking the
+/// relative path by value and returning a future implement it, as do types
+/// with an `async fn transform`.
+pub trait AsyncTransform: Sync {
+    /// Called with the path of each file relative to the source directory
+    fn transform(&self, relative: &Path) -> impl Future<Output = Option<Transform>> + Send;
+}
+
+impl<F, Fut> AsyncTransform for F
+where
+    F: Fn(PathBuf) -> Fut + Sync,
+    Fut: Future<Output = Option<Transform>> + Send,
+{
+    fn transform(&self, relative: &Path) -> impl Future<Output = Option<Transform>> + Send {
+        self(relative.to_path_buf())
+    }
+}
+
+async fn _delete(path: &Path) -> Result<()> {
+    // see `sync::_delete`
+    let metadata = fs::symlink_metadata(path).await?;
+    if metadata.is_symlink() {
+        return remove_link(path, metadata.file_type()).await;
+    }
+    if metadata.permissions().readonly() {
+        // the permission helpers are the same for files and directories
+        let entry = unsafe { AsyncFileInfo::open_uncheck(path) };
+        entry.set_readonly(false).await?;
+    }
+    if metadata.is_dir() {
+        #[cfg(windows)]
+        permissions::set_readonly_tree(path, false).await;
+        remove_dir_all(path).await
+    } else {
+        remove_file(path).await
     }
-    async fn move_new<P: AsRef<Path> + Send + Sync>(&mut self, path: P) -> TryRecoverResult<()>;
+}
+
+/// See `sync::remove_link`
+async fn remove_link(path: &Path, file_type: FileType) -> Result<()> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::FileTypeExt;
+        if file_type.is_symlink_dir() {
+            return fs::remove_dir(path).await;
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = file_type;
+    remove_file(path).await
+}
+
+/// Async twin of `Options::retry`
+pub(crate) async fn retry<T, F, Fut>(options: &Options, mut op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(e) if options.should_retry(attempt + 1, &e) => {
+                attempt += 1;
+                tokio::time::sleep(RETRY_DELAY * attempt).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Async twin of `Options::journaled`
+pub(crate) async fn journaled<T: Written>(
+    options: &Options,
+    operation: Operation,
+    source: &Path,
+    dest: Option<&Path>,
+    op: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    if crate::is_dry_run() {
+        return planned(options.journal.as_deref(), operation, source, dest);
+    }
+    let Some(journal) = options.journal.as_deref() else {
+        return op.await;
+    };
+    let started = SystemTime::now();
+    let result = op.await;
+    record(journal, operation, source, dest, started, result)
+}
+
+/// See `sync::_delete_file`
+async fn _delete_file(path: &Path) -> Result<()> {
+    unsafe { AsyncFileInfo::open_uncheck(path) }
+        .set_readonly(false)
+        .await?;
+    remove_file(path).await
 }
 
 async fn remove_file_any(path: &Path) -> Result<()> {
     let f = unsafe { AsyncFileInfo::open_uncheck(path) };
     f.delete().await
 }
+
+/// Open and validate every path in turn, see `fdir::sync::batch`
+pub(crate) async fn open_many<T, I>(paths: I) -> OpenMany<T>
+where
+    T: AsyncAction,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut opened = (Vec::new(), Vec::new());
+    for (path, fixed) in fix_paths(paths) {
+        let result = match fixed {
+            Ok(fixed) => {
+                let entry = unsafe { T::open_uncheck(fixed) };
+                entry.validate().await.map(|_| entry)
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(entry) => opened.0.push(entry),
+            Err(e) => opened.1.push((path, e)),
+        }
+    }
+    opened
+}
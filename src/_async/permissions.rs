@@ -0,0 +1,146 @@
+use std::fs::{Metadata, Permissions};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+use futures::stream::{self, StreamExt};
+use tokio::{fs, io::Result};
+
+use crate::RecursiveResult;
+
+use super::{dir::AsyncDirectoryInfo, AsyncInfo};
+
+/// Entries changed or directories listed at the same time
+const CONCURRENCY: usize = 32;
+
+impl AsyncDirectoryInfo {
+    /// See `DirectoryInfo::set_readonly_recursive`. The entries of one level
+    /// are changed concurrently, at most 32 at a time.
+    pub async fn set_readonly_recursive(&self, readonly: bool) -> Result<RecursiveResult> {
+        self.validate().await?;
+        Ok(set_readonly_tree(self.as_path(), readonly).await)
+    }
+
+    /// See `DirectoryInfo::set_permissions_recursive`
+    pub async fn set_permissions_recursive(
+        &self,
+        file_perm: Permissions,
+        dir_perm: Permissions,
+    ) -> Result<RecursiveResult> {
+        self.validate().await?;
+        let top_down = !dir_perm.readonly();
+        let change = |path: PathBuf, metadata: Metadata| {
+            let perm = if metadata.is_dir() {
+                &dir_perm
+            } else {
+                &file_perm
+            };
+            let perm = perm.clone();
+            async move { fs::set_permissions(path, perm).await.map(|_| true) }
+        };
+        Ok(change_tree(self.as_path(), top_down, change).await)
+    }
+}
+
+/// Async twin of `sync::permissions::set_readonly_tree`
+pub(crate) async fn set_readonly_tree(path: &Path, readonly: bool) -> RecursiveResult {
+    let change = move |path: PathBuf, metadata: Metadata| async move {
+        let mut perm = metadata.permissions();
+        if perm.readonly() == readonly {
+            return Ok(false);
+        }
+        perm.set_readonly(readonly);
+        fs::set_permissions(path, perm).await.map(|_| true)
+    };
+    change_tree(path, !readonly, change).await
+}
+
+/// Call `change` for `path` and every entry below it level by level, a
+/// level before the next one when `top_down` and after it otherwise. Links
+/// are skipped.
+async fn change_tree<F, Fut>(path: &Path, top_down: bool, change: F) -> RecursiveResult
+where
+    F: Fn(PathBuf, Metadata) -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let mut result = RecursiveResult::default();
+    // the directory itself may be reached through a link
+    let mut level = match fs::metadata(path).await {
+        Ok(metadata) => vec![(path.to_path_buf(), metadata)],
+        Err(e) => {
+            result.failed.push((path.to_path_buf(), e));
+            return result;
+        }
+    };
+    // bottom-up, every level is listed before any is changed
+    let mut levels = Vec::new();
+    while !level.is_empty() {
+        if top_down {
+            apply(&level, &change, &mut result).await;
+        }
+        let dirs = level.iter().filter(|(_, metadata)| metadata.is_dir());
+        let listings: Vec<_> = stream::iter(dirs)
+            .map(|(dir, _)| list(dir))
+            .buffer_unordered(CONCURRENCY)
+            .collect()
+            .await;
+        let mut next = Vec::new();
+        for (entries, failed) in listings {
+            next.extend(entries);
+            result.failed.extend(failed);
+        }
+        if !top_down {
+            levels.push(level);
+        }
+        level = next;
+    }
+    for level in levels.iter().rev() {
+        apply(level, &change, &mut result).await;
+    }
+    result
+}
+
+async fn apply<F, Fut>(level: &[(PathBuf, Metadata)], change: &F, result: &mut RecursiveResult)
+where
+    F: Fn(PathBuf, Metadata) -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let changes: Vec<_> = stream::iter(level)
+        .map(|(path, metadata)| async move { (path, change(path.clone(), metadata.clone()).await) })
+        .buffer_unordered(CONCURRENCY)
+        .collect()
+        .await;
+    for (path, changed) in changes {
+        match changed {
+            Ok(changed) => result.changed += u64::from(changed),
+            Err(e) => result.failed.push((path.clone(), e)),
+        }
+    }
+}
+
+/// The entries of `dir` other than links, and the errors met reading it
+async fn list(dir: &Path) -> (Vec<(PathBuf, Metadata)>, Vec<(PathBuf, std::io::Error)>) {
+    let mut listing = (Vec::new(), Vec::new());
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            listing.1.push((dir.to_path_buf(), e));
+            return listing;
+        }
+    };
+    loop {
+        let entry = match entries.next_entry().await {
+            Ok(Some(entry)) => entry,
+            Ok(None) => break,
+            Err(e) => {
+                listing.1.push((dir.to_path_buf(), e));
+                break;
+            }
+        };
+        match entry.metadata().await {
+            Ok(metadata) if metadata.is_symlink() => (),
+            Ok(metadata) => listing.0.push((entry.path(), metadata)),
+            Err(e) => listing.1.push((entry.path(), e)),
+        }
+    }
+    listing
+}
@@ -1,4 +1,3 @@
-use tokio::fs::{copy, rename};
 use std::io::Result;
 use std::io::{Error, ErrorKind};
 use std::path::PathBuf;
@@ -33,9 +32,9 @@ pub struct TryRecover<'a> {
 }
 use Status::*;
 
-use super::dir::{AsyncDirectoryInfo, _write_dir};
+use super::dir::AsyncDirectoryInfo;
+use crate::Options;
 use super::file::AsyncFileInfo;
-use super::{remove_file_any, AsyncAction, AsyncInfo};
 impl<'a> TryRecover<'a> {
     pub fn new(error: Error, status: Status<'a>) -> TryRecover<'a> {
         Self {
@@ -44,36 +43,21 @@ impl<'a> TryRecover<'a> {
         }
     }
     pub async fn try_recover(self) -> Result<()> {
+        self.try_recover_with(&Options::new()).await
+    }
+    /// See `TryRecover::try_recover_with`
+    pub async fn try_recover_with(self, options: &Options) -> Result<()> {
         if self.error.kind() == ErrorKind::AlreadyExists {
             let status = match self.status {
                 Some(status) => status,
                 _ => return Err(self.error),
             };
+            let options = options.clone().overwrite(true);
             match status {
-                CopyFile(f, to) => {
-                    remove_file_any(&to).await?;
-                    copy(f.as_path(), to).await?;
-                    Ok(())
-                }
-                MoveFile(f, to) => {
-                    remove_file_any(&to).await?;
-                    if rename(f.as_path(), &to).await.is_err() {
-                        copy(f.as_path(), &to).await?;
-                        remove_file_any(f.as_path()).await?;
-                        *f = unsafe { AsyncFileInfo::open_uncheck(to) };
-                    }
-                    Ok(())
-                }
-                CopyDirectory(dir, to) => {
-                    _write_dir(dir, &to, true).await
-                }
-                MoveDirectory(dir, to) => {
-                    if rename(dir.as_path(), to.as_path()).await.is_err() {
-                        _write_dir(dir, &to, false).await?;
-                    }
-                    *dir = unsafe { AsyncDirectoryInfo::open_uncheck(to) };
-                    Ok(())
-                }
+                CopyFile(f, to) => f.copy_new_with(to, &options).await.map(drop),
+                MoveFile(f, to) => f.move_new_with(to, &options).await.map(drop),
+                CopyDirectory(dir, to) => dir.copy_new_with(to, &options).await.map(drop),
+                MoveDirectory(dir, to) => dir.move_new_with(to, &options).await.map(drop),
             }
         } else {
             Err(self.error)
@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+use std::fs::Metadata;
+use std::path::Path;
+
+use tokio::{fs, io::Result};
+
+use crate::sync::sample::Reservoir;
+
+use super::{dir::AsyncDirectoryInfo, file::AsyncFileInfo, AsyncAction, AsyncInfo};
+
+/// See `DirectoryInfo::sample_matching`
+type FilePred<'a> = &'a (dyn Fn(&Path, &Metadata) -> bool + Sync);
+
+impl AsyncDirectoryInfo {
+    /// See `DirectoryInfo::sample`
+    pub async fn sample(
+        &self,
+        n: usize,
+        recursive: bool,
+        seed: Option<u64>,
+    ) -> Result<Vec<AsyncFileInfo>> {
+        self._sample(n, recursive, seed, None).await
+    }
+
+    /// See `DirectoryInfo::sample_matching`
+    pub async fn sample_matching(
+        &self,
+        n: usize,
+        recursive: bool,
+        seed: Option<u64>,
+        pred: impl Fn(&Path, &Metadata) -> bool + Sync,
+    ) -> Result<Vec<AsyncFileInfo>> {
+        self._sample(n, recursive, seed, Some(&pred)).await
+    }
+
+    /// See `DirectoryInfo::random_file`
+    pub async fn random_file(&self, recursive: bool) -> Result<Option<AsyncFileInfo>> {
+        Ok(self.sample(1, recursive, None).await?.pop())
+    }
+
+    /// Breadth-first over the entries, symlinks are not followed
+    async fn _sample(
+        &self,
+        n: usize,
+        recursive: bool,
+        seed: Option<u64>,
+        pred: Option<FilePred<'_>>,
+    ) -> Result<Vec<AsyncFileInfo>> {
+        let mut reservoir = Reservoir::new(n, seed);
+        let mut queue = VecDeque::new();
+        queue.push_back(self.as_path().to_path_buf());
+        while let Some(dir) = queue.pop_front() {
+            let mut read_dir = fs::read_dir(dir).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() && recursive {
+                    queue.push_back(entry.path());
+                }
+                if !file_type.is_file() {
+                    continue;
+                }
+                let path = entry.path();
+                if let Some(pred) = pred {
+                    if !pred(&path, &entry.metadata().await?) {
+                        continue;
+                    }
+                }
+                let relative = path.strip_prefix(self.as_path()).unwrap_or(&path);
+                reservoir.offer(relative, &path);
+            }
+        }
+        Ok(reservoir
+            .into_paths()
+            .into_iter()
+            .map(|path| unsafe { AsyncFileInfo::open_uncheck(path) })
+            .collect())
+    }
+}
@@ -0,0 +1,201 @@
+use std::cmp::Reverse;
+use std::future::poll_fn;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::{Options, PauseToken};
+
+use super::{background::AsyncProgressHandle, dir::AsyncDirectoryInfo, file::AsyncFileInfo};
+
+/// How urgent a job of a [`CopyScheduler`] is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Run when nothing more urgent waits
+    Background,
+    #[default]
+    Normal,
+    /// Run before any other job
+    Interactive,
+}
+
+/// A queue of background copies running the most urgent jobs first
+///
+/// At most `concurrency` jobs run at once, picked by [`Priority`] and
+/// then in the order they were submitted. When a more urgent job comes
+/// in, a running job of lower priority is paused before its next file and
+/// goes on once a slot is free again, so a single file being copied is
+/// never interrupted. Each job is a regular [`AsyncProgressHandle`],
+/// cancelling a queued job drops it from the queue.
+///
+/// The scheduler drives the jobs through [`Options::pause`], a token
+/// already set there is replaced. Clones share the same queue.
+///
+/// # Examples
+/// ```no_run
+/// use fdir::_async::dir::AsyncDirectoryInfo;
+/// use fdir::_async::scheduler::{CopyScheduler, Priority};
+/// use fdir::_async::AsyncAction;
+/// use fdir::Options;
+/// # async fn run() -> std::io::Result<()> {
+/// let scheduler = CopyScheduler::new(1);
+/// let backup = AsyncDirectoryInfo::open("photos").await?;
+/// let backup = scheduler.copy_dir(&backup, "backup", Options::new(), Priority::Background);
+/// let docs = AsyncDirectoryInfo::open("docs").await?;
+/// // runs first, the backup is held at its next file
+/// let docs = scheduler.copy_dir(&docs, "docs_copy", Options::new(), Priority::Interactive);
+/// docs.join().await?;
+/// backup.join().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CopyScheduler {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    concurrency: usize,
+    jobs: Mutex<Jobs>,
+}
+
+#[derive(Debug, Default)]
+struct Jobs {
+    next_id: u64,
+    queued: Vec<Queued>,
+}
+
+/// A job submitted and not over yet
+#[derive(Debug)]
+struct Queued {
+    id: u64,
+    priority: Priority,
+    pause: PauseToken,
+}
+
+impl Shared {
+    fn submit(self: &Arc<Self>, priority: Priority) -> Job {
+        let pause = PauseToken::new();
+        pause.pause();
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        jobs.queued.push(Queued {
+            id,
+            priority,
+            pause: pause.clone(),
+        });
+        self.schedule(&mut jobs);
+        Job(JobRef {
+            shared: Arc::clone(self),
+            id,
+            pause,
+        })
+    }
+
+    fn release(&self, id: u64) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.queued.retain(|job| job.id != id);
+        self.schedule(&mut jobs);
+    }
+
+    /// Resume the most urgent jobs and pause the others
+    fn schedule(&self, jobs: &mut Jobs) {
+        jobs.queued
+            .sort_by_key(|job| (Reverse(job.priority), job.id));
+        for (rank, job) in jobs.queued.iter().enumerate() {
+            if rank < self.concurrency {
+                job.pause.resume();
+            } else {
+                job.pause.pause();
+            }
+        }
+    }
+}
+
+/// A handle on a job, kept by its `AsyncProgressHandle`
+#[derive(Debug, Clone)]
+pub(crate) struct JobRef {
+    shared: Arc<Shared>,
+    id: u64,
+    pause: PauseToken,
+}
+
+impl JobRef {
+    /// Leave the queue, the job runs at once to see it was cancelled
+    pub(crate) fn cancel(&self) {
+        self.shared.release(self.id);
+        self.pause.resume();
+    }
+}
+
+/// The job owned by its task, leaving the queue once dropped
+#[derive(Debug)]
+pub(crate) struct Job(JobRef);
+
+impl Job {
+    pub(crate) fn to_ref(&self) -> JobRef {
+        self.0.clone()
+    }
+
+    /// Wait for a slot, false if the job was cancelled meanwhile
+    pub(crate) async fn started(&self) -> bool {
+        poll_fn(|cx| self.0.pause.poll_resumed(cx)).await;
+        let jobs = self.0.shared.jobs.lock().unwrap();
+        jobs.queued.iter().any(|job| job.id == self.0.id)
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.0.shared.release(self.0.id);
+    }
+}
+
+impl CopyScheduler {
+    /// A scheduler running up to `concurrency` jobs at once, at least one
+    pub fn new(concurrency: usize) -> Self {
+        CopyScheduler {
+            shared: Arc::new(Shared {
+                concurrency: concurrency.max(1),
+                jobs: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Queue a [`AsyncDirectoryInfo::copy_new_background`] of `source`
+    pub fn copy_dir<P: AsRef<Path>>(
+        &self,
+        source: &AsyncDirectoryInfo,
+        path: P,
+        options: Options,
+        priority: Priority,
+    ) -> AsyncProgressHandle {
+        let job = self.shared.submit(priority);
+        let options = options.pause(job.to_ref().pause);
+        source.copy_new_queued(path, options, Some(job))
+    }
+
+    /// Queue a [`AsyncFileInfo::copy_new_background`] of `source`
+    pub fn copy_file<P: AsRef<Path>>(
+        &self,
+        source: &AsyncFileInfo,
+        path: P,
+        options: Options,
+        priority: Priority,
+    ) -> AsyncProgressHandle {
+        let job = self.shared.submit(priority);
+        let options = options.pause(job.to_ref().pause);
+        source.copy_new_queued(path, options, Some(job))
+    }
+
+    /// The number of jobs submitted and not over yet
+    pub fn len(&self) -> usize {
+        self.shared.jobs.lock().unwrap().queued.len()
+    }
+
+    /// Whether no job is waiting or running
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
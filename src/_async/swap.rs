@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use tokio::io::Result;
+
+use crate::sync::{Action, DirectoryInfo};
+
+use super::{dir::AsyncDirectoryInfo, hash::blocking, AsyncInfo};
+
+impl AsyncDirectoryInfo {
+    /// See `DirectoryInfo::swap_with`, the renames run on a blocking thread
+    pub async fn swap_with(&mut self, other: &mut AsyncDirectoryInfo) -> Result<()> {
+        let mut dir = unsafe { DirectoryInfo::open_uncheck(self.as_path()) };
+        let mut with = unsafe { DirectoryInfo::open_uncheck(other.as_path()) };
+        blocking(move || dir.swap_with(&mut with)).await?;
+        std::mem::swap(self, other);
+        Ok(())
+    }
+
+    /// See `DirectoryInfo::replace_with`, the renames run on a blocking
+    /// thread
+    pub async fn replace_with(
+        &mut self,
+        new: AsyncDirectoryInfo,
+        backup: Option<PathBuf>,
+    ) -> Result<()> {
+        let mut dir = unsafe { DirectoryInfo::open_uncheck(self.as_path()) };
+        let new = unsafe { DirectoryInfo::open_uncheck(new.as_path()) };
+        blocking(move || dir.replace_with(new, backup)).await
+    }
+}
@@ -0,0 +1,313 @@
+//! A directory accepting files from untrusted clients, see [`UploadDir`]
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::dir::AsyncDirectoryInfo;
+use super::file::AsyncFileInfo;
+use super::hash::blocking;
+use super::io::copy_stream;
+use super::{AsyncAction, AsyncInfo};
+use crate::convert::{sanitize_file_name, SanitizeOptions};
+use crate::error::{already_exist, no_file_name};
+use crate::io::StreamOptions;
+use crate::sync::{Action, DirectoryInfo, SecureDir};
+use crate::{safe_join, unique_path, ConflictPolicy, Numbering};
+
+/// Limits of an [`UploadDir`], nothing is limited by default
+#[derive(Debug, Clone)]
+pub struct UploadOptions {
+    /// Longest accepted body
+    pub max_file_size: Option<u64>,
+    /// Largest total length of the files below the directory, the upload
+    /// included. Uploads running at the same time do not see each other.
+    pub max_total_size: Option<u64>,
+    /// Accepted extensions, any when empty. With the `infer` feature the
+    /// content must agree with the extension, see
+    /// `FileInfo::matches_extension`.
+    pub extensions: Vec<String>,
+    /// Accepted MIME types detected from the content, any when empty
+    #[cfg(feature = "infer")]
+    pub mime_types: Vec<String>,
+    /// Applied to the last component of the name suggested by the client
+    pub sanitize: SanitizeOptions,
+    /// `Rename` by default, `Skip` is treated as `Fail`
+    pub conflict: ConflictPolicy,
+    /// Names picked by `ConflictPolicy::Rename`
+    pub numbering: Numbering,
+    pub stream: StreamOptions,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            max_file_size: None,
+            max_total_size: None,
+            extensions: Vec::new(),
+            #[cfg(feature = "infer")]
+            mime_types: Vec::new(),
+            sanitize: SanitizeOptions::default(),
+            conflict: ConflictPolicy::Rename,
+            numbering: Numbering::default(),
+            stream: StreamOptions::default(),
+        }
+    }
+}
+
+/// Why [`UploadDir::accept`] refused a file. Carried inside the `io::Error`
+/// it returns, with `ErrorKind::InvalidInput`, see [`UploadRejection::find`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadRejection {
+    /// The body is longer than `UploadOptions::max_file_size`
+    FileTooLarge { limit: u64 },
+    /// The body would take the directory over `UploadOptions::max_total_size`,
+    /// only `remaining` bytes were left
+    DirectoryFull { limit: u64, remaining: u64 },
+    /// The extension or the content is not accepted, `detected` is the MIME
+    /// type found in the content
+    DisallowedType {
+        name: String,
+        detected: Option<&'static str>,
+    },
+}
+
+impl UploadRejection {
+    /// The `UploadRejection` carried by `error`, if any
+    pub fn find(error: &Error) -> Option<&UploadRejection> {
+        error.get_ref()?.downcast_ref()
+    }
+
+    /// 413 for the size limits, 415 for the type
+    pub fn status_code(&self) -> u16 {
+        match self {
+            UploadRejection::FileTooLarge { .. } | UploadRejection::DirectoryFull { .. } => 413,
+            UploadRejection::DisallowedType { .. } => 415,
+        }
+    }
+}
+
+impl fmt::Display for UploadRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadRejection::FileTooLarge { limit } => {
+                write!(f, "The file is larger than {} bytes", limit)
+            }
+            UploadRejection::DirectoryFull { limit, remaining } => write!(
+                f,
+                "The file is larger than the {} bytes left of the {} bytes allowed",
+                remaining, limit
+            ),
+            UploadRejection::DisallowedType { name, detected } => match detected {
+                Some(detected) => write!(
+                    f,
+                    "The file '{}' of type {} is not accepted",
+                    name, detected
+                ),
+                None => write!(f, "The file '{}' is not of an accepted type", name),
+            },
+        }
+    }
+}
+
+impl std::error::Error for UploadRejection {}
+
+fn rejected(rejection: UploadRejection) -> Error {
+    Error::new(ErrorKind::InvalidInput, rejection)
+}
+
+/// A directory receiving uploads: names are sanitised and kept inside the
+/// directory, sizes and types are checked, and a rejected or failed upload
+/// leaves nothing behind
+///
+/// # Examples
+/// ```no_run
+/// # async fn run(body: &[u8]) -> std::io::Result<()> {
+/// use fdir::_async::upload::{UploadDir, UploadOptions, UploadRejection};
+/// use fdir::_async::{dir::AsyncDirectoryInfo, AsyncAction};
+/// let options = UploadOptions {
+///     max_file_size: Some(10 << 20),
+///     extensions: vec!["png".into(), "jpg".into()],
+///     ..Default::default()
+/// };
+/// let uploads = UploadDir::new(AsyncDirectoryInfo::open("/srv/uploads").await?, options);
+/// match uploads.accept(body, "../holiday.png").await {
+///     Ok(file) => println!("stored at {}", file),
+///     Err(e) => match UploadRejection::find(&e) {
+///         Some(rejection) => println!("{}: {}", rejection.status_code(), rejection),
+///         None => return Err(e),
+///     },
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct UploadDir {
+    dir: AsyncDirectoryInfo,
+    options: UploadOptions,
+}
+
+impl UploadDir {
+    pub fn new(dir: AsyncDirectoryInfo, options: UploadOptions) -> Self {
+        Self { dir, options }
+    }
+
+    pub fn dir(&self) -> &AsyncDirectoryInfo {
+        &self.dir
+    }
+
+    pub fn options(&self) -> &UploadOptions {
+        &self.options
+    }
+
+    /// Store `body` in the directory under `suggested_name`, sanitised.
+    /// The body is written to a temporary file that only takes the final
+    /// name once every check passed, both relative to the directory held
+    /// open as a [`SecureDir`].
+    pub async fn accept(
+        &self,
+        body: impl AsyncRead + Unpin,
+        suggested_name: &str,
+    ) -> Result<AsyncFileInfo> {
+        let options = &self.options;
+        // only the last component of a client path is kept
+        let name = suggested_name
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or_default();
+        let name = sanitize_file_name(name, &options.sanitize);
+        let extension = Path::new(&name)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        let accepted = |ext: &String| {
+            let ext = ext.trim_start_matches('.');
+            extension
+                .as_deref()
+                .is_some_and(|extension| ext.eq_ignore_ascii_case(extension))
+        };
+        if !options.extensions.is_empty() && !options.extensions.iter().any(accepted) {
+            return Err(rejected(UploadRejection::DisallowedType {
+                name,
+                detected: None,
+            }));
+        }
+        let path = safe_join(self.dir.as_path(), &name)?;
+        let conflict = match options.conflict {
+            ConflictPolicy::Skip => ConflictPolicy::Fail,
+            conflict => conflict,
+        };
+        let dest = conflict
+            .resolve(path.clone(), options.numbering)?
+            .ok_or_else(|| already_exist(&path))?;
+
+        let mut limit = options
+            .max_file_size
+            .map(|limit| UploadRejection::FileTooLarge { limit });
+        if let Some(max_total) = options.max_total_size {
+            let remaining = max_total.saturating_sub(self.dir.stats().await?.bytes.0);
+            if options.max_file_size.is_none_or(|max| remaining < max) {
+                limit = Some(UploadRejection::DirectoryFull {
+                    limit: max_total,
+                    remaining,
+                });
+            }
+        }
+        // the part file is created and renamed relative to the open
+        // directory, a link swapped in cannot redirect the body
+        let dir = unsafe { DirectoryInfo::open_uncheck(self.dir.as_path()) };
+        let secure = Arc::new(blocking(move || dir.open_secure()).await?);
+        let part = dest.with_file_name(format!(".{}.part", name));
+        let part = unique_path(&part, Numbering::default());
+        let (part, dest_name) = match (part.file_name(), dest.file_name()) {
+            (Some(part), Some(dest)) => (part.to_os_string(), dest.to_os_string()),
+            _ => return Err(no_file_name(&dest, self.dir.as_path())),
+        };
+        let file = {
+            let (secure, part) = (secure.clone(), part.clone());
+            blocking(move || secure.create_child(part)).await?
+        };
+        let result = self
+            .write(
+                body,
+                file,
+                &secure,
+                &part,
+                limit,
+                &name,
+                extension.as_deref(),
+            )
+            .await;
+        let secure_part = (secure.clone(), part.clone());
+        let result = match result {
+            Ok(()) => blocking(move || secure.rename_child(part, dest_name)).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            let (secure, part) = secure_part;
+            let _ = blocking(move || secure.remove_child(part)).await;
+            return Err(e);
+        }
+        AsyncFileInfo::open(dest).await
+    }
+
+    /// Write the body to `file`, the part file `part` of `secure`, and
+    /// check it against `limit` and the types
+    #[allow(clippy::too_many_arguments)]
+    async fn write(
+        &self,
+        body: impl AsyncRead + Unpin,
+        file: std::fs::File,
+        secure: &Arc<SecureDir>,
+        part: &OsStr,
+        limit: Option<UploadRejection>,
+        name: &str,
+        extension: Option<&str>,
+    ) -> Result<()> {
+        let max = match limit {
+            Some(UploadRejection::FileTooLarge { limit }) => limit,
+            Some(UploadRejection::DirectoryFull { remaining, .. }) => remaining,
+            _ => u64::MAX,
+        };
+        // one byte past the limit tells a body at the limit from a longer one
+        let body = body.take(max.saturating_add(1));
+        let copied = copy_stream(body, File::from_std(file), &self.options.stream).await?;
+        if let Some(limit) = limit.filter(|_| copied.bytes > max) {
+            return Err(rejected(limit));
+        }
+        #[cfg(feature = "infer")]
+        {
+            use crate::sync::detect::{extension_matches, PROBE_LEN};
+            let mut probe = Vec::new();
+            let (secure, part) = (secure.clone(), part.to_os_string());
+            File::from_std(blocking(move || secure.open_child(part)).await?)
+                .take(PROBE_LEN)
+                .read_to_end(&mut probe)
+                .await?;
+            let detected = infer::get(&probe).map(|kind| kind.mime_type());
+            let options = &self.options;
+            let mime_accepted = options.mime_types.is_empty()
+                || detected.is_some_and(|detected| {
+                    options
+                        .mime_types
+                        .iter()
+                        .any(|mime| mime.eq_ignore_ascii_case(detected))
+                });
+            let content_accepted =
+                options.extensions.is_empty() || extension_matches(&probe, extension);
+            if !mime_accepted || !content_accepted {
+                return Err(rejected(UploadRejection::DisallowedType {
+                    name: name.to_string(),
+                    detected,
+                }));
+            }
+        }
+        #[cfg(not(feature = "infer"))]
+        let _ = (secure, part, name, extension);
+        Ok(())
+    }
+}
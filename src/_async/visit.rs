@@ -0,0 +1,145 @@
+use std::future::Future;
+use std::io::{Error, Result};
+
+use crate::sync::dir::StatsVisitor;
+use crate::sync::visit::VisitAction;
+use crate::sync::walk::{Walk, WalkEntry};
+use crate::sync::{Action, DirectoryInfo};
+use crate::{TraversalOrder, WalkOptions};
+
+use super::hash::blocking;
+use super::{dir::AsyncDirectoryInfo, file::AsyncFileInfo, AsyncAction, AsyncInfo};
+
+/// Entries read on the blocking pool at once, fewer when a directory
+/// comes first as the visitor must decide whether to descend into it
+const BATCH: usize = 256;
+
+/// See `Visitor`. Methods return `Send` futures, implementations can use
+/// plain `async fn`.
+pub trait AsyncVisitor: Send {
+    fn enter_dir(&mut self, dir: &AsyncDirectoryInfo) -> impl Future<Output = VisitAction> + Send {
+        let _ = dir;
+        async { VisitAction::Descend }
+    }
+    fn visit_file(&mut self, file: &AsyncFileInfo) -> impl Future<Output = Result<()>> + Send;
+    fn leave_dir(&mut self, dir: &AsyncDirectoryInfo) -> impl Future<Output = ()> + Send {
+        let _ = dir;
+        async {}
+    }
+    fn visit_other(&mut self, entry: &WalkEntry) -> impl Future<Output = Result<()>> + Send {
+        let _ = entry;
+        async { Ok(()) }
+    }
+    fn visit_error(&mut self, error: Error) -> impl Future<Output = Result<()>> + Send {
+        async { Err(error) }
+    }
+    fn truncated(&mut self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+impl AsyncDirectoryInfo {
+    /// See `DirectoryInfo::visit`. The tree is read by the same walk, in
+    /// batches on the blocking pool, and the visitor awaited in between.
+    pub async fn visit(
+        &self,
+        options: &WalkOptions,
+        visitor: &mut impl AsyncVisitor,
+    ) -> Result<()> {
+        let options = WalkOptions {
+            order: TraversalOrder::Dfs,
+            ..options.clone()
+        };
+        let mut walk = unsafe { DirectoryInfo::open_uncheck(self.as_path()) }.walk_with(&options);
+        let mut open: Vec<(AsyncDirectoryInfo, usize)> = Vec::new();
+        loop {
+            let entries;
+            (walk, entries) = next_batch(walk).await?;
+            if entries.is_empty() {
+                break;
+            }
+            for entry in entries {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        visitor.visit_error(e).await?;
+                        continue;
+                    }
+                };
+                let depth = entry.depth();
+                let kept = open.partition_point(|(_, open)| *open < depth);
+                for (dir, _) in open.drain(kept..).rev() {
+                    visitor.leave_dir(&dir).await;
+                }
+                let file_type = entry.file_type();
+                if file_type.is_dir() {
+                    // always the last of its batch
+                    let dir = unsafe { AsyncDirectoryInfo::open_uncheck(entry.into_path()) };
+                    match visitor.enter_dir(&dir).await {
+                        VisitAction::Descend => open.push((dir, depth)),
+                        VisitAction::Skip => walk.skip_current_dir(),
+                        VisitAction::Stop => return Ok(()),
+                    }
+                } else if file_type.is_file() {
+                    let file = unsafe { AsyncFileInfo::open_uncheck(entry.into_path()) };
+                    visitor.visit_file(&file).await?;
+                } else {
+                    visitor.visit_other(&entry).await?;
+                }
+            }
+        }
+        while let Some((dir, _)) = open.pop() {
+            visitor.leave_dir(&dir).await;
+        }
+        if walk.is_truncated() {
+            visitor.truncated().await;
+        }
+        Ok(())
+    }
+}
+
+/// The next entries of `walk`, up to and including the next directory
+async fn next_batch(mut walk: Walk) -> Result<(Walk, Vec<Result<WalkEntry>>)> {
+    blocking(move || {
+        let mut entries = Vec::new();
+        for entry in walk.by_ref() {
+            let is_dir = entry.as_ref().is_ok_and(|entry| entry.file_type().is_dir());
+            entries.push(entry);
+            if is_dir || entries.len() == BATCH {
+                break;
+            }
+        }
+        Ok((walk, entries))
+    })
+    .await
+}
+
+impl AsyncVisitor for StatsVisitor {
+    async fn enter_dir(&mut self, dir: &AsyncDirectoryInfo) -> VisitAction {
+        self.entered(dir.as_path(), dir.metadata().await);
+        VisitAction::Descend
+    }
+
+    async fn visit_file(&mut self, file: &AsyncFileInfo) -> Result<()> {
+        self.file(file.as_path(), file.metadata().await);
+        Ok(())
+    }
+
+    async fn leave_dir(&mut self, _: &AsyncDirectoryInfo) {
+        self.left();
+    }
+
+    async fn visit_other(&mut self, entry: &WalkEntry) -> Result<()> {
+        self.record(entry.path(), entry.depth(), entry.metadata());
+        Ok(())
+    }
+
+    async fn visit_error(&mut self, _: Error) -> Result<()> {
+        self.stats.errors += 1;
+        Ok(())
+    }
+
+    async fn truncated(&mut self) {
+        self.stats.truncated = true;
+    }
+}
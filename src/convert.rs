@@ -0,0 +1,631 @@
+use std::ffi::OsStr;
+#[cfg(any(unix, windows, feature = "unicode-normalization"))]
+use std::ffi::OsString;
+use std::fmt::{self, Display};
+use std::io::Result;
+#[cfg(feature = "url")]
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::error::invalid_encoding;
+use crate::{friendly_path, PathIssue};
+
+#[cfg(feature = "url")]
+pub use url::Url;
+
+/// Format a byte count using binary units, e.g. `1536` becomes `"1.5 KiB"`,
+/// see [`Size`](crate::Size)
+///
+/// # Examples
+/// ```
+/// use fdir::convert::human_size;
+/// assert_eq!(human_size(512), "512 B");
+/// assert_eq!(human_size(1536), "1.5 KiB");
+/// ```
+pub fn human_size(bytes: u64) -> String {
+    crate::Size(bytes).to_string()
+}
+
+/// Characters that are invalid in a file name on at least one platform
+const INVALID_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names reserved by Windows, also when followed by an extension
+const RESERVED_NAMES: [&str; 30] = [
+    "CON", "PRN", "AUX", "NUL", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7",
+    "COM8", "COM9", "COM¹", "COM²", "COM³", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6",
+    "LPT7", "LPT8", "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+#[derive(Debug, Clone)]
+pub struct SanitizeOptions {
+    /// Replacement for invalid characters, `None` removes them. A
+    /// replacement that is itself invalid, such as `/`, is taken as `_`.
+    pub replacement: Option<char>,
+    /// Maximum length of the result in bytes
+    pub max_bytes: usize,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        Self {
+            replacement: Some('_'),
+            max_bytes: 255,
+        }
+    }
+}
+
+/// Make `input` a valid file name on every platform
+///
+/// Invalid and control characters are replaced, trailing dots and spaces
+/// are trimmed, Windows device names such as `con.txt` are prefixed with
+/// `_`, and the result is truncated to `max_bytes` without splitting a
+/// UTF-8 sequence. The result is never empty, `_` is used as a fallback.
+///
+/// # Examples
+/// ```
+/// use fdir::convert::{sanitize_file_name, SanitizeOptions};
+/// let options = SanitizeOptions::default();
+/// assert_eq!(sanitize_file_name("report:final?.txt", &options), "report_final_.txt");
+/// assert_eq!(sanitize_file_name("con.txt", &options), "_con.txt");
+/// assert_eq!(sanitize_file_name("..", &options), "_");
+/// let slash = SanitizeOptions { replacement: Some('/'), ..Default::default() };
+/// assert_eq!(sanitize_file_name("a/b\\c", &slash), "a_b_c");
+///
+/// // hostile names always give a portable name within `max_bytes`
+/// use fdir::convert::is_portable_file_name;
+/// let pool = [
+///     "a", "é", "😀", ".", " ", "/", "\\", ":", "*", "?", "\"", "<", ">", "|", "\0", "\n",
+///     "\u{7f}", "\u{202e}", "con", "NUL", "com1", "lpt¹", "..", "~",
+/// ];
+/// let mut seed = 0x9e37_79b9_7f4a_7c15_u64;
+/// let mut next = |n: usize| {
+///     seed ^= seed << 13;
+///     seed ^= seed >> 7;
+///     seed ^= seed << 17;
+///     (seed >> 33) as usize % n
+/// };
+/// let replacements = [Some('_'), None, Some('/'), Some('\0'), Some('.'), Some('é')];
+/// for _ in 0..5000 {
+///     let input: String = (0..next(12)).map(|_| pool[next(pool.len())]).collect();
+///     let options = SanitizeOptions {
+///         replacement: replacements[next(replacements.len())],
+///         max_bytes: 1 + next(16),
+///     };
+///     let name = sanitize_file_name(&input, &options);
+///     assert!(is_portable_file_name(name.as_ref()), "{input:?} gave {name:?}");
+///     assert!(name.len() <= options.max_bytes.max(1), "{input:?} gave {name:?}");
+///     assert_eq!(sanitize_file_name(&name, &options), name, "{input:?}");
+/// }
+/// ```
+pub fn sanitize_file_name(input: &str, options: &SanitizeOptions) -> String {
+    let is_invalid = |c: char| c.is_control() || INVALID_CHARS.contains(&c);
+    // a separator as replacement would make the name a path
+    let replacement = options
+        .replacement
+        .map(|c| if is_invalid(c) { '_' } else { c });
+    let mut name: String = input
+        .chars()
+        .filter_map(|c| if is_invalid(c) { replacement } else { Some(c) })
+        .collect();
+    truncate_name(&mut name, options.max_bytes);
+    if is_reserved_name(&name) {
+        name.insert(0, '_');
+        truncate_name(&mut name, options.max_bytes);
+    }
+    if name.is_empty() {
+        name.push('_');
+    }
+    name
+}
+
+/// Truncate to at most `max_bytes` on a char boundary, then trim trailing dots and spaces
+fn truncate_name(name: &mut String, max_bytes: usize) {
+    if name.len() > max_bytes {
+        let mut end = max_bytes;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name.truncate(end);
+    }
+    let len = name.trim_end_matches(['.', ' ']).len();
+    name.truncate(len);
+}
+
+pub(crate) fn is_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+/// Check whether `name` is a valid file name on every platform, see [`portability_issue`]
+pub fn is_portable_file_name(name: &OsStr) -> bool {
+    portability_issue(name).is_none()
+}
+
+/// Describe why `name` is not a valid file name on Windows, the strictest
+/// supported platform, or return `None` if it is portable
+pub fn portability_issue(name: &OsStr) -> Option<String> {
+    Some(match name_issue(name)? {
+        PathIssue::NotUnicode { .. } => "not valid Unicode".to_string(),
+        PathIssue::EmptyComponent => "empty name".to_string(),
+        PathIssue::InvalidComponentChar { char, .. } => format!("forbidden character {:?}", char),
+        PathIssue::TrailingDotOrSpace { .. } => "trailing dot or space".to_string(),
+        PathIssue::ReservedName { .. } => "reserved device name".to_string(),
+        PathIssue::NameTooLong { limit, actual, .. } => {
+            format!("{} bytes long, the limit is {}", actual, limit)
+        }
+        issue => issue.to_string(),
+    })
+}
+
+/// The issues of every name of `path` that is invalid on Windows
+pub(crate) fn portable_issues(path: &Path) -> Vec<PathIssue> {
+    path.components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(name) => name_issue(name),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Why `name` is not a valid file name on Windows, see [`portability_issue`]
+pub(crate) fn name_issue(name: &OsStr) -> Option<PathIssue> {
+    let component = || name.to_os_string();
+    let Some(text) = name.to_str() else {
+        return Some(PathIssue::NotUnicode {
+            component: component(),
+        });
+    };
+    if text.is_empty() {
+        return Some(PathIssue::EmptyComponent);
+    }
+    if let Some(char) = text
+        .chars()
+        .find(|c| c.is_control() || INVALID_CHARS.contains(c))
+    {
+        return Some(PathIssue::InvalidComponentChar {
+            component: component(),
+            char,
+        });
+    }
+    if text.ends_with(['.', ' ']) {
+        return Some(PathIssue::TrailingDotOrSpace {
+            component: component(),
+        });
+    }
+    if is_reserved_name(text) {
+        return Some(PathIssue::ReservedName {
+            component: component(),
+        });
+    }
+    if text.len() > 255 {
+        return Some(PathIssue::NameTooLong {
+            component: component(),
+            limit: 255,
+            actual: text.len(),
+        });
+    }
+    None
+}
+
+/// Shell whose quoting rules [`quote_path`] follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// Single quotes, a quote inside becomes `'\''`
+    Posix,
+    /// Double quotes, which Windows names cannot contain. `%` is still
+    /// expanded by cmd when it surrounds the name of a variable.
+    Cmd,
+    /// Single quotes, quotes inside are doubled, typographic ones included
+    PowerShell,
+}
+
+impl QuoteStyle {
+    /// PowerShell on Windows, POSIX elsewhere
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            QuoteStyle::PowerShell
+        } else {
+            QuoteStyle::Posix
+        }
+    }
+
+    /// Whether `c` has no special meaning to the shell
+    fn is_bare(self, c: char) -> bool {
+        let punctuation = match self {
+            QuoteStyle::Posix => "_-./:+,@%=",
+            QuoteStyle::Cmd => "_-./:\\+@",
+            QuoteStyle::PowerShell => "_-./:\\+",
+        };
+        c.is_ascii_alphanumeric() || punctuation.contains(c)
+    }
+}
+
+impl Default for QuoteStyle {
+    fn default() -> Self {
+        Self::native()
+    }
+}
+
+/// Quote `path` to be pasted into a shell as a single argument. Paths
+/// without any character special to the shell are left bare, names that are
+/// not valid Unicode are shown lossily.
+///
+/// # Examples
+/// ```
+/// use fdir::convert::{quote_path, QuoteStyle};
+/// assert_eq!(quote_path("/tmp/notes.txt", QuoteStyle::Posix), "/tmp/notes.txt");
+/// assert_eq!(quote_path("/tmp/my notes.txt", QuoteStyle::Posix), "'/tmp/my notes.txt'");
+/// assert_eq!(quote_path("/tmp/it's", QuoteStyle::Posix), r"'/tmp/it'\''s'");
+/// assert_eq!(quote_path("/tmp/café", QuoteStyle::Posix), "'/tmp/café'");
+/// assert_eq!(quote_path(r"/tmp/a\b", QuoteStyle::Posix), r"'/tmp/a\b'");
+/// assert_eq!(quote_path(r"C:\My Files", QuoteStyle::Cmd), r#""C:\My Files""#);
+/// assert_eq!(quote_path(r"C:\it's", QuoteStyle::PowerShell), r"'C:\it''s'");
+/// assert_eq!(quote_path(r"C:\it’s", QuoteStyle::PowerShell), r"'C:\it’’s'");
+/// assert_eq!(quote_path(r"C:\$HOME", QuoteStyle::PowerShell), r"'C:\$HOME'");
+/// ```
+pub fn quote_path(path: impl AsRef<Path>, style: QuoteStyle) -> String {
+    let path = path.as_ref().to_string_lossy();
+    if !path.is_empty() && path.chars().all(|c| style.is_bare(c)) {
+        return path.into_owned();
+    }
+    match style {
+        QuoteStyle::Posix => format!("'{}'", path.replace('\'', r"'\''")),
+        QuoteStyle::Cmd => format!("\"{}\"", path),
+        QuoteStyle::PowerShell => {
+            let mut quoted = String::from("'");
+            for c in path.chars() {
+                // PowerShell also closes single quotes with their typographic forms
+                if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+                    quoted.push(c);
+                }
+                quoted.push(c);
+            }
+            quoted.push('\'');
+            quoted
+        }
+    }
+}
+
+/// A path shown relative to a base directory, see
+/// [`Info::display_relative_to`](crate::Info::display_relative_to)
+#[derive(Debug, Clone)]
+pub struct RelativeDisplay<'a> {
+    path: &'a Path,
+    base: PathBuf,
+}
+
+impl<'a> RelativeDisplay<'a> {
+    /// `base` is made absolute like the paths of the info types, when it can be
+    pub(crate) fn new(path: &'a Path, base: &Path) -> Self {
+        let base = crate::fix_path(base).unwrap_or_else(|_| base.to_path_buf());
+        Self { path, base }
+    }
+}
+
+impl Display for RelativeDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.strip_prefix(&self.base) {
+            Ok(relative) if relative.as_os_str().is_empty() => f.write_str("."),
+            Ok(relative) => write!(f, "{}", relative.display()),
+            Err(_) => write!(f, "{}", friendly_path(self.path).display()),
+        }
+    }
+}
+
+/// A path quoted for the shell of the platform, see
+/// [`Info::display_quoted`](crate::Info::display_quoted)
+#[derive(Debug, Clone, Copy)]
+pub struct QuotedDisplay<'a> {
+    path: &'a Path,
+}
+
+impl<'a> QuotedDisplay<'a> {
+    pub(crate) fn new(path: &'a Path) -> Self {
+        Self { path }
+    }
+}
+
+impl Display for QuotedDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quoted = quote_path(friendly_path(self.path), QuoteStyle::native());
+        f.write_str(&quoted)
+    }
+}
+
+/// Convert a path into a `file://` URL, relative paths are resolved against
+/// the current directory first
+///
+/// Spaces and non-ASCII characters are percent-encoded. On Windows drive
+/// letters become `file:///C:/...` and UNC paths become `file://server/share/...`.
+///
+/// # Examples
+/// ```
+/// use fdir::convert::path_to_file_url;
+/// # #[cfg(unix)]
+/// assert_eq!(
+///     path_to_file_url("/srv/a b/caf\u{e9}.txt").unwrap().as_str(),
+///     "file:///srv/a%20b/caf%C3%A9.txt"
+/// );
+/// # #[cfg(windows)] {
+/// assert_eq!(
+///     path_to_file_url(r"C:\Users\a b\notes.txt").unwrap().as_str(),
+///     "file:///C:/Users/a%20b/notes.txt"
+/// );
+/// assert_eq!(
+///     path_to_file_url(r"\\server\share\dir\notes.txt").unwrap().as_str(),
+///     "file://server/share/dir/notes.txt"
+/// );
+/// # }
+/// ```
+#[cfg(feature = "url")]
+pub fn path_to_file_url(path: impl AsRef<Path>) -> Result<Url> {
+    let path = crate::fix_path(path)?;
+    Url::from_file_path(&path).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "The path '{}' cannot be converted to a file URL",
+                path.display()
+            ),
+        )
+    })
+}
+
+/// Convert a `file://` URL back into a path, URLs with any other scheme are rejected
+///
+/// # Examples
+/// ```
+/// use std::io::ErrorKind;
+/// use std::path::Path;
+/// use fdir::convert::{file_url_to_path, Url};
+/// let url = |url| Url::parse(url).unwrap();
+/// # #[cfg(unix)] {
+/// let path = file_url_to_path(&url("file:///srv/a%20b/caf%C3%A9.txt")).unwrap();
+/// assert_eq!(path, Path::new("/srv/a b/caf\u{e9}.txt"));
+/// // a remote host has no local path
+/// let error = file_url_to_path(&url("file://server/share/notes.txt")).unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::InvalidInput);
+/// # }
+/// # #[cfg(windows)] {
+/// let path = file_url_to_path(&url("file:///C:/Users/a%20b/notes.txt")).unwrap();
+/// assert_eq!(path, Path::new(r"C:\Users\a b\notes.txt"));
+/// let path = file_url_to_path(&url("file://server/share/notes.txt")).unwrap();
+/// assert_eq!(path, Path::new(r"\\server\share\notes.txt"));
+/// # }
+/// let error = file_url_to_path(&url("https://example.com/notes.txt")).unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::InvalidInput);
+/// assert!(error.to_string().ends_with("is not a file URL"));
+/// ```
+#[cfg(feature = "url")]
+pub fn file_url_to_path(url: &Url) -> Result<PathBuf> {
+    if url.scheme() != "file" {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("The URL '{}' is not a file URL", url),
+        ));
+    }
+    url.to_file_path().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("The URL '{}' does not name a local path", url),
+        )
+    })
+}
+
+/// Encode `path` as a string that [`decode_path`] turns back into the same
+/// path, whatever bytes it holds, e.g. to store it in JSON
+///
+/// The encoding is the path as UTF-8 where `%` becomes `%25` and each byte
+/// that is not part of valid UTF-8 becomes `%` followed by two upper case
+/// hex digits, so a UTF-8 path without `%` is encoded as itself. On Windows
+/// an unpaired surrogate is written as the three bytes WTF-8 gives it, e.g.
+/// `%ED%A0%80` for U+D800. The scheme does not change between versions.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use fdir::convert::{decode_path, encode_path};
+/// assert_eq!(encode_path(Path::new("/srv/café 100%.txt")), "/srv/café 100%25.txt");
+/// assert_eq!(decode_path("/srv/caf%C3%A9").unwrap(), Path::new("/srv/café"));
+/// assert!(decode_path("/srv/100%").is_err() && decode_path("/srv/%g0").is_err());
+/// assert!(decode_path("/srv/%+F").is_err() && decode_path("/srv/%-1").is_err());
+///
+/// #[cfg(unix)]
+/// {
+///     use std::ffi::OsStr;
+///     use std::os::unix::ffi::OsStrExt;
+///     let raw = Path::new(OsStr::from_bytes(b"/srv/caf\xe9%"));
+///     assert_eq!(encode_path(raw), "/srv/caf%E9%25");
+///     assert_eq!(decode_path("/srv/caf%e9%25").unwrap(), raw);
+///
+///     // Any sequence of bytes survives the round trip
+///     let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+///     let mut byte = || {
+///         seed ^= seed << 13;
+///         seed ^= seed >> 7;
+///         seed ^= seed << 17;
+///         (seed >> 24) as u8
+///     };
+///     for len in 0..2000 {
+///         let name: Vec<u8> = (0..len % 40).map(|_| byte()).collect();
+///         let path = Path::new(OsStr::from_bytes(&name));
+///         let encoded = encode_path(path);
+///         assert_eq!(decode_path(&encoded).unwrap(), path, "{encoded}");
+///     }
+/// }
+/// #[cfg(windows)]
+/// {
+///     use std::ffi::OsString;
+///     use std::os::windows::ffi::OsStringExt;
+///     let raw = std::path::PathBuf::from(OsString::from_wide(&[0x61, 0xD800, 0x25]));
+///     assert_eq!(encode_path(&raw), "a%ED%A0%80%25");
+///     assert_eq!(decode_path("a%ED%A0%80%25").unwrap(), raw);
+/// }
+/// ```
+pub fn encode_path(path: &Path) -> String {
+    let bytes = path_bytes(path);
+    let mut encoded = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '%' => encoded.push_str("%25"),
+                c => encoded.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Decode a path encoded by [`encode_path`]. Hex digits may be in either
+/// case. A `%` not followed by two hex digits is an error, as are, on
+/// Windows, bytes that are not WTF-8 and, on other platforms than Unix,
+/// bytes that are not UTF-8.
+pub fn decode_path(encoded: &str) -> Result<PathBuf> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        // `from_str_radix` alone would take a sign, as in "%+F"
+        let hex = rest
+            .get(..2)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        let Some(byte) = hex else {
+            return Err(invalid_encoding(
+                encoded,
+                "has a '%' not followed by two hex digits",
+            ));
+        };
+        bytes.push(byte);
+        rest = &rest[2..];
+    }
+    path_from_bytes(bytes)
+        .ok_or_else(|| invalid_encoding(encoded, "is not a valid path on this platform"))
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().into()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(OsString::from_vec(bytes).into())
+}
+
+/// The path as WTF-8, built here as the encoding of `OsStr` is unspecified
+#[cfg(windows)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::windows::ffi::OsStrExt;
+    let mut bytes = Vec::new();
+    for unit in char::decode_utf16(path.as_os_str().encode_wide()) {
+        match unit {
+            Ok(c) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            Err(error) => {
+                let unit = error.unpaired_surrogate();
+                bytes.extend([
+                    0xE0 | (unit >> 12) as u8,
+                    0x80 | (unit >> 6 & 0x3F) as u8,
+                    0x80 | (unit & 0x3F) as u8,
+                ]);
+            }
+        }
+    }
+    bytes.into()
+}
+
+#[cfg(windows)]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+    let mut wide = Vec::with_capacity(bytes.len());
+    let mut rest = &bytes[..];
+    loop {
+        let valid = match std::str::from_utf8(rest) {
+            Ok(valid) => valid,
+            Err(error) => std::str::from_utf8(&rest[..error.valid_up_to()]).ok()?,
+        };
+        wide.extend(valid.encode_utf16());
+        rest = &rest[valid.len()..];
+        match rest {
+            [] => break,
+            [0xED, high @ 0xA0..=0xBF, low @ 0x80..=0xBF, tail @ ..] => {
+                wide.push(0xD000 | u16::from(high & 0x3F) << 6 | u16::from(low & 0x3F));
+                rest = tail;
+            }
+            _ => return None,
+        }
+    }
+    Some(OsString::from_wide(&wide).into())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    path.as_os_str().as_encoded_bytes().into()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// A Unicode normalisation form for file names, see [`normalize_file_name`]
+#[cfg(feature = "unicode-normalization")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Form {
+    /// Composed form, what Windows and most Linux tools produce
+    Nfc,
+    /// Decomposed form, what HFS+ stores and APFS may return
+    Nfd,
+}
+
+#[cfg(feature = "unicode-normalization")]
+impl Form {
+    /// The form names are compared in by default: `Nfc` on macOS, whose
+    /// filesystems may return either form, and `None` elsewhere so names
+    /// are compared as they are on Windows and Linux
+    pub fn compare_default() -> Option<Form> {
+        cfg!(target_os = "macos").then_some(Form::Nfc)
+    }
+}
+
+/// Normalise a file name to `form`, names that are not valid Unicode are returned unchanged
+///
+/// macOS may hand back `é` as `e` followed by a combining accent even when the
+/// file was created with the single code point, so names should be normalised
+/// before being compared.
+#[cfg(feature = "unicode-normalization")]
+pub fn normalize_file_name(name: &OsStr, form: Form) -> OsString {
+    use unicode_normalization::UnicodeNormalization;
+    match name.to_str() {
+        Some(name) => match form {
+            Form::Nfc => name.nfc().collect::<String>().into(),
+            Form::Nfd => name.nfd().collect::<String>().into(),
+        },
+        None => name.to_os_string(),
+    }
+}
+
+/// `path` with each of its names normalised to `form`, see
+/// [`normalize_file_name`]
+#[cfg(feature = "unicode-normalization")]
+pub(crate) fn normalize_path_names(path: &Path, form: Form) -> PathBuf {
+    use std::path::Component;
+    path.components()
+        .map(|component| match component {
+            Component::Normal(name) => normalize_file_name(name, form),
+            component => component.as_os_str().to_os_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,213 @@
+//! Operations described by value, to be queued, sent to other threads or
+//! stored, see [`OpDescriptor`]
+
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+
+use crate::error::invalid_pattern;
+use crate::{Action, ConflictPolicy, CopyReport, DirectoryInfo, Entry, FileInfo, Info, Options};
+
+/// The options of an [`OpDescriptor`], the part of [`Options`] that is
+/// plain data. Hooks, journals and the other shared state cannot be
+/// described and are left to their default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct OpOptions {
+    /// See `Options::conflict`
+    pub conflict: ConflictPolicy,
+    /// See `Options::allow_replace_kind_mismatch`
+    pub allow_replace_kind_mismatch: bool,
+    /// See `Options::portable_names`
+    pub portable_names: bool,
+    /// See `Options::preserve_times`
+    pub preserve_times: bool,
+    /// See `Options::retries`
+    pub retries: u32,
+    /// Glob patterns, see `Options::include`
+    pub include: Vec<String>,
+    /// Glob patterns, see `Options::exclude`
+    pub exclude: Vec<String>,
+}
+
+impl OpOptions {
+    /// The [`Options`] described, an invalid pattern is an
+    /// `ErrorKind::InvalidInput` error
+    pub fn to_options(&self) -> Result<Options> {
+        let mut options = Options::new()
+            .conflict(self.conflict)
+            .allow_replace_kind_mismatch(self.allow_replace_kind_mismatch)
+            .portable_names(self.portable_names)
+            .preserve_times(self.preserve_times)
+            .retries(self.retries);
+        for pattern in &self.include {
+            options = options.include(parse_pattern(pattern)?);
+        }
+        for pattern in &self.exclude {
+            options = options.exclude(parse_pattern(pattern)?);
+        }
+        Ok(options)
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<Pattern> {
+    Pattern::new(pattern).map_err(|e| invalid_pattern(pattern, e))
+}
+
+/// A copy, move or deletion captured by value, `Send + 'static` unlike the
+/// handles and the errors of the methods doing it, so that it can wait in
+/// a queue or go to a worker thread. With the `serde` feature it can be
+/// stored and run after a restart.
+///
+/// The paths are opened when the operation runs, see [`OpDescriptor::execute`].
+///
+/// # Examples
+/// ```
+/// use fdir::descriptor::{OpDescriptor, OpOptions, OpOutcome};
+/// use fdir::*;
+/// let root = std::env::temp_dir().join("fdir_descriptor");
+/// # let _ = std::fs::remove_dir_all(&root);
+/// FileInfo::create(root.join("source/a.txt")).unwrap().write("a").unwrap();
+/// FileInfo::create(root.join("source/b.log")).unwrap().write("bb").unwrap();
+/// let jobs = vec![
+///     OpDescriptor::CopyTree {
+///         from: root.join("source"),
+///         to: root.join("copy"),
+///         options: OpOptions {
+///             exclude: vec!["*.log".to_string()],
+///             ..OpOptions::default()
+///         },
+///     },
+///     OpDescriptor::MoveFile {
+///         from: root.join("source/b.log"),
+///         to: root.join("b.log"),
+///         options: OpOptions::default(),
+///     },
+///     OpDescriptor::Delete {
+///         path: root.join("source"),
+///         options: OpOptions::default(),
+///     },
+/// ];
+/// let worker = std::thread::spawn(move || {
+///     jobs.iter().map(OpDescriptor::execute).collect::<Vec<_>>()
+/// });
+/// let outcomes = worker.join().unwrap();
+/// match &outcomes[0] {
+///     Ok(OpOutcome::Copied(report)) => assert_eq!(report.files.len(), 1),
+///     outcome => panic!("{:?}", outcome),
+/// }
+/// assert!(outcomes.iter().all(Result::is_ok));
+/// assert!(root.join("copy/a.txt").is_file() && !root.join("copy/b.log").exists());
+/// assert!(root.join("b.log").is_file() && !root.join("source").exists());
+///
+/// let missing = OpDescriptor::Delete {
+///     path: root.join("source"),
+///     options: OpOptions::default(),
+/// };
+/// assert_eq!(missing.execute().unwrap_err().kind(), std::io::ErrorKind::NotFound);
+/// # std::fs::remove_dir_all(&root).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpDescriptor {
+    /// See `FileInfo::copy_new_with`
+    CopyFile {
+        from: PathBuf,
+        to: PathBuf,
+        options: OpOptions,
+    },
+    /// See `DirectoryInfo::copy_new_with`
+    CopyTree {
+        from: PathBuf,
+        to: PathBuf,
+        options: OpOptions,
+    },
+    /// See `FileInfo::move_new_with`
+    MoveFile {
+        from: PathBuf,
+        to: PathBuf,
+        options: OpOptions,
+    },
+    /// See `DirectoryInfo::move_new_with`
+    MoveTree {
+        from: PathBuf,
+        to: PathBuf,
+        options: OpOptions,
+    },
+    /// Delete a file or a directory, see `Action::delete_with`
+    Delete { path: PathBuf, options: OpOptions },
+}
+
+/// What an [`OpDescriptor`] did
+#[derive(Debug, Clone)]
+pub enum OpOutcome {
+    Copied(CopyReport),
+    Moved(CopyReport),
+    /// The path deleted
+    Deleted(PathBuf),
+}
+
+impl OpOutcome {
+    /// The report of a copy or a move
+    pub fn report(&self) -> Option<&CopyReport> {
+        match self {
+            OpOutcome::Copied(report) | OpOutcome::Moved(report) => Some(report),
+            OpOutcome::Deleted(_) => None,
+        }
+    }
+}
+
+impl OpDescriptor {
+    /// The source of a copy or a move, the path of a deletion
+    pub fn source(&self) -> &Path {
+        match self {
+            OpDescriptor::CopyFile { from, .. }
+            | OpDescriptor::CopyTree { from, .. }
+            | OpDescriptor::MoveFile { from, .. }
+            | OpDescriptor::MoveTree { from, .. } => from,
+            OpDescriptor::Delete { path, .. } => path,
+        }
+    }
+
+    pub fn options(&self) -> &OpOptions {
+        match self {
+            OpDescriptor::CopyFile { options, .. }
+            | OpDescriptor::CopyTree { options, .. }
+            | OpDescriptor::MoveFile { options, .. }
+            | OpDescriptor::MoveTree { options, .. }
+            | OpDescriptor::Delete { options, .. } => options,
+        }
+    }
+
+    /// Open the paths and run the operation. A source missing or of the
+    /// wrong kind fails as [`Action::open`] does, the rest as the method
+    /// doing the operation.
+    pub fn execute(&self) -> Result<OpOutcome> {
+        let options = self.options().to_options()?;
+        Ok(match self {
+            OpDescriptor::CopyFile { from, to, .. } => {
+                OpOutcome::Copied(FileInfo::open(from)?.copy_new_with(to, &options)?)
+            }
+            OpDescriptor::CopyTree { from, to, .. } => {
+                OpOutcome::Copied(DirectoryInfo::open(from)?.copy_new_with(to, &options)?)
+            }
+            OpDescriptor::MoveFile { from, to, .. } => {
+                OpOutcome::Moved(FileInfo::open(from)?.move_new_with(to, &options)?)
+            }
+            OpDescriptor::MoveTree { from, to, .. } => {
+                OpOutcome::Moved(DirectoryInfo::open(from)?.move_new_with(to, &options)?)
+            }
+            OpDescriptor::Delete { path, .. } => {
+                let entry = Entry::open(path)?;
+                let path = entry.to_path_buf();
+                match entry {
+                    Entry::File(file) => file.delete_with(&options)?,
+                    Entry::Dir(dir) => dir.delete_with(&options)?,
+                }
+                OpOutcome::Deleted(path)
+            }
+        })
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Turn the dry-run mode of the process on or off, to preview what code
+/// using the crate would change on disk
+///
+/// In dry-run mode the methods creating, writing, renaming, copying,
+/// moving and deleting files and directories, and setting permissions,
+/// check their arguments and walk their sources as usual but leave the
+/// filesystem alone and succeed. Each operation they would have done is
+/// recorded as done in `options.journal`, see
+/// [`Journal`](crate::journal::Journal), and their [`CopyReport`](crate::CopyReport)
+/// has `dry_run` set and lists what would have been written. The handles
+/// keep pointing where they were. Appending to a file is not stubbed, so a
+/// [`FileJournal`](crate::journal::FileJournal) still records, nor are the
+/// other features such as merges or manifests.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+/// use fdir::journal::FileJournal;
+/// use fdir::*;
+/// let root = std::env::temp_dir().join("fdir_dry_run");
+/// # let _ = std::fs::remove_dir_all(&root);
+/// FileInfo::create(root.join("source/a.txt")).unwrap().write("a").unwrap();
+/// FileInfo::create(root.join("source/sub/b.txt")).unwrap().write("bb").unwrap();
+///
+/// fdir::set_dry_run(true);
+/// // the journal is still created
+/// let journal = Arc::new(FileJournal::open(root.join("plan/plan.jsonl")).unwrap());
+/// let options = Options::new().journal(journal);
+/// let source = DirectoryInfo::open(root.join("source")).unwrap();
+/// let report = source.copy_new_with(root.join("copy"), &options).unwrap();
+/// assert!(report.dry_run);
+/// assert_eq!((report.files.len(), report.bytes), (2, Size(3)));
+/// let mut moved = source.clone();
+/// let report = moved.move_new_with(root.join("dest"), &options).unwrap();
+/// assert!(report.dry_run && report.renamed);
+/// let file = FileInfo::create(root.join("new.txt")).unwrap();
+/// assert!(!file.exists());
+/// let unique = DirectoryInfo::create_unique(root.join("source")).unwrap();
+/// assert_eq!(unique.as_path(), root.join("source (1)"));
+/// assert!(!unique.as_path().exists());
+/// let a = FileInfo::open(root.join("source/a.txt")).unwrap();
+/// a.write("changed").unwrap();
+/// a.delete().unwrap();
+/// assert!(FileInfo::open(root.join("missing")).is_err());
+/// fdir::set_dry_run(false);
+///
+/// // nothing changed on disk
+/// assert_eq!(moved.as_path(), source.as_path());
+/// assert_eq!(std::fs::read_to_string(root.join("source/a.txt")).unwrap(), "a");
+/// assert!(root.join("source/sub/b.txt").is_file());
+/// assert!(!root.join("copy").exists() && !root.join("dest").exists());
+/// let plan = std::fs::read_to_string(root.join("plan/plan.jsonl")).unwrap();
+/// let mut operations: Vec<_> = plan
+///     .lines()
+///     .map(|line| line.split('"').nth(3).unwrap())
+///     .collect();
+/// operations.sort();
+/// assert_eq!(operations, ["copy", "copy", "create_dir", "create_dir", "move"]);
+/// # std::fs::remove_dir_all(&root).unwrap();
+/// ```
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the process is in dry-run mode, see [`set_dry_run`]
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
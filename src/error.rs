@@ -1,11 +1,510 @@
-use std::{io::{Error, ErrorKind, Result}, path::Path};
+use std::{ffi::{OsStr, OsString}, fmt, fs::Metadata, io::{Error, ErrorKind}, path::{Path, PathBuf}};
 
 
 
 
-pub fn INVALID_PATH<T>() -> Result<T> {
-    Err(Error::new(ErrorKind::InvalidInput, "Invalid path"))
+/// `context` completes the sentence "The path '..' ", e.g. "has no file name"
+pub fn invalid_path(path: impl AsRef<Path>, context: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("The path '{}' {}", path.as_ref().display(), context),
+    )
+}
+/// `context` completes the sentence "The encoded path '..' "
+pub fn invalid_encoding(encoded: &str, context: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("The encoded path '{}' {}", encoded, context),
+    )
+}
+
+pub fn not_a_name(name: &OsStr) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "The name '{}' is not a single file name, `move_new` moves to another directory",
+            Path::new(name).display()
+        ),
+    )
+}
+pub fn no_file_name(source: impl AsRef<Path>, dest: impl AsRef<Path>) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "The path '{}' has no file name to append to '{}'",
+            source.as_ref().display(),
+            dest.as_ref().display()
+        ),
+    )
 }
 pub fn already_exist(path: impl AsRef<Path>) -> Error {
     Error::new(ErrorKind::AlreadyExists, format!("The path '{}' already exists!", path.as_ref().display()))
-}
\ No newline at end of file
+}
+pub fn not_under_base(path: impl AsRef<Path>, base: impl AsRef<Path>) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "The path '{}' is not under '{}'",
+            path.as_ref().display(),
+            base.as_ref().display()
+        ),
+    )
+}
+
+/// `name` names the directory, e.g. "configuration"
+pub fn no_standard_dir(name: &str) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        format!("The {} directory of the current user cannot be found", name),
+    )
+}
+
+pub fn no_longer_exists(path: impl AsRef<Path>) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        format!("The path '{}' no longer exists", path.as_ref().display()),
+    )
+}
+
+pub fn wrong_kind(path: impl AsRef<Path>, expected: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("The path '{}' is not a {}", path.as_ref().display(), expected),
+    )
+}
+
+pub fn exists_as(path: impl AsRef<Path>, kind: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("The path '{}' exists but is a {}", path.as_ref().display(), kind),
+    )
+}
+
+pub fn does_not_exist(path: impl AsRef<Path>) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        format!("The path '{}' does not exist", path.as_ref().display()),
+    )
+}
+
+/// Check `metadata` read for `path` when opening it, a directory is expected
+/// when `dir` is set and anything else otherwise
+pub fn check_kind(
+    path: &Path,
+    metadata: std::io::Result<Metadata>,
+    dir: bool,
+) -> std::io::Result<()> {
+    match metadata {
+        Ok(data) if data.is_dir() == dir => Ok(()),
+        Ok(data) if data.is_dir() => Err(exists_as(path, "directory")),
+        Ok(data) if data.is_file() => Err(exists_as(path, "file")),
+        Ok(_) => Err(exists_as(path, "special file")),
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(does_not_exist(path)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Why a path is refused by [`validate_path`](crate::validate_path), for
+/// tools that explain it in their own words. `component` is the offending
+/// name as given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathIssue {
+    /// The resulting path is longer than `PathPolicy::max_len`, in bytes
+    TooLong { limit: usize, actual: usize },
+    /// A name is longer than the 255 bytes Windows allows
+    NameTooLong { component: OsString, limit: usize, actual: usize },
+    /// A name contains a control character or one of `<>:"/\|?*`
+    InvalidComponentChar { component: OsString, char: char },
+    /// A name ends with a dot or a space, which Windows drops
+    TrailingDotOrSpace { component: OsString },
+    /// A name is a Windows device name such as `CON` or `nul.txt`
+    ReservedName { component: OsString },
+    /// A name is not valid Unicode
+    NotUnicode { component: OsString },
+    /// The path is empty
+    EmptyComponent,
+    /// The path is relative while `PathPolicy::require_absolute` is set
+    NotAbsolute,
+    /// A `..` while `ParentDirs::Deny` refuses them
+    ParentDir,
+    /// A `..` goes above the root, or leaves the directory of
+    /// `ParentDirs::Within`
+    ParentEscapes,
+    /// A `~` that is not expanded: `expand_home` is not set, it is not at
+    /// the start, or there is no home directory
+    HomeDir,
+    /// The parent is missing while `PathPolicy::require_parent` is set,
+    /// `ancestor` is the highest missing directory
+    Nonexistent { ancestor: PathBuf },
+}
+
+impl fmt::Display for PathIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |component: &OsString| component.to_string_lossy().into_owned();
+        match self {
+            PathIssue::TooLong { limit, actual } => {
+                write!(f, "it is {} bytes long, the limit is {}", actual, limit)
+            }
+            PathIssue::NameTooLong { component, limit, actual } => write!(
+                f,
+                "the name '{}' is {} bytes long, the limit is {}",
+                name(component),
+                actual,
+                limit
+            ),
+            PathIssue::InvalidComponentChar { component, char } => write!(
+                f,
+                "the name '{}' contains the forbidden character {:?}",
+                name(component),
+                char
+            ),
+            PathIssue::TrailingDotOrSpace { component } => {
+                write!(f, "the name '{}' ends with a dot or a space", name(component))
+            }
+            PathIssue::ReservedName { component } => {
+                write!(f, "the name '{}' is a reserved device name", name(component))
+            }
+            PathIssue::NotUnicode { component } => {
+                write!(f, "the name '{}' is not valid Unicode", name(component))
+            }
+            PathIssue::EmptyComponent => f.write_str("it is empty"),
+            PathIssue::NotAbsolute => f.write_str("it is not absolute"),
+            PathIssue::ParentDir => f.write_str("it contains '..'"),
+            PathIssue::ParentEscapes => {
+                f.write_str("a '..' leaves the root or the allowed directory")
+            }
+            PathIssue::HomeDir => f.write_str("its '~' cannot be expanded"),
+            PathIssue::Nonexistent { ancestor } => {
+                write!(f, "'{}' does not exist", ancestor.display())
+            }
+        }
+    }
+}
+
+/// A path refused with every reason found, carried inside the `io::Error`
+/// returned by [`fix_path_strict`](crate::fix_path_strict),
+/// [`Action::open_with_policy`](crate::Action::open_with_policy) and the
+/// `create_portable` constructors, see [`InvalidPath::find`]. The error
+/// kind is `NotFound` when every issue is `PathIssue::Nonexistent` and
+/// `InvalidInput` otherwise.
+#[derive(Debug)]
+pub struct InvalidPath {
+    pub path: PathBuf,
+    pub issues: Vec<PathIssue>,
+}
+
+impl InvalidPath {
+    /// The `InvalidPath` carried by `error`, if any
+    pub fn find(error: &Error) -> Option<&InvalidPath> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for InvalidPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let issues: Vec<_> = self.issues.iter().map(|issue| issue.to_string()).collect();
+        write!(f, "The path '{}' is refused: {}", self.path.display(), issues.join("; "))
+    }
+}
+
+impl std::error::Error for InvalidPath {}
+
+pub fn path_issues(path: impl AsRef<Path>, issues: Vec<PathIssue>) -> Error {
+    let kind = if issues.iter().all(|issue| matches!(issue, PathIssue::Nonexistent { .. })) {
+        ErrorKind::NotFound
+    } else {
+        ErrorKind::InvalidInput
+    };
+    Error::new(kind, InvalidPath { path: path.as_ref().to_path_buf(), issues })
+}
+
+/// A followed link leads back to a directory containing it. Carried inside
+/// the `io::Error` yielded by the walk, see [`LinkCycle::find`].
+#[derive(Debug)]
+pub struct LinkCycle {
+    /// The directory the link leads back to, the directories below it down
+    /// to the link, then the link itself
+    pub chain: Vec<PathBuf>,
+}
+
+impl LinkCycle {
+    /// The `LinkCycle` carried by `error`, if any
+    pub fn find(error: &Error) -> Option<&LinkCycle> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for LinkCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chain: Vec<_> = self
+            .chain
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        write!(
+            f,
+            "The link leads back to one of its ancestors: {}",
+            chain.join(" -> ")
+        )
+    }
+}
+
+impl std::error::Error for LinkCycle {}
+
+pub fn link_cycle(chain: Vec<PathBuf>) -> Error {
+    Error::other(LinkCycle { chain })
+}
+
+pub fn invalid_digest(digest: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("'{}' is not a SHA-256 or SHA-512 hex digest", digest),
+    )
+}
+
+pub fn invalid_placeholder(placeholder: &str, pattern: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "The placeholder '{}' of '{}' is not one of {{year}}, {{month}}, {{day}} and {{name}}",
+            placeholder, pattern
+        ),
+    )
+}
+
+pub fn invalid_pattern(pattern: &str, error: glob::PatternError) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("'{}' is not a valid glob pattern: {}", pattern, error),
+    )
+}
+
+pub fn same_file(source: impl AsRef<Path>, dest: impl AsRef<Path>) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "The source '{}' and destination '{}' are the same file",
+            source.as_ref().display(),
+            dest.as_ref().display()
+        ),
+    )
+}
+
+pub fn into_own_subtree(path: impl AsRef<Path>, to: impl AsRef<Path>) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "Cannot copy '{}' into its own subtree '{}'",
+            path.as_ref().display(),
+            to.as_ref().display()
+        ),
+    )
+}
+
+/// A move copied the data to `dest` but could not remove `source`, for
+/// example because it is locked or its directory is read-only. The moved
+/// value already points at `dest` and the caller may need to clean up
+/// `source`. Carried inside the `io::Error` returned by the move, with the
+/// kind of the underlying error, see [`SourceNotRemoved::find`].
+///
+/// # Examples
+/// ```
+/// use fdir::*;
+/// #[cfg(unix)]
+/// {
+///     use std::fs::{metadata, set_permissions, Permissions};
+///     use std::os::unix::fs::{MetadataExt, PermissionsExt};
+///     let root = std::env::temp_dir().join("fdir_source_not_removed");
+///     let _ = std::fs::remove_dir_all(&root);
+///     let locked = root.join("locked");
+///     FileInfo::create(locked.join("dir/a.txt")).unwrap();
+///     FileInfo::create(locked.join("b.txt")).unwrap();
+///     // root ignores the permissions of the read-only parent
+///     if metadata(&locked).unwrap().uid() != 0 {
+///         set_permissions(&locked, Permissions::from_mode(0o555)).unwrap();
+///         let mut dir = DirectoryInfo::open(locked.join("dir")).unwrap();
+///         let dir_error = dir.move_new_with(root.join("moved"), &Options::new());
+///         let mut file = FileInfo::open(locked.join("b.txt")).unwrap();
+///         let file_error = file.move_new_with(root.join("b.txt"), &Options::new());
+///         set_permissions(&locked, Permissions::from_mode(0o755)).unwrap();
+///
+///         let error = dir_error.unwrap_err();
+///         assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+///         let not_removed = SourceNotRemoved::find(&error).unwrap();
+///         assert_eq!(not_removed.source, locked.join("dir"));
+///         assert_eq!(not_removed.dest, root.join("moved"));
+///         assert_eq!(dir.as_path(), root.join("moved"));
+///         assert!(root.join("moved/a.txt").is_file());
+///
+///         let error = file_error.unwrap_err();
+///         assert_eq!(SourceNotRemoved::find(&error).unwrap().source, locked.join("b.txt"));
+///         assert_eq!(file.as_path(), root.join("b.txt"));
+///         assert!(locked.join("b.txt").is_file());
+///     }
+///     std::fs::remove_dir_all(root).unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SourceNotRemoved {
+    pub source: PathBuf,
+    pub dest: PathBuf,
+    pub error: Error,
+}
+
+impl SourceNotRemoved {
+    /// The `SourceNotRemoved` carried by `error`, if any
+    pub fn find(error: &Error) -> Option<&SourceNotRemoved> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for SourceNotRemoved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Moved '{}' to '{}' but could not remove the source: {}",
+            self.source.display(),
+            self.dest.display(),
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for SourceNotRemoved {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+pub fn modified_during_copy(path: impl AsRef<Path>) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("The file '{}' was modified while it was copied", path.as_ref().display()),
+    )
+}
+
+/// Writing `source` to `dest` would replace a directory by a file, or
+/// something else by a directory
+pub fn kind_mismatch(
+    source: impl AsRef<Path>,
+    dest: impl AsRef<Path>,
+    source_is_dir: bool,
+) -> Error {
+    let (kind, dest_kind) = if source_is_dir {
+        ("directory", "not a directory")
+    } else {
+        ("file", "a directory")
+    };
+    let error = Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "The {} '{}' does not replace '{}', which is {}",
+            kind,
+            source.as_ref().display(),
+            dest.as_ref().display(),
+            dest_kind
+        ),
+    );
+    crate::error_kind::normalised(crate::ErrorKind::KindMismatch, error)
+}
+
+/// Listing `dir` would collect more than `limit` entries
+pub fn listing_too_large(dir: impl AsRef<Path>, limit: usize) -> Error {
+    let error = Error::other(format!(
+        "The directory '{}' has more than {} entries, walk it instead of listing it",
+        dir.as_ref().display(),
+        limit
+    ));
+    crate::error_kind::normalised(crate::ErrorKind::ListingTooLarge, error)
+}
+
+/// Adding `incoming` to `dir` would take it over its quota of `limit`
+pub fn quota_exceeded(dir: impl AsRef<Path>, limit: crate::Size, incoming: crate::Size) -> Error {
+    let error = Error::other(format!(
+        "Adding {} to '{}' would exceed its quota of {}",
+        incoming,
+        dir.as_ref().display(),
+        limit
+    ));
+    crate::error_kind::normalised(crate::ErrorKind::StorageFull, error)
+}
+
+pub fn cross_device(path: impl AsRef<Path>, to: impl AsRef<Path>) -> Error {
+    let error = Error::new(
+        ErrorKind::Unsupported,
+        format!(
+            "'{}' and '{}' are on different filesystems, one cannot be renamed to the other",
+            path.as_ref().display(),
+            to.as_ref().display()
+        ),
+    );
+    crate::error_kind::normalised(crate::ErrorKind::CrossesFilesystem, error)
+}
+
+/// The previous content of `path` was renamed to `aside`, where it is left
+/// as the rename back failed with `error`
+pub fn not_restored(path: impl AsRef<Path>, aside: impl AsRef<Path>, error: Error) -> Error {
+    Error::new(
+        error.kind(),
+        format!(
+            "The directory '{}' could not be replaced nor restored, its content is at '{}': {}",
+            path.as_ref().display(),
+            aside.as_ref().display(),
+            error
+        ),
+    )
+}
+
+/// `path` was replaced but its previous content at `aside` could not be
+/// removed or moved to the backup
+pub fn replaced_leftover(path: impl AsRef<Path>, aside: impl AsRef<Path>, error: Error) -> Error {
+    Error::new(
+        error.kind(),
+        format!(
+            "The directory '{}' was replaced but its previous content is left at '{}': {}",
+            path.as_ref().display(),
+            aside.as_ref().display(),
+            error
+        ),
+    )
+}
+
+pub fn source_not_removed(
+    source: impl AsRef<Path>,
+    dest: impl AsRef<Path>,
+    error: Error,
+) -> Error {
+    Error::new(
+        error.kind(),
+        SourceNotRemoved {
+            source: source.as_ref().to_path_buf(),
+            dest: dest.as_ref().to_path_buf(),
+            error,
+        },
+    )
+}
+
+/// Whether `error` reports that `source` itself was moved but not removed
+pub fn is_source_not_removed(error: &Error, source: &Path) -> bool {
+    SourceNotRemoved::find(error).is_some_and(|e| e.source == source)
+}
+
+/// Payload of the error returned when a `StreamOptions::cancel` flag is set
+#[derive(Debug)]
+struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("The copy was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+pub fn cancelled() -> Error {
+    Error::other(Cancelled)
+}
+
+pub fn is_cancelled(error: &Error) -> bool {
+    error.get_ref().is_some_and(|e| e.is::<Cancelled>())
+}
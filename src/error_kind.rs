@@ -0,0 +1,248 @@
+use std::fmt;
+use std::io::{self, Error};
+use std::path::Path;
+
+/// What went wrong, the same on every platform where the raw `io::Error`
+/// varies, see [`ErrorKind::of`]
+///
+/// The errors of fdir stay `io::Error`s, carrying the OS error code when
+/// there is one. Where the code alone is ambiguous, such as a rename onto a
+/// non-empty directory failing with `ERROR_ACCESS_DENIED` on Windows, fdir
+/// checks the destination and tags the error it returns.
+///
+/// # Examples
+/// ```
+/// use fdir::*;
+/// let root = std::env::temp_dir().join("fdir_error_kind");
+/// let _ = std::fs::remove_dir_all(&root);
+/// FileInfo::create(root.join("a/1")).unwrap();
+/// FileInfo::create(root.join("b/2")).unwrap();
+/// // ENOTEMPTY on Linux, EEXIST elsewhere, ERROR_ACCESS_DENIED on Windows
+/// let mut a = DirectoryInfo::open(root.join("a")).unwrap();
+/// let error = a.rename("b").unwrap_err();
+/// assert_eq!(fdir::ErrorKind::of(&error), fdir::ErrorKind::DestinationNotEmpty);
+///
+/// let missing = DirectoryInfo::open(root.join("missing")).unwrap_err();
+/// assert_eq!(fdir::ErrorKind::of(&missing), fdir::ErrorKind::NotFound);
+///
+/// # #[cfg(unix)]
+/// # {
+/// // EXDEV, EROFS and ETXTBSY share their number on Linux and macOS
+/// for (code, kind) in [
+///     (18, fdir::ErrorKind::CrossesFilesystem),
+///     (30, fdir::ErrorKind::ReadOnlyFilesystem),
+///     (26, fdir::ErrorKind::SourceInUse),
+/// ] {
+///     assert_eq!(fdir::ErrorKind::of(&std::io::Error::from_raw_os_error(code)), kind);
+/// }
+/// # }
+/// # #[cfg(windows)]
+/// # {
+/// // ERROR_SHARING_VIOLATION, ERROR_NOT_SAME_DEVICE and ERROR_WRITE_PROTECT
+/// for (code, kind) in [
+///     (32, fdir::ErrorKind::SourceInUse),
+///     (17, fdir::ErrorKind::CrossesFilesystem),
+///     (19, fdir::ErrorKind::ReadOnlyFilesystem),
+/// ] {
+///     assert_eq!(fdir::ErrorKind::of(&std::io::Error::from_raw_os_error(code)), kind);
+/// }
+/// # }
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    NotFound,
+    PermissionDenied,
+    /// The destination exists and is not replaced
+    AlreadyExists,
+    /// A directory cannot be renamed onto or removed while it has content
+    DestinationNotEmpty,
+    /// The file is open or locked by another process, a sharing violation
+    /// on Windows. A file open without the sharing flags may also be
+    /// deleted on Windows, its name then lingers until the last handle is
+    /// closed and recreating it fails with this kind.
+    SourceInUse,
+    /// The source and destination are on different filesystems and cannot
+    /// be renamed into each other
+    CrossesFilesystem,
+    ReadOnlyFilesystem,
+    /// No space left on the device, or a quota reached
+    StorageFull,
+    /// The destination is a directory where a file is written, or the
+    /// reverse, see `Options::allow_replace_kind_mismatch`
+    KindMismatch,
+    /// A directory has more entries than a listing may collect, see
+    /// [`set_max_listing`](crate::set_max_listing)
+    ListingTooLarge,
+    /// Any other error, see the `io::Error` itself
+    Other,
+}
+
+impl ErrorKind {
+    /// The kind of `error`, from the kind fdir tagged it with, its OS error
+    /// code, then its `io::ErrorKind`. The kind of a
+    /// [`SourceNotRemoved`](crate::SourceNotRemoved) is that of the removal.
+    pub fn of(error: &Error) -> ErrorKind {
+        if let Some(inner) = error.get_ref() {
+            if let Some(normalised) = inner.downcast_ref::<Normalised>() {
+                return normalised.kind;
+            }
+            if let Some(not_removed) = crate::SourceNotRemoved::find(error) {
+                return ErrorKind::of(&not_removed.error);
+            }
+        }
+        if let Some(kind) = error.raw_os_error().and_then(from_os_error) {
+            return kind;
+        }
+        match error.kind() {
+            io::ErrorKind::NotFound => ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+            io::ErrorKind::AlreadyExists => ErrorKind::AlreadyExists,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::NotFound => "not found",
+            ErrorKind::PermissionDenied => "permission denied",
+            ErrorKind::AlreadyExists => "already exists",
+            ErrorKind::DestinationNotEmpty => "destination not empty",
+            ErrorKind::SourceInUse => "source in use",
+            ErrorKind::CrossesFilesystem => "crosses filesystems",
+            ErrorKind::ReadOnlyFilesystem => "read-only filesystem",
+            ErrorKind::StorageFull => "storage full",
+            ErrorKind::KindMismatch => "kind mismatch",
+            ErrorKind::ListingTooLarge => "listing too large",
+            ErrorKind::Other => "other error",
+        })
+    }
+}
+
+#[cfg(unix)]
+fn from_os_error(code: i32) -> Option<ErrorKind> {
+    // the same on Linux, macOS and the BSDs
+    const EPERM: i32 = 1;
+    const ENOENT: i32 = 2;
+    const EACCES: i32 = 13;
+    const EBUSY: i32 = 16;
+    const EEXIST: i32 = 17;
+    const EXDEV: i32 = 18;
+    const ETXTBSY: i32 = 26;
+    const ENOSPC: i32 = 28;
+    const EROFS: i32 = 30;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const ENOTEMPTY: i32 = 39;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const ENOTEMPTY: i32 = 66;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const EDQUOT: i32 = 122;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const EDQUOT: i32 = 69;
+
+    Some(match code {
+        ENOENT => ErrorKind::NotFound,
+        EPERM | EACCES => ErrorKind::PermissionDenied,
+        EEXIST => ErrorKind::AlreadyExists,
+        ENOTEMPTY => ErrorKind::DestinationNotEmpty,
+        EBUSY | ETXTBSY => ErrorKind::SourceInUse,
+        EXDEV => ErrorKind::CrossesFilesystem,
+        EROFS => ErrorKind::ReadOnlyFilesystem,
+        ENOSPC | EDQUOT => ErrorKind::StorageFull,
+        _ => return None,
+    })
+}
+
+#[cfg(windows)]
+fn from_os_error(code: i32) -> Option<ErrorKind> {
+    const ERROR_FILE_NOT_FOUND: i32 = 2;
+    const ERROR_PATH_NOT_FOUND: i32 = 3;
+    const ERROR_ACCESS_DENIED: i32 = 5;
+    const ERROR_NOT_SAME_DEVICE: i32 = 17;
+    const ERROR_WRITE_PROTECT: i32 = 19;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+    const ERROR_HANDLE_DISK_FULL: i32 = 39;
+    const ERROR_FILE_EXISTS: i32 = 80;
+    const ERROR_DISK_FULL: i32 = 112;
+    const ERROR_DIR_NOT_EMPTY: i32 = 145;
+    const ERROR_ALREADY_EXISTS: i32 = 183;
+    const ERROR_DELETE_PENDING: i32 = 303;
+    const ERROR_DISK_QUOTA_EXCEEDED: i32 = 1295;
+
+    Some(match code {
+        ERROR_FILE_NOT_FOUND | ERROR_PATH_NOT_FOUND => ErrorKind::NotFound,
+        ERROR_ACCESS_DENIED => ErrorKind::PermissionDenied,
+        ERROR_FILE_EXISTS | ERROR_ALREADY_EXISTS => ErrorKind::AlreadyExists,
+        ERROR_DIR_NOT_EMPTY => ErrorKind::DestinationNotEmpty,
+        ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION | ERROR_DELETE_PENDING => {
+            ErrorKind::SourceInUse
+        }
+        ERROR_NOT_SAME_DEVICE => ErrorKind::CrossesFilesystem,
+        ERROR_WRITE_PROTECT => ErrorKind::ReadOnlyFilesystem,
+        ERROR_HANDLE_DISK_FULL | ERROR_DISK_FULL | ERROR_DISK_QUOTA_EXCEEDED => {
+            ErrorKind::StorageFull
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn from_os_error(_: i32) -> Option<ErrorKind> {
+    None
+}
+
+/// An error fdir gave a kind its OS error code does not tell, it keeps the
+/// `io::ErrorKind` and message of the original
+#[derive(Debug)]
+struct Normalised {
+    kind: ErrorKind,
+    error: Error,
+}
+
+impl fmt::Display for Normalised {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for Normalised {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+pub(crate) fn normalised(kind: ErrorKind, error: Error) -> Error {
+    Error::new(error.kind(), Normalised { kind, error })
+}
+
+/// A copy of `error` with its message, `io::ErrorKind` and `ErrorKind`
+pub(crate) fn duplicate(error: &Error) -> Error {
+    normalised(
+        ErrorKind::of(error),
+        Error::new(error.kind(), error.to_string()),
+    )
+}
+
+/// The error of a failed rename onto `to`, tagged `DestinationNotEmpty`
+/// when `to` is a directory with content whatever the platform reported
+pub(crate) fn rename_error(error: Error, to: &Path) -> Error {
+    let ambiguous = matches!(
+        ErrorKind::of(&error),
+        ErrorKind::PermissionDenied | ErrorKind::AlreadyExists
+    );
+    if ambiguous && std::fs::read_dir(to).is_ok_and(|mut entries| entries.next().is_some()) {
+        return normalised(ErrorKind::DestinationNotEmpty, error);
+    }
+    error
+}
+
+/// Whether `error` comes from `path` being removed meanwhile, by someone
+/// else, rather than from the operation on it
+pub(crate) fn vanished(path: &Path, error: &Error) -> bool {
+    ErrorKind::of(error) == ErrorKind::NotFound
+        && std::fs::symlink_metadata(path).is_err_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
@@ -0,0 +1,122 @@
+use std::fs;
+use std::io::Result;
+use std::path::Path;
+
+/// Identity of a file or directory, equal for every path naming it, hard
+/// links included. Device and inode on Unix, volume serial number and file
+/// index on Windows.
+///
+/// Ids are stable while the file exists, but an id can be reused once the
+/// file is deleted. Some network and FUSE filesystems make inode numbers up
+/// and may change them between mounts or even between calls, and FAT
+/// volumes on Windows renumber files when they are moved, so an id stored
+/// across runs should be checked against the path it was read from.
+///
+/// # Examples
+/// ```
+/// use fdir::{Action, FileInfo, Info};
+/// let dir = std::env::temp_dir().join("fdir_file_id");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// let file = FileInfo::create(dir.join("a")).unwrap();
+/// std::fs::hard_link(dir.join("a"), dir.join("b")).unwrap();
+/// let link = FileInfo::open(dir.join("b")).unwrap();
+/// assert_eq!(file.file_id().unwrap(), link.file_id().unwrap());
+/// let id = file.file_id().unwrap();
+/// assert_eq!(fdir::FileId::from_parts(id.device(), id.index()), id);
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileId {
+    device: u64,
+    index: u64,
+}
+
+impl FileId {
+    /// The id of what `path` names, links are followed
+    pub fn of(path: impl AsRef<Path>) -> Result<FileId> {
+        file_id(path.as_ref())
+    }
+
+    /// Rebuild an id from the values of [`FileId::device`] and
+    /// [`FileId::index`], e.g. after storing them
+    pub fn from_parts(device: u64, index: u64) -> FileId {
+        FileId { device, index }
+    }
+
+    /// Device on Unix, volume serial number on Windows
+    pub fn device(&self) -> u64 {
+        self.device
+    }
+
+    /// Inode on Unix, file index on Windows
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+}
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> Result<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path)?;
+    Ok(FileId {
+        device: metadata.dev(),
+        index: metadata.ino(),
+    })
+}
+
+#[cfg(windows)]
+fn file_id(path: &Path) -> Result<FileId> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::{AsRawHandle, RawHandle};
+
+    /// `BY_HANDLE_FILE_INFORMATION`, the times are `FILETIME`s
+    #[repr(C)]
+    #[derive(Default)]
+    struct ByHandleFileInformation {
+        file_attributes: u32,
+        creation_time: [u32; 2],
+        last_access_time: [u32; 2],
+        last_write_time: [u32; 2],
+        volume_serial_number: u32,
+        file_size_high: u32,
+        file_size_low: u32,
+        number_of_links: u32,
+        file_index_high: u32,
+        file_index_low: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetFileInformationByHandle(
+            file: RawHandle,
+            information: *mut ByHandleFileInformation,
+        ) -> i32;
+    }
+
+    // no access is needed to query the information, FILE_FLAG_BACKUP_SEMANTICS
+    // opens directories
+    let file = fs::OpenOptions::new()
+        .access_mode(0)
+        .custom_flags(0x0200_0000)
+        .open(path)?;
+    let mut information = ByHandleFileInformation::default();
+    // SAFETY: the handle is open for the duration of the call and
+    // `information` has the layout the function writes
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut information) } == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(FileId {
+        device: information.volume_serial_number.into(),
+        index: u64::from(information.file_index_high) << 32 | u64::from(information.file_index_low),
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn file_id(path: &Path) -> Result<FileId> {
+    let _ = fs::metadata(path)?;
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "File ids are not supported on this platform",
+    ))
+}
@@ -0,0 +1,245 @@
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+/// Identity of the filesystem holding a file or directory, the device on
+/// Unix and the volume serial number on Windows. Entries with equal ids can
+/// be renamed into each other, a move between different ids copies the
+/// data.
+///
+/// # Examples
+/// ```
+/// use fdir::{FsId, Info};
+/// let dir = std::env::temp_dir().join("fdir_fs_id");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// let file = fdir::FileInfo::create(dir.join("a")).unwrap();
+/// // a copy to a path that does not exist yet
+/// let dest = FsId::of_nearest(dir.join("new/sub/a")).unwrap();
+/// if file.filesystem_id().unwrap() != dest {
+///     println!("this copy crosses filesystems and may be slow");
+/// }
+/// assert_eq!(file.filesystem_id().unwrap(), dest);
+/// assert!(dir.starts_with(file.mount_point().unwrap()));
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FsId(u64);
+
+impl FsId {
+    /// The filesystem of what `path` names, links are followed
+    pub fn of(path: impl AsRef<Path>) -> Result<FsId> {
+        fs_id(path.as_ref())
+    }
+
+    /// The filesystem `path` would be created on, that of its nearest
+    /// existing ancestor
+    pub fn of_nearest(path: impl AsRef<Path>) -> Result<FsId> {
+        let path = path.as_ref();
+        let mut error = None;
+        for ancestor in path.ancestors().filter(|p| !p.as_os_str().is_empty()) {
+            match fs_id(ancestor) {
+                Ok(id) => return Ok(id),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        Err(error.unwrap_or_else(|| crate::error::does_not_exist(path)))
+    }
+
+    /// Rebuild an id from the value of [`FsId::raw`], e.g. after storing it
+    pub fn from_raw(raw: u64) -> FsId {
+        FsId(raw)
+    }
+
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(unix)]
+fn fs_id(path: &Path) -> Result<FsId> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(FsId(fs::metadata(path)?.dev()))
+}
+
+#[cfg(not(unix))]
+fn fs_id(path: &Path) -> Result<FsId> {
+    Ok(FsId(crate::FileId::of(path)?.device()))
+}
+
+/// Whether `path` can be renamed to `to`, a destination that does not exist
+/// yet is on the filesystem of its nearest ancestor. Assumed when either
+/// cannot be read, the rename then fails by itself.
+pub(crate) fn same_filesystem(path: &Path, to: &Path) -> bool {
+    match (FsId::of(path), FsId::of_nearest(to)) {
+        (Ok(path), Ok(to)) => path == to,
+        _ => true,
+    }
+}
+
+/// The directory `path` is mounted under, see [`Info::mount_point`]
+///
+/// [`Info::mount_point`]: crate::Info::mount_point
+///
+/// # Examples
+/// ```
+/// # #[cfg(target_os = "linux")]
+/// assert_eq!(fdir::mount_point("/proc/self").unwrap(), std::path::Path::new("/proc"));
+/// let temp = fdir::mount_point(std::env::temp_dir()).unwrap();
+/// assert!(std::env::temp_dir().canonicalize().unwrap().starts_with(temp));
+/// ```
+pub fn mount_point(path: impl AsRef<Path>) -> Result<PathBuf> {
+    // canonical paths are verbatim on Windows, which only needs an absolute
+    // one
+    #[cfg(unix)]
+    let path = fs::canonicalize(path)?;
+    #[cfg(not(unix))]
+    let path = {
+        let path = crate::fix_path(path)?;
+        fs::metadata(&path)?;
+        path
+    };
+    _mount_point(&path)
+}
+
+/// The last mount in `/proc/self/mountinfo` covering `path`, later mounts
+/// hide earlier ones on the same directory
+#[cfg(target_os = "linux")]
+fn _mount_point(path: &Path) -> Result<PathBuf> {
+    let Ok(mountinfo) = fs::read("/proc/self/mountinfo") else {
+        // without procfs, e.g. in a chroot
+        return same_device_ancestor(path);
+    };
+    let mut found: Option<PathBuf> = None;
+    for line in mountinfo.split(|&b| b == b'\n') {
+        // id, parent id, major:minor, root, mount point, ...
+        let Some(field) = line.split(|&b| b == b' ').nth(4) else {
+            continue;
+        };
+        let mount = PathBuf::from(unescape(field));
+        let deeper = found
+            .as_ref()
+            .is_none_or(|found| mount.components().count() >= found.components().count());
+        if path.starts_with(&mount) && deeper {
+            found = Some(mount);
+        }
+    }
+    match found {
+        Some(mount) => Ok(mount),
+        None => same_device_ancestor(path),
+    }
+}
+
+/// Undo the `\040` octal escapes of spaces, tabs, newlines and
+/// backslashes in `/proc/self/mountinfo`
+#[cfg(target_os = "linux")]
+fn unescape(field: &[u8]) -> std::ffi::OsString {
+    use std::os::unix::ffi::OsStringExt;
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut i = 0;
+    while i < field.len() {
+        let octal = field
+            .get(i + 1..i + 4)
+            .filter(|_| field[i] == b'\\')
+            .and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok());
+        match octal {
+            Some(byte) => {
+                bytes.push(byte);
+                i += 4;
+            }
+            None => {
+                bytes.push(field[i]);
+                i += 1;
+            }
+        }
+    }
+    std::ffi::OsString::from_vec(bytes)
+}
+
+#[cfg(target_os = "macos")]
+fn _mount_point(path: &Path) -> Result<PathBuf> {
+    use std::ffi::{c_char, c_int, CStr, CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    /// `struct statfs` with 64 bit inodes
+    #[repr(C)]
+    struct StatFs {
+        bsize: u32,
+        iosize: i32,
+        blocks: u64,
+        bfree: u64,
+        bavail: u64,
+        files: u64,
+        ffree: u64,
+        fsid: [i32; 2],
+        owner: u32,
+        fs_type: u32,
+        flags: u32,
+        fssubtype: u32,
+        fstypename: [c_char; 16],
+        mntonname: [c_char; 1024],
+        mntfromname: [c_char; 1024],
+        flags_ext: u32,
+        reserved: [u32; 7],
+    }
+
+    extern "C" {
+        #[cfg_attr(target_arch = "x86_64", link_name = "statfs$INODE64")]
+        fn statfs(path: *const c_char, buf: *mut StatFs) -> c_int;
+    }
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<StatFs>::zeroed();
+    // SAFETY: `path` is nul terminated and `stat` has the layout written
+    if unsafe { statfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: filled by the successful call, `mntonname` is nul terminated
+    let stat = unsafe { stat.assume_init() };
+    let mount = unsafe { CStr::from_ptr(stat.mntonname.as_ptr()) };
+    Ok(PathBuf::from(OsStr::from_bytes(mount.to_bytes())))
+}
+
+#[cfg(windows)]
+fn _mount_point(path: &Path) -> Result<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetVolumePathNameW(file_name: *const u16, volume_path: *mut u16, len: u32) -> i32;
+    }
+
+    let name: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    // at least as long as the path itself, long paths included
+    let mut volume = vec![0u16; name.len().max(261)];
+    // SAFETY: `name` is nul terminated and `volume` holds `len` characters
+    let ok = unsafe { GetVolumePathNameW(name.as_ptr(), volume.as_mut_ptr(), volume.len() as u32) };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let len = volume.iter().position(|&c| c == 0).unwrap_or(volume.len());
+    Ok(PathBuf::from(OsString::from_wide(&volume[..len])))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn _mount_point(path: &Path) -> Result<PathBuf> {
+    same_device_ancestor(path)
+}
+
+/// The highest ancestor of `path` on the same filesystem, which misses bind
+/// mounts of a directory of the same filesystem
+#[cfg(not(any(target_os = "macos", windows)))]
+fn same_device_ancestor(path: &Path) -> Result<PathBuf> {
+    let id = FsId::of(path)?;
+    let mut mount = path;
+    while let Some(parent) = mount.parent() {
+        if FsId::of(parent)? != id {
+            break;
+        }
+        mount = parent;
+    }
+    Ok(mount.to_path_buf())
+}
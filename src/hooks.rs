@@ -0,0 +1,244 @@
+use std::fmt::{self, Debug};
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::{ConflictPolicy, Recorder, Size};
+
+/// Answer of an `on_conflict` hook when the destination of an entry exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictDecision {
+    /// Replace the existing file. An existing directory is merged into
+    /// instead, the files it already holds then raise their own conflicts.
+    Overwrite,
+    /// Leave the existing entry alone. For a directory the whole source
+    /// subtree is left out, it counts once in `CopyReport::skipped`.
+    Skip,
+    /// Use the first free name of the form `name (1).ext`, a directory is
+    /// written there with its whole subtree. See `Options::numbering`.
+    Rename,
+    /// Stop the operation with `ErrorKind::AlreadyExists`
+    Abort,
+}
+
+impl From<ConflictDecision> for ConflictPolicy {
+    fn from(decision: ConflictDecision) -> Self {
+        match decision {
+            ConflictDecision::Overwrite => ConflictPolicy::Overwrite,
+            ConflictDecision::Skip => ConflictPolicy::Skip,
+            ConflictDecision::Rename => ConflictPolicy::Rename,
+            ConflictDecision::Abort => ConflictPolicy::Fail,
+        }
+    }
+}
+
+/// Answer of an `on_error` hook when an entry could not be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorDecision {
+    /// Stop the operation with the error
+    Abort,
+    /// Leave the entry out, for a directory its whole subtree, and go on
+    Skip,
+    /// Try the entry again, the hook is asked again if it fails
+    Retry,
+}
+
+/// Passed to `on_progress` after each file of a directory copy or move
+#[derive(Debug, Clone, Copy)]
+pub struct Progress<'a> {
+    pub source: &'a Path,
+    pub dest: &'a Path,
+    /// Files written so far, including this one
+    pub files: u64,
+    /// Bytes written so far, including this file
+    pub bytes: u64,
+}
+
+/// What a directory copy or move is doing, see `Options::on_event` and
+/// `DirectoryInfo::copy_new_events`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProgressEvent {
+    /// The source was walked, the totals count what the filters or the
+    /// conflicts may leave out
+    Started { files: u64, bytes: Size },
+    /// The file at `path` in the source is about to be written
+    FileStarted { path: PathBuf },
+    /// Bytes of the current file written so far
+    Chunk { bytes: Size },
+    /// The file at `path` in the source was written
+    FileDone { path: PathBuf },
+    /// The destination `path` exists, what happens to it is up to the
+    /// conflict policy
+    Conflict { path: PathBuf },
+    /// The entry at `path` in the source failed and is left out, see
+    /// `Options::on_error`, or was removed meanwhile, see
+    /// [`MissingSource`](crate::MissingSource)
+    Warning { path: PathBuf, error: Error },
+    /// The operation is over, whether it succeeded or not
+    Finished,
+}
+
+impl Clone for ProgressEvent {
+    /// The error of a `Warning` is copied with its message and kinds
+    fn clone(&self) -> Self {
+        match self {
+            ProgressEvent::Started { files, bytes } => ProgressEvent::Started {
+                files: *files,
+                bytes: *bytes,
+            },
+            ProgressEvent::FileStarted { path } => {
+                ProgressEvent::FileStarted { path: path.clone() }
+            }
+            ProgressEvent::Chunk { bytes } => ProgressEvent::Chunk { bytes: *bytes },
+            ProgressEvent::FileDone { path } => ProgressEvent::FileDone { path: path.clone() },
+            ProgressEvent::Conflict { path } => ProgressEvent::Conflict { path: path.clone() },
+            ProgressEvent::Warning { path, error } => ProgressEvent::Warning {
+                path: path.clone(),
+                error: crate::error_kind::duplicate(error),
+            },
+            ProgressEvent::Finished => ProgressEvent::Finished,
+        }
+    }
+}
+
+/// Answer of a `transform` hook for a file of a directory copy, the hook
+/// returns `None` to copy the file unchanged
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use fdir::hooks::Transform;
+/// // `*.tmpl` files lose the suffix and get the project name filled in
+/// let options = fdir::Options::new().transform(|relative: &Path| {
+///     if relative.extension()? != "tmpl" {
+///         return None;
+///     }
+///     // the whole content is held back until the final empty chunk, as a
+///     // placeholder may be cut in two by a chunk boundary
+///     let mut content = Vec::new();
+///     let filter = move |chunk: &[u8]| {
+///         content.extend_from_slice(chunk);
+///         if !chunk.is_empty() {
+///             return Vec::new();
+///         }
+///         String::from_utf8_lossy(&content)
+///             .replace("{{name}}", "demo")
+///             .into_bytes()
+///     };
+///     Some(Transform::new().dest(relative.with_extension("")).filter(filter))
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Transform {
+    /// Destination relative to the destination directory, instead of the
+    /// path of the source relative to the source directory. It cannot lead
+    /// outside of the destination, missing directories are created.
+    pub dest: Option<PathBuf>,
+    /// Called with each chunk read from the source, what it returns is
+    /// written instead. It is called a last time with an empty chunk at the
+    /// end of the content, to write what it held back. A retried copy runs
+    /// the whole content through it again.
+    pub filter: Option<FilterHook>,
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dest(mut self, dest: impl Into<PathBuf>) -> Self {
+        self.dest = Some(dest.into());
+        self
+    }
+
+    pub fn filter(mut self, f: impl FnMut(&[u8]) -> Vec<u8> + Send + 'static) -> Self {
+        self.filter = Some(FilterHook::new(f));
+        self
+    }
+}
+
+/// A callback stored in `Options`, clones of the options share it.
+/// It is only locked for the duration of a call, never across an `.await`.
+pub struct Hook<F: ?Sized>(Arc<Mutex<F>>);
+
+pub type ConflictHook = Hook<dyn FnMut(&Path, &Path) -> ConflictDecision + Send>;
+pub type ErrorHook = Hook<dyn FnMut(&Path, &Error) -> ErrorDecision + Send>;
+pub type ProgressHook = Hook<dyn FnMut(&Progress) + Send>;
+pub type EventHook = Hook<dyn FnMut(&ProgressEvent) + Send>;
+/// Called with the number of bytes copied so far, see `StreamOptions`
+pub type StreamHook = Hook<dyn FnMut(u64) + Send>;
+/// Called with the position of a copy, see `StreamOptions::on_record`
+pub type RecordHook = Hook<dyn FnMut(&Recorder) + Send>;
+/// Called with the path of each file of a directory copy, relative to the
+/// source directory
+pub type TransformHook = Hook<dyn FnMut(&Path) -> Option<Transform> + Send>;
+/// Rewrites the content of a file, see [`Transform::filter`]
+pub type FilterHook = Hook<dyn FnMut(&[u8]) -> Vec<u8> + Send>;
+
+impl<F: ?Sized> Hook<F> {
+    /// A panic in an earlier call does not disable the hook
+    pub(crate) fn lock(&self) -> MutexGuard<'_, F> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl ConflictHook {
+    pub fn new(f: impl FnMut(&Path, &Path) -> ConflictDecision + Send + 'static) -> Self {
+        Hook(Arc::new(Mutex::new(f)))
+    }
+}
+
+impl ErrorHook {
+    pub fn new(f: impl FnMut(&Path, &Error) -> ErrorDecision + Send + 'static) -> Self {
+        Hook(Arc::new(Mutex::new(f)))
+    }
+}
+
+impl ProgressHook {
+    pub fn new(f: impl FnMut(&Progress) + Send + 'static) -> Self {
+        Hook(Arc::new(Mutex::new(f)))
+    }
+}
+
+impl EventHook {
+    pub fn new(f: impl FnMut(&ProgressEvent) + Send + 'static) -> Self {
+        Hook(Arc::new(Mutex::new(f)))
+    }
+}
+
+impl StreamHook {
+    pub fn new(f: impl FnMut(u64) + Send + 'static) -> Self {
+        Hook(Arc::new(Mutex::new(f)))
+    }
+}
+
+impl RecordHook {
+    pub fn new(f: impl FnMut(&Recorder) + Send + 'static) -> Self {
+        Hook(Arc::new(Mutex::new(f)))
+    }
+}
+
+impl TransformHook {
+    pub fn new(f: impl FnMut(&Path) -> Option<Transform> + Send + 'static) -> Self {
+        Hook(Arc::new(Mutex::new(f)))
+    }
+}
+
+impl FilterHook {
+    pub fn new(f: impl FnMut(&[u8]) -> Vec<u8> + Send + 'static) -> Self {
+        Hook(Arc::new(Mutex::new(f)))
+    }
+}
+
+impl<F: ?Sized> Clone for Hook<F> {
+    fn clone(&self) -> Self {
+        Hook(Arc::clone(&self.0))
+    }
+}
+
+impl<F: ?Sized> Debug for Hook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Hook")
+    }
+}
@@ -0,0 +1,398 @@
+//! The chunked copy loop behind every file copy of the crate, usable with
+//! any reader and writer
+
+use std::borrow::Cow;
+use std::fs::{self, File, Metadata};
+use std::io::{ErrorKind, IoSlice, Read, Result, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::cancelled;
+use crate::hooks::{FilterHook, RecordHook, StreamHook};
+use crate::sync::hash::Hasher;
+use crate::{file_times, set_file_times, Algorithm, Recorder};
+
+/// Buffer of small files and of streams of unknown length
+const SMALL_BUFFER: usize = 64 * 1024;
+/// Buffer of files longer than `LARGE_FILE`
+const LARGE_BUFFER: usize = 1024 * 1024;
+const LARGE_FILE: u64 = 8 * 1024 * 1024;
+
+/// Options of [`copy_stream`]
+#[derive(Debug, Clone, Default)]
+pub struct StreamOptions {
+    /// Size of the chunks read and written. By default 1 MiB for files over
+    /// 8 MiB and 64 KiB for smaller ones and streams, as measured by
+    /// `benches/copy.rs`: the larger buffer only pays off for large files.
+    pub buffer_size: Option<usize>,
+    /// Most bytes copied per second, unlimited when `None`
+    pub rate_limit: Option<u64>,
+    /// Checked before each chunk, once set the copy fails with an error of
+    /// kind `ErrorKind::Other` that is never retried
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Hash the content while it is copied, see [`StreamReport::digest`]
+    pub hash: Option<Algorithm>,
+    /// Called after each chunk with the number of bytes copied so far
+    pub on_progress: Option<StreamHook>,
+    /// Called after each chunk with the position in the source, its
+    /// length when known, the throughput and the time left, see
+    /// [`Recorder`]. The recorder is only kept when this is set.
+    pub on_record: Option<RecordHook>,
+}
+
+impl StreamOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    pub fn rate_limit(mut self, bytes_per_second: u64) -> Self {
+        self.rate_limit = Some(bytes_per_second);
+        self
+    }
+
+    pub fn cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    pub fn hash(mut self, algorithm: Algorithm) -> Self {
+        self.hash = Some(algorithm);
+        self
+    }
+
+    pub fn on_progress(mut self, f: impl FnMut(u64) + Send + 'static) -> Self {
+        self.on_progress = Some(StreamHook::new(f));
+        self
+    }
+
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// use std::sync::{Arc, Mutex};
+    /// let root = std::env::temp_dir().join("fdir_on_record");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let file = FileInfo::create(root.join("a")).unwrap();
+    /// std::fs::write(file.as_path(), vec![0u8; 100_000]).unwrap();
+    /// let last = Arc::new(Mutex::new(Recorder::default()));
+    /// let seen = Arc::clone(&last);
+    /// let stream = io::StreamOptions::new()
+    ///     .buffer_size(4096)
+    ///     .on_record(move |recorder| {
+    ///         // e.g. draw `recorder.percent()` and `recorder.eta()`
+    ///         *seen.lock().unwrap() = *recorder;
+    ///     });
+    /// file.copy_new_with(root.join("b"), &Options::new().stream(stream)).unwrap();
+    /// let last = *last.lock().unwrap();
+    /// assert_eq!((last.pos, last.len, last.percent()), (100_000, 100_000, 100));
+    /// assert!(last.is_timed());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn on_record(mut self, f: impl FnMut(&Recorder) + Send + 'static) -> Self {
+        self.on_record = Some(RecordHook::new(f));
+        self
+    }
+}
+
+/// What [`copy_stream`] copied
+#[derive(Debug, Clone, Default)]
+pub struct StreamReport {
+    pub bytes: u64,
+    /// Lowercase hex digest of the content when `StreamOptions::hash` is set
+    pub digest: Option<String>,
+}
+
+/// Bookkeeping of one copy, shared by the sync and async loops
+pub(crate) struct Pump<'a> {
+    options: &'a StreamOptions,
+    filter: Option<&'a FilterHook>,
+    hasher: Option<Hasher>,
+    /// Length of the source when known, see `Pump::gather`
+    len: Option<u64>,
+    read: u64,
+    bytes: u64,
+    start: Instant,
+    /// Only when `on_record` is set
+    recorder: Option<Recorder>,
+}
+
+impl<'a> Pump<'a> {
+    pub(crate) fn new(options: &'a StreamOptions) -> Self {
+        Self {
+            options,
+            filter: None,
+            hasher: options.hash.map(Hasher::new),
+            len: None,
+            read: 0,
+            bytes: 0,
+            start: Instant::now(),
+            recorder: options
+                .on_record
+                .as_ref()
+                .map(|_| Recorder::read(0).timed()),
+        }
+    }
+
+    /// Write what `filter` makes of each chunk, see `Transform::filter`
+    pub(crate) fn with_filter(mut self, filter: Option<&'a FilterHook>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// The bytes to write for `chunk`, an empty chunk ends the content
+    pub(crate) fn filter<'b>(&self, chunk: &'b [u8]) -> Cow<'b, [u8]> {
+        match self.filter {
+            Some(filter) => (filter.lock())(chunk).into(),
+            None => chunk.into(),
+        }
+    }
+
+    /// Copy a source of `len` bytes, which picks the default buffer size
+    /// and lets short reads be gathered
+    pub(crate) fn with_len(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.len = len;
+        }
+        self
+    }
+
+    pub(crate) fn buffer(&self) -> Vec<u8> {
+        let size = self.options.buffer_size.unwrap_or(match self.len {
+            Some(len) if len > LARGE_FILE => LARGE_BUFFER,
+            _ => SMALL_BUFFER,
+        });
+        vec![0; size.max(1)]
+    }
+
+    /// Account for `len` bytes read, returns whether to read more before
+    /// writing. Only a source of known length is read ahead, a stream may
+    /// not have more to give yet.
+    pub(crate) fn gather(&mut self, len: usize) -> bool {
+        self.read += len as u64;
+        self.len.is_some_and(|total| self.read < total)
+    }
+
+    /// Fail once the copy was cancelled
+    pub(crate) fn check(&self) -> Result<()> {
+        match &self.options.cancel {
+            Some(cancel) if cancel.load(Ordering::Relaxed) => Err(cancelled()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Account for a written chunk, returns how long to pause to stay
+    /// under `rate_limit`
+    pub(crate) fn advance(&mut self, chunk: &[u8]) -> Option<Duration> {
+        self.bytes += chunk.len() as u64;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(chunk);
+        }
+        if let Some(hook) = &self.options.on_progress {
+            (hook.lock())(self.bytes);
+        }
+        if let (Some(recorder), Some(hook)) = (&mut self.recorder, &self.options.on_record) {
+            recorder.advance(chunk.len() as u64);
+            (hook.lock())(recorder);
+        }
+        let rate = self.options.rate_limit.filter(|&rate| rate > 0)?;
+        let due = Duration::from_secs_f64(self.bytes as f64 / rate as f64);
+        due.checked_sub(self.start.elapsed())
+    }
+
+    pub(crate) fn finish(self) -> StreamReport {
+        StreamReport {
+            bytes: self.bytes,
+            digest: self.hasher.map(Hasher::finish),
+        }
+    }
+}
+
+/// Copy everything `reader` yields into `writer` in chunks, with the
+/// progress, rate limit, cancellation and hashing of `options`
+///
+/// # Examples
+/// ```
+/// use fdir::io::{copy_stream, StreamOptions};
+/// let mut out = Vec::new();
+/// let options = StreamOptions::new().hash(fdir::Algorithm::Sha256);
+/// let report = copy_stream(&b"hello"[..], &mut out, &options).unwrap();
+/// assert_eq!((report.bytes, out.as_slice()), (5, &b"hello"[..]));
+/// assert!(report.digest.unwrap().starts_with("2cf24dba"));
+/// ```
+pub fn copy_stream(
+    reader: impl Read,
+    writer: impl Write,
+    options: &StreamOptions,
+) -> Result<StreamReport> {
+    pump_stream(reader, writer, Pump::new(options))
+}
+
+fn pump_stream(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    mut pump: Pump,
+) -> Result<StreamReport> {
+    let mut buf = pump.buffer();
+    let mut done = false;
+    while !done {
+        pump.check()?;
+        // short reads fill the rest of the buffer, the chunks are then
+        // written at once
+        let mut chunks = Vec::new();
+        let mut rest = &mut buf[..];
+        while !rest.is_empty() {
+            let len = match reader.read(rest) {
+                Ok(0) => {
+                    done = true;
+                    break;
+                }
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let (chunk, tail) = std::mem::take(&mut rest).split_at_mut(len);
+            chunks.push(pump.filter(chunk));
+            rest = tail;
+            if !pump.gather(len) {
+                break;
+            }
+        }
+        let mut slices: Vec<_> = chunks.iter().map(|chunk| IoSlice::new(chunk)).collect();
+        write_all_vectored(&mut writer, &mut slices)?;
+        let mut pause = None;
+        for chunk in &chunks {
+            pause = pump.advance(chunk);
+        }
+        if let Some(pause) = pause {
+            std::thread::sleep(pause);
+        }
+    }
+    let tail = pump.filter(&[]);
+    if !tail.is_empty() {
+        writer.write_all(&tail)?;
+        pump.advance(&tail);
+    }
+    writer.flush()?;
+    Ok(pump.finish())
+}
+
+/// `Write::write_all_vectored`, which is not stable yet
+fn write_all_vectored(writer: &mut impl Write, mut slices: &mut [IoSlice]) -> Result<()> {
+    // empty slices are skipped
+    IoSlice::advance_slices(&mut slices, 0);
+    while !slices.is_empty() {
+        match writer.write_vectored(slices) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(len) => IoSlice::advance_slices(&mut slices, len),
+            Err(e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Copy the content and permissions of `source` to `dest` like `fs::copy`,
+/// through `filter` if any. A partially written `dest` is removed on failure.
+pub(crate) fn copy_file(
+    source: &Path,
+    dest: &Path,
+    options: &StreamOptions,
+    filter: Option<&FilterHook>,
+) -> Result<StreamReport> {
+    copy_file_as(source, dest, options, filter, false)
+}
+
+/// Same as [`copy_file`], failing with `ErrorKind::AlreadyExists` when
+/// `dest` exists instead of truncating it, which saves checking first
+pub(crate) fn copy_file_new(
+    source: &Path,
+    dest: &Path,
+    options: &StreamOptions,
+    filter: Option<&FilterHook>,
+) -> Result<StreamReport> {
+    copy_file_as(source, dest, options, filter, true)
+}
+
+fn copy_file_as(
+    source: &Path,
+    dest: &Path,
+    options: &StreamOptions,
+    filter: Option<&FilterHook>,
+    create_new: bool,
+) -> Result<StreamReport> {
+    let reader = File::open(source)?;
+    let metadata = reader.metadata()?;
+    let writer = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(!create_new)
+        .create_new(create_new)
+        .open(dest)?;
+    let pump = Pump::new(options)
+        .with_filter(filter)
+        .with_len(metadata.len());
+    let result = pump_stream(&reader, &writer, pump).and_then(|report| {
+        writer
+            .set_permissions(metadata.permissions())
+            .map(|()| report)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(dest);
+    }
+    result
+}
+
+/// Give `dest` what a rename would have kept of `source`, whose metadata is
+/// `metadata`: the extended attributes with the `xattr` feature on Unix,
+/// the owner when the process may change it, the times and the permissions
+pub(crate) fn keep_metadata(source: &Path, dest: &Path, metadata: &Metadata) -> Result<()> {
+    #[cfg(all(unix, feature = "xattr"))]
+    copy_xattrs(source, dest)?;
+    #[cfg(not(all(unix, feature = "xattr")))]
+    let _ = source;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // only privileged processes may give a file away
+        let _ = std::os::unix::fs::chown(dest, Some(metadata.uid()), Some(metadata.gid()));
+    }
+    set_file_times(dest, file_times(metadata))?;
+    fs::set_permissions(dest, metadata.permissions())
+}
+
+/// Copy the extended attributes of `source` to `dest`, nothing is copied
+/// when either filesystem does not support them
+#[cfg(all(unix, feature = "xattr"))]
+fn copy_xattrs(source: &Path, dest: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let names: Vec<_> = match xattr::list(source) {
+        Ok(names) => names.collect(),
+        Err(e) if e.kind() == ErrorKind::Unsupported => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if names.is_empty() {
+        return Ok(());
+    }
+    // setting attributes needs write access, the caller restores the
+    // permissions afterwards
+    let mut permissions = fs::metadata(dest)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o200);
+    fs::set_permissions(dest, permissions)?;
+    for name in names {
+        let Some(value) = xattr::get(source, &name)? else {
+            continue;
+        };
+        match xattr::set(dest, &name, &value) {
+            Err(e) if e.kind() == ErrorKind::Unsupported => return Ok(()),
+            result => result?,
+        }
+    }
+    Ok(())
+}
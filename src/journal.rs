@@ -0,0 +1,424 @@
+//! A durable record of what the crate changed on disk, see [`Journal`]
+
+use std::fmt::{self, Debug, Write as _};
+use std::fs;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::convert::encode_path;
+use crate::{fix_path, is_dry_run, Action, CopyReport, ErrorKind, FileInfo, Info, Size};
+
+/// What a journaled operation did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Operation {
+    /// A file written from its source, by a copy or a move across
+    /// filesystems
+    Copy,
+    /// A file or directory moved to `dest`, by a rename or a copy followed
+    /// by the deletion of the source
+    Move,
+    /// A file or directory renamed in place
+    Rename,
+    /// A file or directory removed, with its content
+    Delete,
+    /// A directory created for the one at `source`
+    CreateDir,
+    /// A link recreated at `dest`, see `LinkPolicy::Recreate`
+    CreateLink,
+}
+
+impl Operation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Operation::Copy => "copy",
+            Operation::Move => "move",
+            Operation::Rename => "rename",
+            Operation::Delete => "delete",
+            Operation::CreateDir => "create_dir",
+            Operation::CreateLink => "create_link",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How a journaled operation ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Done,
+    /// The operation failed, it may have been retried or skipped after,
+    /// which is recorded as another entry
+    Failed {
+        kind: ErrorKind,
+        message: String,
+    },
+}
+
+/// One operation, recorded once it ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub operation: Operation,
+    pub source: PathBuf,
+    /// `None` for a deletion
+    pub dest: Option<PathBuf>,
+    /// Bytes written, 0 unless file content was
+    pub bytes: u64,
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    pub outcome: Outcome,
+}
+
+impl JournalEntry {
+    /// The entry as one line of JSON, ending with a newline. Times are in
+    /// milliseconds since the Unix epoch. Paths are written with
+    /// [`encode_path`], so that [`decode_path`] gives back any path, those
+    /// that are not UTF-8 included: a UTF-8 path without `%` is written as
+    /// is and `%` becomes `%25`.
+    ///
+    /// [`encode_path`]: crate::convert::encode_path
+    /// [`decode_path`]: crate::convert::decode_path
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// use fdir::journal::{JournalEntry, Operation, Outcome};
+    /// let entry = JournalEntry {
+    ///     operation: Operation::Delete,
+    ///     source: "/srv/old \"notes\".txt".into(),
+    ///     dest: None,
+    ///     bytes: 0,
+    ///     started: UNIX_EPOCH + Duration::from_millis(1500),
+    ///     finished: UNIX_EPOCH + Duration::from_millis(1502),
+    ///     outcome: Outcome::Failed {
+    ///         kind: fdir::ErrorKind::PermissionDenied,
+    ///         message: "Permission denied (os error 13)".into(),
+    ///     },
+    /// };
+    /// assert_eq!(
+    ///     entry.to_json_line(),
+    ///     "{\"operation\":\"delete\",\"source\":\"/srv/old \\\"notes\\\".txt\",\"dest\":null,\
+    ///      \"bytes\":0,\"started_ms\":1500,\"finished_ms\":1502,\"outcome\":\"failed\",\
+    ///      \"error_kind\":\"permission denied\",\"error\":\"Permission denied (os error 13)\"}\n"
+    /// );
+    ///
+    /// #[cfg(unix)]
+    /// {
+    ///     use std::ffi::OsStr;
+    ///     use std::os::unix::ffi::OsStrExt;
+    ///     use fdir::convert::decode_path;
+    ///     let source = std::path::Path::new(OsStr::from_bytes(b"/srv/caf\xe9 100%"));
+    ///     let entry = JournalEntry { source: source.into(), ..entry };
+    ///     let line = entry.to_json_line();
+    ///     assert!(line.contains("\"source\":\"/srv/caf%E9 100%25\""));
+    ///     assert_eq!(decode_path("/srv/caf%E9 100%25").unwrap(), source);
+    /// }
+    /// ```
+    pub fn to_json_line(&self) -> String {
+        let mut line = String::from("{\"operation\":");
+        push_json_str(&mut line, self.operation.as_str());
+        line.push_str(",\"source\":");
+        push_json_str(&mut line, &encode_path(&self.source));
+        line.push_str(",\"dest\":");
+        match &self.dest {
+            Some(dest) => push_json_str(&mut line, &encode_path(dest)),
+            None => line.push_str("null"),
+        }
+        let _ = write!(
+            line,
+            ",\"bytes\":{},\"started_ms\":{},\"finished_ms\":{},\"outcome\":",
+            self.bytes,
+            millis(self.started),
+            millis(self.finished)
+        );
+        match &self.outcome {
+            Outcome::Done => line.push_str("\"done\""),
+            Outcome::Failed { kind, message } => {
+                line.push_str("\"failed\",\"error_kind\":");
+                push_json_str(&mut line, &kind.to_string());
+                line.push_str(",\"error\":");
+                push_json_str(&mut line, message);
+            }
+        }
+        line.push_str("}\n");
+        line
+    }
+}
+
+fn millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis())
+}
+
+fn push_json_str(line: &mut String, value: &str) {
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(line, "\\u{:04x}", c as u32);
+            }
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+/// Where the operations of a copy, move, rename or deletion configured with
+/// `Options::journal` are recorded, failures included
+///
+/// Each file written, directory created, link recreated, entry deleted and
+/// rename is one entry, recorded once it ended. An entry that cannot be
+/// recorded fails the operation, which has been done by then. The journal
+/// is shared by the clones of the options and may be called from several
+/// threads.
+pub trait Journal: Send + Sync {
+    fn record(&self, entry: &JournalEntry) -> Result<()>;
+    /// Make the entries recorded so far durable
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Debug for dyn Journal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Journal")
+    }
+}
+
+/// A [`Journal`] appending JSON lines to a file, see
+/// [`JournalEntry::to_json_line`]
+///
+/// A journal from [`FileJournal::open`] appends each entry as it is
+/// recorded, so that entries survive the process crashing. One from
+/// [`FileJournal::batched`] holds them until [`Journal::flush`] or until it
+/// is dropped. Each append is a single write at the end of the file, so
+/// several processes can share the file.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+/// use fdir::journal::FileJournal;
+/// use fdir::*;
+/// let dir = std::env::temp_dir().join("fdir_journal");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// FileInfo::create(dir.join("source/a.txt")).unwrap().write("hello").unwrap();
+/// let journal = Arc::new(FileJournal::open(dir.join("journal.jsonl")).unwrap());
+/// let options = Options::new().journal(journal.clone());
+///
+/// let source = DirectoryInfo::open(dir.join("source")).unwrap();
+/// source.copy_new_with(dir.join("copy"), &options).unwrap();
+/// let mut copy = FileInfo::open(dir.join("copy/a.txt")).unwrap();
+/// copy.rename_with("b.txt", &options).unwrap();
+/// copy.delete_with(&options).unwrap();
+/// // already gone
+/// let missing = unsafe { FileInfo::open_uncheck(dir.join("copy/b.txt")) };
+/// assert!(missing.delete_with(&options).is_err());
+///
+/// let lines = std::fs::read_to_string(dir.join("journal.jsonl")).unwrap();
+/// let lines: Vec<_> = lines.lines().collect();
+/// assert_eq!(lines.len(), 5);
+/// assert!(lines[0].starts_with("{\"operation\":\"create_dir\""));
+/// assert!(lines[1].contains("\"copy\"") && lines[1].contains("\"bytes\":5"));
+/// assert!(lines[2].starts_with("{\"operation\":\"rename\""));
+/// assert!(lines[3].starts_with("{\"operation\":\"delete\"") && lines[3].ends_with("\"done\"}"));
+/// assert!(lines[4].contains("\"error_kind\":\"not found\""));
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct FileJournal {
+    file: FileInfo,
+    /// Lines not appended yet, `None` when each entry is appended at once
+    pending: Option<Mutex<String>>,
+}
+
+impl FileJournal {
+    /// Append to the file at `path`, created with its parents if missing
+    pub fn open(path: impl AsRef<Path>) -> Result<FileJournal> {
+        Ok(FileJournal {
+            file: open_or_create(path.as_ref())?,
+            pending: None,
+        })
+    }
+
+    /// Same as [`FileJournal::open`], entries are only appended by
+    /// [`Journal::flush`] and when the journal is dropped
+    pub fn batched(path: impl AsRef<Path>) -> Result<FileJournal> {
+        Ok(FileJournal {
+            file: open_or_create(path.as_ref())?,
+            pending: Some(Mutex::new(String::new())),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.as_path()
+    }
+
+    fn pending(pending: &Mutex<String>) -> MutexGuard<'_, String> {
+        pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Created on disk in dry-run mode too, see `set_dry_run`
+fn open_or_create(path: &Path) -> Result<FileInfo> {
+    let path = fix_path(path)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)?;
+    FileInfo::open(path)
+}
+
+impl Journal for FileJournal {
+    fn record(&self, entry: &JournalEntry) -> Result<()> {
+        let line = entry.to_json_line();
+        match &self.pending {
+            Some(pending) => {
+                FileJournal::pending(pending).push_str(&line);
+                Ok(())
+            }
+            None => self.file.append(line),
+        }
+    }
+
+    fn flush(&self) -> Result<()> {
+        let Some(pending) = &self.pending else {
+            return Ok(());
+        };
+        let mut pending = FileJournal::pending(pending);
+        if pending.is_empty() {
+            return Ok(());
+        }
+        self.file.append(pending.as_bytes())?;
+        pending.clear();
+        Ok(())
+    }
+}
+
+impl Drop for FileJournal {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// What a journaled operation wrote
+pub(crate) trait Written: Sized {
+    fn bytes(&self) -> u64 {
+        0
+    }
+    /// What the operation would have returned, see `set_dry_run`
+    fn planned(operation: Operation, source: &Path, dest: Option<&Path>) -> Self;
+}
+
+impl Written for () {
+    fn planned(_: Operation, _: &Path, _: Option<&Path>) {}
+}
+
+impl Written for bool {
+    fn planned(_: Operation, _: &Path, _: Option<&Path>) -> bool {
+        true
+    }
+}
+
+impl Written for CopyReport {
+    fn bytes(&self) -> u64 {
+        self.bytes.0
+    }
+    fn planned(operation: Operation, source: &Path, dest: Option<&Path>) -> CopyReport {
+        let mut report = CopyReport::new();
+        if matches!(operation, Operation::Copy | Operation::Move) {
+            report.bytes = Size(std::fs::metadata(source).map_or(0, |data| data.len()));
+            report.files.extend(dest.map(Path::to_path_buf));
+        }
+        report
+    }
+}
+
+/// Run `op`, recording it in `journal` when there is one. In dry-run mode
+/// `op` is not run and recorded as done.
+pub(crate) fn journaled<T: Written>(
+    journal: Option<&dyn Journal>,
+    operation: Operation,
+    source: &Path,
+    dest: Option<&Path>,
+    op: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    if is_dry_run() {
+        return planned(journal, operation, source, dest);
+    }
+    let Some(journal) = journal else {
+        return op();
+    };
+    let started = SystemTime::now();
+    let result = op();
+    record(journal, operation, source, dest, started, result)
+}
+
+/// What `operation` would have returned, recorded as done in `journal`
+pub(crate) fn planned<T: Written>(
+    journal: Option<&dyn Journal>,
+    operation: Operation,
+    source: &Path,
+    dest: Option<&Path>,
+) -> Result<T> {
+    let result = Ok(T::planned(operation, source, dest));
+    match journal {
+        Some(journal) => record(journal, operation, source, dest, SystemTime::now(), result),
+        None => result,
+    }
+}
+
+/// Record the operation started at `started` that ended with `result`,
+/// which is returned unless the entry cannot be recorded
+pub(crate) fn record<T: Written>(
+    journal: &dyn Journal,
+    operation: Operation,
+    source: &Path,
+    dest: Option<&Path>,
+    started: SystemTime,
+    result: Result<T>,
+) -> Result<T> {
+    let (bytes, outcome) = match &result {
+        Ok(value) => (value.bytes(), Outcome::Done),
+        Err(e) => (
+            0,
+            Outcome::Failed {
+                kind: ErrorKind::of(e),
+                message: e.to_string(),
+            },
+        ),
+    };
+    let recorded = journal.record(&JournalEntry {
+        operation,
+        source: source.to_path_buf(),
+        dest: dest.map(Path::to_path_buf),
+        bytes,
+        started,
+        finished: SystemTime::now(),
+        outcome,
+    });
+    match (result, recorded) {
+        (Ok(_), Err(e)) => Err(Error::new(
+            e.kind(),
+            format!(
+                "The operation on '{}' could not be journaled: {}",
+                source.display(),
+                e
+            ),
+        )),
+        (result, _) => result,
+    }
+}
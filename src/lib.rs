@@ -1,54 +1,234 @@
-// pub mod _async;
+#[cfg(feature = "async")]
+pub mod _async;
 pub mod convert;
-#[allow(non_snake_case)]
+pub mod descriptor;
+pub(crate) mod dry_run;
 pub(crate) mod error;
+pub(crate) mod error_kind;
+pub(crate) mod file_id;
+pub(crate) mod fs_id;
+pub mod hooks;
+pub mod io;
+pub mod journal;
+pub mod options;
+pub(crate) mod recorder;
+pub mod report;
+pub(crate) mod size;
+pub mod stats;
 pub mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "hyper")]
+mod web;
 use std::io::Result;
 use std::{
+    collections::HashSet,
     env::current_dir,
     ffi::OsStr,
-    path::{Path, PathBuf},
+    fs,
+    path::{Component, Path, PathBuf},
 };
+pub use self::options::{
+    ConflictPolicy, CopyOptions, CyclePolicy, KeptExtension, LinkPolicy, LinkRewrite,
+    MissingSource, ModifiedPolicy, Numbering, Options, ParentDirs, PathPolicy, ReadOrder,
+    StagingMode, TraversalOrder, UpdateCheck, UpdateFallback, WalkOptions,
+};
+pub use self::dry_run::{is_dry_run, set_dry_run};
+pub use self::error::{InvalidPath, LinkCycle, PathIssue, SourceNotRemoved};
+pub use self::error_kind::ErrorKind;
+pub use self::file_id::FileId;
+pub use self::fs_id::{mount_point, FsId};
+pub use self::recorder::Recorder;
+pub use self::hooks::{ConflictDecision, ErrorDecision, Progress, ProgressEvent};
+pub use self::report::{CopyReport, MergeReport, RecursiveResult};
+pub use self::size::{ParseSizeError, Size};
+pub use self::stats::DirStats;
 pub use self::sync::*;
 use error::*;
 
-fn push_file_name<P: AsRef<Path>>(file_name: Option<&OsStr>, path: P) -> Result<PathBuf> {
+/// `path` joined with the file name of `source`
+fn push_file_name<P: AsRef<Path>>(source: &Path, path: P) -> Result<PathBuf> {
     let mut path = path.as_ref().to_path_buf();
-    let file_name = match file_name {
+    let file_name = match source.file_name() {
         Some(file_name) => file_name,
-        _ => return INVALID_PATH(),
+        _ => return Err(no_file_name(source, path)),
     };
     path.push(file_name);
     Ok(path)
 }
 
-fn is_same_root(path: &Path, to: &Path) -> bool {
-    let mut path = path.to_path_buf();
-    while path.pop() {}
-    to.starts_with(path)
+/// `name` when it is a single file name: not empty, without a separator
+/// and other than `.` or `..`
+pub(crate) fn single_name(name: &OsStr) -> Option<&OsStr> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(normal)), None) if normal == name => Some(name),
+        _ => None,
+    }
 }
 
-pub struct Recorder {
-    pub pos: u64,
-    pub len: u64
+/// `name` when it is a single file name, an error of kind `InvalidInput`
+/// otherwise
+pub(crate) fn checked_name(name: &OsStr) -> Result<&OsStr> {
+    single_name(name).ok_or_else(|| invalid_path(name, "is not a single file name"))
 }
-impl Recorder {
-    pub fn read(len: u64) -> Self {
-        Self { pos: 0, len }
+
+/// `path` renamed to `name` in the same directory, refused when `name`
+/// would lead somewhere else
+pub(crate) fn renamed(path: &Path, name: &OsStr) -> Result<PathBuf> {
+    match single_name(name) {
+        Some(name) => Ok(path.with_file_name(name)),
+        None => Err(not_a_name(name)),
     }
-    pub fn write(pos: u64) -> Self {
-        Self { pos, len: 0 }
+}
+
+/// `path` with its final extension replaced as [`Path::with_extension`]
+/// does, refused when `extension` would lead to another directory
+pub(crate) fn renamed_extension(path: &Path, extension: &OsStr) -> Result<PathBuf> {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    if !extension.is_empty() {
+        name.push(".");
+        name.push(extension);
     }
-    #[allow(non_snake_case)]
-    pub fn EOF(&self) -> bool {
-        self.pos >= self.len
+    renamed(path, &name)
+}
+
+/// Dotfiles are treated as hidden on every platform
+pub(crate) fn is_hidden(file_name: &OsStr) -> bool {
+    file_name.to_string_lossy().starts_with('.')
+}
+
+/// Everything after the first dot of `name`, ignoring the leading dots of
+/// dotfiles, `None` when there is none or it is empty
+pub(crate) fn full_extension(name: &OsStr) -> Option<&OsStr> {
+    let bytes = name.as_encoded_bytes();
+    let start = bytes.iter().position(|&b| b != b'.')?;
+    let dot = bytes[start..].iter().position(|&b| b == b'.')? + start;
+    let extension = &bytes[dot + 1..];
+    if extension.is_empty() {
+        return None;
+    }
+    // SAFETY: the bytes come from an `OsStr` and are split right after an ASCII dot
+    Some(unsafe { OsStr::from_encoded_bytes_unchecked(extension) })
+}
+
+/// The name of `path` as UTF-8, an [`InvalidPath`] error carries it when it
+/// is not
+pub(crate) fn file_name_str(path: &Path) -> Result<&str> {
+    let Some(name) = path.file_name() else {
+        return Err(invalid_path(path, "has no file name"));
+    };
+    name.to_str().ok_or_else(|| not_unicode(path, name))
+}
+
+/// `path` as UTF-8, an [`InvalidPath`] error carries its first name that is
+/// not
+pub(crate) fn path_str(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| {
+        let name = path
+            .components()
+            .map(|component| component.as_os_str())
+            .find(|name| name.to_str().is_none())
+            .unwrap_or(path.as_os_str());
+        not_unicode(path, name)
+    })
+}
+
+fn not_unicode(path: &Path, name: &OsStr) -> std::io::Error {
+    let component = name.to_os_string();
+    path_issues(path, vec![PathIssue::NotUnicode { component }])
+}
+
+/// Whether `name` ends with the extension `extension`, ignoring case and a
+/// leading dot. A multi-part extension such as `tar.gz` matches the last parts.
+pub(crate) fn has_extension(name: &OsStr, extension: &str) -> bool {
+    let extension = extension.trim_start_matches('.').to_lowercase();
+    let full = match full_extension(name) {
+        Some(full) => full.to_string_lossy().to_lowercase(),
+        None => return false,
+    };
+    !extension.is_empty() && (full == extension || full.ends_with(&format!(".{}", extension)))
+}
+
+/// Whether `a` and `b` name the same existing file or directory, however
+/// they are spelled, see [`FileId`]. Links are followed, a missing path is
+/// never the same.
+pub(crate) fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (FileId::of(a), FileId::of(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// The access and modification times recorded in `metadata`
+pub(crate) fn file_times(metadata: &std::fs::Metadata) -> std::fs::FileTimes {
+    let mut times = std::fs::FileTimes::new();
+    if let Ok(accessed) = metadata.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    if let Ok(modified) = metadata.modified() {
+        times = times.set_modified(modified);
+    }
+    times
+}
+
+/// Set the times of the file or directory at `path`, which may be read-only
+pub(crate) fn set_file_times(path: &Path, times: std::fs::FileTimes) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        // FILE_WRITE_ATTRIBUTES, granted on read-only files too, and
+        // FILE_FLAG_BACKUP_SEMANTICS to open directories
+        options.access_mode(0x100).custom_flags(0x0200_0000);
     }
+    #[cfg(not(windows))]
+    options.read(true);
+    options.open(path)?.set_times(times)
+}
 
+/// First path of the form `name (n).ext` that does not exist yet
+pub(crate) fn unique_path(path: &Path, numbering: Numbering) -> PathBuf {
+    numbered_paths(path, numbering, path.is_dir())
+        .find(|candidate| !candidate.exists())
+        .expect("ran out of candidate names")
 }
 
+/// The numbered variants of `path` that are not taken in its directory, in
+/// order, numbered after the whole name for a directory. The directory is
+/// listed once, so picking the nth name does not check every name before it
+/// on disk, but a name taken since or matching an entry with a different
+/// case is still returned.
+pub(crate) fn numbered_paths(
+    path: &Path,
+    numbering: Numbering,
+    is_dir: bool,
+) -> impl Iterator<Item = PathBuf> + '_ {
+    let (stem, extension) = if is_dir {
+        (path.file_name(), None)
+    } else {
+        (path.file_stem(), path.extension())
+    };
+    let stem = stem.unwrap_or_default().to_string_lossy();
+    let extension = extension.map(|ext| ext.to_string_lossy());
+    let taken: HashSet<_> = path
+        .parent()
+        .and_then(|parent| fs::read_dir(parent).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.file_name()))
+        .collect();
+    (1..)
+        .map(move |n| numbering.apply(&stem, n, extension.as_deref()))
+        .filter(move |name| !taken.contains(OsStr::new(name)))
+        .map(|name| path.with_file_name(name))
+}
 
 pub(crate) fn fix_path(path: impl AsRef<Path>) -> Result<PathBuf> {
     let path = path.as_ref();
+    if path.as_os_str().is_empty() {
+        return Err(invalid_path(path, "is empty"));
+    }
     let mut builder = if path.is_absolute() {
         PathBuf::new()
     } else {
@@ -57,14 +237,320 @@ pub(crate) fn fix_path(path: impl AsRef<Path>) -> Result<PathBuf> {
     for os in path.iter() {
         push_os_str(os, &mut builder)?
     }
+    #[cfg(windows)]
+    let builder = extend_length(builder);
     Ok(builder)
 }
 
+/// Make `path` absolute against the current directory and resolve `.`,
+/// `..` and `~` lexically, without touching the filesystem. This is the
+/// lenient normalisation every `open` applies: `..` above the root is
+/// ignored and `~` anywhere restarts the path at the home directory, see
+/// [`fix_path_strict`] to refuse such paths instead. Trailing and repeated
+/// separators are dropped, and the empty path fails with
+/// `ErrorKind::InvalidInput` rather than meaning the current directory.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// assert_eq!(fdir::normalize("/a/./b/../c").unwrap(), Path::new("/a/c"));
+/// assert_eq!(fdir::normalize("/a//b/").unwrap().to_str(), Some("/a/b"));
+/// let error = fdir::normalize("").unwrap_err();
+/// assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+/// ```
+pub fn normalize(path: impl AsRef<Path>) -> Result<PathBuf> {
+    fix_path(path)
+}
+
+/// Same as [`normalize`], but fail with `ErrorKind::InvalidInput` where
+/// `policy` does not allow a `~` or a `..`, instead of rewriting the path
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use fdir::{fix_path_strict, ParentDirs, PathPolicy};
+/// let strict = PathPolicy::default();
+/// assert_eq!(fix_path_strict("/a/./b", &strict).unwrap(), Path::new("/a/b"));
+/// assert!(fix_path_strict("/a/../b", &strict).is_err());
+/// assert!(fix_path_strict("~/notes", &strict).is_err());
+///
+/// let home = PathPolicy { expand_home: true, ..Default::default() };
+/// assert!(fix_path_strict("~/notes", &home).is_ok());
+/// assert!(fix_path_strict("/srv/~/notes", &home).is_err());
+///
+/// let parents = PathPolicy { parent_dirs: ParentDirs::Allow, ..Default::default() };
+/// assert_eq!(fix_path_strict("/a/b/../c", &parents).unwrap(), Path::new("/a/c"));
+/// assert!(fix_path_strict("/a/../..", &parents).is_err());
+///
+/// let within = PathPolicy {
+///     parent_dirs: ParentDirs::Within("/srv/files".into()),
+///     ..Default::default()
+/// };
+/// assert!(fix_path_strict("/srv/files/a/../b", &within).is_ok());
+/// assert!(fix_path_strict("/srv/files/../secrets", &within).is_err());
+/// // leaving and coming back is refused as well
+/// assert!(fix_path_strict("/srv/files/../files/a", &within).is_err());
+///
+/// let existing = PathPolicy { require_parent: true, ..Default::default() };
+/// let temp = std::env::temp_dir();
+/// assert!(fix_path_strict(temp.join("new.txt"), &existing).is_ok());
+/// let error = fix_path_strict(temp.join("missing/new.txt"), &existing).unwrap_err();
+/// assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+/// ```
+pub fn fix_path_strict(path: impl AsRef<Path>, policy: &PathPolicy) -> Result<PathBuf> {
+    let path = path.as_ref();
+    let base = if path.is_absolute() {
+        PathBuf::new()
+    } else {
+        current_dir()?
+    };
+    let (builder, issues) = check_path(path, policy, base);
+    if !issues.is_empty() {
+        return Err(path_issues(path, issues));
+    }
+    #[cfg(windows)]
+    let builder = extend_length(builder);
+    Ok(builder)
+}
+
+/// Every reason for [`fix_path_strict`] to refuse `path`, in the order of
+/// the components. The error carried by `fix_path_strict` holds the same
+/// issues, see [`InvalidPath`].
+///
+/// # Examples
+/// ```
+/// use std::path::PathBuf;
+/// use fdir::{validate_path, ParentDirs, PathIssue, PathPolicy};
+/// let root = std::env::temp_dir();
+/// let strict = PathPolicy {
+///     require_absolute: true,
+///     portable_names: true,
+///     ..Default::default()
+/// };
+/// assert_eq!(validate_path(root.join("notes.txt"), &strict), Ok(()));
+///
+/// let issues = validate_path(root.join("a?b").join("CON.txt").join("x. "), &strict);
+/// assert_eq!(
+///     issues.unwrap_err(),
+///     [
+///         PathIssue::InvalidComponentChar { component: "a?b".into(), char: '?' },
+///         PathIssue::ReservedName { component: "CON.txt".into() },
+///         PathIssue::TrailingDotOrSpace { component: "x. ".into() },
+///     ]
+/// );
+/// let long = "n".repeat(256);
+/// assert_eq!(
+///     validate_path(root.join(&long), &strict).unwrap_err(),
+///     [PathIssue::NameTooLong { component: long.into(), limit: 255, actual: 256 }]
+/// );
+/// # #[cfg(unix)] {
+/// use std::os::unix::ffi::OsStrExt;
+/// let invalid = std::ffi::OsStr::from_bytes(b"caf\xe9");
+/// assert_eq!(
+///     validate_path(root.join(invalid), &strict).unwrap_err(),
+///     [PathIssue::NotUnicode { component: invalid.into() }]
+/// );
+/// # }
+/// assert_eq!(validate_path("", &strict).unwrap_err(), [PathIssue::EmptyComponent]);
+/// assert_eq!(validate_path("notes.txt", &strict).unwrap_err(), [PathIssue::NotAbsolute]);
+///
+/// let short = PathPolicy { max_len: Some(4), ..Default::default() };
+/// let actual = root.join("notes.txt").as_os_str().len();
+/// assert_eq!(
+///     validate_path(root.join("notes.txt"), &short).unwrap_err(),
+///     [PathIssue::TooLong { limit: 4, actual }]
+/// );
+///
+/// let issues = validate_path(root.join("a/../~/b"), &PathPolicy::default());
+/// assert_eq!(issues.unwrap_err(), [PathIssue::ParentDir, PathIssue::HomeDir]);
+/// let within = PathPolicy {
+///     parent_dirs: ParentDirs::Within(root.join("srv")),
+///     ..Default::default()
+/// };
+/// let issues = validate_path(root.join("srv/../etc"), &within);
+/// assert_eq!(issues.unwrap_err(), [PathIssue::ParentEscapes]);
+///
+/// let existing = PathPolicy { require_parent: true, ..Default::default() };
+/// let missing = root.join("fdir_validate_missing");
+/// assert_eq!(
+///     validate_path(missing.join("a/b.txt"), &existing).unwrap_err(),
+///     [PathIssue::Nonexistent { ancestor: missing.clone() }]
+/// );
+///
+/// // the error of `fix_path_strict` carries the issues
+/// let error = fdir::fix_path_strict(missing.join("a/b.txt"), &existing).unwrap_err();
+/// assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+/// let invalid = fdir::InvalidPath::find(&error).unwrap();
+/// assert_eq!(invalid.issues, [PathIssue::Nonexistent { ancestor: missing }]);
+/// ```
+pub fn validate_path(
+    path: impl AsRef<Path>,
+    policy: &PathPolicy,
+) -> std::result::Result<(), Vec<PathIssue>> {
+    let path = path.as_ref();
+    // without a current directory, a relative path is checked as it is
+    let base = if path.is_absolute() {
+        PathBuf::new()
+    } else {
+        current_dir().unwrap_or_default()
+    };
+    let (_, issues) = check_path(path, policy, base);
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+/// Resolve `path` against `base` as `policy` allows, with the issues met
+fn check_path(path: &Path, policy: &PathPolicy, mut builder: PathBuf) -> (PathBuf, Vec<PathIssue>) {
+    let mut issues = Vec::new();
+    if path.as_os_str().is_empty() {
+        issues.push(PathIssue::EmptyComponent);
+    } else if policy.require_absolute && !path.is_absolute() {
+        issues.push(PathIssue::NotAbsolute);
+    }
+    let root = match &policy.parent_dirs {
+        ParentDirs::Within(root) => Some(fix_path(root).unwrap_or_else(|_| root.clone())),
+        _ => None,
+    };
+    let mut escaped = false;
+    for (i, component) in path.components().enumerate() {
+        match component {
+            Component::CurDir => (),
+            // a doubled or trailing separator of a verbatim path
+            Component::Normal(name) if name.is_empty() => (),
+            Component::ParentDir => {
+                if policy.parent_dirs == ParentDirs::Deny {
+                    issues.push(PathIssue::ParentDir);
+                    continue;
+                }
+                let outside = !builder.pop()
+                    || root.as_ref().is_some_and(|root| {
+                        !matches!(relation(root, &builder), Relation::Ancestor | Relation::Same)
+                    });
+                // reported once, the rest of the path is relative to nothing
+                if outside && !escaped {
+                    issues.push(PathIssue::ParentEscapes);
+                    escaped = true;
+                }
+            }
+            Component::Normal(name) if name == "~" => {
+                match dirs::home_dir() {
+                    Some(home) if i == 0 && policy.expand_home => builder = home,
+                    _ => issues.push(PathIssue::HomeDir),
+                }
+            }
+            Component::Normal(name) => {
+                if policy.portable_names {
+                    issues.extend(convert::name_issue(name));
+                }
+                builder.push(name);
+            }
+            component => builder.push(component),
+        }
+    }
+    if let Some(limit) = policy.max_len {
+        let actual = builder.as_os_str().len();
+        if actual > limit {
+            issues.push(PathIssue::TooLong { limit, actual });
+        }
+    }
+    if policy.require_parent {
+        if let Some(ancestor) = highest_missing(&builder) {
+            issues.push(PathIssue::Nonexistent { ancestor });
+        }
+    }
+    (builder, issues)
+}
+
+/// The highest missing directory above `path`, `None` when its parent exists
+fn highest_missing(path: &Path) -> Option<PathBuf> {
+    let parent = path.parent()?;
+    if parent.is_dir() {
+        return None;
+    }
+    let mut missing = parent;
+    while let Some(up) = missing.parent().filter(|up| !up.is_dir()) {
+        missing = up;
+    }
+    Some(missing.to_path_buf())
+}
+
+/// Paths at least this long need the `\\?\` prefix on Windows
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+/// Add the extended-length prefix to absolute paths that exceed `MAX_PATH`,
+/// `\\server\share` becomes `\\?\UNC\server\share`.
+/// The path must be normalised, verbatim paths do not support `.` and `..`.
+#[cfg(windows)]
+fn extend_length(path: PathBuf) -> PathBuf {
+    use std::ffi::OsString;
+    use std::path::{Component, Prefix};
+    if path.as_os_str().len() < MAX_PATH {
+        return path;
+    }
+    let mut components = path.components();
+    let mut extended = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(_) => {
+                let mut extended = OsString::from(r"\\?\");
+                extended.push(prefix.as_os_str());
+                extended
+            }
+            Prefix::UNC(server, share) => {
+                let mut extended = OsString::from(r"\\?\UNC\");
+                extended.push(server);
+                extended.push(r"\");
+                extended.push(share);
+                extended
+            }
+            // already verbatim or a device path
+            _ => return path,
+        },
+        _ => return path,
+    };
+    for component in components {
+        if let Component::Normal(name) = component {
+            extended.push(r"\");
+            extended.push(name);
+        }
+    }
+    PathBuf::from(extended)
+}
+
+/// Strip the extended-length prefix added by `fix_path` for display
+pub(crate) fn friendly_path(path: &Path) -> std::borrow::Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        let lossy = path.to_string_lossy();
+        if let Some(unc) = lossy.strip_prefix(r"\\?\UNC\") {
+            return PathBuf::from(format!(r"\\{}", unc)).into();
+        }
+        if let Some(disk) = lossy.strip_prefix(r"\\?\") {
+            if disk.as_bytes().get(1) == Some(&b':') {
+                return PathBuf::from(disk).into();
+            }
+        }
+    }
+    path.into()
+}
+
+/// Write the path for `Display`, only the file name with the alternate flag
+pub(crate) fn fmt_path(path: &Path, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match path.file_name() {
+        Some(name) if f.alternate() => write!(f, "{}", Path::new(name).display()),
+        _ => write!(f, "{}", friendly_path(path).display()),
+    }
+}
+
 fn push_os_str(os_str: &OsStr, path: &mut PathBuf) -> Result<()> {
     let pat = os_str.to_string_lossy();
     match pat.as_ref() {
-        "." => (),
-        "~" => *path = dirs::home_dir().expect("'~' is not supported in the current system"),
+        // pushing an empty name would add a trailing separator
+        "" | "." => (),
+        "~" => *path = dirs::home_dir().ok_or_else(|| no_standard_dir("home"))?,
         ".." => {
             path.pop();
         }
@@ -81,17 +567,161 @@ pub fn get_file_path(file_debug: String) -> String {
     path
 }
 
-#[inline]
-pub(crate) fn replace(path: &Path, base: &Path, to: &Path) -> PathBuf {
-    let path_queue: Vec<_> = path.iter().collect();
-    let base_queue: Vec<_> = base.iter().collect();
-    let mut to = to.to_path_buf();
-    let mut i = 0;
-    while i < base_queue.len() && path_queue.get(i) == base_queue.get(i) {
-        i += 1;
+/// Remap `path` from `from_base` onto `to_base`.
+///
+/// Returns an `InvalidInput` error if `path` is not located under `from_base`.
+/// Paths are compared by components, so a trailing separator makes no
+/// difference, and `from_base` itself becomes `to_base`.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// let path = fdir::rebase("/a/b/c.txt", "/a", "/x").unwrap();
+/// assert_eq!(path, Path::new("/x/b/c.txt"));
+/// assert_eq!(fdir::rebase("/a/b/c.txt", "/a/", "/x/").unwrap(), path);
+/// assert_eq!(fdir::rebase("/a/b/", "/a", "/x").unwrap().as_os_str(), "/x/b");
+/// assert_eq!(fdir::rebase("/a", "/a/", "/x").unwrap().as_os_str(), "/x");
+/// assert!(fdir::rebase("/a/b/c.txt", "/y", "/x").is_err());
+/// // only whole components match
+/// assert!(fdir::rebase("/ab/c.txt", "/a", "/x").is_err());
+/// assert!(fdir::rebase("/a", "/a/b", "/x").is_err());
+///
+/// #[cfg(windows)]
+/// {
+///     let path = fdir::rebase(r"C:\a\b.txt", r"C:\a\", r"D:\x").unwrap();
+///     assert_eq!(path, Path::new(r"D:\x\b.txt"));
+///     assert!(fdir::rebase(r"C:\a\b.txt", r"D:\a", r"D:\x").is_err());
+///     assert!(fdir::rebase(r"C:\a\b.txt", r"\a", r"D:\x").is_err());
+/// }
+/// ```
+pub fn rebase(
+    path: impl AsRef<Path>,
+    from_base: impl AsRef<Path>,
+    to_base: impl AsRef<Path>,
+) -> Result<PathBuf> {
+    let (path, from_base) = (path.as_ref(), from_base.as_ref());
+    match path.strip_prefix(from_base) {
+        // joining nothing would add a trailing separator
+        Ok(relative) if relative.as_os_str().is_empty() => Ok(to_base.as_ref().to_path_buf()),
+        Ok(relative) => Ok(to_base.as_ref().join(relative)),
+        Err(_) => Err(not_under_base(path, from_base)),
     }
-    for os in path_queue.iter().skip(i) {
-        to.push(os)
+}
+
+/// `path` relative to the directory `base`, both absolute and normalised,
+/// with `..` for the components of `base` it is not under. `path` itself
+/// when they have no common root, such as different drives.
+pub(crate) fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let shared = shared_components(path, base);
+    if shared == 0 {
+        return path.to_path_buf();
+    }
+    let mut relative: PathBuf = base.components().skip(shared).map(|_| "..").collect();
+    relative.extend(path.components().skip(shared));
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+/// How a path is located relative to another one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    /// The path contains the other one
+    Ancestor,
+    /// The path is contained in the other one
+    Descendant,
+    Same,
+    Disjoint,
+}
+
+/// Normalise with `fix_path` for comparison, falling back to the path as given
+fn comparable(path: &Path) -> PathBuf {
+    fix_path(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Components are compared case-insensitively on Windows only
+fn same_component(a: Component, b: Component) -> bool {
+    #[cfg(windows)]
+    {
+        a.as_os_str().to_string_lossy().to_lowercase()
+            == b.as_os_str().to_string_lossy().to_lowercase()
+    }
+    #[cfg(not(windows))]
+    {
+        a == b
+    }
+}
+
+/// Number of leading components shared by `a` and `b`
+fn shared_components(a: &Path, b: &Path) -> usize {
+    a.components()
+        .zip(b.components())
+        .take_while(|(a, b)| same_component(*a, *b))
+        .count()
+}
+
+/// Locate `path` relative to `other`, comparing whole components after
+/// normalisation, so `/foo/bar` is unrelated to `/foo/barbaz`.
+/// Symlinks are not resolved.
+///
+/// # Examples
+/// ```
+/// use fdir::{relation, Relation};
+/// assert_eq!(relation("/foo", "/foo/bar"), Relation::Ancestor);
+/// assert_eq!(relation("/foo/bar", "/foo/barbaz"), Relation::Disjoint);
+/// ```
+pub fn relation(path: impl AsRef<Path>, other: impl AsRef<Path>) -> Relation {
+    let (path, other) = (comparable(path.as_ref()), comparable(other.as_ref()));
+    let (len, other_len) = (path.components().count(), other.components().count());
+    let shared = shared_components(&path, &other);
+    if shared == len && shared == other_len {
+        Relation::Same
+    } else if shared == len {
+        Relation::Ancestor
+    } else if shared == other_len {
+        Relation::Descendant
+    } else {
+        Relation::Disjoint
+    }
+}
+
+/// Whether `ancestor` strictly contains `path`, see [`relation`]
+pub fn is_ancestor_of(ancestor: impl AsRef<Path>, path: impl AsRef<Path>) -> bool {
+    relation(ancestor, path) == Relation::Ancestor
+}
+
+/// The deepest path containing every path in `paths`, which is the path
+/// itself when only one is given. Returns `None` for an empty slice or
+/// paths without a common root, such as different drives.
+pub fn common_ancestor<P: AsRef<Path>>(paths: &[P]) -> Option<PathBuf> {
+    let (first, rest) = paths.split_first()?;
+    let mut ancestor = comparable(first.as_ref());
+    for path in rest {
+        let path = comparable(path.as_ref());
+        let shared = shared_components(&ancestor, &path);
+        ancestor = ancestor.components().take(shared).collect();
+    }
+    if ancestor.as_os_str().is_empty() {
+        None
+    } else {
+        Some(ancestor)
     }
-    to
-}
\ No newline at end of file
+}
+
+/// Join `relative` onto `base`, failing with `InvalidInput` if the result
+/// would escape `base`, for example through `..` or an absolute path
+///
+/// # Examples
+/// ```
+/// assert!(fdir::safe_join("/srv/files", "a/b.txt").is_ok());
+/// assert!(fdir::safe_join("/srv/files", "../etc/passwd").is_err());
+/// ```
+pub fn safe_join(base: impl AsRef<Path>, relative: impl AsRef<Path>) -> Result<PathBuf> {
+    let base = fix_path(base)?;
+    let joined = fix_path(base.join(relative))?;
+    match relation(&base, &joined) {
+        Relation::Ancestor | Relation::Same => Ok(joined),
+        _ => Err(not_under_base(joined, base)),
+    }
+}
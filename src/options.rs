@@ -0,0 +1,1449 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, FileTimes, Metadata};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use glob::Pattern;
+
+use crate::convert::{sanitize_file_name, SanitizeOptions};
+use crate::error::{
+    already_exist, cancelled, invalid_path, is_cancelled, kind_mismatch, SourceNotRemoved,
+};
+use crate::error_kind::{duplicate, vanished};
+use crate::hooks::{
+    ConflictHook, ErrorHook, EventHook, ProgressEvent, ProgressHook, Transform, TransformHook,
+};
+use crate::io::StreamOptions;
+use crate::journal::{journaled, Journal, Operation, Written};
+use crate::{
+    file_times, unique_path, ConflictDecision, CopyReport, ErrorDecision, PauseToken, Progress,
+};
+
+/// Order in which a directory tree is traversed
+///
+/// # Examples
+/// ```
+/// use fdir::{Action, DirectoryInfo, TraversalOrder, WalkOptions};
+/// let root = std::env::temp_dir().join("fdir_traversal_order");
+/// let _ = std::fs::remove_dir_all(&root);
+/// std::fs::create_dir_all(root.join("a/deep")).unwrap();
+/// std::fs::create_dir_all(root.join("b/deep")).unwrap();
+/// let dir = DirectoryInfo::open(&root).unwrap();
+/// let depths = |order| -> Vec<usize> {
+///     let options = WalkOptions { order, ..Default::default() };
+///     dir.walk_with(&options).map(|entry| entry.unwrap().depth()).collect()
+/// };
+/// // both top level directories come first
+/// assert_eq!(depths(TraversalOrder::Bfs), [1, 1, 2, 2]);
+/// // each directory is followed by its own content
+/// assert_eq!(depths(TraversalOrder::Dfs), [1, 2, 1, 2]);
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TraversalOrder {
+    /// Level by level, memory grows with the widest level of the tree
+    #[default]
+    Bfs,
+    /// Each subtree is finished before its next sibling, memory grows with the depth
+    Dfs,
+}
+
+/// Pause before the first retry, it grows linearly with each attempt
+pub(crate) const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How a directory copy handles symlinks and junctions to directories,
+/// links to files are always copied as regular files. Sizes and walks do
+/// not descend into such links either unless `WalkOptions::follow_links`
+/// is set.
+///
+/// # Examples
+/// ```
+/// # #[cfg(unix)] {
+/// use fdir::*;
+/// let root = std::env::temp_dir().join("fdir_link_policy");
+/// let _ = std::fs::remove_dir_all(&root);
+/// FileInfo::create(root.join("src/data/a.bin")).unwrap().write([0; 100]).unwrap();
+/// std::os::unix::fs::symlink(root.join("src/data"), root.join("src/link")).unwrap();
+/// let src = DirectoryInfo::open(root.join("src")).unwrap();
+/// // the linked directory is not counted twice
+/// assert_eq!(src.size_checked(&WalkOptions::default()).unwrap().bytes, Size(100));
+/// let follow = WalkOptions { follow_links: true, ..Default::default() };
+/// assert_eq!(src.size_checked(&follow).unwrap().bytes, Size(200));
+///
+/// let report = src.copy_new_with(root.join("skip"), &Options::new()).unwrap();
+/// assert_eq!(report.skipped, 1);
+/// assert!(root.join("skip/data/a.bin").is_file());
+/// assert!(std::fs::symlink_metadata(root.join("skip/link")).is_err());
+///
+/// let options = Options::new().links(LinkPolicy::Follow);
+/// src.copy_new_with(root.join("follow"), &options).unwrap();
+/// let copied = std::fs::symlink_metadata(root.join("follow/link")).unwrap();
+/// assert!(copied.is_dir());
+/// assert!(root.join("follow/link/a.bin").is_file());
+///
+/// let options = Options::new().links(LinkPolicy::Recreate);
+/// src.copy_new_with(root.join("recreate"), &options).unwrap();
+/// let recreated = std::fs::symlink_metadata(root.join("recreate/link")).unwrap();
+/// assert!(recreated.file_type().is_symlink());
+/// # std::fs::remove_dir_all(root).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkPolicy {
+    /// Leave the link out, it is counted in `CopyReport::skipped`
+    #[default]
+    Skip,
+    /// Copy the content of the linked directory
+    Follow,
+    /// Create a link with the same target at the destination. On Windows a
+    /// directory symlink is created, which may require a privilege.
+    Recreate,
+}
+
+/// Target given to the links recreated by `LinkPolicy::Recreate`
+///
+/// Relative targets are always kept verbatim, as are absolute ones outside
+/// the copied directory. Absolute targets inside it are compared after
+/// [`normalize`](crate::normalize), so `..` is resolved but other links are
+/// not.
+///
+/// # Examples
+/// ```
+/// # #[cfg(unix)] {
+/// use fdir::*;
+/// use std::os::unix::fs::symlink;
+/// use std::path::Path;
+/// let root = std::env::temp_dir().join("fdir_link_rewrite");
+/// let _ = std::fs::remove_dir_all(&root);
+/// let src = root.join("src");
+/// FileInfo::create(src.join("data/a")).unwrap();
+/// std::fs::create_dir(root.join("outside")).unwrap();
+/// symlink(src.join("data"), src.join("absolute")).unwrap();
+/// symlink("data", src.join("relative")).unwrap();
+/// symlink(root.join("outside"), src.join("external")).unwrap();
+/// symlink(src.join("missing"), src.join("dangling")).unwrap();
+/// let dir = DirectoryInfo::open(&src).unwrap();
+/// let targets = |dest: &Path| {
+///     ["absolute", "relative", "external", "dangling"]
+///         .map(|link| std::fs::read_link(dest.join(link)).unwrap())
+/// };
+///
+/// let options = Options::new().links(LinkPolicy::Recreate);
+/// dir.copy_new_with(root.join("kept"), &options).unwrap();
+/// let [absolute, relative, ..] = targets(&root.join("kept"));
+/// assert_eq!((absolute, relative), (src.join("data"), "data".into()));
+///
+/// let rebase = options.clone().link_rewrite(LinkRewrite::RebaseInsideTree);
+/// dir.copy_new_with(root.join("rebased"), &rebase).unwrap();
+/// let [absolute, relative, external, dangling] = targets(&root.join("rebased"));
+/// assert_eq!(absolute, root.join("rebased/data"));
+/// assert_eq!(relative, Path::new("data"));
+/// assert_eq!(external, root.join("outside"));
+/// assert_eq!(dangling, root.join("rebased/missing"));
+///
+/// let relative = options.link_rewrite(LinkRewrite::MakeRelative);
+/// dir.copy_new_with(root.join("relative"), &relative).unwrap();
+/// let [absolute, _, external, dangling] = targets(&root.join("relative"));
+/// assert_eq!((absolute, dangling), ("data".into(), "missing".into()));
+/// assert_eq!(external, root.join("outside"));
+/// assert!(root.join("relative/absolute/a").exists());
+/// std::fs::remove_dir_all(root).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkRewrite {
+    /// Keep the target of the source link, an absolute one inside the tree
+    /// then still points into the source
+    #[default]
+    Preserve,
+    /// Remap absolute targets inside the tree onto the destination with
+    /// [`rebase`](crate::rebase)
+    RebaseInsideTree,
+    /// Remap them like `RebaseInsideTree`, then store them relative to the
+    /// directory of the link so the copy can be moved as a whole
+    MakeRelative,
+}
+
+/// What a traversal following links does with a link that leads back to a
+/// directory it is inside of
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CyclePolicy {
+    /// Yield an error carrying the [`LinkCycle`](crate::LinkCycle) instead
+    /// of the link
+    #[default]
+    Fail,
+    /// Yield the link without following it
+    Skip,
+}
+
+/// What to do when the destination of a file operation already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConflictPolicy {
+    /// Fail with `ErrorKind::AlreadyExists`
+    #[default]
+    Fail,
+    /// Leave both the source and the destination untouched
+    Skip,
+    /// Replace the destination
+    Overwrite,
+    /// Use the first free name of the form `name (1).ext`, see [`Numbering`]
+    Rename,
+    /// Replace a file only when the source is newer, as `cp -u` does. A
+    /// destination at least as recent is left alone and counted in
+    /// `CopyReport::up_to_date`, see [`UpdateCheck`]. An existing directory
+    /// is merged into.
+    Update,
+}
+
+impl ConflictPolicy {
+    /// The path to write to, `None` when the entry is skipped
+    pub(crate) fn resolve(self, dest: PathBuf, numbering: Numbering) -> Result<Option<PathBuf>> {
+        if !dest.try_exists()? {
+            return Ok(Some(dest));
+        }
+        match self {
+            ConflictPolicy::Fail => Err(already_exist(&dest)),
+            ConflictPolicy::Skip => Ok(None),
+            // an up to date destination was already left alone
+            ConflictPolicy::Overwrite | ConflictPolicy::Update => Ok(Some(dest)),
+            ConflictPolicy::Rename => Ok(Some(unique_path(&dest, numbering))),
+        }
+    }
+}
+
+/// How `ConflictPolicy::Update` decides that a destination is up to date
+///
+/// # Examples
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use fdir::*;
+/// let dir = std::env::temp_dir().join("fdir_update");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// let source = FileInfo::create(dir.join("source")).unwrap();
+/// std::fs::write(source.as_path(), "new").unwrap();
+/// std::fs::write(dir.join("dest"), "newer").unwrap();
+/// let options = Options::new().conflict(ConflictPolicy::Update);
+/// let report = source.copy_new_with(dir.join("dest"), &options).unwrap();
+/// assert_eq!(report.up_to_date, 1);
+/// assert_eq!(std::fs::read_to_string(dir.join("dest")).unwrap(), "newer");
+///
+/// let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+/// let dest = std::fs::File::options().write(true).open(dir.join("dest")).unwrap();
+/// dest.set_modified(hour_ago).unwrap();
+/// let report = source.copy_new_with(dir.join("dest"), &options).unwrap();
+/// assert_eq!((report.up_to_date, report.files.len()), (0, 1));
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateCheck {
+    /// How much more recent the source must be, 2 seconds by default to
+    /// cover FAT timestamps
+    pub tolerance: Duration,
+    /// Used when a modification time cannot be read
+    pub fallback: UpdateFallback,
+}
+
+impl Default for UpdateCheck {
+    fn default() -> Self {
+        Self {
+            tolerance: Duration::from_secs(2),
+            fallback: UpdateFallback::default(),
+        }
+    }
+}
+
+/// How `UpdateCheck` compares files whose modification time is unavailable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UpdateFallback {
+    /// The destination is up to date when it has the length of the source
+    #[default]
+    Size,
+    /// The destination is never up to date
+    Always,
+}
+
+impl UpdateCheck {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn fallback(mut self, fallback: UpdateFallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Whether the file `dest` is at least as recent as `source`, taking
+    /// `tolerance` into account
+    pub(crate) fn is_up_to_date(&self, source: &Path, dest: &Path) -> Result<bool> {
+        let dest = match fs::metadata(dest) {
+            Ok(dest) if dest.is_file() => dest,
+            Ok(_) => return Ok(false),
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let source = fs::metadata(source)?;
+        match (source.modified(), dest.modified()) {
+            (Ok(source), Ok(dest)) => Ok(source <= dest + self.tolerance),
+            _ => Ok(self.fallback == UpdateFallback::Size && source.len() == dest.len()),
+        }
+    }
+}
+
+/// What a file copy does when its source is modified while it is read, as a
+/// log being written to. The source is modified when its length or
+/// modification time differ before and after the copy.
+///
+/// # Examples
+/// A filter appending to its own source, which is then modified by every
+/// copy:
+/// ```
+/// use std::io::{ErrorKind, Write};
+/// use fdir::hooks::Transform;
+/// use fdir::*;
+/// let root = std::env::temp_dir().join("fdir_modified_during_copy");
+/// let _ = std::fs::remove_dir_all(&root);
+/// FileInfo::create(root.join("logs/app.log")).unwrap();
+/// let log = root.join("logs/app.log");
+/// let options = |policy| {
+///     let log = log.clone();
+///     Options::new().modified_during_copy(policy).transform(move |_| {
+///         let log = log.clone();
+///         Some(Transform::new().filter(move |chunk: &[u8]| {
+///             if chunk.is_empty() {
+///                 let mut file = std::fs::File::options().append(true).open(&log).unwrap();
+///                 file.write_all(b"line\n").unwrap();
+///             }
+///             chunk.to_vec()
+///         }))
+///     })
+/// };
+/// let logs = DirectoryInfo::open(root.join("logs")).unwrap();
+/// // copied twice by default, then flagged
+/// let retried = options(ModifiedPolicy::default());
+/// let report = logs.copy_new_with(root.join("copy"), &retried).unwrap();
+/// assert_eq!(report.modified_during_copy, [root.join("copy/app.log")]);
+/// assert_eq!(std::fs::read_to_string(root.join("copy/app.log")).unwrap(), "line\n");
+///
+/// let failing = options(ModifiedPolicy::Fail);
+/// let error = logs.copy_new_with(root.join("failed"), &failing).unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::InvalidData);
+/// assert!(!root.join("failed/app.log").exists());
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifiedPolicy {
+    /// Copy the file again up to this many times, then keep the last copy
+    /// as with `Flag`. One retry by default.
+    Retry(u32),
+    /// Keep the copy and list it in `CopyReport::modified_during_copy`
+    Flag,
+    /// Remove the copy and fail with `ErrorKind::InvalidData`
+    Fail,
+}
+
+impl Default for ModifiedPolicy {
+    fn default() -> Self {
+        ModifiedPolicy::Retry(1)
+    }
+}
+
+impl ModifiedPolicy {
+    /// Whether the copy is made again after attempt number `attempt`,
+    /// counting from 1, saw its source modified
+    pub(crate) fn retries(self, attempt: u32) -> bool {
+        matches!(self, ModifiedPolicy::Retry(retries) if attempt <= retries)
+    }
+}
+
+/// What a traversal does with an entry it listed that is removed before
+/// its turn comes, as temporary files and rotated logs are. Only entries
+/// that are gone by then are concerned, the root of the traversal must
+/// exist.
+///
+/// # Examples
+/// A log rotated while a directory is copied:
+/// ```
+/// use std::io::ErrorKind;
+/// use fdir::*;
+/// let root = std::env::temp_dir().join("fdir_missing_source");
+/// let _ = std::fs::remove_dir_all(&root);
+/// for name in ["app.log", "app.log.1", "config.toml"] {
+///     FileInfo::create(root.join("logs").join(name)).unwrap();
+/// }
+/// let logs = DirectoryInfo::open(root.join("logs")).unwrap();
+/// let rotated = root.join("logs/app.log.1");
+/// let options = |policy| {
+///     let rotated = rotated.clone();
+///     Options::new()
+///         .read_order(ReadOrder::ByName)
+///         .missing_source(policy)
+///         .on_event(move |event| {
+///             // removed once listed, right before it is copied
+///             if let ProgressEvent::FileStarted { path } = event {
+///                 if *path == rotated {
+///                     std::fs::remove_file(path).unwrap();
+///                 }
+///             }
+///         })
+/// };
+///
+/// let error = logs.copy_new_with(root.join("failed"), &options(MissingSource::Error));
+/// assert_eq!(error.unwrap_err().kind(), ErrorKind::NotFound);
+///
+/// FileInfo::create(&rotated).unwrap();
+/// let report = logs.copy_new_with(root.join("copy"), &options(MissingSource::Skip)).unwrap();
+/// assert_eq!(report.skipped_missing, [rotated.clone()]);
+/// assert_eq!(report.files, [root.join("copy/app.log"), root.join("copy/config.toml")]);
+///
+/// // a directory listed by a walk and removed before it is read
+/// std::fs::create_dir_all(root.join("logs/old/2024")).unwrap();
+/// let walk = WalkOptions { missing_source: MissingSource::Skip, ..Default::default() };
+/// let mut entries = logs.walk_with(&walk);
+/// while let Some(entry) = entries.next() {
+///     let entry = entry.unwrap();
+///     if entry.path().ends_with("old") {
+///         std::fs::remove_dir_all(entry.path()).unwrap();
+///     }
+/// }
+/// assert_eq!(entries.missing(), [root.join("logs/old")]);
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingSource {
+    /// Fail as for any other error, the default
+    #[default]
+    Error,
+    /// Leave the entry out. Copies and moves list it in
+    /// `CopyReport::skipped_missing`, `DirStats::errors` does not count it.
+    Skip,
+    /// Same as `Skip`, copies and moves also report a
+    /// `ProgressEvent::Warning` to `Options::on_event`
+    SkipWithWarning,
+}
+
+impl MissingSource {
+    /// Whether `path` is left out after failing with `error` as it was
+    /// removed meanwhile
+    pub(crate) fn skips(self, path: &Path, error: &Error) -> bool {
+        self != MissingSource::Error && vanished(path, error)
+    }
+}
+
+/// Length and modification time of a source file, compared before and after
+/// it is copied
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Snapshot {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Snapshot {
+    pub(crate) fn of(metadata: &Metadata) -> Self {
+        Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
+/// How a copied file is exposed while it is written
+///
+/// # Examples
+/// Observers never see the destination partly written:
+/// ```
+/// use fdir::io::StreamOptions;
+/// use fdir::{Action, FileInfo, Options, StagingMode};
+/// let dir = std::env::temp_dir().join("fdir_staging");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("source"), vec![7; 32 * 1024]).unwrap();
+/// let dest = dir.join("dest");
+/// let watched = dest.clone();
+/// let observer = std::thread::spawn(move || loop {
+///     if let Ok(data) = std::fs::metadata(&watched) {
+///         return data.len();
+///     }
+///     std::thread::yield_now();
+/// });
+/// let stream = StreamOptions::new().buffer_size(4096).rate_limit(128 * 1024);
+/// let options = Options::new().staging(StagingMode::PartSuffix).stream(stream);
+/// let source = FileInfo::open(dir.join("source")).unwrap();
+/// source.copy_new_with(&dest, &options).unwrap();
+/// assert_eq!(observer.join().unwrap(), 32 * 1024);
+/// assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StagingMode {
+    /// Write the destination itself, it exists partly written until the
+    /// copy completes
+    #[default]
+    Direct,
+    /// Write `name.<unique>.part` next to the destination, then rename it
+    /// into place
+    PartSuffix,
+    /// Write the hidden `.name.<unique>.tmp` next to the destination, then
+    /// rename it into place
+    HiddenTemp,
+}
+
+impl StagingMode {
+    /// Where a file bound for `dest` is written, `None` for `Direct`
+    pub(crate) fn temp_path(self, dest: &Path) -> Result<Option<PathBuf>> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        if self == StagingMode::Direct {
+            return Ok(None);
+        }
+        let Some(name) = dest.file_name() else {
+            return Err(invalid_path(dest, "has no file name"));
+        };
+        let unique = format!(
+            "{}.{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut temp = OsString::new();
+        if self == StagingMode::HiddenTemp {
+            temp.push(".");
+        }
+        temp.push(name);
+        temp.push(match self {
+            StagingMode::PartSuffix => format!(".{}.part", unique),
+            _ => format!(".{}.tmp", unique),
+        });
+        Ok(Some(dest.with_file_name(temp)))
+    }
+}
+
+/// In which order a directory copy writes the files of each directory,
+/// its subdirectories are always created first
+///
+/// # Examples
+/// ```
+/// use fdir::*;
+/// let dir = std::env::temp_dir().join("fdir_read_order");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// for name in ["c", "a", "b"] {
+///     FileInfo::create(dir.join("source").join(name)).unwrap();
+/// }
+/// let source = DirectoryInfo::open(dir.join("source")).unwrap();
+/// let options = Options::new().read_order(ReadOrder::ByName);
+/// let report = source.copy_new_with(dir.join("copy"), &options).unwrap();
+/// let names: Vec<_> = report.files.iter().map(|file| file.file_name().unwrap()).collect();
+/// assert_eq!(names, ["a", "b", "c"]);
+/// assert_eq!(report.read_order, Some(ReadOrder::ByName));
+///
+/// let options = Options::new().read_order(ReadOrder::ByInode);
+/// let report = source.copy_new_with(dir.join("by_inode"), &options).unwrap();
+/// # #[cfg(unix)]
+/// assert_eq!(report.read_order, Some(ReadOrder::ByInode));
+/// # #[cfg(not(unix))]
+/// # assert_eq!(report.read_order, Some(ReadOrder::ByName));
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadOrder {
+    /// As the directory lists them
+    #[default]
+    DirectoryOrder,
+    ByName,
+    /// By inode number, which follows the placement on disk on most Unix
+    /// filesystems and saves seeks on spinning disks. Taken from the
+    /// directory listing, without a `stat` each. Elsewhere `ByName`.
+    ByInode,
+}
+
+impl ReadOrder {
+    /// The order followed on this platform
+    pub(crate) fn effective(self) -> ReadOrder {
+        match self {
+            ReadOrder::ByInode if cfg!(not(unix)) => ReadOrder::ByName,
+            order => order,
+        }
+    }
+}
+
+/// Which extension [`FileInfo::rename_keeping_extension`] keeps
+///
+/// [`FileInfo::rename_keeping_extension`]: crate::FileInfo::rename_keeping_extension
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeptExtension {
+    /// The final one, `gz` of `archive.tar.gz`, see `Info::extension`
+    #[default]
+    Last,
+    /// All of them, `tar.gz` of `archive.tar.gz`, see `Info::full_extension`
+    Full,
+}
+
+impl KeptExtension {
+    /// The name made of `stem` and the kept extension of `path`
+    pub(crate) fn name(self, stem: &OsStr, path: &Path) -> OsString {
+        let extension = match self {
+            KeptExtension::Last => path.extension().filter(|ext| !ext.is_empty()),
+            KeptExtension::Full => path.file_name().and_then(crate::full_extension),
+        };
+        let mut name = stem.to_os_string();
+        if let Some(extension) = extension {
+            name.push(".");
+            name.push(extension);
+        }
+        name
+    }
+}
+
+/// How a number is added to a name that is taken, the extension of a file
+/// stays last while a directory is numbered after its whole name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Numbering {
+    /// `name (1).ext`
+    #[default]
+    Parenthesized,
+    /// `name-1.ext`
+    Dashed,
+}
+
+impl Numbering {
+    pub(crate) fn apply(self, stem: &str, n: u64, extension: Option<&str>) -> String {
+        let numbered = match self {
+            Numbering::Parenthesized => format!("{} ({})", stem, n),
+            Numbering::Dashed => format!("{}-{}", stem, n),
+        };
+        match extension {
+            Some(extension) => format!("{}.{}", numbered, extension),
+            None => numbered,
+        }
+    }
+}
+
+/// How [`fix_path_strict`](crate::fix_path_strict) treats `..` components
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ParentDirs {
+    /// Refuse any `..`
+    #[default]
+    Deny,
+    /// Remove the previous component, refusing to go above the root
+    Allow,
+    /// Like `Allow`, but refuse a `..` that leaves this directory. Paths
+    /// without `..` are not restricted.
+    Within(PathBuf),
+}
+
+/// What [`fix_path_strict`](crate::fix_path_strict) and
+/// [`validate_path`](crate::validate_path) accept. The default is the
+/// strictest about `~` and `..` and checks nothing else.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathPolicy {
+    /// Expand a leading `~` to the home directory. A `~` anywhere else is
+    /// always refused.
+    pub expand_home: bool,
+    pub parent_dirs: ParentDirs,
+    /// Fail with `ErrorKind::NotFound` unless the parent of the resulting
+    /// path is an existing directory
+    pub require_parent: bool,
+    /// Refuse relative paths instead of resolving them against the current
+    /// directory
+    pub require_absolute: bool,
+    /// Refuse a resulting path longer than this many bytes
+    pub max_len: Option<usize>,
+    /// Refuse names that are invalid on Windows, see
+    /// [`portability_issue`](crate::convert::portability_issue)
+    pub portable_names: bool,
+}
+
+/// Options shared by every traversal of a directory tree
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    pub order: TraversalOrder,
+    /// Do not descend below this depth, direct children have depth 1
+    pub max_depth: Option<usize>,
+    /// Stop after this many entries, the traversal is then flagged as truncated.
+    /// A directory copy or move walks the tree first and refuses to start
+    /// when it holds more entries.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::{Action, DirectoryInfo, Options, WalkOptions};
+    /// let root = std::env::temp_dir().join("fdir_max_entries");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// std::fs::create_dir_all(root.join("src/sub")).unwrap();
+    /// std::fs::write(root.join("src/a.txt"), "a").unwrap();
+    /// std::fs::write(root.join("src/sub/b.txt"), "b").unwrap();
+    /// let src = DirectoryInfo::open(root.join("src")).unwrap();
+    ///
+    /// let exact = WalkOptions { max_entries: Some(3), ..Default::default() };
+    /// let mut walk = src.walk_with(&exact);
+    /// assert_eq!(walk.by_ref().count(), 3);
+    /// assert!(!walk.is_truncated());
+    ///
+    /// let short = WalkOptions { max_entries: Some(2), ..Default::default() };
+    /// let mut walk = src.walk_with(&short);
+    /// assert_eq!(walk.by_ref().count(), 2);
+    /// assert!(walk.is_truncated());
+    ///
+    /// let error = src
+    ///     .copy_new_with(root.join("copy"), &Options::new().walk(short))
+    ///     .unwrap_err();
+    /// assert!(error.to_string().contains("more than 2 entries"));
+    /// assert!(!root.join("copy").exists());
+    /// src.copy_new_with(root.join("copy"), &Options::new().walk(exact))
+    ///     .unwrap();
+    /// assert!(root.join("copy/sub/b.txt").exists());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub max_entries: Option<u64>,
+    /// Do not descend into directories on another filesystem than the root,
+    /// like `du -x`, see [`FsId`](crate::FsId)
+    pub same_filesystem: bool,
+    /// Descend into symlinks and junctions that point at directories.
+    /// By default they are yielded as links and never followed.
+    pub follow_links: bool,
+    /// What to do with a followed link leading back to a directory
+    /// containing it. Cycles are told apart by [`FileId`](crate::FileId),
+    /// so links spelled in any way are caught.
+    pub on_cycle: CyclePolicy,
+    /// With `follow_links`, descend into each directory only once even when
+    /// links reach it by several paths, it is still yielded for the other
+    /// paths. By default a directory is walked once per path leading to it
+    /// and only cycles are cut.
+    pub visit_once: bool,
+    /// Skip entries matched by `.gitignore` and `.ignore` files found in the
+    /// tree, including nested files and negated patterns. Ignore files above
+    /// the traversed directory are not consulted.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_ignore_files");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let files = [
+    ///     // above the walked directory, never consulted
+    ///     (".gitignore", "*.txt\n"),
+    ///     ("project/.gitignore", "*.log\n!keep.log\n"),
+    ///     ("project/notes.txt", ""),
+    ///     ("project/debug.log", ""),
+    ///     ("project/keep.log", ""),
+    ///     // re-ignores what the root negated
+    ///     ("project/sub/.gitignore", "keep.log\n"),
+    ///     ("project/sub/keep.log", ""),
+    ///     ("project/sub/trace.log", ""),
+    ///     ("project/sub/data.txt", ""),
+    /// ];
+    /// for (path, content) in files {
+    ///     FileInfo::create(root.join(path)).unwrap().write(content).unwrap();
+    /// }
+    /// let project = DirectoryInfo::open(root.join("project")).unwrap();
+    /// let walk = WalkOptions { ignore_files: true, ..Default::default() };
+    /// let mut kept: Vec<_> = project
+    ///     .walk_with(&walk)
+    ///     .map(|entry| entry.unwrap().path().strip_prefix(project.as_path()).unwrap().to_path_buf())
+    ///     .collect();
+    /// kept.sort();
+    /// let expected = [".gitignore", "keep.log", "notes.txt", "sub", "sub/.gitignore", "sub/data.txt"];
+    /// assert_eq!(kept, expected.map(std::path::PathBuf::from));
+    /// let options = Options::new().walk(walk);
+    /// project.copy_new_with(root.join("copy"), &options).unwrap();
+    /// assert!(root.join("copy/keep.log").exists());
+    /// assert!(root.join("copy/sub/data.txt").exists());
+    /// assert!(!root.join("copy/debug.log").exists());
+    /// assert!(!root.join("copy/sub/keep.log").exists());
+    /// assert!(!root.join("copy/sub/trace.log").exists());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    #[cfg(feature = "ignore")]
+    pub ignore_files: bool,
+    /// Count a file reached by several hard links once in the sizes of
+    /// `stats_with` and `size_checked`, as `du` does. Files are told apart
+    /// by [`FileId`](crate::FileId).
+    pub count_hard_links_once: bool,
+    /// Count the length of files in those sizes, the default. Otherwise
+    /// count the space allocated for them, `st_blocks * 512` on Unix and
+    /// the compressed size on Windows, which is smaller for sparse and
+    /// compressed files and matches `du` without `--apparent-size`.
+    pub apparent_size: bool,
+    /// What to do with an entry removed after it was listed
+    pub missing_source: MissingSource,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            order: TraversalOrder::default(),
+            max_depth: None,
+            max_entries: None,
+            same_filesystem: false,
+            follow_links: false,
+            on_cycle: CyclePolicy::default(),
+            visit_once: false,
+            #[cfg(feature = "ignore")]
+            ignore_files: false,
+            count_hard_links_once: false,
+            apparent_size: true,
+            missing_source: MissingSource::Error,
+        }
+    }
+}
+
+/// Options for the `copy_new_with`, `move_new_with` and `delete_with`
+/// variants, built once and shared by a session of operations.
+/// `Default` gives the behaviour of the methods without options.
+///
+/// # Examples
+/// ```
+/// let options = fdir::Options::new().overwrite(true).preserve_times(true).retries(3);
+/// assert_eq!(options.conflict, fdir::ConflictPolicy::Overwrite);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Scan the whole source tree before writing anything and fail with
+    /// `ErrorKind::InvalidInput` listing every name that is invalid on Windows
+    pub portable_names: bool,
+    /// Scan the whole source tree before writing anything and fail with
+    /// `ErrorKind::InvalidInput` listing the names that differ only by case,
+    /// for destinations on a case-insensitive filesystem
+    pub fail_on_case_collision: bool,
+    pub walk: WalkOptions,
+    /// Overrides `walk.follow_links`
+    pub links: LinkPolicy,
+    /// Target of the links recreated with `LinkPolicy::Recreate`
+    pub link_rewrite: LinkRewrite,
+    /// When not empty, only files matching one of the patterns are copied,
+    /// directories are still traversed
+    pub include: Vec<Pattern>,
+    /// Entries matching one of the patterns are skipped, a matching
+    /// directory is never traversed. Exclusion wins over inclusion.
+    pub exclude: Vec<Pattern>,
+    /// What to do when a destination already exists. An existing directory
+    /// is merged into when overwriting, its existing files are replaced.
+    pub conflict: ConflictPolicy,
+    /// Let a conflict resolved by overwriting replace a directory by a
+    /// file, or anything else by a directory, by removing the destination
+    /// first. Without it such a conflict fails with
+    /// `ErrorKind::KindMismatch` and the destination is left untouched.
+    pub allow_replace_kind_mismatch: bool,
+    /// Names picked by `ConflictPolicy::Rename`
+    pub numbering: Numbering,
+    /// How `ConflictPolicy::Update` compares the files
+    pub update: UpdateCheck,
+    /// How each copied file is exposed while it is written, also used when
+    /// a move falls back to copying
+    pub staging: StagingMode,
+    /// Order of the files written into each directory by a directory copy
+    /// or a move falling back to copying
+    pub read_order: ReadOrder,
+    /// Give written files the access and modification times of their source
+    pub preserve_times: bool,
+    /// What a file copy does when its source is modified meanwhile
+    pub modified_during_copy: ModifiedPolicy,
+    /// Copy the NTFS alternate data streams of each file. A destination
+    /// volume without streams is not an error, the file is listed in
+    /// `CopyReport::streams_skipped`.
+    #[cfg(all(windows, feature = "ads"))]
+    pub preserve_ads: bool,
+    /// Copy the DACL of each file and directory, and what `acl` adds to it.
+    /// A descriptor that cannot be set is listed in
+    /// `CopyReport::acl_warnings` unless `acl.strict`. Moves by a rename
+    /// keep their descriptor anyway.
+    #[cfg(all(windows, feature = "acl"))]
+    pub preserve_acl: bool,
+    #[cfg(all(windows, feature = "acl"))]
+    pub acl: crate::sync::acl::AclOptions,
+    /// How many times a failing file operation is tried again, errors such
+    /// as `NotFound` or `AlreadyExists` are never retried
+    pub retries: u32,
+    /// Decide each conflict of a directory operation, takes precedence over
+    /// `conflict`
+    pub on_conflict: Option<ConflictHook>,
+    /// Decide what to do with an entry of a directory operation that could
+    /// not be written once `retries` are exhausted. Without it the
+    /// operation stops at the first error.
+    pub on_error: Option<ErrorHook>,
+    /// Called after each file written by a directory operation
+    pub on_progress: Option<ProgressHook>,
+    /// Called with the `FileStarted`, `FileDone`, `Conflict` and `Warning`
+    /// events of a copy or move, see
+    /// [`DirectoryInfo::copy_new_events`](crate::DirectoryInfo::copy_new_events)
+    /// for all the events as they come
+    pub on_event: Option<EventHook>,
+    /// Decide for each file of a directory copy whether it is written
+    /// under another path or with rewritten content, see
+    /// [`Transform`](crate::hooks::Transform). Moves do not use it.
+    pub transform: Option<TransformHook>,
+    /// How the content of each file is copied
+    pub stream: StreamOptions,
+    /// Where the operations are recorded, see
+    /// [`Journal`](crate::journal::Journal)
+    pub journal: Option<Arc<dyn Journal>>,
+    /// Checked before each file of a directory copy or move, which waits
+    /// while it is paused, see [`PauseToken`]
+    pub pause: Option<PauseToken>,
+    /// Pass the file name of the destination of a file copy through
+    /// [`sanitize_file_name`] before its conflict is resolved, so that the
+    /// names picked by `ConflictPolicy::Rename` are sanitized as well
+    pub sanitize: Option<SanitizeOptions>,
+}
+
+/// Former name of [`Options`]
+pub type CopyOptions = Options;
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn portable_names(mut self, portable_names: bool) -> Self {
+        self.portable_names = portable_names;
+        self
+    }
+
+    pub fn fail_on_case_collision(mut self, fail_on_case_collision: bool) -> Self {
+        self.fail_on_case_collision = fail_on_case_collision;
+        self
+    }
+
+    pub fn walk(mut self, walk: WalkOptions) -> Self {
+        self.walk = walk;
+        self
+    }
+
+    pub fn links(mut self, links: LinkPolicy) -> Self {
+        self.links = links;
+        self
+    }
+
+    pub fn link_rewrite(mut self, link_rewrite: LinkRewrite) -> Self {
+        self.link_rewrite = link_rewrite;
+        self
+    }
+
+    /// Add a pattern to `include`
+    pub fn include(mut self, pattern: Pattern) -> Self {
+        self.include.push(pattern);
+        self
+    }
+
+    /// Add a pattern to `exclude`
+    pub fn exclude(mut self, pattern: Pattern) -> Self {
+        self.exclude.push(pattern);
+        self
+    }
+
+    pub fn conflict(mut self, conflict: ConflictPolicy) -> Self {
+        self.conflict = conflict;
+        self
+    }
+
+    /// See [`Options::allow_replace_kind_mismatch`](#structfield.allow_replace_kind_mismatch)
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_kind_mismatch");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let file = FileInfo::create(root.join("a.txt")).unwrap();
+    /// FileInfo::create(root.join("dest/a.txt/kept")).unwrap();
+    /// let dest = root.join("dest/a.txt");
+    ///
+    /// // a file is not written over a directory, even when overwriting
+    /// let error = file.copy_new(&dest).unwrap_err();
+    /// assert_eq!(fdir::ErrorKind::of(&error.error), fdir::ErrorKind::AlreadyExists);
+    /// let error = error.try_recover().unwrap_err();
+    /// assert_eq!(fdir::ErrorKind::of(&error), fdir::ErrorKind::KindMismatch);
+    /// let error = file.copy_new_with(&dest, &Options::new().overwrite(true)).unwrap_err();
+    /// assert_eq!(fdir::ErrorKind::of(&error), fdir::ErrorKind::KindMismatch);
+    /// assert!(dest.join("kept").is_file());
+    ///
+    /// // nor a directory over a file
+    /// let dir = DirectoryInfo::open(root.join("dest")).unwrap();
+    /// let error = dir.copy_new_with(file.as_path(), &Options::new().overwrite(true));
+    /// assert_eq!(fdir::ErrorKind::of(&error.unwrap_err()), fdir::ErrorKind::KindMismatch);
+    /// assert!(file.as_path().is_file());
+    ///
+    /// let replace = Options::new().overwrite(true).allow_replace_kind_mismatch(true);
+    /// file.copy_new_with(&dest, &replace).unwrap();
+    /// assert!(dest.is_file());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn allow_replace_kind_mismatch(mut self, allow: bool) -> Self {
+        self.allow_replace_kind_mismatch = allow;
+        self
+    }
+
+    pub fn numbering(mut self, numbering: Numbering) -> Self {
+        self.numbering = numbering;
+        self
+    }
+
+    pub fn update(mut self, update: UpdateCheck) -> Self {
+        self.update = update;
+        self
+    }
+
+    pub fn staging(mut self, staging: StagingMode) -> Self {
+        self.staging = staging;
+        self
+    }
+
+    pub fn read_order(mut self, read_order: ReadOrder) -> Self {
+        self.read_order = read_order;
+        self
+    }
+
+    /// Shorthand for `conflict(ConflictPolicy::Overwrite)`, `false` restores
+    /// `ConflictPolicy::Fail`
+    pub fn overwrite(self, overwrite: bool) -> Self {
+        self.conflict(if overwrite {
+            ConflictPolicy::Overwrite
+        } else {
+            ConflictPolicy::Fail
+        })
+    }
+
+    pub fn preserve_times(mut self, preserve_times: bool) -> Self {
+        self.preserve_times = preserve_times;
+        self
+    }
+
+    pub fn modified_during_copy(mut self, policy: ModifiedPolicy) -> Self {
+        self.modified_during_copy = policy;
+        self
+    }
+
+    /// Set `walk.missing_source`
+    pub fn missing_source(mut self, policy: MissingSource) -> Self {
+        self.walk.missing_source = policy;
+        self
+    }
+
+    #[cfg(all(windows, feature = "ads"))]
+    pub fn preserve_ads(mut self, preserve_ads: bool) -> Self {
+        self.preserve_ads = preserve_ads;
+        self
+    }
+
+    #[cfg(all(windows, feature = "acl"))]
+    pub fn preserve_acl(mut self, preserve_acl: bool) -> Self {
+        self.preserve_acl = preserve_acl;
+        self
+    }
+
+    #[cfg(all(windows, feature = "acl"))]
+    pub fn acl(mut self, acl: crate::sync::acl::AclOptions) -> Self {
+        self.acl = acl;
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn stream(mut self, stream: StreamOptions) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    pub fn journal(mut self, journal: Arc<dyn Journal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn pause(mut self, pause: PauseToken) -> Self {
+        self.pause = Some(pause);
+        self
+    }
+
+    /// See [`Options::sanitize`](#structfield.sanitize)
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// use fdir::convert::SanitizeOptions;
+    /// let root = std::env::temp_dir().join("fdir_copy_sanitized");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let file = FileInfo::create(root.join("a.txt")).unwrap();
+    /// FileInfo::create(root.join("out/what_.txt")).unwrap();
+    /// let options = Options::new()
+    ///     .conflict(ConflictPolicy::Rename)
+    ///     .sanitize(SanitizeOptions::default());
+    /// let report = file.copy_new_with(root.join("out/what?.txt"), &options).unwrap();
+    /// assert_eq!(report.files, [root.join("out/what_ (1).txt")]);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn sanitize(mut self, sanitize: SanitizeOptions) -> Self {
+        self.sanitize = Some(sanitize);
+        self
+    }
+
+    /// Called with the source and the existing destination
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// use std::sync::{Arc, Mutex};
+    /// let root = std::env::temp_dir().join("fdir_hooks");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for name in ["src/keep.txt", "src/new.txt", "src/clash", "src/sub/c.txt"] {
+    ///     FileInfo::create(root.join(name)).unwrap().write("source").unwrap();
+    /// }
+    /// FileInfo::create(root.join("dest/keep.txt")).unwrap().write("dest").unwrap();
+    /// FileInfo::create(root.join("dest/sub/c.txt")).unwrap().write("dest").unwrap();
+    /// // a directory where the source has a file cannot be written over
+    /// std::fs::create_dir_all(root.join("dest/clash")).unwrap();
+    ///
+    /// let conflicts = Arc::new(Mutex::new(Vec::new()));
+    /// let errors = Arc::new(Mutex::new(Vec::new()));
+    /// let written = Arc::new(Mutex::new(Vec::new()));
+    /// let (c, e, w) = (conflicts.clone(), errors.clone(), written.clone());
+    /// let options = Options::new()
+    ///     .on_conflict(move |source, _| {
+    ///         c.lock().unwrap().push(source.file_name().unwrap().to_owned());
+    ///         // merge into the existing destination, but leave the existing
+    ///         // subdirectory out with its content
+    ///         if source.ends_with("sub") {
+    ///             ConflictDecision::Skip
+    ///         } else {
+    ///             ConflictDecision::Overwrite
+    ///         }
+    ///     })
+    ///     .on_error(move |source, _| {
+    ///         e.lock().unwrap().push(source.file_name().unwrap().to_owned());
+    ///         ErrorDecision::Skip
+    ///     })
+    ///     .on_progress(move |progress| w.lock().unwrap().push(progress.files));
+    /// let src = DirectoryInfo::open(root.join("src")).unwrap();
+    /// let report = src.copy_new_with(root.join("dest"), &options).unwrap();
+    /// let mut conflicts = conflicts.lock().unwrap().clone();
+    /// conflicts.sort();
+    /// assert_eq!(conflicts, ["clash", "keep.txt", "src", "sub"]);
+    /// assert_eq!(*errors.lock().unwrap(), ["clash"]);
+    /// assert_eq!(*written.lock().unwrap(), [1, 2]);
+    /// // the skipped subdirectory and the failed file
+    /// assert_eq!(report.skipped, 2);
+    /// let read = |name| std::fs::read_to_string(root.join("dest").join(name)).unwrap();
+    /// assert_eq!(read("keep.txt"), "source");
+    /// assert_eq!(read("new.txt"), "source");
+    /// assert_eq!(read("sub/c.txt"), "dest");
+    /// assert!(root.join("dest/clash").is_dir());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn on_conflict(
+        mut self,
+        f: impl FnMut(&Path, &Path) -> ConflictDecision + Send + 'static,
+    ) -> Self {
+        self.on_conflict = Some(ConflictHook::new(f));
+        self
+    }
+
+    /// Called with the source of the entry and the error
+    pub fn on_error(
+        mut self,
+        f: impl FnMut(&Path, &Error) -> ErrorDecision + Send + 'static,
+    ) -> Self {
+        self.on_error = Some(ErrorHook::new(f));
+        self
+    }
+
+    pub fn on_progress(mut self, f: impl FnMut(&Progress) + Send + 'static) -> Self {
+        self.on_progress = Some(ProgressHook::new(f));
+        self
+    }
+
+    pub fn on_event(mut self, f: impl FnMut(&ProgressEvent) + Send + 'static) -> Self {
+        self.on_event = Some(EventHook::new(f));
+        self
+    }
+
+    /// Called with the path of each file relative to the source directory
+    pub fn transform(mut self, f: impl FnMut(&Path) -> Option<Transform> + Send + 'static) -> Self {
+        self.transform = Some(TransformHook::new(f));
+        self
+    }
+
+    /// `dest` with its file name sanitized when `sanitize` is set
+    pub(crate) fn sanitized(&self, dest: PathBuf) -> PathBuf {
+        match (&self.sanitize, dest.file_name()) {
+            (Some(sanitize), Some(name)) => {
+                let name = sanitize_file_name(&name.to_string_lossy(), sanitize);
+                dest.with_file_name(name)
+            }
+            _ => dest,
+        }
+    }
+
+    /// The path to write `source` to, `None` when it is skipped
+    pub(crate) fn resolve_conflict(&self, source: &Path, dest: PathBuf) -> Result<Option<PathBuf>> {
+        if self.on_event.is_some() && dest.try_exists()? {
+            self.event(ProgressEvent::Conflict { path: dest.clone() });
+        }
+        let policy = match &self.on_conflict {
+            Some(hook) if dest.try_exists()? => (hook.lock())(source, &dest).into(),
+            _ => self.conflict,
+        };
+        let Some(dest) = policy.resolve(dest, self.numbering)? else {
+            return Ok(None);
+        };
+        if let Some(error) = replaced_kind(source, &dest)? {
+            if !self.allow_replace_kind_mismatch {
+                return Err(error);
+            }
+            self.journaled(Operation::Delete, &dest, None, || {
+                crate::sync::remove_file_any(&dest)
+            })?;
+        }
+        Ok(Some(dest))
+    }
+
+    /// Whether the file `source` is left alone because `conflict` is
+    /// `ConflictPolicy::Update` and `dest` is up to date. An `on_conflict`
+    /// hook takes precedence.
+    pub(crate) fn up_to_date(&self, source: &Path, dest: &Path) -> Result<bool> {
+        if self.on_conflict.is_some() || self.conflict != ConflictPolicy::Update {
+            return Ok(false);
+        }
+        self.update.is_up_to_date(source, dest)
+    }
+
+    /// Run `op` for the entry `source`, asking `on_error` what to do when
+    /// it fails. `None` means the entry is skipped.
+    pub(crate) fn handle<T>(
+        &self,
+        source: &Path,
+        mut op: impl FnMut() -> Result<T>,
+    ) -> Result<Option<T>> {
+        loop {
+            let error = match op() {
+                Ok(value) => return Ok(Some(value)),
+                Err(e) => e,
+            };
+            match self.decide(source, &error) {
+                ErrorDecision::Retry => continue,
+                ErrorDecision::Skip => return Ok(None),
+                ErrorDecision::Abort => return Err(error),
+            }
+        }
+    }
+
+    pub(crate) fn decide(&self, source: &Path, error: &Error) -> ErrorDecision {
+        let decision = match &self.on_error {
+            Some(hook) => (hook.lock())(source, error),
+            None => ErrorDecision::Abort,
+        };
+        self.skipped(source, error, decision)
+    }
+
+    /// Same as `decide` for an entry removed meanwhile, which is skipped
+    /// without a hook
+    pub(crate) fn decide_vanished(&self, source: &Path, error: &Error) -> ErrorDecision {
+        let decision = match &self.on_error {
+            Some(hook) => (hook.lock())(source, error),
+            None => ErrorDecision::Skip,
+        };
+        self.skipped(source, error, decision)
+    }
+
+    /// `result` of the operation on `source`, an entry listed by a
+    /// traversal, with the error of a source removed meanwhile turned into
+    /// `Ok(None)` when `walk.missing_source` leaves it out
+    pub(crate) fn missing<T>(&self, source: &Path, result: Result<T>) -> Result<Option<T>> {
+        match result {
+            Err(e) if self.walk.missing_source.skips(source, &e) => {
+                self.warn_missing(source, &e);
+                Ok(None)
+            }
+            result => result.map(Some),
+        }
+    }
+
+    /// Report `source`, left out as removed meanwhile, as a warning when
+    /// `walk.missing_source` asks for it
+    pub(crate) fn warn_missing(&self, source: &Path, error: &Error) {
+        if self.walk.missing_source == MissingSource::SkipWithWarning {
+            self.event(ProgressEvent::Warning {
+                path: source.to_path_buf(),
+                error: duplicate(error),
+            });
+        }
+    }
+
+    /// Report `error` as a warning when `decision` leaves `source` out
+    fn skipped(&self, source: &Path, error: &Error, decision: ErrorDecision) -> ErrorDecision {
+        if decision == ErrorDecision::Skip && self.on_event.is_some() {
+            self.event(ProgressEvent::Warning {
+                path: source.to_path_buf(),
+                error: duplicate(error),
+            });
+        }
+        decision
+    }
+
+    pub(crate) fn event(&self, event: ProgressEvent) {
+        if let Some(hook) = &self.on_event {
+            (hook.lock())(&event);
+        }
+    }
+
+    /// Wait until `pause` lets the next file of a directory operation go,
+    /// fails once the operation is cancelled
+    pub(crate) fn wait_resumed(&self) -> Result<()> {
+        if let Some(pause) = &self.pause {
+            pause.wait_unless(self.stream.cancel.as_deref());
+        }
+        self.check_cancelled()
+    }
+
+    /// Fail once `stream.cancel` is set
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        match &self.stream.cancel {
+            Some(cancel) if cancel.load(Ordering::Relaxed) => Err(cancelled()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Report the file `source` of a directory operation before writing it
+    pub(crate) fn file_started(&self, source: &Path) {
+        if self.on_event.is_some() {
+            self.event(ProgressEvent::FileStarted {
+                path: source.to_path_buf(),
+            });
+        }
+    }
+
+    /// How the file at `relative` in the source directory is copied
+    pub(crate) fn transform_of(&self, relative: &Path) -> Option<Transform> {
+        (self.transform.as_ref()?.lock())(relative)
+    }
+
+    /// Run `op`, recorded in `journal` when there is one
+    pub(crate) fn journaled<T: Written>(
+        &self,
+        operation: Operation,
+        source: &Path,
+        dest: Option<&Path>,
+        op: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        journaled(self.journal.as_deref(), operation, source, dest, op)
+    }
+
+    /// Record an operation that was done
+    pub(crate) fn journal_done(
+        &self,
+        operation: Operation,
+        source: &Path,
+        dest: Option<&Path>,
+    ) -> Result<()> {
+        self.journaled(operation, source, dest, || Ok(()))
+    }
+
+    /// Report `source`, written to the last file of `report`
+    pub(crate) fn progress(&self, source: &Path, report: &CopyReport) {
+        if self.on_event.is_some() {
+            self.event(ProgressEvent::FileDone {
+                path: source.to_path_buf(),
+            });
+        }
+        if let (Some(hook), Some(dest)) = (&self.on_progress, report.files.last()) {
+            (hook.lock())(&Progress {
+                source,
+                dest,
+                files: report.files.len() as u64,
+                bytes: report.bytes.0,
+            });
+        }
+    }
+
+    /// Whether attempt number `attempt`, counting from 1, follows `error`.
+    /// A move that already reached its destination is not repeated.
+    pub(crate) fn should_retry(&self, attempt: u32, error: &Error) -> bool {
+        attempt <= self.retries
+            && SourceNotRemoved::find(error).is_none()
+            && !is_cancelled(error)
+            && !matches!(
+                error.kind(),
+                ErrorKind::NotFound
+                    | ErrorKind::AlreadyExists
+                    | ErrorKind::InvalidInput
+                    | ErrorKind::InvalidData
+                    | ErrorKind::Unsupported
+            )
+    }
+
+    /// Run `op` until it succeeds or `should_retry` gives up
+    pub(crate) fn retry<T>(&self, mut op: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match op() {
+                Err(e) if self.should_retry(attempt + 1, &e) => {
+                    attempt += 1;
+                    std::thread::sleep(RETRY_DELAY * attempt);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The times to give the destination of a write, `None` unless
+    /// `preserve_times` is set
+    pub(crate) fn times_of(&self, source: &Metadata) -> Option<FileTimes> {
+        self.preserve_times.then(|| file_times(source))
+    }
+
+    pub(crate) fn has_filters(&self) -> bool {
+        !self.include.is_empty() || !self.exclude.is_empty()
+    }
+
+    /// Decide whether an entry is copied, `relative` is relative to the source root
+    ///
+    /// A pattern without a separator is also matched against the bare file
+    /// name, so `*.tmp` or `target` apply at any depth.
+    pub(crate) fn accepts(&self, relative: &Path, is_dir: bool) -> bool {
+        if self
+            .exclude
+            .iter()
+            .any(|pattern| matches(pattern, relative))
+        {
+            return false;
+        }
+        is_dir
+            || self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| matches(pattern, relative))
+    }
+}
+
+pub(crate) fn matches(pattern: &Pattern, relative: &Path) -> bool {
+    if pattern.matches_path(relative) {
+        return true;
+    }
+    let has_separator = pattern.as_str().contains(['/', '\\']);
+    !has_separator
+        && relative
+            .file_name()
+            .is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+}
+
+/// The error of writing `source` over the existing `dest` when one is a
+/// directory and the other is not. A link at `dest` is replaced itself by
+/// a file, while a directory is merged into what it leads to.
+fn replaced_kind(source: &Path, dest: &Path) -> Result<Option<Error>> {
+    let Ok(dest_metadata) = fs::symlink_metadata(dest) else {
+        return Ok(None);
+    };
+    let source_is_dir = fs::metadata(source)?.is_dir();
+    let mismatch = if source_is_dir {
+        !fs::metadata(dest).is_ok_and(|data| data.is_dir())
+    } else {
+        dest_metadata.is_dir()
+    };
+    Ok(mismatch.then(|| kind_mismatch(source, dest, source_is_dir)))
+}
@@ -0,0 +1,208 @@
+use std::time::{Duration, Instant};
+
+/// Number of samples kept by the throughput window
+const SAMPLES: usize = 8;
+/// Least time between two samples, the window spans about
+/// `SAMPLES * SLICE`
+const SLICE: Duration = Duration::from_millis(500);
+
+/// Position in a stream of `len` bytes, with the throughput and the time
+/// left once [`Recorder::timed`]
+///
+/// Untimed recorders never read the clock. A timed one keeps a few samples
+/// inline and stays `Copy`, the throughput is measured over the last four
+/// seconds or so, which follows a changing rate without jumping on every
+/// chunk.
+///
+/// # Examples
+/// ```
+/// use std::time::{Duration, Instant};
+/// use fdir::Recorder;
+/// let start = Instant::now();
+/// let mut recorder = Recorder::read(2000).timed_at(start);
+/// assert_eq!((recorder.percent(), recorder.eta()), (0, None));
+/// // 100 bytes per second for 3 seconds
+/// for second in 1..=3 {
+///     recorder.advance_at(100, start + Duration::from_secs(second));
+/// }
+/// assert_eq!(recorder.remaining(), 1700);
+/// assert_eq!(recorder.percent(), 15);
+/// assert_eq!(recorder.bytes_per_sec(), Some(100.0));
+/// assert_eq!(recorder.eta(), Some(Duration::from_secs(17)));
+/// // the rate doubles, the window forgets the slow start
+/// for second in 4..=11 {
+///     recorder.advance_at(200, start + Duration::from_secs(second));
+/// }
+/// assert_eq!(recorder.bytes_per_sec(), Some(200.0));
+/// assert_eq!(recorder.eta(), Some(Duration::from_millis(500)));
+/// recorder.advance_at(100, start + Duration::from_secs(12));
+/// assert_eq!((recorder.percent(), recorder.eta()), (100, Some(Duration::ZERO)));
+/// assert_eq!(Recorder::read(0).fraction(), 1.0);
+/// assert!(Recorder::read(10).eta().is_none());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Recorder {
+    pub pos: u64,
+    pub len: u64,
+    window: Option<Window>,
+}
+
+/// Ring of `(time, pos)` samples, `last` is the time of the latest advance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Window {
+    samples: [(Instant, u64); SAMPLES],
+    /// Index of the oldest sample
+    oldest: usize,
+    count: usize,
+    last: (Instant, u64),
+}
+
+impl Window {
+    fn new(now: Instant, pos: u64) -> Self {
+        Self {
+            samples: [(now, pos); SAMPLES],
+            oldest: 0,
+            count: 1,
+            last: (now, pos),
+        }
+    }
+
+    fn record(&mut self, now: Instant, pos: u64) {
+        let newest = self.samples[(self.oldest + self.count - 1) % SAMPLES];
+        if now.saturating_duration_since(newest.0) >= SLICE {
+            if self.count == SAMPLES {
+                self.oldest = (self.oldest + 1) % SAMPLES;
+            } else {
+                self.count += 1;
+            }
+            self.samples[(self.oldest + self.count - 1) % SAMPLES] = (now, pos);
+        }
+        self.last = (now, pos);
+    }
+
+    fn bytes_per_sec(&self) -> Option<f64> {
+        let (start, from) = self.samples[self.oldest];
+        let (end, to) = self.last;
+        let elapsed = end.saturating_duration_since(start).as_secs_f64();
+        (elapsed > 0.0).then(|| to.saturating_sub(from) as f64 / elapsed)
+    }
+}
+
+impl Recorder {
+    pub fn read(len: u64) -> Self {
+        Self {
+            pos: 0,
+            len,
+            window: None,
+        }
+    }
+    pub fn write(pos: u64) -> Self {
+        Self {
+            pos,
+            len: 0,
+            window: None,
+        }
+    }
+    /// Measure the throughput from now on
+    pub fn timed(self) -> Self {
+        self.timed_at(Instant::now())
+    }
+    /// Measure the throughput from `start`, see [`Recorder::advance_at`]
+    pub fn timed_at(mut self, start: Instant) -> Self {
+        self.window = Some(Window::new(start, self.pos));
+        self
+    }
+    pub fn is_timed(&self) -> bool {
+        self.window.is_some()
+    }
+    #[allow(non_snake_case)]
+    pub fn EOF(&self) -> bool {
+        self.pos >= self.len
+    }
+    /// Move `n` bytes further, the clock is only read when timed
+    pub fn advance(&mut self, n: u64) {
+        match self.window {
+            Some(_) => self.advance_at(n, Instant::now()),
+            None => self.pos = self.pos.saturating_add(n),
+        }
+    }
+    /// Move `n` bytes further at the time `now`, for callers that already
+    /// read the clock and for tests
+    pub fn advance_at(&mut self, n: u64, now: Instant) {
+        self.pos = self.pos.saturating_add(n);
+        if let Some(window) = &mut self.window {
+            window.record(now, self.pos);
+        }
+    }
+    pub fn remaining(&self) -> u64 {
+        self.len.saturating_sub(self.pos)
+    }
+    /// Share of `len` done, from 0 to 1. An empty stream is done.
+    pub fn fraction(&self) -> f64 {
+        if self.len == 0 {
+            return 1.0;
+        }
+        (self.pos as f64 / self.len as f64).min(1.0)
+    }
+    /// [`Recorder::fraction`] in percent, rounded down so that 100 means
+    /// done. Computed on integers, without the float rounding error.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::Recorder;
+    /// let at = |pos, len| {
+    ///     let mut recorder = Recorder::read(len);
+    ///     recorder.pos = pos;
+    ///     recorder.percent()
+    /// };
+    /// assert_eq!([at(29, 100), at(57, 100), at(58, 100)], [29, 57, 58]);
+    /// assert_eq!([at(1, 3), at(2, 3), at(99, 100)], [33, 66, 99]);
+    /// assert_eq!([at(0, 0), at(200, 100)], [100, 100]);
+    /// assert_eq!(at(u64::MAX - 1, u64::MAX), 99);
+    /// ```
+    pub fn percent(&self) -> u8 {
+        if self.len == 0 {
+            return 100;
+        }
+        (self.pos.min(self.len) as u128 * 100 / self.len as u128) as u8
+    }
+    /// Bytes per second over the recent window, `None` until timed and
+    /// some time has passed
+    pub fn bytes_per_sec(&self) -> Option<f64> {
+        self.window?.bytes_per_sec()
+    }
+    /// Time left at the current throughput, `None` while it is unknown or
+    /// zero, or when the rate is so low that the time left overflows a
+    /// [`Duration`]
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use fdir::Recorder;
+    /// let start = Instant::now();
+    /// let mut recorder = Recorder::read(1000).timed_at(start);
+    /// // no time has passed yet
+    /// recorder.advance_at(100, start);
+    /// assert_eq!(recorder.eta(), None);
+    /// // 100 bytes in 4 seconds, then a stall
+    /// recorder.advance_at(0, start + Duration::from_secs(4));
+    /// assert_eq!(recorder.eta(), Some(Duration::from_secs(36)));
+    /// // 1 byte in 2 seconds leaves far more than `Duration::MAX`
+    /// let mut huge = Recorder::read(u64::MAX).timed_at(start);
+    /// huge.advance_at(1, start + Duration::from_secs(2));
+    /// assert_eq!(huge.bytes_per_sec(), Some(0.5));
+    /// assert_eq!(huge.eta(), None);
+    /// // nothing moved, the rate is zero
+    /// let mut stalled = Recorder::read(10).timed_at(start);
+    /// stalled.advance_at(0, start + Duration::from_secs(1));
+    /// assert_eq!(stalled.eta(), None);
+    /// ```
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.remaining();
+        if remaining == 0 {
+            return self.window.map(|_| Duration::ZERO);
+        }
+        let rate = self.bytes_per_sec().filter(|&rate| rate > 0.0)?;
+        Duration::try_from_secs_f64(remaining as f64 / rate).ok()
+    }
+}
@@ -0,0 +1,126 @@
+use std::io::Error;
+use std::path::PathBuf;
+
+use crate::{is_dry_run, ReadOrder, Size};
+
+/// What a directory copy or move did, paths are destination paths
+#[derive(Debug, Clone, Default)]
+pub struct CopyReport {
+    /// Files in the order they were written
+    pub files: Vec<PathBuf>,
+    /// Directories in the order they were created, starting with the root
+    pub directories: Vec<PathBuf>,
+    /// Total length of the written files
+    pub bytes: Size,
+    /// Links recreated at the destination
+    pub links: Vec<PathBuf>,
+    /// Destination files deleted because they were removed from the source
+    pub removed: Vec<PathBuf>,
+    /// Entries left out by the include and exclude filters or the link
+    /// policy, an excluded directory counts once
+    pub skipped: u64,
+    /// Files left alone because their destination was at least as recent,
+    /// see `ConflictPolicy::Update`
+    pub up_to_date: u64,
+    /// Files whose source was still modified after the last copy, see
+    /// [`ModifiedPolicy`](crate::ModifiedPolicy). Their content may not
+    /// match the source.
+    pub modified_during_copy: Vec<PathBuf>,
+    /// Source entries removed after they were listed and left out, see
+    /// [`MissingSource`](crate::MissingSource)
+    pub skipped_missing: Vec<PathBuf>,
+    /// Files whose alternate data streams were left out as the destination
+    /// volume has none, see `Options::preserve_ads`
+    #[cfg(all(windows, feature = "ads"))]
+    pub streams_skipped: Vec<PathBuf>,
+    /// Files and directories whose security descriptor could not be set,
+    /// with the error message, see `Options::preserve_acl`
+    #[cfg(all(windows, feature = "acl"))]
+    pub acl_warnings: Vec<(PathBuf, String)>,
+    /// Whether a move was done by a single rename. A move across
+    /// filesystems copies and deletes instead, keeping the permissions,
+    /// times and extended attributes of what it moves.
+    pub renamed: bool,
+    /// Order the files of each directory were written in, `None` when no
+    /// directory was copied. See `Options::read_order`.
+    pub read_order: Option<ReadOrder>,
+    /// Whether nothing was written, the report listing what would have
+    /// been, see [`set_dry_run`](crate::set_dry_run)
+    pub dry_run: bool,
+}
+
+impl CopyReport {
+    /// An empty report for an operation starting now
+    pub(crate) fn new() -> Self {
+        Self {
+            dry_run: is_dry_run(),
+            ..Default::default()
+        }
+    }
+
+    /// Whether no entry was left out, a move then leaves nothing behind
+    pub(crate) fn is_complete(&self) -> bool {
+        self.skipped == 0 && self.up_to_date == 0
+    }
+
+    /// Add the entries of `other`, the report of a nested operation
+    pub(crate) fn append(&mut self, other: CopyReport) {
+        self.files.extend(other.files);
+        self.directories.extend(other.directories);
+        self.bytes += other.bytes;
+        self.links.extend(other.links);
+        self.removed.extend(other.removed);
+        self.skipped += other.skipped;
+        self.up_to_date += other.up_to_date;
+        self.modified_during_copy.extend(other.modified_during_copy);
+        self.skipped_missing.extend(other.skipped_missing);
+        #[cfg(all(windows, feature = "ads"))]
+        self.streams_skipped.extend(other.streams_skipped);
+        #[cfg(all(windows, feature = "acl"))]
+        self.acl_warnings.extend(other.acl_warnings);
+        self.read_order = self.read_order.or(other.read_order);
+        self.dry_run |= other.dry_run;
+    }
+}
+
+/// What [`DirectoryInfo::merge_into`](crate::DirectoryInfo::merge_into)
+/// did, each list in path order. Paths are destination paths, except the
+/// skipped ones which are left in the source.
+#[derive(Debug, Clone, Default)]
+pub struct MergeReport {
+    /// Entries moved where nothing existed, under their new name with
+    /// `ConflictPolicy::Rename`. A directory counts once with its content.
+    pub moved: Vec<PathBuf>,
+    /// Directories found on both sides, whose content was merged
+    pub merged: Vec<PathBuf>,
+    /// Destination files replaced by their source
+    pub overwritten: Vec<PathBuf>,
+    /// Source entries left in place by the conflict policy
+    pub skipped: Vec<PathBuf>,
+}
+
+impl MergeReport {
+    pub(crate) fn sort(&mut self) {
+        self.moved.sort();
+        self.merged.sort();
+        self.overwritten.sort();
+        self.skipped.sort();
+    }
+}
+
+/// What a recursive permission change did, see
+/// [`DirectoryInfo::set_readonly_recursive`](crate::DirectoryInfo::set_readonly_recursive)
+#[derive(Debug, Default)]
+pub struct RecursiveResult {
+    /// Entries whose permissions were set, the directory itself included
+    pub changed: u64,
+    /// Entries that could not be changed and directories that could not be
+    /// listed, the rest of the tree is changed anyway
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+impl RecursiveResult {
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
@@ -0,0 +1,269 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
+use std::str::FromStr;
+
+/// Binary units, each 1024 times the previous one
+const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// A number of bytes, shown in binary units
+///
+/// Formatting picks the largest unit the value reaches, with one decimal
+/// unless a precision is given. A value that would round up to 1024 of a
+/// unit is shown in the next one instead.
+///
+/// Parsing takes a number with an optional fraction followed by an optional
+/// unit, case insensitively and with or without a space in between: `B`,
+/// binary units as `KiB` or `K` up to `EiB` or `E`, and decimal units as
+/// `KB` or `kB` up to `EB`. Fractions of a byte are dropped.
+///
+/// # Examples
+/// ```
+/// use fdir::Size;
+/// assert_eq!(Size(0).to_string(), "0 B");
+/// assert_eq!(Size(1023).to_string(), "1023 B");
+/// assert_eq!(Size(1024).to_string(), "1.0 KiB");
+/// assert_eq!(Size(1536).to_string(), "1.5 KiB");
+/// assert_eq!(Size(1 << 30).to_string(), "1.0 GiB");
+/// // rounds up to the next unit rather than to "1024.0 KiB"
+/// assert_eq!(Size((1 << 20) - 1).to_string(), "1.0 MiB");
+/// assert_eq!(format!("{:.2}", Size(1_500_000)), "1.43 MiB");
+/// assert_eq!(Size(1 << 60).to_string(), "1.0 EiB");
+/// assert_eq!(Size(u64::MAX).to_string(), "16.0 EiB");
+///
+/// assert_eq!("0".parse::<Size>().unwrap(), Size(0));
+/// assert_eq!("512 B".parse::<Size>().unwrap(), Size(512));
+/// assert_eq!("1.5GiB".parse::<Size>().unwrap(), Size(3 << 29));
+/// assert_eq!("1.5 gib".parse::<Size>().unwrap(), Size(3 << 29));
+/// assert_eq!("64k".parse::<Size>().unwrap(), Size(64 << 10));
+/// assert_eq!("2 MB".parse::<Size>().unwrap(), Size(2_000_000));
+/// assert_eq!("0.1 KiB".parse::<Size>().unwrap(), Size(102));
+/// assert_eq!("1 EiB".parse::<Size>().unwrap(), Size(1 << 60));
+/// assert_eq!("16 EiB".parse::<Size>().unwrap_err().to_string(),
+///     "invalid size '16 EiB': too large");
+/// assert!("".parse::<Size>().is_err());
+/// assert!("-1 KiB".parse::<Size>().is_err());
+/// assert!("1.5 KiBs".parse::<Size>().is_err());
+///
+/// // formatting and parsing round trip on exact values
+/// let size = Size(5 << 40);
+/// assert_eq!(size.to_string().parse::<Size>().unwrap(), size);
+/// assert_eq!(Size(1000) + Size(24), Size(1024));
+/// assert_eq!(Size(3) * 2 / 4, Size(1));
+/// assert_eq!([Size(1), Size(2)].into_iter().sum::<Size>(), Size(3));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Size(pub u64);
+
+impl Size {
+    pub const ZERO: Size = Size(0);
+
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+
+    pub fn checked_add(self, other: Size) -> Option<Size> {
+        self.0.checked_add(other.0).map(Size)
+    }
+
+    pub fn checked_sub(self, other: Size) -> Option<Size> {
+        self.0.checked_sub(other.0).map(Size)
+    }
+
+    pub fn saturating_add(self, other: Size) -> Size {
+        Size(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Size) -> Size {
+        Size(self.0.saturating_sub(other.0))
+    }
+}
+
+impl Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let precision = f.precision().unwrap_or(1);
+        // the largest value shown below 1024 at this precision
+        let limit = 1024.0 - 0.5 / 10f64.powi(precision.min(16) as i32);
+        let mut size = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while size >= limit && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        write!(f, "{:.*} {}", precision, size, UNITS[unit])
+    }
+}
+
+impl FromStr for Size {
+    type Err = ParseSizeError;
+
+    fn from_str(input: &str) -> Result<Size, ParseSizeError> {
+        let error = |reason| ParseSizeError {
+            input: input.to_string(),
+            reason,
+        };
+        let text = input.trim();
+        let number_len = text
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(text.len());
+        let (number, unit) = text.split_at(number_len);
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(error("no number"));
+        }
+        if fraction.contains('.') {
+            return Err(error("more than one decimal point"));
+        }
+        let multiplier = unit_multiplier(unit.trim_start()).ok_or(error("unknown unit"))?;
+
+        let too_large = || error("too large");
+        let whole: u128 = match whole {
+            "" => 0,
+            whole => whole.parse().map_err(|_| too_large())?,
+        };
+        let mut bytes = whole.checked_mul(multiplier).ok_or_else(too_large)?;
+        // digits past the 19th are below a byte for every unit
+        let fraction = &fraction[..fraction.len().min(19)];
+        if !fraction.is_empty() {
+            let digits: u128 = fraction.parse().map_err(|_| too_large())?;
+            bytes += digits * multiplier / 10u128.pow(fraction.len() as u32);
+        }
+        u64::try_from(bytes).map(Size).map_err(|_| too_large())
+    }
+}
+
+/// Bytes in one `unit`, `None` when it is not one
+fn unit_multiplier(unit: &str) -> Option<u128> {
+    let unit = unit.to_ascii_lowercase();
+    if unit.is_empty() || unit == "b" {
+        return Some(1);
+    }
+    let (prefix, suffix) = unit.split_at(unit.chars().next()?.len_utf8());
+    let power = "kmgtpe".find(prefix)? as u32 + 1;
+    match suffix {
+        "" | "ib" => Some(1024u128.pow(power)),
+        "b" => Some(1000u128.pow(power)),
+        _ => None,
+    }
+}
+
+/// Why a string is not a [`Size`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSizeError {
+    input: String,
+    reason: &'static str,
+}
+
+impl Display for ParseSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid size '{}': {}", self.input, self.reason)
+    }
+}
+
+impl Error for ParseSizeError {}
+
+impl From<u64> for Size {
+    fn from(bytes: u64) -> Size {
+        Size(bytes)
+    }
+}
+
+impl From<Size> for u64 {
+    fn from(size: Size) -> u64 {
+        size.0
+    }
+}
+
+impl Add for Size {
+    type Output = Size;
+
+    fn add(self, other: Size) -> Size {
+        Size(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Size {
+    fn add_assign(&mut self, other: Size) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Size {
+    type Output = Size;
+
+    fn sub(self, other: Size) -> Size {
+        Size(self.0 - other.0)
+    }
+}
+
+impl SubAssign for Size {
+    fn sub_assign(&mut self, other: Size) {
+        self.0 -= other.0;
+    }
+}
+
+impl Mul<u64> for Size {
+    type Output = Size;
+
+    fn mul(self, factor: u64) -> Size {
+        Size(self.0 * factor)
+    }
+}
+
+impl Div<u64> for Size {
+    type Output = Size;
+
+    fn div(self, divisor: u64) -> Size {
+        Size(self.0 / divisor)
+    }
+}
+
+impl Sum for Size {
+    fn sum<I: Iterator<Item = Size>>(iter: I) -> Size {
+        iter.fold(Size::ZERO, Add::add)
+    }
+}
+
+/// Serialized as a number of bytes, a string such as `"1.5 GiB"` is also
+/// read
+#[cfg(feature = "serde")]
+impl serde::Serialize for Size {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Size {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Size, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = Size;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a number of bytes or a size such as \"1.5 GiB\"")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, bytes: u64) -> Result<Size, E> {
+                Ok(Size(bytes))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, bytes: i64) -> Result<Size, E> {
+                u64::try_from(bytes)
+                    .map(Size)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Signed(bytes), &self))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, text: &str) -> Result<Size, E> {
+                text.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
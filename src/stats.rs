@@ -0,0 +1,165 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::Metadata;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{FileId, Size, WalkOptions};
+
+/// Summary of a directory tree, see `DirectoryInfo::stats`
+#[derive(Debug, Clone, Default)]
+pub struct DirStats {
+    pub files: u64,
+    pub directories: u64,
+    pub symlinks: u64,
+    /// Total length of all regular files, or their allocated size, see
+    /// `WalkOptions::apparent_size` and `WalkOptions::count_hard_links_once`
+    pub bytes: Size,
+    pub largest_file: Option<(PathBuf, Size)>,
+    /// Most recent modification time of any regular file
+    pub newest: Option<SystemTime>,
+    /// Oldest modification time of any regular file
+    pub oldest: Option<SystemTime>,
+    /// Number of regular files per lowercased extension, files without an
+    /// extension are counted under `""`
+    pub extensions: BTreeMap<String, u64>,
+    /// Depth of the deepest entry, direct children have depth 1
+    pub max_depth: usize,
+    /// Entries that could not be read
+    pub errors: u64,
+    /// The traversal stopped at `WalkOptions::max_entries`
+    pub truncated: bool,
+}
+
+/// Result of `DirectoryInfo::size_checked`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirSize {
+    /// Counted as set by `WalkOptions::apparent_size` and
+    /// `WalkOptions::count_hard_links_once`
+    pub bytes: Size,
+    /// The traversal stopped at `WalkOptions::max_entries`
+    pub truncated: bool,
+}
+
+impl DirStats {
+    /// Account one entry, `metadata` must not follow symlinks
+    pub(crate) fn record(
+        &mut self,
+        path: &Path,
+        depth: usize,
+        metadata: &Metadata,
+        sizes: &mut SizeCounter,
+    ) {
+        self.max_depth = self.max_depth.max(depth);
+        let file_type = metadata.file_type();
+        if file_type.is_symlink() {
+            self.symlinks += 1;
+        } else if file_type.is_dir() {
+            self.directories += 1;
+        } else {
+            self.files += 1;
+            let len = Size(metadata.len());
+            match sizes.size(path, metadata) {
+                Ok(size) => self.bytes += Size(size),
+                Err(_) => self.errors += 1,
+            }
+            if self
+                .largest_file
+                .as_ref()
+                .is_none_or(|(_, size)| len > *size)
+            {
+                self.largest_file = Some((path.to_path_buf(), len));
+            }
+            if let Ok(modified) = metadata.modified() {
+                self.newest = Some(self.newest.map_or(modified, |t| t.max(modified)));
+                self.oldest = Some(self.oldest.map_or(modified, |t| t.min(modified)));
+            }
+            let extension = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            *self.extensions.entry(extension).or_default() += 1;
+        }
+    }
+}
+
+/// The size a file adds to a total, as set by the `WalkOptions` of the
+/// traversal
+#[derive(Debug, Default)]
+pub(crate) struct SizeCounter {
+    allocated: bool,
+    /// Files already counted, only when counting hard links once
+    seen: Option<HashSet<FileId>>,
+}
+
+impl SizeCounter {
+    pub(crate) fn new(options: &WalkOptions) -> Self {
+        Self {
+            allocated: !options.apparent_size,
+            seen: options.count_hard_links_once.then(HashSet::new),
+        }
+    }
+
+    /// Size of the regular file at `path`, 0 when it was already counted
+    /// through another link
+    pub(crate) fn size(&mut self, path: &Path, metadata: &Metadata) -> Result<u64> {
+        if let Some(seen) = &mut self.seen {
+            if let Some(id) = linked_id(path, metadata)? {
+                if !seen.insert(id) {
+                    return Ok(0);
+                }
+            }
+        }
+        if self.allocated {
+            return allocated_size(path, metadata);
+        }
+        Ok(metadata.len())
+    }
+}
+
+/// The id of a file with other hard links, `None` when it has a single one
+#[cfg(unix)]
+fn linked_id(_: &Path, metadata: &Metadata) -> Result<Option<FileId>> {
+    use std::os::unix::fs::MetadataExt;
+    Ok((metadata.nlink() > 1).then(|| FileId::from_parts(metadata.dev(), metadata.ino())))
+}
+
+/// The link count is not in the metadata, every file is looked up
+#[cfg(not(unix))]
+fn linked_id(path: &Path, _: &Metadata) -> Result<Option<FileId>> {
+    FileId::of(path).map(Some)
+}
+
+#[cfg(unix)]
+fn allocated_size(_: &Path, metadata: &Metadata) -> Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(metadata.blocks() * 512)
+}
+
+#[cfg(windows)]
+fn allocated_size(path: &Path, _: &Metadata) -> Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCompressedFileSizeW(file_name: *const u16, size_high: *mut u32) -> u32;
+    }
+
+    let name: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    let mut high = 0u32;
+    // SAFETY: `name` is nul terminated and `high` outlives the call
+    let low = unsafe { GetCompressedFileSizeW(name.as_ptr(), &mut high) };
+    // INVALID_FILE_SIZE is also a valid low half, the last error tells
+    if low == u32::MAX {
+        let error = std::io::Error::last_os_error();
+        if error.raw_os_error() != Some(0) {
+            return Err(error);
+        }
+    }
+    Ok(u64::from(high) << 32 | u64::from(low))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn allocated_size(_: &Path, metadata: &Metadata) -> Result<u64> {
+    Ok(metadata.len())
+}
@@ -0,0 +1,214 @@
+//! Windows security descriptors, the access control lists and owner of
+//! files and directories, copied along with them by `Options::preserve_acl`
+
+use std::ffi::c_void;
+use std::io::{Error, Result};
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::CopyReport;
+
+/// `SE_FILE_OBJECT`
+const FILE_OBJECT: i32 = 1;
+const OWNER_SECURITY_INFORMATION: u32 = 0x1;
+const GROUP_SECURITY_INFORMATION: u32 = 0x2;
+const DACL_SECURITY_INFORMATION: u32 = 0x4;
+const SACL_SECURITY_INFORMATION: u32 = 0x8;
+const PROTECTED_DACL_SECURITY_INFORMATION: u32 = 0x8000_0000;
+const PROTECTED_SACL_SECURITY_INFORMATION: u32 = 0x4000_0000;
+const UNPROTECTED_DACL_SECURITY_INFORMATION: u32 = 0x2000_0000;
+const UNPROTECTED_SACL_SECURITY_INFORMATION: u32 = 0x1000_0000;
+/// `SE_DACL_PROTECTED` and `SE_SACL_PROTECTED` among the control flags
+const DACL_PROTECTED: u16 = 0x1000;
+const SACL_PROTECTED: u16 = 0x2000;
+
+#[link(name = "advapi32")]
+extern "system" {
+    fn GetNamedSecurityInfoW(
+        object_name: *const u16,
+        object_type: i32,
+        security_info: u32,
+        owner: *mut *mut c_void,
+        group: *mut *mut c_void,
+        dacl: *mut *mut c_void,
+        sacl: *mut *mut c_void,
+        security_descriptor: *mut *mut c_void,
+    ) -> u32;
+    fn SetNamedSecurityInfoW(
+        object_name: *const u16,
+        object_type: i32,
+        security_info: u32,
+        owner: *mut c_void,
+        group: *mut c_void,
+        dacl: *mut c_void,
+        sacl: *mut c_void,
+    ) -> u32;
+    fn GetSecurityDescriptorControl(
+        security_descriptor: *mut c_void,
+        control: *mut u16,
+        revision: *mut u32,
+    ) -> i32;
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn LocalFree(mem: *mut c_void) -> *mut c_void;
+}
+
+/// What `Options::preserve_acl` copies besides the DACL, the list of
+/// access control entries, and what a failure does
+///
+/// The owner, group and SACL need privileges most processes lack: setting
+/// another owner needs `SeRestorePrivilege` and reading or setting the SACL
+/// `SeSecurityPrivilege`.
+///
+/// # Examples
+/// ```
+/// use fdir::*;
+/// use fdir::sync::acl::AclOptions;
+/// use std::process::Command;
+/// let dir = std::env::temp_dir().join("fdir_acl");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// let file = FileInfo::create(dir.join("report.txt")).unwrap();
+/// // deny everyone the right to delete the file
+/// let status = Command::new("icacls")
+///     .arg(file.as_path())
+///     .arg("/deny")
+///     .arg("*S-1-1-0:(DE)")
+///     .status()
+///     .unwrap();
+/// assert!(status.success());
+///
+/// let options = Options::new().preserve_acl(true).acl(AclOptions { strict: true, ..Default::default() });
+/// let report = file.copy_new_with(dir.join("copy.txt"), &options).unwrap();
+/// assert!(report.acl_warnings.is_empty());
+/// let listed = Command::new("icacls").arg(dir.join("copy.txt")).output().unwrap();
+/// let listed = String::from_utf8_lossy(&listed.stdout);
+/// assert!(listed.contains("(DENY)"), "{}", listed);
+/// # for name in ["report.txt", "copy.txt"] {
+/// #     Command::new("icacls").arg(dir.join(name)).arg("/reset").status().unwrap();
+/// # }
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AclOptions {
+    /// Also copy the owner and the primary group
+    pub owner: bool,
+    /// Also copy the SACL, the auditing entries
+    pub sacl: bool,
+    /// Fail the copy when a descriptor cannot be copied, rather than list
+    /// it in `CopyReport::acl_warnings`
+    pub strict: bool,
+}
+
+/// A security descriptor read from a file, with pointers into it
+struct Descriptor {
+    raw: *mut c_void,
+    owner: *mut c_void,
+    group: *mut c_void,
+    dacl: *mut c_void,
+    sacl: *mut c_void,
+}
+
+impl Drop for Descriptor {
+    fn drop(&mut self) {
+        // SAFETY: allocated by `GetNamedSecurityInfoW` for the caller to free
+        unsafe { LocalFree(self.raw) };
+    }
+}
+
+fn wide(path: &Path) -> Vec<u16> {
+    path.as_os_str().encode_wide().chain([0]).collect()
+}
+
+fn check(code: u32) -> Result<()> {
+    match code {
+        0 => Ok(()),
+        code => Err(Error::from_raw_os_error(code as i32)),
+    }
+}
+
+/// Copy the parts of the security descriptor of `source` that `options`
+/// ask for to `dest`. Inherited entries are left to the new parent, unless
+/// the list of `source` is protected from inheritance.
+fn copy_descriptor(source: &Path, dest: &Path, options: &AclOptions) -> Result<()> {
+    let mut info = DACL_SECURITY_INFORMATION;
+    if options.owner {
+        info |= OWNER_SECURITY_INFORMATION | GROUP_SECURITY_INFORMATION;
+    }
+    if options.sacl {
+        info |= SACL_SECURITY_INFORMATION;
+    }
+    let source = wide(source);
+    let mut descriptor = Descriptor {
+        raw: std::ptr::null_mut(),
+        owner: std::ptr::null_mut(),
+        group: std::ptr::null_mut(),
+        dacl: std::ptr::null_mut(),
+        sacl: std::ptr::null_mut(),
+    };
+    // SAFETY: `source` is nul terminated and each out pointer is valid, the
+    // parts point into `raw` which `Descriptor` frees
+    check(unsafe {
+        GetNamedSecurityInfoW(
+            source.as_ptr(),
+            FILE_OBJECT,
+            info,
+            &mut descriptor.owner,
+            &mut descriptor.group,
+            &mut descriptor.dacl,
+            &mut descriptor.sacl,
+            &mut descriptor.raw,
+        )
+    })?;
+    let (mut control, mut revision) = (0u16, 0u32);
+    // SAFETY: `raw` is the descriptor just read
+    if unsafe { GetSecurityDescriptorControl(descriptor.raw, &mut control, &mut revision) } == 0 {
+        return Err(Error::last_os_error());
+    }
+    info |= if control & DACL_PROTECTED != 0 {
+        PROTECTED_DACL_SECURITY_INFORMATION
+    } else {
+        UNPROTECTED_DACL_SECURITY_INFORMATION
+    };
+    if options.sacl {
+        info |= if control & SACL_PROTECTED != 0 {
+            PROTECTED_SACL_SECURITY_INFORMATION
+        } else {
+            UNPROTECTED_SACL_SECURITY_INFORMATION
+        };
+    }
+    let dest = wide(dest);
+    // SAFETY: `dest` is nul terminated and the parts live in `descriptor`
+    // until it is dropped after the call
+    check(unsafe {
+        SetNamedSecurityInfoW(
+            dest.as_ptr(),
+            FILE_OBJECT,
+            info,
+            descriptor.owner,
+            descriptor.group,
+            descriptor.dacl,
+            descriptor.sacl,
+        )
+    })
+}
+
+/// Copy the descriptor of `source` to `dest`, a failure is listed in
+/// `report` unless `options.strict`
+pub(crate) fn copy_acl(
+    source: &Path,
+    dest: &Path,
+    options: &AclOptions,
+    report: &mut CopyReport,
+) -> Result<()> {
+    match copy_descriptor(source, dest, options) {
+        Err(e) if !options.strict => {
+            report
+                .acl_warnings
+                .push((dest.to_path_buf(), e.to_string()));
+            Ok(())
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,197 @@
+//! NTFS alternate data streams, the named streams a file carries next to
+//! its content, such as the `Zone.Identifier` of downloaded files
+
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, Error, Result};
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::path::{Path, PathBuf};
+
+use crate::error::invalid_path;
+
+use super::{Action, FileInfo, Info};
+
+/// `ERROR_HANDLE_EOF`, there are no more streams
+const HANDLE_EOF: i32 = 38;
+/// `ERROR_INVALID_PARAMETER`, given for files on volumes without streams
+const INVALID_PARAMETER: i32 = 87;
+/// `FILE_NAMED_STREAMS` among the volume flags
+const NAMED_STREAMS: u32 = 0x0004_0000;
+
+/// `WIN32_FIND_STREAM_DATA`, the name has room for `MAX_PATH + 36` units
+#[repr(C)]
+struct FindStreamData {
+    stream_size: i64,
+    stream_name: [u16; 296],
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn FindFirstStreamW(
+        file_name: *const u16,
+        info_level: i32,
+        find_stream_data: *mut FindStreamData,
+        flags: u32,
+    ) -> RawHandle;
+    fn FindNextStreamW(find_stream: RawHandle, find_stream_data: *mut FindStreamData) -> i32;
+    fn FindClose(find_file: RawHandle) -> i32;
+    #[allow(clippy::too_many_arguments)]
+    fn GetVolumeInformationByHandleW(
+        file: RawHandle,
+        volume_name: *mut u16,
+        volume_name_size: u32,
+        serial_number: *mut u32,
+        max_component_length: *mut u32,
+        file_system_flags: *mut u32,
+        file_system_name: *mut u16,
+        file_system_name_size: u32,
+    ) -> i32;
+}
+
+impl FileInfo {
+    /// Name and length of every named stream of the file, the unnamed
+    /// stream holding the content is left out. A file on a volume without
+    /// streams, e.g. FAT, has none.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let dir = std::env::temp_dir().join("fdir_ads");
+    /// let _ = std::fs::remove_dir_all(&dir);
+    /// let file = FileInfo::create(dir.join("download.zip")).unwrap();
+    /// // the temporary directory may not be on NTFS
+    /// if file.write_stream("Zone.Identifier", "[ZoneTransfer]\r\nZoneId=3").is_ok() {
+    ///     let streams = file.alternate_streams().unwrap();
+    ///     assert_eq!(streams, [("Zone.Identifier".into(), 24)]);
+    ///     let options = Options::new().preserve_ads(true);
+    ///     let report = file.copy_new_with(dir.join("copy.zip"), &options).unwrap();
+    ///     assert!(report.streams_skipped.is_empty());
+    ///     let copy = FileInfo::open(dir.join("copy.zip")).unwrap();
+    ///     assert_eq!(copy.read_stream("Zone.Identifier").unwrap(), b"[ZoneTransfer]\r\nZoneId=3");
+    /// }
+    /// # std::fs::remove_dir_all(dir).unwrap();
+    /// ```
+    pub fn alternate_streams(&self) -> Result<Vec<(OsString, u64)>> {
+        list_streams(self.as_path())
+    }
+
+    /// The content of the stream `name`
+    pub fn read_stream(&self, name: impl AsRef<OsStr>) -> Result<Vec<u8>> {
+        fs::read(stream_path(self.as_path(), name.as_ref())?)
+    }
+
+    /// Replace the content of the stream `name`, it is created when missing
+    pub fn write_stream(&self, name: impl AsRef<OsStr>, data: impl AsRef<[u8]>) -> Result<()> {
+        fs::write(stream_path(self.as_path(), name.as_ref())?, data)
+    }
+}
+
+/// `path:name`, the path of the stream `name` of `path`
+fn stream_path(path: &Path, name: &OsStr) -> Result<PathBuf> {
+    if name.is_empty() {
+        return Err(invalid_path(
+            path,
+            "cannot have a stream with an empty name",
+        ));
+    }
+    let mut stream = path.as_os_str().to_os_string();
+    stream.push(":");
+    stream.push(name);
+    Ok(PathBuf::from(stream))
+}
+
+fn list_streams(path: &Path) -> Result<Vec<(OsString, u64)>> {
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    let mut data = FindStreamData {
+        stream_size: 0,
+        stream_name: [0; 296],
+    };
+    // SAFETY: `wide` is nul terminated and `data` has the layout of
+    // `FindStreamInfoStandard`
+    let find = unsafe { FindFirstStreamW(wide.as_ptr(), 0, &mut data, 0) };
+    if find as isize == -1 {
+        let error = Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(HANDLE_EOF | INVALID_PARAMETER) => Ok(Vec::new()),
+            _ => Err(error),
+        };
+    }
+    let mut streams = Vec::new();
+    let result = loop {
+        // names are `:name:$DATA`, the unnamed stream is `::$DATA`
+        let len = data.stream_name.iter().position(|&unit| unit == 0);
+        let name = OsString::from_wide(&data.stream_name[..len.unwrap_or(296)]);
+        let name = name.to_string_lossy();
+        if let Some(name) = name
+            .strip_prefix(':')
+            .and_then(|name| name.strip_suffix(":$DATA"))
+        {
+            if !name.is_empty() {
+                streams.push((OsString::from(name), data.stream_size as u64));
+            }
+        }
+        // SAFETY: `find` is a valid search handle until it is closed below
+        if unsafe { FindNextStreamW(find, &mut data) } == 0 {
+            let error = Error::last_os_error();
+            break match error.raw_os_error() {
+                Some(HANDLE_EOF) => Ok(streams),
+                _ => Err(error),
+            };
+        }
+    };
+    // SAFETY: the handle was returned by `FindFirstStreamW`
+    unsafe { FindClose(find) };
+    result
+}
+
+/// Whether the volume of the file `path` has named streams
+fn supports_streams(path: &Path) -> Result<bool> {
+    let file = File::open(path)?;
+    let mut flags = 0;
+    // SAFETY: the handle is open for the duration of the call and only the
+    // flags are asked for
+    let ok = unsafe {
+        GetVolumeInformationByHandleW(
+            file.as_raw_handle(),
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut flags,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ok == 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(flags & NAMED_STREAMS != 0)
+}
+
+/// Copy the named streams of `source` to the file `dest`, `false` when
+/// `dest` is on a volume without streams and they were left out
+pub(crate) fn copy_streams(source: &Path, dest: &Path) -> Result<bool> {
+    let streams = list_streams(source)?;
+    if streams.is_empty() {
+        return Ok(true);
+    }
+    if !supports_streams(dest)? {
+        return Ok(false);
+    }
+    // the copy may have been given read-only permissions
+    let file = unsafe { FileInfo::open_uncheck(dest) };
+    let readonly = file.read_only()?;
+    if readonly {
+        file.set_readonly(false)?;
+    }
+    let result = streams.iter().try_for_each(|(name, _)| {
+        let mut reader = File::open(stream_path(source, name)?)?;
+        let mut writer = File::create(stream_path(dest, name)?)?;
+        io::copy(&mut reader, &mut writer).map(|_| ())
+    });
+    if readonly {
+        file.set_readonly(true)?;
+    }
+    result.map(|()| true)
+}
@@ -0,0 +1,718 @@
+//! Directory and file operations run on their own thread, reporting their
+//! progress through a handle polled by the caller instead of callbacks
+
+use std::collections::VecDeque;
+use std::io::Result;
+use std::panic::resume_unwind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+
+use crate::error::is_cancelled;
+use crate::hooks::{EventHook, ProgressHook, StreamHook};
+use crate::{CopyReport, Options, ProgressEvent, Recorder, Size};
+
+use super::{DirectoryInfo, FileInfo, Info};
+
+/// Stage of an operation followed by a [`ProgressHandle`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationState {
+    /// Walking the source to find `files_total` and `bytes_total`
+    Counting,
+    Running,
+    Done,
+    /// Stopped by an error, `join` returns it
+    Failed,
+    /// Stopped by [`ProgressHandle::cancel`] or the `StreamOptions::cancel`
+    /// flag of the options
+    Cancelled,
+}
+
+impl OperationState {
+    fn from_u8(state: u8) -> Self {
+        match state {
+            0 => OperationState::Counting,
+            1 => OperationState::Running,
+            2 => OperationState::Done,
+            3 => OperationState::Failed,
+            _ => OperationState::Cancelled,
+        }
+    }
+
+    /// Whether the operation is over
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            OperationState::Done | OperationState::Failed | OperationState::Cancelled
+        )
+    }
+}
+
+/// Where an operation stands, see [`ProgressHandle::snapshot`]
+///
+/// The totals are those of the whole source, entries left out by the
+/// filters or skipped on conflict are counted in them but never done. They
+/// are 0 while `state` is `Counting`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    /// Bytes written so far, the file being copied included
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// Source of the last file written, `None` before the first one
+    pub current_path: Option<PathBuf>,
+    pub files_done: u64,
+    pub files_total: u64,
+    pub state: OperationState,
+    /// `bytes_done` out of `bytes_total`, timed from the end of `Counting`
+    /// for the throughput and the time left
+    pub recorder: Recorder,
+}
+
+/// The state shared by an operation and its handle, only atomics and the
+/// mutexes holding the current path and the recorder
+#[derive(Debug, Default)]
+pub(crate) struct Tracker {
+    bytes_done: AtomicU64,
+    /// Bytes of the files already written, the stream hook counts the
+    /// current one on top of them
+    bytes_finished: AtomicU64,
+    bytes_total: AtomicU64,
+    files_done: AtomicU64,
+    files_total: AtomicU64,
+    current_path: Mutex<Option<PathBuf>>,
+    recorder: Mutex<Recorder>,
+    state: AtomicU8,
+    cancel: Arc<AtomicBool>,
+    /// Woken on cancel, the operation may be paused on it
+    pause: Option<PauseToken>,
+}
+
+impl Tracker {
+    /// A tracker fed by the hooks of `options`. The hooks already set are
+    /// still called, an existing cancel flag is shared with the handle.
+    pub(crate) fn attach(options: &mut Options) -> Arc<Tracker> {
+        let cancel = options.stream.cancel.get_or_insert_with(Default::default);
+        let tracker = Arc::new(Tracker {
+            cancel: Arc::clone(cancel),
+            pause: options.pause.clone(),
+            ..Default::default()
+        });
+        let hook = options.on_progress.take();
+        let this = Arc::clone(&tracker);
+        options.on_progress = Some(ProgressHook::new(move |progress| {
+            this.files_done.store(progress.files, Ordering::Relaxed);
+            this.bytes_finished.store(progress.bytes, Ordering::Relaxed);
+            this.set_bytes_done(progress.bytes);
+            *this.current() = Some(progress.source.to_path_buf());
+            if let Some(hook) = &hook {
+                (hook.lock())(progress);
+            }
+        }));
+        let hook = options.stream.on_progress.take();
+        let this = Arc::clone(&tracker);
+        options.stream.on_progress = Some(StreamHook::new(move |bytes| {
+            let finished = this.bytes_finished.load(Ordering::Relaxed);
+            this.set_bytes_done(finished + bytes);
+            if let Some(hook) = &hook {
+                (hook.lock())(bytes);
+            }
+        }));
+        tracker
+    }
+
+    fn current(&self) -> MutexGuard<'_, Option<PathBuf>> {
+        self.current_path
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn recorder(&self) -> MutexGuard<'_, Recorder> {
+        self.recorder.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set_bytes_done(&self, bytes: u64) {
+        self.bytes_done.store(bytes, Ordering::Relaxed);
+        let mut recorder = self.recorder();
+        let n = bytes.saturating_sub(recorder.pos);
+        recorder.advance(n);
+    }
+
+    fn set_state(&self, state: OperationState) {
+        self.state.store(state as u8, Ordering::Relaxed);
+    }
+
+    /// Record the totals and start running
+    pub(crate) fn start(&self, files: u64, bytes: u64) {
+        self.files_total.store(files, Ordering::Relaxed);
+        self.bytes_total.store(bytes, Ordering::Relaxed);
+        *self.recorder() = Recorder::read(bytes).timed();
+        self.set_state(OperationState::Running);
+    }
+
+    /// Record the outcome of the operation on `source`. A directory renamed
+    /// at once is done without calling the hooks.
+    pub(crate) fn finish(&self, source: &Path, result: &Result<CopyReport>) {
+        let state = match result {
+            Ok(report) => {
+                let (files, bytes) = if report.renamed && report.files.is_empty() {
+                    (
+                        self.files_total.load(Ordering::Relaxed),
+                        self.bytes_total.load(Ordering::Relaxed),
+                    )
+                } else {
+                    (report.files.len() as u64, report.bytes.0)
+                };
+                self.files_done.store(files, Ordering::Relaxed);
+                self.set_bytes_done(bytes);
+                let mut current = self.current();
+                if current.is_none() {
+                    *current = Some(source.to_path_buf());
+                }
+                OperationState::Done
+            }
+            Err(e) if is_cancelled(e) => OperationState::Cancelled,
+            Err(_) => OperationState::Failed,
+        };
+        self.set_state(state);
+    }
+
+    pub(crate) fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            bytes_done: self.bytes_done.load(Ordering::Relaxed),
+            bytes_total: self.bytes_total.load(Ordering::Relaxed),
+            current_path: self.current().clone(),
+            files_done: self.files_done.load(Ordering::Relaxed),
+            files_total: self.files_total.load(Ordering::Relaxed),
+            state: OperationState::from_u8(self.state.load(Ordering::Relaxed)),
+            recorder: *self.recorder(),
+        }
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        if let Some(pause) = &self.pause {
+            pause.wake();
+        }
+    }
+}
+
+/// An operation running on its own thread, returned by the `_background`
+/// methods of [`DirectoryInfo`] and [`FileInfo`]
+///
+/// Dropping the handle detaches the thread, the operation still completes.
+///
+/// # Examples
+/// ```
+/// use fdir::*;
+/// let root = std::env::temp_dir().join("fdir_background");
+/// let _ = std::fs::remove_dir_all(&root);
+/// for name in ["a.txt", "b.txt", "sub/c.txt"] {
+///     let file = FileInfo::create(root.join("src").join(name)).unwrap();
+///     std::fs::write(file.as_path(), name).unwrap();
+/// }
+/// let src = DirectoryInfo::open(root.join("src")).unwrap();
+/// let handle = src.copy_new_background(root.join("dest"), Options::new());
+/// while !handle.snapshot().state.is_finished() {
+///     // redraw a progress bar from `handle.snapshot()`
+///     let recorder = handle.snapshot().recorder;
+///     println!("{}% {:?} left", recorder.percent(), recorder.eta());
+///     std::thread::sleep(std::time::Duration::from_millis(10));
+/// }
+/// let snapshot = handle.snapshot();
+/// assert_eq!(snapshot.state, OperationState::Done);
+/// assert_eq!((snapshot.files_done, snapshot.files_total), (3, 3));
+/// assert_eq!(snapshot.bytes_done, snapshot.bytes_total);
+/// assert_eq!(snapshot.recorder.percent(), 100);
+/// assert_eq!(handle.join().unwrap().files.len(), 3);
+/// ```
+#[derive(Debug)]
+pub struct ProgressHandle {
+    tracker: Arc<Tracker>,
+    thread: JoinHandle<Result<CopyReport>>,
+}
+
+impl ProgressHandle {
+    /// Run `op` on a new thread, after `totals` gave the number of files
+    /// and bytes to write
+    fn spawn<T, O>(source: PathBuf, mut options: Options, totals: T, op: O) -> Self
+    where
+        T: FnOnce() -> Result<(u64, u64)> + Send + 'static,
+        O: FnOnce(&Options) -> Result<CopyReport> + Send + 'static,
+    {
+        let tracker = Tracker::attach(&mut options);
+        let this = Arc::clone(&tracker);
+        let thread = thread::spawn(move || {
+            let result = totals().and_then(|(files, bytes)| {
+                this.start(files, bytes);
+                op(&options)
+            });
+            this.finish(&source, &result);
+            result
+        });
+        ProgressHandle { tracker, thread }
+    }
+
+    /// Where the operation stands, cheap enough to call on every frame
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        self.tracker.snapshot()
+    }
+
+    /// Ask the operation to stop before its next chunk, `join` then returns
+    /// an error of kind `ErrorKind::Other`. What was written stays in place.
+    /// An operation held by a `PauseToken` stops without being resumed.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_cancel_paused");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// FileInfo::create(root.join("src/a.txt")).unwrap();
+    /// let pause = PauseToken::new();
+    /// pause.pause();
+    /// let src = DirectoryInfo::open(root.join("src")).unwrap();
+    /// let handle = src.copy_new_background(root.join("dest"), Options::new().pause(pause.clone()));
+    /// std::thread::sleep(std::time::Duration::from_millis(50));
+    /// handle.cancel();
+    /// let error = handle.join().unwrap_err();
+    /// assert_eq!(error.kind(), std::io::ErrorKind::Other);
+    /// assert!(pause.is_paused());
+    /// assert!(!root.join("dest/a.txt").exists());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn cancel(&self) {
+        self.tracker.cancel();
+    }
+
+    /// Wait for the end of the operation. A panic of the operation is
+    /// resumed on the calling thread.
+    pub fn join(self) -> Result<CopyReport> {
+        self.thread
+            .join()
+            .unwrap_or_else(|panic| resume_unwind(panic))
+    }
+}
+
+impl DirectoryInfo {
+    /// [`DirectoryInfo::copy_new_with`] on its own thread, see
+    /// [`ProgressHandle`]
+    pub fn copy_new_background<P: AsRef<Path>>(&self, path: P, options: Options) -> ProgressHandle {
+        let source = self.clone();
+        let path = path.as_ref().to_path_buf();
+        let walk = options.walk.clone();
+        let dir = self.clone();
+        ProgressHandle::spawn(
+            self.to_path_buf(),
+            options,
+            move || {
+                dir.stats_with(&walk)
+                    .map(|stats| (stats.files, stats.bytes.0))
+            },
+            move |options| source.copy_new_with(path, options),
+        )
+    }
+
+    /// [`DirectoryInfo::copy_new_with`] on its own thread, with its
+    /// events as they come, see [`ProgressEvents`]. The handle tells the
+    /// outcome, the events end with `Finished` once it is known.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_copy_events");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for name in ["a.txt", "sub/b.txt"] {
+    ///     let file = FileInfo::create(root.join("src").join(name)).unwrap();
+    ///     std::fs::write(file.as_path(), "hello").unwrap();
+    /// }
+    /// FileInfo::create(root.join("dest/a.txt")).unwrap();
+    /// let src = DirectoryInfo::open(root.join("src")).unwrap();
+    /// let options = Options::new().overwrite(true);
+    /// let (handle, events) = src.copy_new_events(root.join("dest"), options);
+    /// let (mut names, mut conflicts) = (Vec::new(), Vec::new());
+    /// for event in events {
+    ///     match event {
+    ///         ProgressEvent::Started { files, bytes } => {
+    ///             assert_eq!((files, bytes), (2, Size(10)))
+    ///         }
+    ///         ProgressEvent::FileStarted { path } => names.push(path),
+    ///         ProgressEvent::Chunk { bytes } => println!("{} written", bytes),
+    ///         ProgressEvent::Conflict { path } => conflicts.push(path),
+    ///         ProgressEvent::Finished => println!("done"),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// assert_eq!(names.len(), 2);
+    /// // the destination merged into, then the file replaced
+    /// assert_eq!(conflicts, [root.join("dest"), root.join("dest/a.txt")]);
+    /// assert_eq!(handle.join().unwrap().files.len(), 2);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn copy_new_events<P: AsRef<Path>>(
+        &self,
+        path: P,
+        mut options: Options,
+    ) -> (ProgressHandle, ProgressEvents) {
+        let (sender, events) = EventSender::attach(&mut options);
+        let source = self.clone();
+        let path = path.as_ref().to_path_buf();
+        let walk = options.walk.clone();
+        let dir = self.clone();
+        let handle = ProgressHandle::spawn(
+            self.to_path_buf(),
+            options,
+            move || {
+                let stats = dir.stats_with(&walk)?;
+                sender.send(ProgressEvent::Started {
+                    files: stats.files,
+                    bytes: stats.bytes,
+                });
+                Ok((stats.files, stats.bytes.0))
+            },
+            move |options| source.copy_new_with(path, options),
+        );
+        (handle, events)
+    }
+
+    /// [`DirectoryInfo::move_new_with`] on its own thread, see
+    /// [`ProgressHandle`]. Open the destination once it is done.
+    pub fn move_new_background<P: AsRef<Path>>(self, path: P, options: Options) -> ProgressHandle {
+        let path = path.as_ref().to_path_buf();
+        let walk = options.walk.clone();
+        let dir = self.clone();
+        let mut source = self;
+        ProgressHandle::spawn(
+            source.to_path_buf(),
+            options,
+            move || {
+                dir.stats_with(&walk)
+                    .map(|stats| (stats.files, stats.bytes.0))
+            },
+            move |options| source.move_new_with(path, options),
+        )
+    }
+}
+
+impl FileInfo {
+    /// [`FileInfo::copy_new_with`] on its own thread, see [`ProgressHandle`]
+    pub fn copy_new_background<P: AsRef<Path>>(&self, path: P, options: Options) -> ProgressHandle {
+        let source = self.clone();
+        let path = path.as_ref().to_path_buf();
+        let file = self.clone();
+        ProgressHandle::spawn(
+            self.to_path_buf(),
+            options,
+            move || file_totals(&file),
+            move |options| source.copy_new_with(path, options),
+        )
+    }
+
+    /// [`FileInfo::move_new_with`] on its own thread, see [`ProgressHandle`]
+    pub fn move_new_background<P: AsRef<Path>>(self, path: P, options: Options) -> ProgressHandle {
+        let path = path.as_ref().to_path_buf();
+        let file = self.clone();
+        let mut source = self;
+        ProgressHandle::spawn(
+            source.to_path_buf(),
+            options,
+            move || file_totals(&file),
+            move |options| source.move_new_with(path, options),
+        )
+    }
+}
+
+fn file_totals(file: &FileInfo) -> Result<(u64, u64)> {
+    file.validate()?;
+    Ok((1, file.metadata()?.len()))
+}
+
+/// Holds the directory copies and moves given it with `Options::pause`
+/// before their next file, the file being written is finished first.
+/// Clones share the state. A paused operation that is cancelled stops
+/// without waiting to be resumed.
+///
+/// # Examples
+/// ```
+/// use fdir::*;
+/// let root = std::env::temp_dir().join("fdir_pause");
+/// let _ = std::fs::remove_dir_all(&root);
+/// for name in ["a.txt", "b.txt"] {
+///     FileInfo::create(root.join("src").join(name)).unwrap();
+/// }
+/// let pause = PauseToken::new();
+/// pause.pause();
+/// let src = DirectoryInfo::open(root.join("src")).unwrap();
+/// let handle = src.copy_new_background(root.join("dest"), Options::new().pause(pause.clone()));
+/// std::thread::sleep(std::time::Duration::from_millis(50));
+/// assert_eq!(handle.snapshot().files_done, 0);
+/// assert!(!handle.snapshot().state.is_finished());
+/// pause.resume();
+/// assert_eq!(handle.join().unwrap().files.len(), 2);
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PauseToken {
+    state: Arc<PauseState>,
+}
+
+#[derive(Debug, Default)]
+struct PauseState {
+    paused: Mutex<Paused>,
+    resumed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Paused {
+    paused: bool,
+    /// The tasks waiting in `poll_resumed`
+    wakers: Vec<Waker>,
+}
+
+impl PauseToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn paused(&self) -> MutexGuard<'_, Paused> {
+        self.state
+            .paused
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Hold the operations before their next file
+    pub fn pause(&self) {
+        self.paused().paused = true;
+    }
+
+    /// Let the operations go on
+    pub fn resume(&self) {
+        let mut paused = self.paused();
+        paused.paused = false;
+        paused.wakers.drain(..).for_each(Waker::wake);
+        self.state.resumed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused().paused
+    }
+
+    /// Block while paused
+    pub fn wait(&self) {
+        self.wait_unless(None);
+    }
+
+    /// Block while paused and `cancel` is not set
+    pub(crate) fn wait_unless(&self, cancel: Option<&AtomicBool>) {
+        let mut paused = self.paused();
+        while paused.paused && !is_set(cancel) {
+            paused = self
+                .state
+                .resumed
+                .wait(paused)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Ready unless paused, the task of `cx` is woken once resumed. The
+    /// async operations poll it.
+    pub fn poll_resumed(&self, cx: &mut Context<'_>) -> Poll<()> {
+        self.poll_resumed_unless(cx, None)
+    }
+
+    /// Same as [`PauseToken::poll_resumed`], also ready once `cancel` is set
+    pub(crate) fn poll_resumed_unless(
+        &self,
+        cx: &mut Context<'_>,
+        cancel: Option<&AtomicBool>,
+    ) -> Poll<()> {
+        let mut paused = self.paused();
+        if !paused.paused || is_set(cancel) {
+            return Poll::Ready(());
+        }
+        if !paused
+            .wakers
+            .iter()
+            .any(|waker| waker.will_wake(cx.waker()))
+        {
+            paused.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    /// Wake the waiting operations without resuming them, to let them
+    /// see they were cancelled
+    pub(crate) fn wake(&self) {
+        let mut paused = self.paused();
+        paused.wakers.drain(..).for_each(Waker::wake);
+        self.state.resumed.notify_all();
+    }
+}
+
+fn is_set(cancel: Option<&AtomicBool>) -> bool {
+    cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+}
+
+/// Events waiting beyond which `Chunk` events are dropped
+const EVENT_CAPACITY: usize = 64;
+
+/// The events of an operation in order, see
+/// [`DirectoryInfo::copy_new_events`]
+///
+/// While more than 64 events wait, new `Chunk` events are dropped rather
+/// than slowing the operation down, the next one still tells the bytes
+/// written. The other events are always kept. The last event is
+/// `Finished`, sent once the operation is over and its options dropped.
+#[derive(Debug)]
+pub struct ProgressEvents {
+    channel: Arc<Channel>,
+}
+
+#[derive(Debug, Default)]
+struct Channel {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    events: VecDeque<ProgressEvent>,
+    senders: usize,
+    /// Nothing is queued once the receiver is gone
+    closed: bool,
+    /// The task waiting in `poll_next_event`
+    waker: Option<Waker>,
+}
+
+impl Channel {
+    fn queue(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push(&self, event: ProgressEvent) {
+        let mut queue = self.queue();
+        let full = queue.events.len() >= EVENT_CAPACITY;
+        if queue.closed || (full && matches!(event, ProgressEvent::Chunk { .. })) {
+            return;
+        }
+        queue.events.push_back(event);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+        self.ready.notify_all();
+    }
+}
+
+/// Feeds [`ProgressEvents`], the last sender dropped sends `Finished`
+#[derive(Debug)]
+pub(crate) struct EventSender {
+    channel: Arc<Channel>,
+}
+
+impl EventSender {
+    /// A sender fed by the hooks of `options` and its receiver. The hooks
+    /// already set are still called.
+    pub(crate) fn attach(options: &mut Options) -> (EventSender, ProgressEvents) {
+        let channel = Arc::new(Channel::default());
+        let sender = EventSender::new(&channel);
+        let hook = options.on_event.take();
+        let this = sender.clone();
+        options.on_event = Some(EventHook::new(move |event| {
+            this.send(event.clone());
+            if let Some(hook) = &hook {
+                (hook.lock())(event);
+            }
+        }));
+        let hook = options.stream.on_progress.take();
+        let this = sender.clone();
+        options.stream.on_progress = Some(StreamHook::new(move |bytes| {
+            this.send(ProgressEvent::Chunk { bytes: Size(bytes) });
+            if let Some(hook) = &hook {
+                (hook.lock())(bytes);
+            }
+        }));
+        (sender, ProgressEvents { channel })
+    }
+
+    fn new(channel: &Arc<Channel>) -> Self {
+        channel.queue().senders += 1;
+        EventSender {
+            channel: Arc::clone(channel),
+        }
+    }
+
+    pub(crate) fn send(&self, event: ProgressEvent) {
+        self.channel.push(event);
+    }
+}
+
+impl Clone for EventSender {
+    fn clone(&self) -> Self {
+        EventSender::new(&self.channel)
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        let last = {
+            let mut queue = self.channel.queue();
+            queue.senders -= 1;
+            queue.senders == 0
+        };
+        if last {
+            self.channel.push(ProgressEvent::Finished);
+        }
+    }
+}
+
+impl ProgressEvents {
+    /// The next event if one is waiting, without blocking
+    pub fn try_next(&mut self) -> Option<ProgressEvent> {
+        self.channel.queue().events.pop_front()
+    }
+
+    /// The next event, or wake the task of `cx` once there is one. `None`
+    /// after `Finished`. The async `Stream` implementation polls it.
+    pub fn poll_next_event(&mut self, cx: &mut Context<'_>) -> Poll<Option<ProgressEvent>> {
+        let mut queue = self.channel.queue();
+        if let Some(event) = queue.events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if queue.senders == 0 {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Blocks until the next event, ends after `Finished`
+impl Iterator for ProgressEvents {
+    type Item = ProgressEvent;
+
+    fn next(&mut self) -> Option<ProgressEvent> {
+        let mut queue = self.channel.queue();
+        loop {
+            if let Some(event) = queue.events.pop_front() {
+                return Some(event);
+            }
+            if queue.senders == 0 {
+                return None;
+            }
+            queue = self
+                .channel
+                .ready
+                .wait(queue)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+impl Drop for ProgressEvents {
+    fn drop(&mut self) {
+        let mut queue = self.channel.queue();
+        queue.closed = true;
+        queue.events.clear();
+    }
+}
@@ -0,0 +1,317 @@
+//! Operations on many paths at once, each path succeeding or failing on its
+//! own
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+use crate::{fix_path, CopyReport, Options};
+
+use super::{Action, Entry, FileInfo};
+
+/// Successfully opened entries and the paths that failed with their error,
+/// both in input order
+pub type OpenMany<T> = (Vec<T>, Vec<(PathBuf, Error)>);
+
+/// Normalise every path, the parent of sibling paths is only fixed once
+pub(crate) fn fix_paths<I>(paths: I) -> Vec<(PathBuf, Result<PathBuf>)>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut parents: HashMap<PathBuf, PathBuf> = HashMap::new();
+    paths
+        .into_iter()
+        .map(|path| {
+            let path = path.as_ref();
+            let fixed = match (path.parent(), path.file_name()) {
+                (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+                    match parents.get(parent) {
+                        Some(fixed) => Ok(fixed.join(name)),
+                        None => fix_path(parent).map(|fixed| {
+                            parents.insert(parent.to_path_buf(), fixed.clone());
+                            fixed.join(name)
+                        }),
+                    }
+                }
+                _ => fix_path(path),
+            };
+            (path.to_path_buf(), fixed)
+        })
+        .collect()
+}
+
+pub(crate) fn open_many<T, I>(paths: I) -> OpenMany<T>
+where
+    T: Action,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut opened = (Vec::new(), Vec::new());
+    for (path, fixed) in fix_paths(paths) {
+        push(&mut opened, path, fixed.and_then(validate::<T>));
+    }
+    opened
+}
+
+pub(crate) fn open_many_parallel<T, I>(paths: I, threads: usize) -> OpenMany<T>
+where
+    T: Action + Send,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut rest = fix_paths(paths);
+    let chunk = rest.len().div_ceil(threads.max(1)).max(1);
+    let mut chunks = Vec::new();
+    while !rest.is_empty() {
+        let tail = rest.split_off(chunk.min(rest.len()));
+        chunks.push(rest);
+        rest = tail;
+    }
+    let mut opened = (Vec::new(), Vec::new());
+    thread::scope(|scope| {
+        let workers: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .into_iter()
+                        .map(|(path, fixed)| (path, fixed.and_then(validate::<T>)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (path, result) in worker.join().expect("validation thread panicked") {
+                push(&mut opened, path, result);
+            }
+        }
+    });
+    opened
+}
+
+fn validate<T: Action>(path: PathBuf) -> Result<T> {
+    let entry = unsafe { T::open_uncheck(path) };
+    entry.validate()?;
+    Ok(entry)
+}
+
+fn push<T>(opened: &mut OpenMany<T>, path: PathBuf, result: Result<T>) {
+    match result {
+        Ok(entry) => opened.0.push(entry),
+        Err(e) => opened.1.push((path, e)),
+    }
+}
+
+/// One operation of [`try_all`]
+#[derive(Debug, Clone)]
+pub enum Op {
+    Delete(Entry),
+    /// Move `from`, a file or a directory, to the new path `to`
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Copy `from`, a file or a directory, to the new path `to`
+    Copy {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Create or truncate a file, its missing parents included
+    CreateFile(PathBuf),
+    /// Create a directory and its missing parents, nothing is done when it
+    /// exists
+    CreateDir(PathBuf),
+}
+
+impl Op {
+    fn run(&self, options: &Options) -> Result<CopyReport> {
+        match self {
+            Op::Delete(entry) => match entry.clone() {
+                Entry::File(file) => file.delete_with(options),
+                Entry::Dir(dir) => dir.delete_with(options),
+            }
+            .map(|_| CopyReport::default()),
+            Op::Move { from, to } => match Entry::open(from)? {
+                Entry::File(mut file) => file.move_new_with(to, options),
+                Entry::Dir(mut dir) => dir.move_new_with(to, options),
+            },
+            Op::Copy { from, to } => match Entry::open(from)? {
+                Entry::File(file) => file.copy_new_with(to, options),
+                Entry::Dir(dir) => dir.copy_new_with(to, options),
+            },
+            Op::CreateFile(path) => FileInfo::create(path).map(|_| CopyReport::default()),
+            Op::CreateDir(path) => {
+                fs::create_dir_all(fix_path(path)?).map(|_| CopyReport::default())
+            }
+        }
+    }
+}
+
+/// How [`try_all_with`] runs the operations
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /// Start no further operation once one failed, those left are reported
+    /// in [`BatchOutcome::skipped`]. With several threads the operations
+    /// already running still complete.
+    pub stop_on_first_error: bool,
+    /// Run the operations on this many threads, one after the other when
+    /// 0 or 1. They must then not depend on each other, e.g. a copy of a
+    /// directory another operation creates.
+    pub threads: usize,
+    /// Used by the copies, moves and deletions
+    pub options: Options,
+}
+
+/// Everything [`try_all`] did, its `Display` is a one line summary
+#[derive(Debug, Default)]
+pub struct BatchOutcome {
+    /// The operations that ran with their result, in input order. Deletions
+    /// and creations report an empty `CopyReport`.
+    pub results: Vec<(Op, Result<CopyReport>)>,
+    /// The operations never started because of
+    /// [`BatchOptions::stop_on_first_error`], in input order
+    pub skipped: Vec<Op>,
+}
+
+impl BatchOutcome {
+    pub fn succeeded(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|(_, result)| result.is_ok())
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+
+    /// The operations that failed with their error
+    pub fn errors(&self) -> impl Iterator<Item = (&Op, &Error)> {
+        self.results
+            .iter()
+            .filter_map(|(op, result)| result.as_ref().err().map(|e| (op, e)))
+    }
+
+    /// Whether every operation ran and succeeded
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty() && self.failed() == 0
+    }
+}
+
+impl fmt::Display for BatchOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} succeeded, {} failed",
+            self.succeeded(),
+            self.failed()
+        )?;
+        if !self.skipped.is_empty() {
+            write!(f, ", {} skipped", self.skipped.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every operation whatever happens to the others and report each
+/// result, e.g. for a cleanup at shutdown. Nothing is rolled back, see
+/// [`try_all_with`] to stop early or use threads.
+///
+/// # Examples
+/// ```
+/// use fdir::*;
+/// use fdir::batch::{try_all, Op};
+/// let root = std::env::temp_dir().join("fdir_try_all");
+/// let _ = std::fs::remove_dir_all(&root);
+/// let log = FileInfo::create(root.join("app.log")).unwrap();
+/// let outcome = try_all([
+///     Op::Delete(Entry::File(log)),
+///     Op::Move { from: root.join("missing.lock"), to: root.join("old.lock") },
+///     Op::CreateDir(root.join("archive")),
+///     Op::CreateFile(root.join("archive/closed")),
+/// ]);
+/// assert_eq!(outcome.to_string(), "3 succeeded, 1 failed");
+/// for (op, error) in outcome.errors() {
+///     assert!(matches!(op, Op::Move { .. }));
+///     assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+/// }
+/// assert!(!root.join("app.log").exists() && root.join("archive/closed").exists());
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+pub fn try_all(ops: impl IntoIterator<Item = Op>) -> BatchOutcome {
+    try_all_with(ops, &BatchOptions::default())
+}
+
+/// Same as [`try_all`], configured by `batch`
+///
+/// # Examples
+/// ```
+/// use fdir::batch::{try_all_with, BatchOptions, Op};
+/// let root = std::env::temp_dir().join("fdir_try_all_with");
+/// let _ = std::fs::remove_dir_all(&root);
+/// let batch = BatchOptions { stop_on_first_error: true, ..Default::default() };
+/// let outcome = try_all_with(
+///     [
+///         Op::CreateFile(root.join("a")),
+///         Op::Copy { from: root.join("missing"), to: root.join("b") },
+///         Op::CreateFile(root.join("c")),
+///     ],
+///     &batch,
+/// );
+/// assert_eq!(outcome.to_string(), "1 succeeded, 1 failed, 1 skipped");
+/// assert!(!outcome.is_complete() && !root.join("c").exists());
+///
+/// let batch = BatchOptions { threads: 4, ..Default::default() };
+/// let ops = (0..20).map(|i| Op::CreateFile(root.join("many").join(i.to_string())));
+/// assert!(try_all_with(ops, &batch).is_complete());
+/// assert_eq!(std::fs::read_dir(root.join("many")).unwrap().count(), 20);
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+pub fn try_all_with(ops: impl IntoIterator<Item = Op>, batch: &BatchOptions) -> BatchOutcome {
+    let queue = Mutex::new(ops.into_iter().enumerate().collect::<Vec<_>>().into_iter());
+    let failed = AtomicBool::new(false);
+    let work = || {
+        let mut results = Vec::new();
+        while !(batch.stop_on_first_error && failed.load(Ordering::Relaxed)) {
+            let next = queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+            let Some((index, op)) = next else {
+                break;
+            };
+            let result = op.run(&batch.options);
+            if result.is_err() {
+                failed.store(true, Ordering::Relaxed);
+            }
+            results.push((index, op, result));
+        }
+        results
+    };
+    let mut results = if batch.threads > 1 {
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..batch.threads).map(|_| scope.spawn(work)).collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("batch thread panicked"))
+                .collect()
+        })
+    } else {
+        work()
+    };
+    results.sort_by_key(|(index, _, _)| *index);
+    BatchOutcome {
+        results: results
+            .into_iter()
+            .map(|(_, op, result)| (op, result))
+            .collect(),
+        skipped: queue
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .map(|(_, op)| op)
+            .collect(),
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt::Display;
+use std::fs::{self, DirEntry, Metadata};
+use std::io::Result;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::error::wrong_kind;
+use crate::fix_path;
+
+use super::{Action, DirectoryInfo, FileInfo, Info};
+
+/// A [`FileInfo`] whose metadata is fetched once and then served from memory
+///
+/// `metadata`, `size`, `permissions`, `read_only` and `modified` answer from
+/// the cache and may be stale: changes made to the file after the metadata
+/// was read, by this process or another one, are only seen after
+/// [`CachedFileInfo::refresh`]. `exists` and `validate` always ask the
+/// filesystem.
+///
+/// # Examples
+/// ```
+/// use fdir::{Action, FileInfo, Info};
+/// let path = std::env::temp_dir().join("fdir_cached_file.txt");
+/// let file = FileInfo::create(&path).unwrap();
+/// file.write("four").unwrap();
+/// let mut cached = file.clone().with_cached_metadata().unwrap();
+/// assert_eq!(cached.size(), 4);
+/// file.write("eight by").unwrap();
+/// // still the size read when the metadata was cached
+/// assert_eq!(cached.size(), 4);
+/// cached.refresh().unwrap();
+/// assert_eq!(cached.size(), 8);
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CachedFileInfo {
+    file: FileInfo,
+    metadata: Metadata,
+}
+
+impl Display for CachedFileInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.file.fmt(f)
+    }
+}
+
+impl CachedFileInfo {
+    /// Cache metadata that was already read, for example during a listing.
+    /// Fails if `metadata` does not describe a file.
+    pub fn from_metadata<P: AsRef<Path>>(path: P, metadata: Metadata) -> Result<Self> {
+        let path = fix_path(path)?;
+        if !metadata.is_file() {
+            return Err(wrong_kind(&path, "file"));
+        }
+        Ok(Self {
+            file: unsafe { FileInfo::open_uncheck(path) },
+            metadata,
+        })
+    }
+
+    /// Cache the metadata of a `read_dir` entry, symlinks are resolved
+    pub fn from_dir_entry(entry: &DirEntry) -> Result<Self> {
+        let metadata = if entry.file_type()?.is_symlink() {
+            fs::metadata(entry.path())?
+        } else {
+            entry.metadata()?
+        };
+        Self::from_metadata(entry.path(), metadata)
+    }
+
+    /// Read the metadata again
+    pub fn refresh(&mut self) -> Result<()> {
+        self.metadata = self.file.metadata()?;
+        Ok(())
+    }
+
+    /// Last modification time from the cache
+    pub fn modified(&self) -> Result<SystemTime> {
+        self.metadata.modified()
+    }
+
+    pub fn file(&self) -> &FileInfo {
+        &self.file
+    }
+
+    pub fn into_file(self) -> FileInfo {
+        self.file
+    }
+}
+
+impl Info for CachedFileInfo {
+    fn as_path(&self) -> &Path {
+        self.file.as_path()
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(self.metadata.clone())
+    }
+
+    fn size(&self) -> u64 {
+        self.metadata.len()
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.file.validate()
+    }
+}
+
+impl FileInfo {
+    /// Fetch the metadata once, see [`CachedFileInfo`]
+    pub fn with_cached_metadata(self) -> Result<CachedFileInfo> {
+        let metadata = self.metadata()?;
+        if !metadata.is_file() {
+            return Err(wrong_kind(self.as_path(), "file"));
+        }
+        Ok(CachedFileInfo {
+            file: self,
+            metadata,
+        })
+    }
+}
+
+impl DirectoryInfo {
+    /// Same as [`DirectoryInfo::files`], with the metadata read during the
+    /// listing cached
+    pub fn cached_files(&self) -> Result<Vec<CachedFileInfo>> {
+        Ok(fs::read_dir(self.as_path())?
+            .filter_map(|entry| CachedFileInfo::from_dir_entry(&entry.ok()?).ok())
+            .collect())
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::Result;
+
+use infer::MatcherType;
+
+use super::{FileInfo, Info};
+
+/// Number of leading bytes inspected when detecting the file type
+pub(crate) const PROBE_LEN: u64 = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Image,
+    Archive,
+    Audio,
+    Video,
+    Document,
+    Executable,
+    Font,
+    Text,
+    Other,
+}
+
+/// A file type detected from the magic bytes of the content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileKind {
+    mime_type: &'static str,
+    extension: &'static str,
+    category: Category,
+}
+
+impl FileKind {
+    pub fn mime_type(&self) -> &'static str {
+        self.mime_type
+    }
+    /// The canonical extension of the type, without the leading dot
+    pub fn extension(&self) -> &'static str {
+        self.extension
+    }
+    pub fn category(&self) -> Category {
+        self.category
+    }
+}
+
+impl From<infer::Type> for FileKind {
+    fn from(value: infer::Type) -> Self {
+        let category = match value.matcher_type() {
+            MatcherType::Image => Category::Image,
+            MatcherType::Archive => Category::Archive,
+            MatcherType::Audio => Category::Audio,
+            MatcherType::Video => Category::Video,
+            MatcherType::Doc | MatcherType::Book => Category::Document,
+            MatcherType::App => Category::Executable,
+            MatcherType::Font => Category::Font,
+            MatcherType::Text => Category::Text,
+            MatcherType::Custom => Category::Other,
+        };
+        Self {
+            mime_type: value.mime_type(),
+            extension: value.extension(),
+            category,
+        }
+    }
+}
+
+impl FileInfo {
+    /// Classify the file from its first few KiB, the rest of the file is never read
+    ///
+    /// Returns `Ok(None)` for empty files and unknown formats.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::detect::Category;
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_detect_type");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// std::fs::create_dir_all(&root).unwrap();
+    /// // the content wins over the extension
+    /// std::fs::write(root.join("photo.txt"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+    /// let photo = FileInfo::open(root.join("photo.txt")).unwrap();
+    /// let kind = photo.detect_type().unwrap().unwrap();
+    /// assert_eq!(kind.mime_type(), "image/png");
+    /// assert_eq!(kind.category(), Category::Image);
+    /// assert!(!photo.matches_extension().unwrap());
+    /// // unknown content falls back to trusting the extension
+    /// std::fs::write(root.join("notes.txt"), "plain words").unwrap();
+    /// let notes = FileInfo::open(root.join("notes.txt")).unwrap();
+    /// assert_eq!(notes.detect_type().unwrap(), None);
+    /// assert!(notes.matches_extension().unwrap());
+    /// let empty = FileInfo::create(root.join("empty.bin")).unwrap();
+    /// assert_eq!(empty.detect_type().unwrap(), None);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn detect_type(&self) -> Result<Option<FileKind>> {
+        Ok(infer::get(&self.read_prefix(PROBE_LEN)?).map(FileKind::from))
+    }
+
+    /// Check whether the content agrees with the extension of the path
+    ///
+    /// Content that cannot be classified never contradicts the extension,
+    /// while a recognised format under an extension that the detector does not
+    /// know (or no extension at all) is a mismatch.
+    pub fn matches_extension(&self) -> Result<bool> {
+        let probe = self.read_prefix(PROBE_LEN)?;
+        let extension = self
+            .as_path()
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        Ok(extension_matches(&probe, extension.as_deref()))
+    }
+}
+
+/// Whether the leading bytes `probe` agree with the lowercased `extension`,
+/// see [`FileInfo::matches_extension`]
+pub(crate) fn extension_matches(probe: &[u8], extension: Option<&str>) -> bool {
+    let extension = match extension {
+        Some("jpeg" | "jpe") => "jpg",
+        Some("tiff") => "tif",
+        Some("htm") => "html",
+        Some(ext) => ext,
+        None => return infer::get(probe).is_none(),
+    };
+    if infer::is_supported(extension) {
+        infer::is(probe, extension)
+    } else {
+        infer::get(probe).is_none()
+    }
+}
@@ -1,144 +1,1428 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fmt::Display;
 use std::fs::{self, create_dir_all, rename};
 use std::io::{Error, ErrorKind, Result};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
 
-use crate::error::already_exist;
+use crate::error::{
+    already_exist, check_kind, into_own_subtree, invalid_path, is_source_not_removed,
+    no_longer_exists, no_standard_dir, same_file, source_not_removed, wrong_kind,
+};
+use crate::error_kind::{rename_error, vanished};
+use crate::fs_id::same_filesystem;
+use crate::hooks::FilterHook;
+use crate::io::keep_metadata;
+use crate::journal::Operation;
+use crate::stats::{DirSize, SizeCounter};
 use crate::sync::recover::{Status, TryRecover};
-use crate::{fix_path, replace};
+use crate::convert::portability_issue;
+use crate::{
+    fix_path, fmt_path, is_ancestor_of, is_dry_run, is_same_file, numbered_paths, rebase, relation,
+    relative_path, renamed, safe_join, CopyReport, DirStats, ErrorDecision, LinkPolicy,
+    LinkRewrite, MissingSource, Numbering, Options, Relation, Size, WalkOptions,
+};
 
+use super::batch::{self, OpenMany};
+use super::entry::Entry;
 use super::file::FileInfo;
+use super::listing::ListingLimit;
 use super::recover::TryRecoverResult;
-use super::{Action, Info};
+use super::visit::{VisitAction, Visitor};
+use super::walk::{Ordered, WalkEntry};
+use super::{_delete, Action, Info};
 
+/// Which entries [`DirectoryInfo::count_entries`] counts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EntryFilter {
+    Files,
+    Directories,
+    /// Files, directories and anything else such as symlinks
+    #[default]
+    All,
+}
+
+impl EntryFilter {
+    pub(crate) fn matches(self, file_type: fs::FileType) -> bool {
+        match self {
+            EntryFilter::Files => file_type.is_file(),
+            EntryFilter::Directories => file_type.is_dir(),
+            EntryFilter::All => true,
+        }
+    }
+}
+
+/// A directory, cheap to clone: the path is shared by the clones
 #[derive(Debug, Clone)]
 pub struct DirectoryInfo {
-    path: PathBuf,
+    path: Arc<Path>,
 }
 impl Display for DirectoryInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}", self.path.display()))
+        fmt_path(&self.path, f)
+    }
+}
+
+impl From<DirectoryInfo> for PathBuf {
+    fn from(value: DirectoryInfo) -> Self {
+        value.path.to_path_buf()
+    }
+}
+
+impl AsRef<Path> for DirectoryInfo {
+    fn as_ref(&self) -> &Path {
+        &self.path
     }
 }
 
 impl DirectoryInfo {
+    /// The path as an owned `PathBuf`, a copy of the shared path
+    pub fn into_path_buf(self) -> PathBuf {
+        self.path.to_path_buf()
+    }
+
+    /// Open and validate every path, a bad path does not fail the batch.
+    /// Returns the opened directory entries and the failed paths with their
+    /// error, both in input order.
+    pub fn open_many<I>(paths: I) -> OpenMany<DirectoryInfo>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        batch::open_many(paths)
+    }
+
+    /// Same as [`DirectoryInfo::open_many`], validating on up to `threads` threads.
+    /// Worth it where every `stat` is slow, such as network filesystems.
+    pub fn open_many_parallel<I>(paths: I, threads: usize) -> OpenMany<DirectoryInfo>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        batch::open_many_parallel(paths, threads)
+    }
+
+    /// Create the directory `path`, or `path (1)`, `path (2)`... when the
+    /// name is taken, like `ConflictPolicy::Rename` does
+    pub fn create_unique<P: AsRef<Path>>(path: P) -> Result<DirectoryInfo> {
+        Self::create_unique_with(path, Numbering::default())
+    }
+
+    /// Same as [`DirectoryInfo::create_unique`], numbered by `numbering`.
+    /// Missing parents are created. A name is claimed by creating the
+    /// directory, so concurrent callers never get the same one. In dry-run
+    /// mode the first free name is returned and nothing is created.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::{Action, DirectoryInfo, Info, Numbering};
+    /// let root = std::env::temp_dir().join("fdir_create_unique");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let first = DirectoryInfo::create_unique(root.join("report")).unwrap();
+    /// let second = DirectoryInfo::create_unique(root.join("report")).unwrap();
+    /// assert_eq!(second.as_path(), root.join("report (1)"));
+    /// let third = DirectoryInfo::create_unique_with(root.join("report"), Numbering::Dashed);
+    /// assert_eq!(third.unwrap().as_path(), root.join("report-1"));
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn create_unique_with<P: AsRef<Path>>(
+        path: P,
+        numbering: Numbering,
+    ) -> Result<DirectoryInfo> {
+        let path = fix_path(path)?;
+        let mut candidates =
+            std::iter::once(path.clone()).chain(numbered_paths(&path, numbering, true));
+        if is_dry_run() {
+            let candidate = candidates.find(|candidate| !candidate.exists());
+            let path = candidate.expect("ran out of candidate names");
+            return Ok(DirectoryInfo { path: path.into() });
+        }
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        loop {
+            let candidate = candidates.next().expect("ran out of candidate names");
+            match fs::create_dir(&candidate) {
+                Ok(()) => return Ok(DirectoryInfo { path: candidate.into() }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// The home directory of the current user
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::{DirectoryInfo, Info};
+    /// let home = DirectoryInfo::home().unwrap();
+    /// assert!(home.as_path().is_absolute());
+    /// ```
+    pub fn home() -> Result<DirectoryInfo> {
+        let home = dirs::home_dir().ok_or_else(|| no_standard_dir("home"))?;
+        DirectoryInfo::open(home)
+    }
+
+    /// The directory holding the configuration of `app_name`, created with
+    /// its parents when missing: `$XDG_CONFIG_HOME/app_name` or
+    /// `~/.config/app_name` on Linux, `~/Library/Application Support/app_name`
+    /// on macOS and `%APPDATA%\app_name` on Windows. `app_name` must be a
+    /// single name. Nothing is created in dry-run mode.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::{Action, DirectoryInfo, Info};
+    /// let config = DirectoryInfo::config_dir("fdir_config_dir").unwrap();
+    /// assert!(config.as_path().ends_with("fdir_config_dir"));
+    /// assert!(DirectoryInfo::config_dir("../elsewhere").is_err());
+    /// assert!(DirectoryInfo::config_dir("").is_err());
+    /// # config.delete().unwrap();
+    /// ```
+    pub fn config_dir(app_name: impl AsRef<Path>) -> Result<DirectoryInfo> {
+        app_dir(dirs::config_dir(), "configuration", app_name.as_ref())
+    }
+
+    /// Same as [`DirectoryInfo::config_dir`] for the data of `app_name`:
+    /// under `$XDG_DATA_HOME` or `~/.local/share` on Linux,
+    /// `~/Library/Application Support` on macOS and `%APPDATA%` on Windows
+    pub fn data_dir(app_name: impl AsRef<Path>) -> Result<DirectoryInfo> {
+        app_dir(dirs::data_dir(), "data", app_name.as_ref())
+    }
+
+    /// Same as [`DirectoryInfo::config_dir`] for the cache of `app_name`:
+    /// under `$XDG_CACHE_HOME` or `~/.cache` on Linux, `~/Library/Caches` on
+    /// macOS and `%LOCALAPPDATA%` on Windows
+    pub fn cache_dir(app_name: impl AsRef<Path>) -> Result<DirectoryInfo> {
+        app_dir(dirs::cache_dir(), "cache", app_name.as_ref())
+    }
+
+    /// The directory holding the running executable, as found by
+    /// `std::env::current_exe`, which may not resolve links
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::{DirectoryInfo, Info};
+    /// let dir = DirectoryInfo::executable_dir().unwrap();
+    /// let exe = std::env::current_exe().unwrap();
+    /// assert!(exe.starts_with(dir.as_path()));
+    /// ```
+    pub fn executable_dir() -> Result<DirectoryInfo> {
+        let exe = std::env::current_exe()?;
+        let dir = exe
+            .parent()
+            .ok_or_else(|| invalid_path(&exe, "has no parent directory"))?;
+        DirectoryInfo::open(dir)
+    }
+
+    /// The path of the entry `name` in the directory, which need not
+    /// exist. `name` must be a single file name: an empty name, `.`, `..`
+    /// or a name with a path separator is refused with
+    /// `ErrorKind::InvalidInput`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::ErrorKind;
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_child");
+    /// # let _ = std::fs::remove_dir_all(&root);
+    /// FileInfo::create(root.join("data/a.txt")).unwrap();
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// assert_eq!(dir.child("new.txt").unwrap(), root.join("new.txt"));
+    /// for name in ["", ".", "..", "data/a.txt"] {
+    ///     assert_eq!(dir.child(name).unwrap_err().kind(), ErrorKind::InvalidInput);
+    /// }
+    ///
+    /// let data = dir.child_dir("data").unwrap();
+    /// let file = data.child_file("a.txt").unwrap();
+    /// assert_eq!(file.as_path(), root.join("data/a.txt"));
+    /// assert_eq!(data.child_file("b.txt").unwrap_err().kind(), ErrorKind::NotFound);
+    /// assert!(dir.child_file("data").is_err() && data.child_dir("a.txt").is_err());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn child(&self, name: impl AsRef<OsStr>) -> Result<PathBuf> {
+        Ok(self.as_path().join(crate::checked_name(name.as_ref())?))
+    }
+
+    /// Open the file `name` in the directory, see [`DirectoryInfo::child`]
+    pub fn child_file(&self, name: impl AsRef<OsStr>) -> Result<FileInfo> {
+        FileInfo::open(self.child(name)?)
+    }
+
+    /// Open the directory `name` in the directory, see
+    /// [`DirectoryInfo::child`]
+    pub fn child_dir(&self, name: impl AsRef<OsStr>) -> Result<DirectoryInfo> {
+        DirectoryInfo::open(self.child(name)?)
+    }
+
     pub fn children(&self) -> Result<Vec<PathBuf>> {
         read_dir(self.as_path(), |_| true)
     }
+    /// Direct children as [`Entry`], classified from the directory listing.
+    /// Symlinks are resolved, entries that are neither a file nor a
+    /// directory are left out.
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        let mut limit = ListingLimit::new(self.as_path());
+        for entry in fs::read_dir(self.as_path())? {
+            limit.count()?;
+            let Ok(entry) = entry else { continue };
+            let file_type = match entry.file_type() {
+                Ok(file_type) if file_type.is_symlink() => match fs::metadata(entry.path()) {
+                    Ok(metadata) => metadata.file_type(),
+                    Err(_) => continue,
+                },
+                Ok(file_type) => file_type,
+                Err(_) => continue,
+            };
+            if let Ok(entry) = unsafe { Entry::classify(entry.path(), file_type) } {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+    /// Call `f` with each direct child, listed once beforehand. A child
+    /// removed meanwhile, which fails to open or makes `f` fail with
+    /// `NotFound` once gone, is handed to `options.on_error` and skipped
+    /// without a hook. Other errors follow `on_error` as in a copy. Returns
+    /// the children skipped as removed.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_for_each_entry");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for name in ["a", "b", "c", "d"] {
+    ///     FileInfo::create(root.join(name)).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let mut visited = Vec::new();
+    /// let vanished = dir
+    ///     .for_each_entry(&Options::default(), |entry| {
+    ///         if visited.is_empty() {
+    ///             // another process cleans up meanwhile
+    ///             let (root, current) = (root.clone(), entry.to_path_buf());
+    ///             std::thread::spawn(move || {
+    ///                 for file in DirectoryInfo::open(root).unwrap().files().unwrap() {
+    ///                     if file.as_path() != current {
+    ///                         file.delete().unwrap();
+    ///                     }
+    ///                 }
+    ///             })
+    ///             .join()
+    ///             .unwrap();
+    ///         }
+    ///         visited.push(entry.metadata()?.len());
+    ///         Ok(())
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!((visited.len(), vanished.len()), (1, 3));
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn for_each_entry(
+        &self,
+        options: &Options,
+        mut f: impl FnMut(Entry) -> Result<()>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+        for path in self.children()? {
+            loop {
+                let error = match Entry::open(&path).and_then(&mut f) {
+                    Ok(()) => break,
+                    Err(e) => e,
+                };
+                let gone = vanished(&path, &error);
+                let decision = if gone {
+                    options.decide_vanished(&path, &error)
+                } else {
+                    options.decide(&path, &error)
+                };
+                match decision {
+                    ErrorDecision::Retry => continue,
+                    ErrorDecision::Skip if gone => removed.push(path),
+                    ErrorDecision::Skip => {}
+                    ErrorDecision::Abort => return Err(error),
+                }
+                break;
+            }
+        }
+        Ok(removed)
+    }
     pub fn files(&self) -> Result<Vec<FileInfo>> {
-        Ok(read_dir(self.as_path(), |path| path.is_file())?
+        Ok(typed_children(self.as_path(), |file_type| file_type.is_file())?
             .into_iter()
-            .map(|path| unsafe { FileInfo::open_uncheck(path) })
+            .map(|path| unsafe { FileInfo::from_path_buf(path) })
             .collect())
     }
 
     pub fn directories(&self) -> Result<Vec<DirectoryInfo>> {
-        Ok(read_dir(self.as_path(), |path| path.is_dir())?
+        Ok(typed_children(self.as_path(), |file_type| file_type.is_dir())?
             .into_iter()
             .map(|path| unsafe { DirectoryInfo::open_uncheck(path) })
             .collect())
     }
+
+    /// Count the entries matching `filter` without collecting their paths,
+    /// symlinks are not followed. Only direct children are counted unless
+    /// `recursive` is set.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_count_entries");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for name in ["a/1", "a/b/2", "3"] {
+    ///     FileInfo::create(root.join(name)).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// assert_eq!(dir.count_entries(EntryFilter::All, false).unwrap(), 2);
+    /// assert_eq!(dir.count_entries(EntryFilter::Files, true).unwrap(), 3);
+    /// assert_eq!(dir.count_entries(EntryFilter::Directories, true).unwrap(), 2);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn count_entries(&self, filter: EntryFilter, recursive: bool) -> Result<u64> {
+        let options = WalkOptions {
+            max_depth: (!recursive).then_some(1),
+            ..Default::default()
+        };
+        let mut visitor = CountVisitor { filter, count: 0 };
+        self.visit(&options, &mut visitor)?;
+        Ok(visitor.count)
+    }
+
+    /// Whether the directory has no entries, stops at the first one found
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(fs::read_dir(self.as_path())?.next().is_none())
+    }
+
+    /// Locate the directory relative to `path`, see [`crate::relation`]
+    pub fn relation_to(&self, path: impl AsRef<Path>) -> Relation {
+        relation(self.as_path(), path)
+    }
+
+    /// Same as [`Action::copy_new`], configured by `options`
+    pub fn copy_new_with<P: AsRef<Path>>(&self, path: P, options: &Options) -> Result<CopyReport> {
+        self.validate()?;
+        self.check_options(options)?;
+        let path = fix_path(path)?;
+        if is_same_file(self.as_path(), &path) {
+            return Err(same_file(self.as_path(), &path));
+        }
+        match options.resolve_conflict(self.as_path(), path)? {
+            Some(path) => _write_dir(self, &path, true, options),
+            None => Ok(CopyReport {
+                skipped: 1,
+                ..CopyReport::new()
+            }),
+        }
+    }
+
+    /// Same as [`Action::move_new`], configured by `options`
+    ///
+    /// The report is empty when the directory could simply be renamed.
+    /// Entries skipped by the filters stay in the source directory, which is
+    /// then kept and `self` keeps pointing at it.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::{Action, DirectoryInfo, Info, Options};
+    /// use glob::Pattern;
+    /// let root = std::env::temp_dir().join("fdir_move_filtered");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for file in ["a.txt", "b.bin", "secret.txt", "drafts/c.txt", "sub/d.txt"] {
+    ///     fdir::FileInfo::create(root.join("src").join(file))
+    ///         .unwrap()
+    ///         .write(file)
+    ///         .unwrap();
+    /// }
+    /// let mut dir = DirectoryInfo::open(root.join("src")).unwrap();
+    /// let options = Options::new()
+    ///     .include(Pattern::new("*.txt").unwrap())
+    ///     .exclude(Pattern::new("secret.txt").unwrap())
+    ///     .exclude(Pattern::new("drafts").unwrap());
+    /// let report = dir.move_new_with(root.join("dest"), &options).unwrap();
+    /// assert!(!report.renamed);
+    /// assert!(root.join("dest/a.txt").exists());
+    /// assert!(root.join("dest/sub/d.txt").exists());
+    /// assert!(!root.join("dest/b.bin").exists());
+    /// assert!(!root.join("dest/secret.txt").exists());
+    /// assert!(!root.join("dest/drafts").exists());
+    /// // the filtered entries stay behind, moved ones are gone
+    /// assert_eq!(dir.as_path(), root.join("src"));
+    /// assert!(!root.join("src/a.txt").exists());
+    /// assert!(root.join("src/b.bin").exists());
+    /// assert!(root.join("src/secret.txt").exists());
+    /// assert!(root.join("src/drafts/c.txt").exists());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn move_new_with<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &Options,
+    ) -> Result<CopyReport> {
+        self.validate()?;
+        self.check_options(options)?;
+        let path = fix_path(path)?;
+        if is_same_file(self.as_path(), &path) {
+            return Ok(CopyReport::default());
+        }
+        let mut report = CopyReport::new();
+        let Some(path) = options.resolve_conflict(self.as_path(), path)? else {
+            report.skipped += 1;
+            return Ok(report);
+        };
+        // an existing destination is merged into rather than renamed over
+        report.renamed = !options.has_filters()
+            && !path.exists()
+            && if report.dry_run {
+                path.parent().is_some_and(Path::is_dir) && same_filesystem(self.as_path(), &path)
+            } else {
+                rename(self.as_path(), path.as_path()).is_ok()
+            };
+        if report.renamed {
+            options.journal_done(Operation::Move, self.as_path(), Some(&path))?;
+        } else {
+            report = match _write_dir(self, &path, false, options) {
+                Err(e) if is_source_not_removed(&e, self.as_path()) => {
+                    self.path = path.into();
+                    return Err(e);
+                }
+                result => result?,
+            };
+        }
+        if report.is_complete() && !report.dry_run {
+            self.path = path.into();
+        }
+        Ok(report)
+    }
+
+    fn check_options(&self, options: &Options) -> Result<()> {
+        if let Some(max) = options.walk.max_entries {
+            let mut walk = self.walk_with(&options.walk);
+            walk.by_ref().for_each(drop);
+            if walk.is_truncated() {
+                return Err(Error::other(format!(
+                    "The directory '{}' has more than {} entries (max_entries)",
+                    self, max
+                )));
+            }
+        }
+        if options.portable_names {
+            let unportable = self.unportable_names()?;
+            if !unportable.is_empty() {
+                let names: Vec<_> = unportable
+                    .iter()
+                    .map(|(path, reason)| format!("'{}' ({})", path.display(), reason))
+                    .collect();
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Names not portable to Windows: {}", names.join(", ")),
+                ));
+            }
+        }
+        if options.fail_on_case_collision {
+            let collisions = self.case_collisions_with(&options.walk)?;
+            if !collisions.is_empty() {
+                let groups: Vec<_> = collisions
+                    .iter()
+                    .map(|group| {
+                        let paths: Vec<_> = group
+                            .iter()
+                            .map(|path| format!("'{}'", path.display()))
+                            .collect();
+                        paths.join(" and ")
+                    })
+                    .collect();
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Names differing only by case: {}", groups.join(", ")),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Direct children whose name equals `name` ignoring case, see
+    /// [`DirectoryInfo::detect_case_collisions`] for the case folding
+    pub fn find_case_insensitive(&self, name: impl AsRef<OsStr>) -> Result<Vec<PathBuf>> {
+        let name = fold_case(name.as_ref());
+        let mut found = Vec::new();
+        for entry in fs::read_dir(self.as_path())? {
+            let entry = entry?;
+            if fold_case(&entry.file_name()) == name {
+                found.push(entry.path());
+            }
+        }
+        Ok(found)
+    }
+
+    /// Whether a direct child named `name` exists, ignoring case
+    pub fn exists_case_insensitive(&self, name: impl AsRef<OsStr>) -> Result<bool> {
+        let name = fold_case(name.as_ref());
+        for entry in fs::read_dir(self.as_path())? {
+            if fold_case(&entry?.file_name()) == name {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Group the entries of a same directory whose names differ only by
+    /// case, they collide on case-insensitive filesystems such as the
+    /// Windows and macOS defaults. Only direct children are checked unless
+    /// `recursive` is set.
+    ///
+    /// Names are compared after Unicode lowercasing, which matches what those
+    /// filesystems do for nearly all names but not every special case.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(target_os = "linux")] {
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_case_collisions");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for name in ["src/a.TXT", "src/a.txt", "src/b.txt", "src/sub/B.txt", "src/sub/b.TXT"] {
+    ///     FileInfo::create(root.join(name)).unwrap();
+    /// }
+    /// let src = DirectoryInfo::open(root.join("src")).unwrap();
+    /// let pair = [root.join("src/a.TXT"), root.join("src/a.txt")];
+    /// assert_eq!(src.detect_case_collisions(false).unwrap(), [pair.clone()]);
+    /// assert_eq!(src.detect_case_collisions(true).unwrap().len(), 2);
+    /// let mut found = src.find_case_insensitive("A.txt").unwrap();
+    /// found.sort();
+    /// assert_eq!(found, pair);
+    /// assert!(src.find_case_insensitive("c.txt").unwrap().is_empty());
+    ///
+    /// let options = Options::new().fail_on_case_collision(true);
+    /// let error = src.copy_new_with(root.join("copy"), &options).unwrap_err();
+    /// assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    /// assert!(error.to_string().contains("differing only by case"));
+    /// assert!(!root.join("copy").exists());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// # }
+    /// ```
+    pub fn detect_case_collisions(&self, recursive: bool) -> Result<Vec<Vec<PathBuf>>> {
+        let options = WalkOptions {
+            max_depth: (!recursive).then_some(1),
+            ..Default::default()
+        };
+        self.case_collisions_with(&options)
+    }
+
+    fn case_collisions_with(&self, options: &WalkOptions) -> Result<Vec<Vec<PathBuf>>> {
+        let mut names: BTreeMap<(PathBuf, String), Vec<PathBuf>> = BTreeMap::new();
+        for entry in self.walk_with(options) {
+            let entry = entry?;
+            let parent = entry.path().parent().unwrap_or(entry.path()).to_path_buf();
+            let name = fold_case(entry.path().file_name().unwrap_or_default());
+            names
+                .entry((parent, name))
+                .or_default()
+                .push(entry.into_path());
+        }
+        Ok(names
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(|mut group| {
+                group.sort();
+                group
+            })
+            .collect())
+    }
+
+    /// List every entry below the directory whose name is invalid on Windows,
+    /// with the reason, see [`crate::convert::portability_issue`]
+    pub fn unportable_names(&self) -> Result<Vec<(PathBuf, String)>> {
+        let mut unportable = Vec::new();
+        for entry in self.walk() {
+            let entry = entry?;
+            let name = entry.path().file_name().unwrap_or_default();
+            if let Some(reason) = portability_issue(name) {
+                unportable.push((entry.into_path(), reason));
+            }
+        }
+        Ok(unportable)
+    }
+
+    /// Find the direct child file named `name`, comparing names after
+    /// Unicode normalisation so composed and decomposed spellings match.
+    /// Only this lookup normalises, other lookups compare names as they are.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_find_normalized");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// // `e` followed by a combining acute accent, as macOS may store it
+    /// let decomposed = FileInfo::create(root.join("cafe\u{301}.txt")).unwrap();
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let found = dir.find_file_normalized("caf\u{e9}.txt").unwrap().unwrap();
+    /// assert_eq!(found.as_path(), decomposed.as_path());
+    /// assert!(dir.find_file_normalized("cafe.txt").unwrap().is_none());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    #[cfg(feature = "unicode-normalization")]
+    pub fn find_file_normalized(
+        &self,
+        name: impl AsRef<std::ffi::OsStr>,
+    ) -> Result<Option<FileInfo>> {
+        use crate::convert::{normalize_file_name, Form};
+        let name = normalize_file_name(name.as_ref(), Form::Nfc);
+        Ok(self.files()?.into_iter().find(|file| {
+            file.file_name()
+                .is_some_and(|file_name| normalize_file_name(file_name, Form::Nfc) == name)
+        }))
+    }
+
+    /// Collect a [`DirStats`] summary in a single walk
+    ///
+    /// Entries that cannot be read are counted in `errors` instead of
+    /// aborting, only an unreadable root directory is an error.
+    pub fn stats(&self) -> Result<DirStats> {
+        self.stats_with(&WalkOptions::default())
+    }
+
+    /// Same as [`DirectoryInfo::stats`], traversing according to `options`
+    /// with [`DirectoryInfo::visit`], which is always depth first
+    pub fn stats_with(&self, options: &WalkOptions) -> Result<DirStats> {
+        fs::read_dir(self.as_path())?;
+        let mut visitor = StatsVisitor::new(options);
+        self.visit(options, &mut visitor)?;
+        Ok(visitor.stats)
+    }
+
+    /// Total length of the files below the directory, traversing according
+    /// to `options` with [`DirectoryInfo::visit`]. Unlike [`Info::size`]
+    /// errors are reported and symlinks are not followed.
+    ///
+    /// `WalkOptions::count_hard_links_once` and `apparent_size` give the
+    /// number `du` reports.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_size_checked");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let file = FileInfo::create(root.join("a")).unwrap();
+    /// std::fs::write(file.as_path(), [0u8; 1000]).unwrap();
+    /// for link in ["b", "c"] {
+    ///     std::fs::hard_link(root.join("a"), root.join(link)).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// assert_eq!(dir.size_checked(&WalkOptions::default()).unwrap().bytes, Size(3000));
+    /// let once = WalkOptions { count_hard_links_once: true, ..Default::default() };
+    /// assert_eq!(dir.size_checked(&once).unwrap().bytes, Size(1000));
+    /// assert_eq!(dir.stats_with(&once).unwrap().bytes, Size(1000));
+    /// let du = WalkOptions { apparent_size: false, ..once };
+    /// // whole blocks, at least the length unless compressed
+    /// println!("{}", dir.size_checked(&du).unwrap().bytes);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn size_checked(&self, options: &WalkOptions) -> Result<DirSize> {
+        let mut visitor = SizeVisitor {
+            size: DirSize::default(),
+            sizes: SizeCounter::new(options),
+            missing_source: options.missing_source,
+        };
+        self.visit(options, &mut visitor)?;
+        Ok(visitor.size)
+    }
+
+    /// The most recently modified file, only direct children are considered
+    /// unless `recursive` is set. Returns `None` if there are no files.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::{Action, DirectoryInfo, Info};
+    /// use std::time::{Duration, SystemTime};
+    /// let root = std::env::temp_dir().join("fdir_newest_file");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// std::fs::create_dir_all(root.join("sub")).unwrap();
+    /// std::fs::create_dir_all(root.join("empty")).unwrap();
+    /// let day = Duration::from_secs(24 * 60 * 60);
+    /// let ages = [("old.txt", 3), ("mid.txt", 2), ("sub/new.txt", 1), ("sub/ancient.txt", 9)];
+    /// for (name, days) in ages {
+    ///     let file = std::fs::File::create(root.join(name)).unwrap();
+    ///     file.set_modified(SystemTime::now() - day * days).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let newest = |recursive| dir.newest_file(recursive).unwrap().unwrap();
+    /// let oldest = |recursive| dir.oldest_file(recursive).unwrap().unwrap();
+    /// assert_eq!(newest(false).as_path(), root.join("mid.txt"));
+    /// assert_eq!(newest(true).as_path(), root.join("sub/new.txt"));
+    /// assert_eq!(oldest(false).as_path(), root.join("old.txt"));
+    /// assert_eq!(oldest(true).as_path(), root.join("sub/ancient.txt"));
+    ///
+    /// let empty = DirectoryInfo::open(root.join("empty")).unwrap();
+    /// assert!(empty.newest_file(true).unwrap().is_none());
+    /// assert!(empty.oldest_file(true).unwrap().is_none());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn newest_file(&self, recursive: bool) -> Result<Option<FileInfo>> {
+        self.select_file(recursive, |candidate, best| candidate > best)
+    }
+
+    /// The least recently modified file, see [`DirectoryInfo::newest_file`]
+    pub fn oldest_file(&self, recursive: bool) -> Result<Option<FileInfo>> {
+        self.select_file(recursive, |candidate, best| candidate < best)
+    }
+
+    /// Every file modified after `since`, in traversal order
+    pub fn modified_since(&self, since: SystemTime, recursive: bool) -> Result<Vec<FileInfo>> {
+        let mut files = Vec::new();
+        for entry in self.walk_with(&file_walk(recursive)) {
+            let entry = entry?;
+            if entry.file_type().is_file() && entry.metadata()?.modified()? > since {
+                files.push(unsafe { FileInfo::open_uncheck(entry.into_path()) });
+            }
+        }
+        Ok(files)
+    }
+
+    /// Files whose extension is `extension`, see
+    /// [`DirectoryInfo::files_with_extensions`]
+    pub fn files_with_extension(
+        &self,
+        extension: impl AsRef<OsStr>,
+        recursive: bool,
+    ) -> Result<Vec<FileInfo>> {
+        self.files_with_extensions(&[extension], recursive)
+    }
+
+    /// Files whose final extension is one of `extensions`, ignoring case and
+    /// a leading dot, found in a single scan. Only direct children are
+    /// considered unless `recursive` is set, symlinks are not followed.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_with_extensions");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for name in ["a.JPG", "b.jpeg", "c.png", "d.gif", "sub/e.png"] {
+    ///     FileInfo::create(root.join(name)).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let images = dir.files_with_extensions(&["jpg", "jpeg", ".png"], false).unwrap();
+    /// assert_eq!(images.len(), 3);
+    /// assert_eq!(dir.files_with_extension("png", true).unwrap().len(), 2);
+    /// # dir.delete().unwrap();
+    /// ```
+    pub fn files_with_extensions<E: AsRef<OsStr>>(
+        &self,
+        extensions: &[E],
+        recursive: bool,
+    ) -> Result<Vec<FileInfo>> {
+        self.files_matching(extension_matcher(extensions), recursive)
+    }
+
+    /// Files whose name is accepted by `pred`, in traversal order. Names are
+    /// tested during the scan, see [`DirectoryInfo::files_with_extensions`].
+    pub fn files_matching(
+        &self,
+        pred: impl Fn(&OsStr) -> bool,
+        recursive: bool,
+    ) -> Result<Vec<FileInfo>> {
+        let mut files = Vec::new();
+        for entry in self.walk_with(&file_walk(recursive)) {
+            let entry = entry?;
+            let accepted = entry.path().file_name().is_some_and(&pred);
+            if accepted && entry.file_type().is_file() {
+                files.push(unsafe { FileInfo::open_uncheck(entry.into_path()) });
+            }
+        }
+        Ok(files)
+    }
+
+    /// Keep the file whose modification time is `better` than every other,
+    /// only the current best is held while walking
+    fn select_file(
+        &self,
+        recursive: bool,
+        better: fn(SystemTime, SystemTime) -> bool,
+    ) -> Result<Option<FileInfo>> {
+        let mut best: Option<(PathBuf, SystemTime)> = None;
+        for entry in self.walk_with(&file_walk(recursive)) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let modified = entry.metadata()?.modified()?;
+            if best
+                .as_ref()
+                .is_none_or(|(_, time)| better(modified, *time))
+            {
+                best = Some((entry.into_path(), modified));
+            }
+        }
+        Ok(best.map(|(path, _)| unsafe { FileInfo::open_uncheck(path) }))
+    }
+}
+
+fn fold_case(name: &OsStr) -> String {
+    name.to_string_lossy().to_lowercase()
+}
+
+/// Whether a file name has one of `extensions` as final extension, ignoring
+/// case and a leading dot
+pub(crate) fn extension_matcher<E: AsRef<OsStr>>(extensions: &[E]) -> impl Fn(&OsStr) -> bool {
+    let wanted: Vec<String> = extensions
+        .iter()
+        .map(|extension| fold_case(extension.as_ref()))
+        .map(|extension| extension.trim_start_matches('.').to_string())
+        .filter(|extension| !extension.is_empty())
+        .collect();
+    move |name| {
+        Path::new(name)
+            .extension()
+            .is_some_and(|extension| wanted.contains(&fold_case(extension)))
+    }
+}
+
+pub(crate) fn file_walk(recursive: bool) -> WalkOptions {
+    WalkOptions {
+        max_depth: (!recursive).then_some(1),
+        ..Default::default()
+    }
+}
+
+/// The children of `path` whose type, links followed, passes `keep`. The
+/// type comes from the listing, only links cost a `stat`.
+fn typed_children(path: &Path, keep: fn(fs::FileType) -> bool) -> Result<Vec<PathBuf>> {
+    let mut children = Vec::new();
+    let mut limit = ListingLimit::new(path);
+    for entry in fs::read_dir(path)? {
+        limit.count()?;
+        let Ok(entry) = entry else { continue };
+        let Ok(mut file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() {
+            match fs::metadata(entry.path()) {
+                Ok(metadata) => file_type = metadata.file_type(),
+                Err(_) => continue,
+            }
+        }
+        if keep(file_type) {
+            children.push(entry.path());
+        }
+    }
+    Ok(children)
 }
 
 pub fn read_dir<F>(path: impl AsRef<Path>, f: F) -> Result<Vec<PathBuf>>
 where
     F: Fn(&PathBuf) -> bool,
 {
-    let read_dir = fs::read_dir(path)?
-        .filter_map(|d| {
-            d.ok().and_then(|d| {
-                let path = d.path();
-                if f(&path) {
-                    Some(path)
-                } else {
-                    None
-                }
-            })
-        })
-        .collect();
-    Ok(read_dir)
+    let path = path.as_ref();
+    let mut children = Vec::new();
+    let mut limit = ListingLimit::new(path);
+    for entry in fs::read_dir(path)? {
+        limit.count()?;
+        if let Ok(entry) = entry {
+            let path = entry.path();
+            if f(&path) {
+                children.push(path);
+            }
+        }
+    }
+    Ok(children)
+}
+
+/// `app_name` under the standard directory `base`, created when missing
+fn app_dir(base: Option<PathBuf>, name: &str, app_name: &Path) -> Result<DirectoryInfo> {
+    let mut components = app_name.components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(std::path::Component::Normal(_)), None)
+    ) {
+        return Err(invalid_path(app_name, "is not a single directory name"));
+    }
+    let path = base.ok_or_else(|| no_standard_dir(name))?.join(app_name);
+    if is_dry_run() {
+        let path = fix_path(path)?;
+        return Ok(DirectoryInfo { path: path.into() });
+    }
+    create_dir_all(&path)?;
+    DirectoryInfo::open(path)
 }
 
 impl Action for DirectoryInfo {
     fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = fix_path(path)?;
-        if path.is_dir() {
-            Ok(DirectoryInfo { path })
-        } else {
-            Err(Error::new(
-                ErrorKind::NotFound,
-                format!(
-                    "The path '{}' is not a directory or does not exist",
-                    path.display()
-                ),
-            ))
-        }
+        check_kind(&path, fs::metadata(&path), true)?;
+        Ok(DirectoryInfo { path: path.into() })
     }
 
     unsafe fn open_uncheck<P: AsRef<Path>>(path: P) -> Self {
         DirectoryInfo {
-            path: path.as_ref().to_path_buf(),
+            path: path.as_ref().into(),
         }
     }
 
     fn rename<T: AsRef<std::ffi::OsStr>>(&mut self, name: T) -> Result<()> {
-        let mut path = self.path.clone();
-        path.set_file_name(name);
-        rename(self.as_path(), &path)?;
-        self.path = path;
+        let path = renamed(self.as_path(), name.as_ref())?;
+        if is_dry_run() {
+            return self.validate();
+        }
+        rename(self.as_path(), &path).map_err(|e| rename_error(e, &path))?;
+        self.path = path.into();
         Ok(())
     }
 
-    fn copy_new<P: AsRef<Path>>(&self, path: P) -> TryRecoverResult<()> {
+    fn copy_new<P: AsRef<Path>>(&self, path: P) -> TryRecoverResult<'_, ()> {
+        self.validate()?;
         let path = fix_path(path)?;
-        if path.try_exists()? {
+        if !is_same_file(self.as_path(), &path) && path.try_exists()? {
             return Err(TryRecover::new(
                 already_exist(&path),
                 Status::CopyDirectory(self, path),
             ));
         }
-        _write_dir(self.clone(), &path, true)?;
+        self.copy_new_with(path, &Options::default())?;
         Ok(())
     }
 
-    fn move_new<P: AsRef<Path>>(&mut self, path: P) -> TryRecoverResult<()> {
+    fn move_new<P: AsRef<Path>>(&mut self, path: P) -> TryRecoverResult<'_, ()> {
+        self.validate()?;
         let path = fix_path(path)?;
-        if path.try_exists()? {
+        if !is_same_file(self.as_path(), &path) && path.try_exists()? {
             return Err(TryRecover::new(
                 already_exist(&path),
                 Status::MoveDirectory(self, path),
             ));
         }
-        if rename(self.as_path(), path.as_path()).is_err() {
-            _write_dir(self.clone(), &path, false)?;
-        }
-        self.path = path;
+        self.move_new_with(path, &Options::default())?;
         Ok(())
     }
 }
-pub(crate) fn _write_dir(dir: DirectoryInfo, to: &Path, is_copy: bool) -> Result<()> {
-    let mut queue = VecDeque::new();
-    let path = dir.path.clone();
-    queue.push_back(dir.clone());
-    while let Some(dir) = queue.pop_front() {
-        queue.append(&mut dir.directories()?.into());
-        let dir_path = replace(dir.as_path(), path.as_path(), to);
-        if !dir_path.is_dir() {
-            create_dir_all(dir_path.as_path())?;
-        }
-        for mut file in dir.files()? {
-            if is_copy {
-                file.copy_to(dir_path.as_path())
-                    .or_else(|op| op.try_recover())?;
+#[cfg(unix)]
+fn symlink_dir(target: &Path, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink_dir(target: &Path, link: &Path) -> Result<()> {
+    std::os::windows::fs::symlink_dir(target, link)
+}
+
+/// The target of the link written at `link` in the copy of `from` to `to`,
+/// see [`LinkRewrite`]
+fn link_target(
+    rewrite: LinkRewrite,
+    target: PathBuf,
+    from: &Path,
+    to: &Path,
+    link: &Path,
+) -> PathBuf {
+    if rewrite == LinkRewrite::Preserve || !target.is_absolute() {
+        return target;
+    }
+    let Ok(rebased) = fix_path(&target).and_then(|target| rebase(target, from, to)) else {
+        return target;
+    };
+    match (rewrite, link.parent()) {
+        (LinkRewrite::MakeRelative, Some(parent)) => relative_path(&rebased, parent),
+        _ => rebased,
+    }
+}
+
+pub(crate) fn _write_dir(
+    dir: &DirectoryInfo,
+    to: &Path,
+    is_copy: bool,
+    options: &Options,
+) -> Result<CopyReport> {
+    dir.validate()?;
+    if is_same_file(dir.as_path(), to) {
+        return if is_copy {
+            Err(same_file(dir.as_path(), to))
+        } else {
+            Ok(CopyReport::default())
+        };
+    }
+    if is_ancestor_of(dir.as_path(), to) {
+        return Err(into_own_subtree(dir.as_path(), to));
+    }
+    let mut report = CopyReport::new();
+    // directories created by a move with the metadata of their source, read
+    // before moving their content changes it
+    let mut new_dirs = Vec::new();
+    // directories created by this copy, the files copied into them need no
+    // conflict checks
+    let mut fresh = HashSet::new();
+    if !to.is_dir() {
+        if !is_copy {
+            new_dirs.push((
+                dir.as_path().to_path_buf(),
+                to.to_path_buf(),
+                dir.metadata()?,
+            ));
+        }
+        options.journaled(Operation::CreateDir, dir.as_path(), Some(to), || {
+            create_dir_all(to)
+        })?;
+        fresh.insert(to.to_path_buf());
+    }
+    report.directories.push(to.to_path_buf());
+    let mut sources = vec![dir.as_path().to_path_buf()];
+    // destination of every directory written so far, renamed ones included
+    let mut dests = HashMap::from([(dir.as_path().to_path_buf(), to.to_path_buf())]);
+    let mut walk_options = options.walk.clone();
+    walk_options.follow_links = options.links == LinkPolicy::Follow;
+    let mut walk = Ordered::new(dir.walk_with(&walk_options), options.read_order);
+    report.read_order = Some(options.read_order.effective());
+    while let Some(entry) = walk.next() {
+        let entry = entry?;
+        let is_dir = entry.file_type().is_dir();
+        // unresolved links to directories, dangling links are treated alike
+        let is_dir_link = entry.file_type().is_symlink()
+            && fs::metadata(entry.path()).map_or(true, |data| data.is_dir());
+        if options.has_filters() {
+            let relative = entry
+                .path()
+                .strip_prefix(dir.as_path())
+                .unwrap_or(entry.path());
+            if !options.accepts(relative, is_dir) {
+                walk.skip_current_dir();
+                report.skipped += 1;
+                continue;
+            }
+        }
+        let dest = match entry.path().parent().and_then(|parent| dests.get(parent)) {
+            Some(parent) => parent.join(entry.path().file_name().unwrap_or_default()),
+            None => rebase(entry.path(), dir.as_path(), to)?,
+        };
+        if is_dir_link {
+            let recreated = options.links == LinkPolicy::Recreate
+                && options
+                    .handle(entry.path(), || {
+                        let target = fs::read_link(entry.path())?;
+                        let target =
+                            link_target(options.link_rewrite, target, dir.as_path(), to, &dest);
+                        options.journaled(Operation::CreateLink, entry.path(), Some(&dest), || {
+                            symlink_dir(&target, &dest)
+                        })
+                    })?
+                    .is_some();
+            if recreated {
+                report.links.push(dest);
+            } else {
+                report.skipped += 1;
+            }
+            continue;
+        }
+        if is_dir {
+            let created = match options.resolve_conflict(entry.path(), dest)? {
+                Some(dest) => options
+                    .handle(entry.path(), || {
+                        let created = create_dest(entry.path(), &dest, is_copy, options);
+                        options.missing(entry.path(), created)
+                    })?
+                    .map(|created| {
+                        created.map(|(metadata, created)| {
+                            if let Some(metadata) = metadata {
+                                new_dirs.push((entry.path().to_path_buf(), dest.clone(), metadata));
+                            }
+                            if created {
+                                fresh.insert(dest.clone());
+                            }
+                            dest
+                        })
+                    }),
+                None => None,
+            };
+            match created {
+                Some(Some(dest)) => {
+                    dests.insert(entry.path().to_path_buf(), dest.clone());
+                    report.directories.push(dest);
+                    sources.push(entry.into_path());
+                }
+                Some(None) => {
+                    walk.skip_current_dir();
+                    report.skipped_missing.push(entry.into_path());
+                }
+                None => {
+                    walk.skip_current_dir();
+                    report.skipped += 1;
+                }
+            }
+            continue;
+        }
+        let mut file = unsafe { FileInfo::open_uncheck(entry.path()) };
+        let (dest, filter) = if is_copy {
+            transform(options, dir.as_path(), entry.path(), to, dest)?
+        } else {
+            (dest, None)
+        };
+        // links to files are copied through the checked path
+        let is_fresh = is_copy
+            && entry.file_type().is_file()
+            && dest.parent().is_some_and(|parent| fresh.contains(parent));
+        options.wait_resumed()?;
+        options.file_started(entry.path());
+        let written = options.handle(entry.path(), || {
+            let written = if is_fresh {
+                entry.metadata().and_then(|metadata| {
+                    file.copy_fresh(dest.clone(), metadata, options, filter.as_ref())
+                })
+            } else if is_copy {
+                file.copy_filtered(&dest, options, filter.as_ref())
             } else {
-                file.move_to(dir_path.as_path())
-                    .or_else(|op| op.try_recover())?;
+                file.move_new_with(&dest, options)
+            };
+            options.missing(entry.path(), written)
+        })?;
+        match written {
+            Some(Some(written)) if written.files.is_empty() => report.append(written),
+            Some(Some(written)) => {
+                report.append(written);
+                options.progress(entry.path(), &report);
             }
+            Some(None) => report.skipped_missing.push(entry.into_path()),
+            None => report.skipped += 1,
+        }
+    }
+    for dir in walk.missing() {
+        options.warn_missing(dir, &no_longer_exists(dir));
+        report.skipped_missing.push(dir.clone());
+    }
+    #[cfg(all(windows, feature = "acl"))]
+    if options.preserve_acl && !report.dry_run {
+        // after their content, the copied lists may deny writing into them
+        for (source, dest) in &dests {
+            super::acl::copy_acl(source, dest, &options.acl, &mut report)?;
         }
     }
     if !is_copy {
-        dir.delete()?;
+        // after their content, which changes their modification time, and
+        // deepest first, as the permissions may deny writing
+        for (source, dest, metadata) in new_dirs.iter().rev() {
+            if !report.dry_run {
+                keep_metadata(source, dest, metadata)?;
+            }
+        }
+        if report.is_complete() {
+            options
+                .journaled(Operation::Delete, dir.as_path(), None, || {
+                    dir.clone().delete()
+                })
+                .map_err(|e| source_not_removed(dir.as_path(), to, e))?;
+        } else {
+            // only remove the directories that were emptied, deepest first
+            sources.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+            for source in sources {
+                if !report.dry_run && fs::remove_dir(&source).is_ok() {
+                    options.journal_done(Operation::Delete, &source, None)?;
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Create `dest` for the directory `source` unless it exists, with whether
+/// it was created. When a move creates it, the metadata of `source` is read
+/// first and returned.
+fn create_dest(
+    source: &Path,
+    dest: &Path,
+    is_copy: bool,
+    options: &Options,
+) -> Result<(Option<fs::Metadata>, bool)> {
+    if dest.is_dir() {
+        return Ok((None, false));
+    }
+    let metadata = if is_copy {
+        None
+    } else {
+        Some(fs::metadata(source)?)
+    };
+    options.journaled(Operation::CreateDir, source, Some(dest), || {
+        create_dir_all(dest)
+    })?;
+    Ok((metadata, true))
+}
+
+/// Accounts every entry of [`DirectoryInfo::stats_with`], those that
+/// cannot be read included. The async visitor shares it.
+pub(crate) struct StatsVisitor {
+    pub(crate) stats: DirStats,
+    sizes: SizeCounter,
+    /// Depth of the directory being visited, the root has depth 0
+    depth: usize,
+    missing_source: MissingSource,
+}
+
+impl StatsVisitor {
+    pub(crate) fn new(options: &WalkOptions) -> Self {
+        StatsVisitor {
+            stats: DirStats::default(),
+            sizes: SizeCounter::new(options),
+            depth: 0,
+            missing_source: options.missing_source,
+        }
+    }
+
+    pub(crate) fn record(&mut self, path: &Path, depth: usize, metadata: Result<fs::Metadata>) {
+        match metadata {
+            Ok(metadata) => self.stats.record(path, depth, &metadata, &mut self.sizes),
+            Err(e) if self.missing_source.skips(path, &e) => {}
+            Err(_) => self.stats.errors += 1,
+        }
+    }
+
+    /// Account the directory `path` entered
+    pub(crate) fn entered(&mut self, path: &Path, metadata: Result<fs::Metadata>) {
+        self.depth += 1;
+        self.record(path, self.depth, metadata);
+    }
+
+    /// Account the file `path` of the directory being visited
+    pub(crate) fn file(&mut self, path: &Path, metadata: Result<fs::Metadata>) {
+        self.record(path, self.depth + 1, metadata);
+    }
+
+    pub(crate) fn left(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+impl Visitor for StatsVisitor {
+    fn enter_dir(&mut self, dir: &DirectoryInfo) -> VisitAction {
+        self.entered(dir.as_path(), dir.metadata());
+        VisitAction::Descend
+    }
+
+    fn visit_file(&mut self, file: &FileInfo) -> Result<()> {
+        self.file(file.as_path(), file.metadata());
+        Ok(())
+    }
+
+    fn leave_dir(&mut self, _: &DirectoryInfo) {
+        self.left();
+    }
+
+    fn visit_other(&mut self, entry: &WalkEntry) -> Result<()> {
+        self.record(entry.path(), entry.depth(), entry.metadata());
+        Ok(())
+    }
+
+    fn visit_error(&mut self, _: Error) -> Result<()> {
+        self.stats.errors += 1;
+        Ok(())
+    }
+
+    fn truncated(&mut self) {
+        self.stats.truncated = true;
+    }
+}
+
+/// Counts the entries of [`DirectoryInfo::count_entries`]
+struct CountVisitor {
+    filter: EntryFilter,
+    count: u64,
+}
+
+impl Visitor for CountVisitor {
+    fn enter_dir(&mut self, _: &DirectoryInfo) -> VisitAction {
+        if self.filter != EntryFilter::Files {
+            self.count += 1;
+        }
+        VisitAction::Descend
+    }
+
+    fn visit_file(&mut self, _: &FileInfo) -> Result<()> {
+        if self.filter != EntryFilter::Directories {
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn visit_other(&mut self, entry: &WalkEntry) -> Result<()> {
+        if self.filter.matches(entry.file_type()) {
+            self.count += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Adds up the files of [`DirectoryInfo::size_checked`]
+struct SizeVisitor {
+    size: DirSize,
+    sizes: SizeCounter,
+    missing_source: MissingSource,
+}
+
+impl Visitor for SizeVisitor {
+    fn visit_file(&mut self, file: &FileInfo) -> Result<()> {
+        let metadata = match file.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) if self.missing_source.skips(file.as_path(), &e) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        self.size.bytes += Size(self.sizes.size(file.as_path(), &metadata)?);
+        Ok(())
+    }
+
+    fn truncated(&mut self) {
+        self.size.truncated = true;
+    }
+}
+
+/// Destination and content filter of the file `source` copied from `dir`
+/// to `to`, as decided by `options.transform`
+pub(crate) fn transform(
+    options: &Options,
+    dir: &Path,
+    source: &Path,
+    to: &Path,
+    dest: PathBuf,
+) -> Result<(PathBuf, Option<FilterHook>)> {
+    let relative = source.strip_prefix(dir).unwrap_or(source);
+    let Some(transform) = options.transform_of(relative) else {
+        return Ok((dest, None));
+    };
+    let dest = match transform.dest {
+        Some(relative) => {
+            let dest = safe_join(to, relative)?;
+            if let Some(parent) = dest.parent().filter(|_| !is_dry_run()) {
+                create_dir_all(parent)?;
+            }
+            dest
+        }
+        None => dest,
+    };
+    Ok((dest, transform.filter))
+}
+
+/// Remove the entries of `dir` accepted by the filters of `options`, then
+/// the directories that were emptied, deepest first
+pub(crate) fn _delete_filtered(dir: &Path, options: &Options) -> Result<()> {
+    let root = unsafe { DirectoryInfo::open_uncheck(dir) };
+    let mut directories = vec![dir.to_path_buf()];
+    let mut walk = root.walk_with(&options.walk);
+    while let Some(entry) = walk.next() {
+        let entry = entry?;
+        let is_dir = entry.file_type().is_dir();
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        if !options.accepts(relative, is_dir) {
+            walk.skip_current_dir();
+            continue;
+        }
+        if is_dir {
+            directories.push(entry.into_path());
+        } else {
+            options.journaled(Operation::Delete, entry.path(), None, || {
+                options.retry(|| _delete(entry.path()))
+            })?;
+        }
+    }
+    directories.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+    for directory in directories {
+        if !is_dry_run() && fs::remove_dir(&directory).is_ok() {
+            options.journal_done(Operation::Delete, &directory, None)?;
+        }
     }
     Ok(())
 }
@@ -148,22 +1432,23 @@ impl Info for DirectoryInfo {
         &self.path
     }
 
+    /// Links to directories are not followed and unreadable entries are
+    /// ignored, see [`DirectoryInfo::size_checked`]
     fn size(&self) -> u64 {
-        let mut queue = VecDeque::new();
-        queue.push_back(self.as_path().to_path_buf());
-        let mut size = 0;
-        while let Some(dir) = queue.pop_front() {
-            if let Ok(readdir) = std::fs::read_dir(dir) {
-                for dir_entry in readdir.flatten() {
-                    let path = dir_entry.path();
-                    if path.is_dir() {
-                        queue.push_back(path)
-                    } else {
-                        size += path.metadata().map_or(0, |f| f.len());
-                    }
-                }
-            }
+        self.walk()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.metadata().map_or(0, |data| data.len()))
+            .sum()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.as_path().is_dir() {
+            Ok(())
+        } else if self.exists() {
+            Err(wrong_kind(self.as_path(), "directory"))
+        } else {
+            Err(no_longer_exists(self.as_path()))
         }
-        size
     }
 }
@@ -0,0 +1,110 @@
+//! Object-safe counterparts of [`Info`] and [`Action`]
+//!
+//! Implemented for every `Info`/`Action` type, so files and directories can
+//! be mixed in a `Vec<Box<dyn DynAction>>`. Paths are taken as `&Path`
+//! instead of `impl AsRef<Path>`. The traits are not re-exported from the
+//! crate root, as their methods share names with `Info` and `Action`.
+//!
+//! # Examples
+//! ```
+//! use std::ffi::OsStr;
+//! use std::io::Result;
+//! use std::path::Path;
+//! use fdir::dynamic::DynAction;
+//! use fdir::*;
+//!
+//! fn process(items: &mut [Box<dyn DynAction>], dest: &Path) -> Result<()> {
+//!     for item in items {
+//!         let name = item.as_path().file_name().unwrap().to_string_lossy();
+//!         let name = format!("done_{}", name);
+//!         item.rename(OsStr::new(&name))?;
+//!         item.copy_to(&dest.join("copies")).map_err(|e| e.error)?;
+//!         item.move_to(&dest.join("moved")).map_err(|e| e.error)?;
+//!     }
+//!     Ok(())
+//! }
+//!
+//! let root = std::env::temp_dir().join("fdir_dyn_action");
+//! let _ = std::fs::remove_dir_all(&root);
+//! let file = FileInfo::create(root.join("in/a.txt")).unwrap();
+//! FileInfo::create(root.join("in/b/c.txt")).unwrap();
+//! let dir = DirectoryInfo::open(root.join("in/b")).unwrap();
+//! std::fs::create_dir_all(root.join("copies")).unwrap();
+//! std::fs::create_dir_all(root.join("moved")).unwrap();
+//! let mut items: Vec<Box<dyn DynAction>> = vec![Box::new(file), Box::new(dir)];
+//! process(&mut items, &root).unwrap();
+//! assert!(root.join("copies/done_a.txt").is_file());
+//! assert!(root.join("copies/done_b/c.txt").is_file());
+//! assert_eq!(items[0].as_path(), root.join("moved/done_a.txt"));
+//! assert_eq!(items[1].as_path(), root.join("moved/done_b"));
+//! for item in items {
+//!     item.delete().unwrap();
+//! }
+//! assert!(!root.join("moved/done_a.txt").exists());
+//! assert!(!root.join("moved/done_b").exists());
+//! # std::fs::remove_dir_all(root).unwrap();
+//! ```
+
+use std::ffi::OsStr;
+use std::fs::Metadata;
+use std::io::Result;
+use std::path::Path;
+
+use super::recover::TryRecoverResult;
+use super::{Action, Info};
+
+pub trait DynInfo {
+    fn as_path(&self) -> &Path;
+    fn metadata(&self) -> Result<Metadata>;
+    fn size(&self) -> u64;
+    fn exists(&self) -> bool;
+    fn validate(&self) -> Result<()>;
+}
+
+pub trait DynAction: DynInfo {
+    fn rename(&mut self, name: &OsStr) -> Result<()>;
+    fn delete(self: Box<Self>) -> Result<()>;
+    fn copy_to(&self, path: &Path) -> TryRecoverResult<'_, ()>;
+    fn copy_new(&self, path: &Path) -> TryRecoverResult<'_, ()>;
+    fn move_to(&mut self, path: &Path) -> TryRecoverResult<'_, ()>;
+    fn move_new(&mut self, path: &Path) -> TryRecoverResult<'_, ()>;
+}
+
+impl<T: Info> DynInfo for T {
+    fn as_path(&self) -> &Path {
+        Info::as_path(self)
+    }
+    fn metadata(&self) -> Result<Metadata> {
+        Info::metadata(self)
+    }
+    fn size(&self) -> u64 {
+        Info::size(self)
+    }
+    fn exists(&self) -> bool {
+        Info::exists(self)
+    }
+    fn validate(&self) -> Result<()> {
+        Info::validate(self)
+    }
+}
+
+impl<T: Action> DynAction for T {
+    fn rename(&mut self, name: &OsStr) -> Result<()> {
+        Action::rename(self, name)
+    }
+    fn delete(self: Box<Self>) -> Result<()> {
+        Action::delete(*self)
+    }
+    fn copy_to(&self, path: &Path) -> TryRecoverResult<'_, ()> {
+        Action::copy_to(self, path)
+    }
+    fn copy_new(&self, path: &Path) -> TryRecoverResult<'_, ()> {
+        Action::copy_new(self, path)
+    }
+    fn move_to(&mut self, path: &Path) -> TryRecoverResult<'_, ()> {
+        Action::move_to(self, path)
+    }
+    fn move_new(&mut self, path: &Path) -> TryRecoverResult<'_, ()> {
+        Action::move_new(self, path)
+    }
+}
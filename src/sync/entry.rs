@@ -0,0 +1,157 @@
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+use crate::error::wrong_kind;
+use crate::fix_path;
+
+use super::recover::TryRecoverResult;
+use super::{Action, DirectoryInfo, FileInfo, Info};
+
+/// A file or a directory, classified once when opened
+///
+/// # Examples
+/// ```
+/// use fdir::{Entry, Info};
+/// let root = std::env::temp_dir().join("fdir_entry");
+/// let _ = std::fs::remove_dir_all(&root);
+/// std::fs::create_dir_all(root.join("dir")).unwrap();
+/// std::fs::write(root.join("file.txt"), "text").unwrap();
+/// assert!(Entry::open(root.join("file.txt")).unwrap().is_file());
+/// assert!(Entry::open(root.join("dir")).unwrap().is_dir());
+/// # #[cfg(unix)] {
+/// // a link is classified by what it leads to
+/// std::os::unix::fs::symlink(root.join("dir"), root.join("link")).unwrap();
+/// let link = Entry::open(root.join("link")).unwrap();
+/// assert!(link.is_dir());
+/// assert_eq!(link.as_path(), root.join("link"));
+/// std::os::unix::fs::symlink(root.join("missing"), root.join("dangling")).unwrap();
+/// let error = Entry::open(root.join("dangling")).unwrap_err();
+/// assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+/// # }
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub enum Entry {
+    File(FileInfo),
+    Dir(DirectoryInfo),
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Entry::File(file) => file.fmt(f),
+            Entry::Dir(dir) => dir.fmt(f),
+        }
+    }
+}
+
+impl From<FileInfo> for Entry {
+    fn from(value: FileInfo) -> Self {
+        Entry::File(value)
+    }
+}
+
+impl From<DirectoryInfo> for Entry {
+    fn from(value: DirectoryInfo) -> Self {
+        Entry::Dir(value)
+    }
+}
+
+impl Entry {
+    /// Open `path` as whatever it is, with a single metadata call.
+    /// Symlinks are followed, anything that is neither a file nor a
+    /// directory is an `InvalidInput` error.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = fix_path(path)?;
+        let metadata = fs::metadata(&path)?;
+        unsafe { Self::classify(path, metadata.file_type()) }
+    }
+
+    /// # Safety
+    /// `path` is not checked or fixed, see [`Action::open_uncheck`]
+    pub(crate) unsafe fn classify(path: PathBuf, file_type: fs::FileType) -> Result<Self> {
+        if file_type.is_dir() {
+            Ok(Entry::Dir(DirectoryInfo::open_uncheck(path)))
+        } else if file_type.is_file() {
+            Ok(Entry::File(FileInfo::from_path_buf(path)))
+        } else {
+            Err(wrong_kind(path, "file or directory"))
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        matches!(self, Entry::File(_))
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self, Entry::Dir(_))
+    }
+
+    pub fn rename<T: AsRef<OsStr>>(&mut self, name: T) -> Result<()> {
+        match self {
+            Entry::File(file) => file.rename(name),
+            Entry::Dir(dir) => dir.rename(name),
+        }
+    }
+
+    pub fn delete(self) -> Result<()> {
+        match self {
+            Entry::File(file) => file.delete(),
+            Entry::Dir(dir) => dir.delete(),
+        }
+    }
+
+    pub fn copy_to<P: AsRef<Path>>(&self, path: P) -> TryRecoverResult<'_, ()> {
+        match self {
+            Entry::File(file) => file.copy_to(path),
+            Entry::Dir(dir) => dir.copy_to(path),
+        }
+    }
+
+    pub fn copy_new<P: AsRef<Path>>(&self, path: P) -> TryRecoverResult<'_, ()> {
+        match self {
+            Entry::File(file) => file.copy_new(path),
+            Entry::Dir(dir) => dir.copy_new(path),
+        }
+    }
+
+    pub fn move_to<P: AsRef<Path>>(&mut self, path: P) -> TryRecoverResult<'_, ()> {
+        match self {
+            Entry::File(file) => file.move_to(path),
+            Entry::Dir(dir) => dir.move_to(path),
+        }
+    }
+
+    pub fn move_new<P: AsRef<Path>>(&mut self, path: P) -> TryRecoverResult<'_, ()> {
+        match self {
+            Entry::File(file) => file.move_new(path),
+            Entry::Dir(dir) => dir.move_new(path),
+        }
+    }
+}
+
+impl Info for Entry {
+    fn as_path(&self) -> &Path {
+        match self {
+            Entry::File(file) => file.as_path(),
+            Entry::Dir(dir) => dir.as_path(),
+        }
+    }
+
+    fn size(&self) -> u64 {
+        match self {
+            Entry::File(file) => file.size(),
+            Entry::Dir(dir) => dir.size(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        match self {
+            Entry::File(file) => file.validate(),
+            Entry::Dir(dir) => dir.validate(),
+        }
+    }
+}
@@ -1,11 +1,29 @@
+use super::batch::{self, OpenMany};
 use super::recover::{Status, TryRecover, TryRecoverResult};
-use super::{Action, Info, _delete_file};
-use crate::error::{already_exist, INVALID_PATH};
-use crate::{fix_path, get_file_path, is_same_root};
+use super::{remove_file_any, Action, Info, _delete_file};
+use crate::convert::{portable_issues, sanitize_file_name, SanitizeOptions};
+use crate::error::{
+    already_exist, check_kind, invalid_path, is_source_not_removed, modified_during_copy,
+    no_longer_exists, path_issues, same_file, source_not_removed, wrong_kind,
+};
+use crate::fs_id::same_filesystem;
+use crate::error_kind::rename_error;
+use crate::hooks::FilterHook;
+use crate::io::{
+    copy_file, copy_file_new, copy_stream, keep_metadata, StreamOptions, StreamReport,
+};
+use crate::journal::Operation;
+use crate::options::Snapshot;
+use crate::{
+    fix_path, fmt_path, get_file_path, is_dry_run, is_same_file, push_file_name, renamed,
+    renamed_extension, set_file_times, CopyReport, KeptExtension, ModifiedPolicy, Options, Size,
+};
+use std::ffi::{OsStr, OsString};
 use std::fmt::{Debug, Display};
-use std::fs::{copy, create_dir_all, rename, File};
-use std::io::{Error, Result};
+use std::fs::{self, create_dir_all, rename, File, Metadata};
+use std::io::{BufWriter, Error, ErrorKind, Read, Result, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[derive(Clone, Debug)]
 pub struct FileInfo {
@@ -17,7 +35,19 @@ unsafe impl Sync for FileInfo {}
 
 impl Display for FileInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}", self.path.display()))
+        fmt_path(&self.path, f)
+    }
+}
+
+impl From<FileInfo> for PathBuf {
+    fn from(value: FileInfo) -> Self {
+        value.path
+    }
+}
+
+impl AsRef<Path> for FileInfo {
+    fn as_ref(&self) -> &Path {
+        &self.path
     }
 }
 
@@ -31,23 +61,712 @@ impl TryFrom<File> for FileInfo {
 }
 
 impl FileInfo {
+    /// The path, without cloning it
+    pub fn into_path_buf(self) -> PathBuf {
+        self.path
+    }
+
+    /// # Safety
+    /// Same as [`Action::open_uncheck`], taking the path without copying it
+    pub(crate) unsafe fn from_path_buf(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The path of a sibling named after this file with `suffix` inserted
+    /// before the final extension, `report-old.csv` for `report.csv`, see
+    /// [`Info::sibling`]
+    pub fn with_suffix(&self, suffix: impl AsRef<OsStr>) -> Result<PathBuf> {
+        self.sibling(suffixed_name(self.as_path(), suffix.as_ref()))
+    }
+
     pub fn create<P: AsRef<Path>>(path: P) -> Result<FileInfo> {
         let path = fix_path(path)?;
         if let Some(parent) = path.parent() {
+            if is_dry_run() {
+                return Ok(Self { path });
+            }
             if !parent.is_dir() {
                 create_dir_all(parent)?;
             }
             File::create(&path)?;
             Ok(Self { path })
         } else {
-            INVALID_PATH()
+            Err(invalid_path(&path, "has no parent directory"))
+        }
+    }
+
+    /// Same as [`FileInfo::create`], but fails if the file already exists
+    /// instead of truncating it
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_create_new");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let file = FileInfo::create_new(root.join("a/b.txt")).unwrap();
+    /// file.write("kept").unwrap();
+    /// let error = FileInfo::create_new(root.join("a/b.txt")).unwrap_err();
+    /// assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+    /// assert_eq!(file.read_to_string().unwrap(), "kept");
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn create_new<P: AsRef<Path>>(path: P) -> Result<FileInfo> {
+        let path = fix_path(path)?;
+        if let Some(parent) = path.parent() {
+            if is_dry_run() {
+                if path.try_exists()? {
+                    return Err(already_exist(&path));
+                }
+                return Ok(Self { path });
+            }
+            if !parent.is_dir() {
+                create_dir_all(parent)?;
+            }
+            File::create_new(&path).map_err(|e| match e.kind() {
+                ErrorKind::AlreadyExists => already_exist(&path),
+                _ => e,
+            })?;
+            Ok(Self { path })
+        } else {
+            Err(invalid_path(&path, "has no parent directory"))
+        }
+    }
+
+    /// Replace the content of the file with `bytes`
+    pub fn write(&self, bytes: impl AsRef<[u8]>) -> Result<()> {
+        self.validate()?;
+        if is_dry_run() {
+            return Ok(());
+        }
+        fs::write(self.as_path(), bytes)
+    }
+
+    /// Add `bytes` at the end of the file
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_file_append");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let file = FileInfo::create(root.join("log.txt")).unwrap();
+    /// file.write("one\n").unwrap();
+    /// file.append(b"two\n").unwrap();
+    /// assert_eq!(file.read().unwrap(), b"one\ntwo\n");
+    /// file.write("three").unwrap();
+    /// assert_eq!(file.read_to_string().unwrap(), "three");
+    /// // the file must exist
+    /// let gone = root.join("gone.txt");
+    /// let gone = unsafe { FileInfo::open_uncheck(&gone) };
+    /// assert!(gone.append("x").is_err() && gone.write("x").is_err());
+    /// assert!(!gone.as_path().exists());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn append(&self, bytes: impl AsRef<[u8]>) -> Result<()> {
+        self.validate()?;
+        fs::OpenOptions::new()
+            .append(true)
+            .open(self.as_path())?
+            .write_all(bytes.as_ref())
+    }
+
+    /// Replace the content of the file with everything `reader` yields,
+    /// see [`copy_stream`]
+    pub fn write_from(&self, reader: impl Read, options: &StreamOptions) -> Result<StreamReport> {
+        self.validate()?;
+        copy_stream(reader, File::create(self.as_path())?, options)
+    }
+
+    /// The whole content of the file, see [`FileInfo::append`]
+    pub fn read(&self) -> Result<Vec<u8>> {
+        fs::read(self.as_path())
+    }
+
+    /// The whole content of the file, which must be UTF-8
+    pub fn read_to_string(&self) -> Result<String> {
+        fs::read_to_string(self.as_path())
+    }
+
+    /// Same as [`FileInfo::create`], but the file name is first passed through
+    /// [`sanitize_file_name`]
+    pub fn create_sanitized<P: AsRef<Path>>(
+        path: P,
+        options: &SanitizeOptions,
+    ) -> Result<FileInfo> {
+        let path = fix_path(path)?;
+        let name = match path.file_name() {
+            Some(name) => sanitize_file_name(&name.to_string_lossy(), options),
+            _ => return Err(invalid_path(&path, "has no file name")),
+        };
+        FileInfo::create(path.with_file_name(name))
+    }
+
+    /// Same as [`FileInfo::create`], but every component of `path` must be a
+    /// valid name on Windows, even when running on another platform
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let path = std::env::temp_dir().join("aux").join("what?.txt");
+    /// let error = FileInfo::create_portable(&path).unwrap_err();
+    /// let issues = &InvalidPath::find(&error).unwrap().issues;
+    /// assert_eq!(issues[0], PathIssue::ReservedName { component: "aux".into() });
+    /// assert!(matches!(issues[1], PathIssue::InvalidComponentChar { char: '?', .. }));
+    /// ```
+    pub fn create_portable<P: AsRef<Path>>(path: P) -> Result<FileInfo> {
+        let issues = portable_issues(path.as_ref());
+        if !issues.is_empty() {
+            return Err(path_issues(path, issues));
+        }
+        FileInfo::create(path)
+    }
+
+    /// Open and validate every path, a bad path does not fail the batch.
+    /// Returns the opened file entries and the failed paths with their
+    /// error, both in input order.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::{FileInfo, Info};
+    /// let root = std::env::temp_dir().join("fdir_file_open_many");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// std::fs::create_dir_all(root.join("dir")).unwrap();
+    /// for name in ["a", "c", "e", "g"] {
+    ///     std::fs::write(root.join(name), name).unwrap();
+    /// }
+    /// // "b" and "f" are missing, "dir" is not a file
+    /// let paths: Vec<_> = ["a", "b", "c", "dir", "e", "f", "g"]
+    ///     .iter()
+    ///     .map(|name| root.join(name))
+    ///     .collect();
+    /// let expected_files = [root.join("a"), root.join("c"), root.join("e"), root.join("g")];
+    /// let expected_failed = [root.join("b"), root.join("dir"), root.join("f")];
+    /// for (files, failed) in [
+    ///     FileInfo::open_many(&paths),
+    ///     FileInfo::open_many_parallel(&paths, 3),
+    /// ] {
+    ///     let files: Vec<_> = files.iter().map(|file| file.as_path()).collect();
+    ///     let failed: Vec<_> = failed.into_iter().map(|(path, _)| path).collect();
+    ///     assert_eq!(files, expected_files);
+    ///     assert_eq!(failed, expected_failed);
+    /// }
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn open_many<I>(paths: I) -> OpenMany<FileInfo>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        batch::open_many(paths)
+    }
+
+    /// Same as [`FileInfo::open_many`], validating on up to `threads` threads.
+    /// Worth it where every `stat` is slow, such as network filesystems.
+    pub fn open_many_parallel<I>(paths: I, threads: usize) -> OpenMany<FileInfo>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        batch::open_many_parallel(paths, threads)
+    }
+
+    /// Copy the file into every directory of `dests`, reading the source
+    /// only once. Each destination gets its own result, a failing one does
+    /// not abort the others. Only an unreadable source is an error for all.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::{Action, FileInfo};
+    /// use std::io::ErrorKind;
+    /// let root = std::env::temp_dir().join("fdir_copy_to_many");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let file = FileInfo::create(root.join("src/report.txt")).unwrap();
+    /// file.write("report").unwrap();
+    /// FileInfo::create(root.join("taken/report.txt")).unwrap();
+    /// std::fs::create_dir_all(root.join("a")).unwrap();
+    /// std::fs::create_dir_all(root.join("b")).unwrap();
+    /// let dests = [root.join("a"), root.join("taken"), root.join("missing"), root.join("b")];
+    /// let results = file.copy_to_many(&dests).unwrap();
+    /// assert_eq!(results.len(), 4);
+    /// assert_eq!(results[0].as_ref().unwrap().read().unwrap(), b"report");
+    /// assert_eq!(results[1].as_ref().unwrap_err().kind(), ErrorKind::AlreadyExists);
+    /// assert!(results[2].is_err());
+    /// assert_eq!(results[3].as_ref().unwrap().read().unwrap(), b"report");
+    /// // the existing file was left alone
+    /// assert!(std::fs::read(root.join("taken/report.txt")).unwrap().is_empty());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn copy_to_many<P: AsRef<Path>>(&self, dests: &[P]) -> Result<Vec<Result<FileInfo>>> {
+        self.validate()?;
+        let mut source = File::open(self.as_path())?;
+        let permissions = source.metadata()?.permissions();
+        let mut targets: Vec<Result<(PathBuf, File)>> = dests
+            .iter()
+            .map(|dest| {
+                let path = fix_path(push_file_name(self.as_path(), dest)?)?;
+                let file = File::create_new(&path).map_err(|e| match e.kind() {
+                    ErrorKind::AlreadyExists => already_exist(&path),
+                    _ => e,
+                })?;
+                Ok((path, file))
+            })
+            .collect();
+        let mut buf = vec![0; 64 * 1024];
+        let read = loop {
+            let len = match source.read(&mut buf) {
+                Ok(0) => break Ok(()),
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e),
+            };
+            for target in targets.iter_mut() {
+                if let Ok((path, file)) = target {
+                    if let Err(e) = file.write_all(&buf[..len]) {
+                        let _ = fs::remove_file(&path);
+                        *target = Err(e);
+                    }
+                }
+            }
+        };
+        if let Err(e) = read {
+            for (path, _) in targets.iter().flatten() {
+                let _ = fs::remove_file(path);
+            }
+            return Err(e);
+        }
+        Ok(targets
+            .into_iter()
+            .map(|target| {
+                let (path, file) = target?;
+                file.set_permissions(permissions.clone())?;
+                Ok(FileInfo { path })
+            })
+            .collect())
+    }
+
+    /// Same as [`Action::copy_new`], configured by `options`. Only
+    /// `conflict`, `update`, `staging`, `preserve_times`,
+    /// `modified_during_copy`, `preserve_ads`, `preserve_acl` and `retries`
+    /// apply to a single file.
+    pub fn copy_new_with<P: AsRef<Path>>(&self, path: P, options: &Options) -> Result<CopyReport> {
+        let path = options.sanitized(fix_path(path)?);
+        self.copy_filtered(path, options, None)
+    }
+
+    /// [`FileInfo::copy_new_with`] writing the content through `filter`
+    pub(crate) fn copy_filtered<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &Options,
+        filter: Option<&FilterHook>,
+    ) -> Result<CopyReport> {
+        self.validate()?;
+        let path = fix_path(path)?;
+        if is_same_file(self.as_path(), &path) {
+            return Err(same_file(self.as_path(), &path));
+        }
+        let mut report = CopyReport::new();
+        if options.up_to_date(self.as_path(), &path)? {
+            report.up_to_date += 1;
+            return Ok(report);
+        }
+        let Some(path) = options.resolve_conflict(self.as_path(), path)? else {
+            report.skipped += 1;
+            return Ok(report);
+        };
+        // the conflict was resolved by replacing the destination
+        let overwrite = path.try_exists()?;
+        self.copy_resolved(path, self.metadata()?, overwrite, options, filter, report)
+    }
+
+    /// [`FileInfo::copy_filtered`] to `dest` in a directory the copy of a
+    /// tree just created, where it cannot exist: `dest` was built from
+    /// normalised paths and `metadata` of the source was read by the walk,
+    /// so none of them is checked again. When `dest` exists after all, e.g.
+    /// for names differing by case on a case-insensitive filesystem, the
+    /// copy starts over with the checks and the conflict policy.
+    pub(crate) fn copy_fresh(
+        &self,
+        dest: PathBuf,
+        metadata: Metadata,
+        options: &Options,
+        filter: Option<&FilterHook>,
+    ) -> Result<CopyReport> {
+        let report = CopyReport::new();
+        match self.copy_resolved(dest.clone(), metadata, false, options, filter, report) {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                self.copy_filtered(dest, options, filter)
+            }
+            result => result,
+        }
+    }
+
+    /// Copy to `path` once its conflict is resolved, `metadata` is that of
+    /// the source and `overwrite` whether `path` exists
+    fn copy_resolved(
+        &self,
+        path: PathBuf,
+        metadata: Metadata,
+        overwrite: bool,
+        options: &Options,
+        filter: Option<&FilterHook>,
+        report: CopyReport,
+    ) -> Result<CopyReport> {
+        let dest = path.clone();
+        options.journaled(Operation::Copy, self.as_path(), Some(&dest), || {
+            self.write_resolved(path, metadata, overwrite, options, filter, report)
+        })
+    }
+
+    /// [`FileInfo::copy_resolved`] once journaled
+    fn write_resolved(
+        &self,
+        path: PathBuf,
+        mut metadata: Metadata,
+        mut overwrite: bool,
+        options: &Options,
+        filter: Option<&FilterHook>,
+        mut report: CopyReport,
+    ) -> Result<CopyReport> {
+        let mut attempt = 0;
+        loop {
+            let times = options.times_of(&metadata);
+            report.bytes = Size(options.retry(|| {
+                copy_staged(
+                    self.as_path(),
+                    &path,
+                    options,
+                    filter,
+                    overwrite,
+                    |written| match times {
+                        Some(times) => set_file_times(written, times),
+                        None => Ok(()),
+                    },
+                )
+            })?);
+            // a source removed meanwhile counts as modified
+            let after = self.metadata().ok().map(|data| Snapshot::of(&data));
+            if after == Some(Snapshot::of(&metadata)) {
+                break;
+            }
+            attempt += 1;
+            match options.modified_during_copy {
+                policy if policy.retries(attempt) => {
+                    metadata = self.metadata()?;
+                    // written by the earlier attempt
+                    overwrite = path.try_exists()?;
+                    continue;
+                }
+                ModifiedPolicy::Fail => {
+                    let _ = remove_file_any(&path);
+                    return Err(modified_during_copy(self.as_path()));
+                }
+                _ => {
+                    report.modified_during_copy.push(path.clone());
+                    break;
+                }
+            }
+        }
+        #[cfg(all(windows, feature = "ads"))]
+        if options.preserve_ads && !super::ads::copy_streams(self.as_path(), &path)? {
+            report.streams_skipped.push(path.clone());
+        }
+        #[cfg(all(windows, feature = "acl"))]
+        if options.preserve_acl {
+            super::acl::copy_acl(self.as_path(), &path, &options.acl, &mut report)?;
+        }
+        report.files.push(path);
+        Ok(report)
+    }
+
+    /// Same as [`Action::move_new`], configured by `options`, see
+    /// [`FileInfo::copy_new_with`]. `self` is left in place when skipped.
+    /// Across filesystems the file is copied with its metadata then
+    /// deleted, `CopyReport::renamed` is false.
+    pub fn move_new_with<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &Options,
+    ) -> Result<CopyReport> {
+        self.validate()?;
+        let path = fix_path(path)?;
+        let mut report = CopyReport::new();
+        if is_same_file(self.as_path(), &path) {
+            return Ok(report);
+        }
+        if options.up_to_date(self.as_path(), &path)? {
+            report.up_to_date += 1;
+            return Ok(report);
+        }
+        let Some(path) = options.resolve_conflict(self.as_path(), path)? else {
+            report.skipped += 1;
+            return Ok(report);
+        };
+        let (source, dest) = (self.to_path_buf(), path.clone());
+        options.journaled(Operation::Move, &source, Some(&dest), || {
+            self.move_resolved(path, options, report)
+        })
+    }
+
+    /// Move to `path` once its conflict is resolved
+    fn move_resolved(
+        &mut self,
+        path: PathBuf,
+        options: &Options,
+        mut report: CopyReport,
+    ) -> Result<CopyReport> {
+        match path.parent() {
+            Some(parent) => create_dir_all(parent)?,
+            None => return Err(invalid_path(&path, "has no parent directory")),
+        }
+        let metadata = self.metadata()?;
+        let times = options.times_of(&metadata);
+        let overwrite = path.try_exists()?;
+        let result = options.retry(|| {
+            if path.try_exists()? {
+                remove_file_any(&path)?;
+            }
+            if same_filesystem(self.as_path(), &path) && rename(self.as_path(), &path).is_ok() {
+                return Ok(true);
+            }
+            copy_staged(self.as_path(), &path, options, None, overwrite, |written| {
+                keep_metadata(self.as_path(), written, &metadata)
+            })?;
+            _delete_file(self).map_err(|e| source_not_removed(self.as_path(), &path, e))?;
+            Ok(false)
+        });
+        report.renamed = match result {
+            Err(e) if is_source_not_removed(&e, self.as_path()) => {
+                self.path = path;
+                return Err(e);
+            }
+            result => result?,
+        };
+        if let Some(times) = times {
+            set_file_times(&path, times)?;
+        }
+        self.path = path.clone();
+        report.bytes = Size(metadata.len());
+        report.files.push(path);
+        Ok(report)
+    }
+
+    /// Rename the file to `stem` followed by its extension, the final one
+    /// or all of them as `kept` says. A name without an extension, such as
+    /// a dotfile, becomes `stem`. An existing file of the new name is dealt
+    /// with by `options.conflict` or `options.on_conflict`, returns `false`
+    /// when the rename is skipped.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let dir = std::env::temp_dir().join("fdir_rename_keeping_extension");
+    /// let _ = std::fs::remove_dir_all(&dir);
+    /// let options = Options::new();
+    /// for (name, kept, renamed) in [
+    ///     ("archive.tar.gz", KeptExtension::Last, "new.gz"),
+    ///     ("archive.tar.gz", KeptExtension::Full, "new.tar.gz"),
+    ///     (".hidden", KeptExtension::Full, "new"),
+    ///     ("noext", KeptExtension::Last, "new"),
+    ///     ("weird.name.with.dots", KeptExtension::Last, "new.dots"),
+    ///     ("weird.name.with.dots", KeptExtension::Full, "new.name.with.dots"),
+    /// ] {
+    ///     let mut file = FileInfo::create(dir.join(name)).unwrap();
+    ///     assert!(file.rename_keeping_extension("new", kept, &options).unwrap());
+    ///     assert_eq!(file.as_path(), dir.join(renamed));
+    ///     assert!(file.exists() && !dir.join(name).exists());
+    ///     file.delete().unwrap();
+    /// }
+    ///
+    /// // the conflict policy applies to an existing name
+    /// FileInfo::create(dir.join("report.txt")).unwrap();
+    /// let mut draft = FileInfo::create(dir.join("draft.txt")).unwrap();
+    /// let error = draft.rename_keeping_extension("report", KeptExtension::Last, &options);
+    /// assert_eq!(error.unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+    /// let skip = Options::new().conflict(ConflictPolicy::Skip);
+    /// assert!(!draft.rename_keeping_extension("report", KeptExtension::Last, &skip).unwrap());
+    /// let numbered = Options::new().conflict(ConflictPolicy::Rename);
+    /// draft.rename_keeping_extension("report", KeptExtension::Last, &numbered).unwrap();
+    /// assert_eq!(draft.as_path(), dir.join("report (1).txt"));
+    /// # std::fs::remove_dir_all(dir).unwrap();
+    /// ```
+    pub fn rename_keeping_extension(
+        &mut self,
+        stem: impl AsRef<OsStr>,
+        kept: KeptExtension,
+        options: &Options,
+    ) -> Result<bool> {
+        let name = kept.name(stem.as_ref(), self.as_path());
+        self.rename_resolved(renamed(self.as_path(), &name)?, options)
+    }
+
+    /// Rename the file on disk to its name with the final extension
+    /// replaced by `extension`, or removed when it is empty, as
+    /// [`Path::with_extension`] builds it. Conflicts are handled as by
+    /// [`FileInfo::rename_keeping_extension`].
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let dir = std::env::temp_dir().join("fdir_change_extension");
+    /// let _ = std::fs::remove_dir_all(&dir);
+    /// let options = Options::new();
+    /// let mut file = FileInfo::create(dir.join("archive.tar.gz")).unwrap();
+    /// file.change_extension("zst", &options).unwrap();
+    /// assert_eq!(file.as_path(), dir.join("archive.tar.zst"));
+    /// file.change_extension("", &options).unwrap();
+    /// assert_eq!(file.as_path(), dir.join("archive.tar"));
+    /// assert!(file.exists());
+    /// # std::fs::remove_dir_all(dir).unwrap();
+    /// ```
+    pub fn change_extension(
+        &mut self,
+        extension: impl AsRef<OsStr>,
+        options: &Options,
+    ) -> Result<bool> {
+        let dest = renamed_extension(self.as_path(), extension.as_ref())?;
+        self.rename_resolved(dest, options)
+    }
+
+    /// Rename to `dest` in the same directory once its conflict is resolved
+    fn rename_resolved(&mut self, dest: PathBuf, options: &Options) -> Result<bool> {
+        self.validate()?;
+        // a change of case only on a case-insensitive filesystem
+        let dest = if is_same_file(self.as_path(), &dest) {
+            dest
+        } else {
+            match options.resolve_conflict(self.as_path(), dest)? {
+                Some(dest) => dest,
+                None => return Ok(false),
+            }
+        };
+        let source = self.path.clone();
+        options.journaled(Operation::Rename, &source, Some(&dest), || {
+            rename(&source, &dest).map_err(|e| rename_error(e, &dest))
+        })?;
+        if !is_dry_run() {
+            self.path = dest;
+        }
+        Ok(true)
+    }
+
+    /// Read at most `len` bytes from the start of the file
+    pub(crate) fn read_prefix(&self, len: u64) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(len as usize);
+        File::open(self.as_path())?
+            .take(len)
+            .read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Copy `source` to `dest` as `options.staging` asks, `prepare` is given the
+/// written file before it takes its final name. A `dest` created during the
+/// copy is only replaced when `overwrite` is set.
+fn copy_staged(
+    source: &Path,
+    dest: &Path,
+    options: &Options,
+    filter: Option<&FilterHook>,
+    overwrite: bool,
+    prepare: impl Fn(&Path) -> Result<()>,
+) -> Result<u64> {
+    let Some(temp) = options.staging.temp_path(dest)? else {
+        // a destination that appeared meanwhile is not replaced
+        if !overwrite {
+            let copied = copy_file_new(source, dest, &options.stream, filter)?;
+            if let Err(e) = prepare(dest) {
+                let _ = remove_file_any(dest);
+                return Err(e);
+            }
+            return Ok(copied.bytes);
         }
+        if dest.try_exists()? {
+            remove_file_any(dest)?;
+        }
+        let copied = copy_file(source, dest, &options.stream, filter)?;
+        prepare(dest)?;
+        return Ok(copied.bytes);
+    };
+    let result = copy_file(source, &temp, &options.stream, filter).and_then(|copied| {
+        prepare(&temp)?;
+        commit_staged(&temp, dest, overwrite)?;
+        Ok(copied.bytes)
+    });
+    if result.is_err() {
+        let _ = remove_file_any(&temp);
     }
+    result
+}
 
+/// Give the complete file `temp` its final name `dest`, an existing `dest`
+/// is only replaced when `overwrite` is set
+fn commit_staged(temp: &Path, dest: &Path, overwrite: bool) -> Result<()> {
+    if overwrite {
+        if rename(temp, dest).is_ok() {
+            return Ok(());
+        }
+        // e.g. a read-only destination on Windows
+        if dest.try_exists()? {
+            remove_file_any(dest)?;
+        }
+        return rename(temp, dest);
+    }
+    // unlike a rename, a hard link fails when `dest` appeared meanwhile
+    match fs::hard_link(temp, dest) {
+        // the copy may have been given read-only permissions
+        Ok(()) => remove_file_any(temp),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Err(already_exist(dest)),
+        // filesystems without hard links
+        Err(_) => {
+            if dest.try_exists()? {
+                return Err(already_exist(dest));
+            }
+            rename(temp, dest)
+        }
+    }
+}
+
+/// Write `path` through a temporary file in the same directory that is
+/// renamed over the target once `f` succeeded, so readers never observe a
+/// half-written file. The permissions of an existing target are kept.
+pub(crate) fn write_atomic<F>(path: &Path, f: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+{
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let file_name = match path.file_name() {
+        Some(file_name) => file_name,
+        _ => return Err(invalid_path(path, "has no file name")),
+    };
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = path.with_file_name(temp_name);
+    let result = (|| {
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        f(&mut writer)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
 }
 
 impl Action for FileInfo {
     fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = fix_path(path)?;
+        // opening a directory as a file works on Unix
+        check_kind(&path, fs::metadata(&path), false)?;
         FileInfo::try_from(File::open(path)?)
     }
 
@@ -57,49 +776,41 @@ impl Action for FileInfo {
         }
     }
 
+    /// `name` is taken literally, see
+    /// [`FileInfo::rename_keeping_extension`] to keep the extension
     fn rename<T: AsRef<std::ffi::OsStr>>(&mut self, name: T) -> Result<()> {
-        let mut new_path = self.path.clone();
-        new_path.set_file_name(name.as_ref());
-        if let Some(ext) = self.as_path().extension() {
-            new_path.set_extension(ext);
+        let new_path = renamed(self.as_path(), name.as_ref())?;
+        if is_dry_run() {
+            return self.validate();
         }
-        rename(self.as_path(), &new_path)?;
+        rename(self.as_path(), &new_path).map_err(|e| rename_error(e, &new_path))?;
         self.path = new_path;
         Ok(())
     }
 
-    fn copy_new<P: AsRef<Path>>(&self, path: P) -> TryRecoverResult<()> {
+    fn copy_new<P: AsRef<Path>>(&self, path: P) -> TryRecoverResult<'_, ()> {
+        self.validate()?;
         let path = fix_path(path)?;
-        if path.try_exists()? {
+        if !is_same_file(self.as_path(), &path) && path.try_exists()? {
             return Err(TryRecover::new(
                 already_exist(&path),
                 Status::CopyFile(self, path),
             ));
         }
-        copy(self.as_path(), &path)?;
+        self.copy_new_with(path, &Options::default())?;
         Ok(())
     }
 
-    fn move_new<P: AsRef<Path>>(&mut self, path: P) -> TryRecoverResult<()> {
+    fn move_new<P: AsRef<Path>>(&mut self, path: P) -> TryRecoverResult<'_, ()> {
+        self.validate()?;
         let path = fix_path(path)?;
-        if path.try_exists()? {
+        if !is_same_file(self.as_path(), &path) && path.try_exists()? {
             return Err(TryRecover::new(
                 already_exist(&path),
                 Status::MoveFile(self, path),
             ));
         }
-        if let Some(parent) = path.parent() {
-            create_dir_all(parent)?;
-        } else {
-            INVALID_PATH()?;
-        }
-        if is_same_root(self.as_path(), &path) {
-            rename(self.as_path(), &path)?;
-        } else {
-            copy(self.as_path(), &path)?;
-            _delete_file(self)?;
-        }
-        self.path = path;
+        self.move_new_with(path, &Options::default())?;
         Ok(())
     }
 }
@@ -112,4 +823,25 @@ impl Info for FileInfo {
     fn size(&self) -> u64 {
         self.metadata().map_or(0, |f| f.len())
     }
+
+    fn validate(&self) -> Result<()> {
+        if self.as_path().is_file() {
+            Ok(())
+        } else if self.exists() {
+            Err(wrong_kind(self.as_path(), "file"))
+        } else {
+            Err(no_longer_exists(self.as_path()))
+        }
+    }
+}
+
+/// The name of `path` with `suffix` inserted before the final extension
+pub(crate) fn suffixed_name(path: &Path, suffix: &OsStr) -> OsString {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    name
 }
@@ -0,0 +1,510 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::error::{invalid_digest, modified_during_copy};
+use crate::StagingMode;
+
+use super::{remove_file_any, Action, DirectoryInfo, FileInfo, Info};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    /// Guess the algorithm from the length of a hex digest
+    fn from_hex_len(len: usize) -> Option<Self> {
+        match len {
+            64 => Some(Algorithm::Sha256),
+            128 => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+}
+
+/// Result of [`DirectoryInfo::verify_manifest`], paths are relative to the directory
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub ok: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    /// Listed in the manifest but not found in the directory
+    pub missing: Vec<PathBuf>,
+    /// Found in the directory but not listed in the manifest
+    pub extra: Vec<PathBuf>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+impl FileInfo {
+    /// Hash the file content as a lowercase hex string, the file is read in chunks
+    pub fn hash(&self, algorithm: Algorithm) -> Result<String> {
+        let file = File::open(self.as_path())?;
+        match algorithm {
+            Algorithm::Sha256 => digest::<Sha256>(file),
+            Algorithm::Sha512 => digest::<Sha512>(file),
+        }
+    }
+}
+
+/// Incremental hash for content that is only seen in chunks
+pub(crate) enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    pub(crate) fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+            Hasher::Sha512(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /// Lowercase hex digest, the same as [`FileInfo::hash`]
+    pub(crate) fn finish(self) -> String {
+        match self {
+            Hasher::Sha256(hasher) => to_hex(&hasher.finalize()),
+            Hasher::Sha512(hasher) => to_hex(&hasher.finalize()),
+        }
+    }
+}
+
+fn digest<D: Digest>(mut reader: impl Read) -> Result<String> {
+    let mut hasher = D::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let len = reader.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        hex.push(HEX[(byte >> 4) as usize] as char);
+        hex.push(HEX[(byte & 0xf) as usize] as char);
+    }
+    hex
+}
+
+/// Relative path with `/` separators, so manifests are portable between platforms
+fn portable_relative(path: &Path, base: &Path) -> String {
+    let relative = path.strip_prefix(base).unwrap_or(path);
+    let components: Vec<_> = relative
+        .iter()
+        .map(|os| os.to_string_lossy().into_owned())
+        .collect();
+    components.join("/")
+}
+
+impl DirectoryInfo {
+    /// Write a `sha256sum` compatible manifest (`<hex>  <relative path>` per
+    /// line) of every file below the directory to `dest`
+    ///
+    /// If `dest` is inside the directory it is not listed in itself.
+    pub fn write_manifest(&self, dest: impl AsRef<Path>, algorithm: Algorithm) -> Result<FileInfo> {
+        self.write_manifest_parallel(dest, algorithm, 1)
+    }
+
+    /// Same as [`DirectoryInfo::write_manifest`], the files are hashed on
+    /// `threads` threads, largest first. The manifest is the same whatever
+    /// the number of threads.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_manifest_parallel_doc");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for i in 0..20 {
+    ///     let file = FileInfo::create(root.join(format!("data/{}/file.bin", i % 3))).unwrap();
+    ///     std::fs::write(file.as_path(), vec![i as u8; i * 1000]).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(root.join("data")).unwrap();
+    /// dir.write_manifest(root.join("serial.sha256"), Algorithm::Sha256).unwrap();
+    /// dir.write_manifest_parallel(root.join("parallel.sha256"), Algorithm::Sha256, 4).unwrap();
+    /// let serial = std::fs::read(root.join("serial.sha256")).unwrap();
+    /// assert_eq!(serial, std::fs::read(root.join("parallel.sha256")).unwrap());
+    /// assert!(dir.verify_manifest_parallel(root.join("parallel.sha256"), 4).unwrap().is_ok());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn write_manifest_parallel(
+        &self,
+        dest: impl AsRef<Path>,
+        algorithm: Algorithm,
+        threads: usize,
+    ) -> Result<FileInfo> {
+        let dest = crate::fix_path(dest)?;
+        let jobs: Vec<_> = self
+            .manifest_files(&dest)?
+            .into_iter()
+            .map(|file| (file, algorithm))
+            .collect();
+        let mut lines = Vec::new();
+        for ((file, _), hash) in jobs.iter().zip(hash_files(&jobs, threads)) {
+            lines.push((portable_relative(file, self.as_path()), hash?));
+        }
+        lines.sort();
+        let manifest = FileInfo::create(&dest)?;
+        let mut writer = std::io::BufWriter::new(File::create(manifest.as_path())?);
+        for (path, hash) in lines {
+            writeln!(writer, "{}  {}", hash, path)?;
+        }
+        writer.flush()?;
+        Ok(manifest)
+    }
+
+    /// Re-hash every file listed in `manifest` and compare against the directory
+    ///
+    /// The algorithm of every line is inferred from the digest length.
+    pub fn verify_manifest(&self, manifest: impl AsRef<Path>) -> Result<VerifyReport> {
+        self.verify_manifest_parallel(manifest, 1)
+    }
+
+    /// Same as [`DirectoryInfo::verify_manifest`], the files are hashed on
+    /// `threads` threads, largest first
+    pub fn verify_manifest_parallel(
+        &self,
+        manifest: impl AsRef<Path>,
+        threads: usize,
+    ) -> Result<VerifyReport> {
+        let manifest = crate::fix_path(manifest)?;
+        let mut expected = BTreeMap::new();
+        for line in BufReader::new(File::open(&manifest)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (hash, path) = parse_manifest_line(&line)?;
+            expected.insert(path.to_string(), hash.to_lowercase());
+        }
+        let mut report = VerifyReport::default();
+        let mut listed = Vec::new();
+        let mut jobs = Vec::new();
+        for file in self.manifest_files(&manifest)? {
+            let relative = portable_relative(&file, self.as_path());
+            let hash = match expected.remove(&relative) {
+                Some(hash) => hash,
+                None => {
+                    report.extra.push(PathBuf::from(relative));
+                    continue;
+                }
+            };
+            let algorithm = Algorithm::from_hex_len(hash.len())
+                .ok_or_else(|| invalid_line(&format!("{}  {}", hash, relative)))?;
+            listed.push((relative, hash));
+            jobs.push((file, algorithm));
+        }
+        for ((relative, hash), actual) in listed.into_iter().zip(hash_files(&jobs, threads)) {
+            if actual? == hash {
+                report.ok.push(PathBuf::from(relative));
+            } else {
+                report.modified.push(PathBuf::from(relative));
+            }
+        }
+        report.missing = expected.into_keys().map(PathBuf::from).collect();
+        Ok(report)
+    }
+
+    /// Group the files below the directory that have the same content.
+    /// Only files of the same length are hashed, each group is sorted by
+    /// path and the groups by their first path.
+    pub fn find_duplicates(&self, algorithm: Algorithm) -> Result<Vec<Vec<PathBuf>>> {
+        self.find_duplicates_parallel(algorithm, 1)
+    }
+
+    /// Same as [`DirectoryInfo::find_duplicates`], the files are hashed on
+    /// `threads` threads, largest first
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_find_duplicates");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for (name, content) in [
+    ///     ("a.txt", "same"),
+    ///     ("b/c.txt", "same"),
+    ///     ("b/d.txt", "sane"),
+    ///     ("e.txt", "other"),
+    ///     ("f/g.txt", "other"),
+    ///     ("h.txt", "unique"),
+    /// ] {
+    ///     let file = FileInfo::create(root.join(name)).unwrap();
+    ///     std::fs::write(file.as_path(), content).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let groups = dir.find_duplicates_parallel(Algorithm::Sha256, 4).unwrap();
+    /// assert_eq!(groups, [
+    ///     vec![root.join("a.txt"), root.join("b/c.txt")],
+    ///     vec![root.join("e.txt"), root.join("f/g.txt")],
+    /// ]);
+    /// assert_eq!(dir.find_duplicates(Algorithm::Sha256).unwrap(), groups);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn find_duplicates_parallel(
+        &self,
+        algorithm: Algorithm,
+        threads: usize,
+    ) -> Result<Vec<Vec<PathBuf>>> {
+        let mut by_len: BTreeMap<u64, Vec<PathBuf>> = BTreeMap::new();
+        for entry in self.walk() {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                let len = entry.metadata()?.len();
+                by_len.entry(len).or_default().push(entry.into_path());
+            }
+        }
+        let jobs: Vec<_> = by_len
+            .into_values()
+            .filter(|files| files.len() > 1)
+            .flatten()
+            .map(|file| (file, algorithm))
+            .collect();
+        let mut by_hash: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        let hashes = hash_files(&jobs, threads);
+        for ((file, _), hash) in jobs.into_iter().zip(hashes) {
+            by_hash.entry(hash?).or_default().push(file);
+        }
+        let mut groups: Vec<_> = by_hash
+            .into_values()
+            .filter(|files| files.len() > 1)
+            .map(|mut files| {
+                files.sort();
+                files
+            })
+            .collect();
+        groups.sort();
+        Ok(groups)
+    }
+
+    fn manifest_files(&self, manifest: &Path) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in self.walk() {
+            let entry = entry?;
+            if entry.file_type().is_file() && entry.path() != manifest {
+                files.push(entry.into_path());
+            }
+        }
+        Ok(files)
+    }
+}
+
+impl DirectoryInfo {
+    /// Store `source` in the directory as a content addressed object named
+    /// by its digest, fanned out over one level of two hex digit
+    /// directories as git does (`ab/cdef…`). Returns the digest and the
+    /// object. See [`DirectoryInfo::store_by_hash_with`].
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_store_by_hash");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// std::fs::create_dir_all(root.join("objects")).unwrap();
+    /// let objects = DirectoryInfo::open(root.join("objects")).unwrap();
+    /// let source = FileInfo::create(root.join("hello.txt")).unwrap();
+    /// std::fs::write(source.as_path(), "hello").unwrap();
+    ///
+    /// // stores of the same content from several threads share one object
+    /// let stored: Vec<_> = std::thread::scope(|scope| {
+    ///     let stores: Vec<_> = (0..8)
+    ///         .map(|_| scope.spawn(|| objects.store_by_hash(&source, Algorithm::Sha256).unwrap()))
+    ///         .collect();
+    ///     stores.into_iter().map(|store| store.join().unwrap()).collect()
+    /// });
+    /// let (digest, object) = &stored[0];
+    /// assert!(stored.iter().all(|(other, _)| other == digest));
+    /// assert_eq!(object.as_path(), objects.as_path().join(&digest[..2]).join(&digest[2..]));
+    /// assert_eq!(objects.retrieve_by_hash(digest).unwrap().read().unwrap(), b"hello");
+    /// assert_eq!(std::fs::read_dir(objects.as_path().join(&digest[..2])).unwrap().count(), 1);
+    ///
+    /// let missing = "0".repeat(64);
+    /// let error = objects.retrieve_by_hash(&missing).unwrap_err();
+    /// assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+    /// let error = objects.retrieve_by_hash("../../etc/passwd").unwrap_err();
+    /// assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn store_by_hash(
+        &self,
+        source: &FileInfo,
+        algorithm: Algorithm,
+    ) -> Result<(String, FileInfo)> {
+        self.store_by_hash_with(source, algorithm, 1)
+    }
+
+    /// Same as [`DirectoryInfo::store_by_hash`], fanned out over `depth`
+    /// levels of directories, none when `depth` is 0
+    ///
+    /// The source is hashed first and an existing object is returned
+    /// without copying anything. Otherwise the copy is written to a
+    /// temporary file next to the object and renamed into place, so
+    /// concurrent stores never expose a partial object. The copy is hashed
+    /// again while it is written and the store fails with
+    /// `ErrorKind::InvalidData` when the source changed meanwhile.
+    pub fn store_by_hash_with(
+        &self,
+        source: &FileInfo,
+        algorithm: Algorithm,
+        depth: usize,
+    ) -> Result<(String, FileInfo)> {
+        self.validate()?;
+        let digest = source.hash(algorithm)?;
+        let path = object_path(self.as_path(), &digest, depth)?;
+        if path.is_file() {
+            return Ok((digest, unsafe { FileInfo::open_uncheck(path) }));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp = StagingMode::HiddenTemp
+            .temp_path(&path)?
+            .expect("staged writes have a temporary path");
+        let result = write_hashed(source.as_path(), &temp, algorithm).and_then(|written| {
+            if written != digest {
+                return Err(modified_during_copy(source.as_path()));
+            }
+            // another store of the same content may have won the race, the
+            // objects are identical
+            match fs::rename(&temp, &path) {
+                Err(_) if path.is_file() => remove_file_any(&temp),
+                result => result,
+            }
+        });
+        if result.is_err() {
+            let _ = remove_file_any(&temp);
+        }
+        result.map(|()| (digest, unsafe { FileInfo::open_uncheck(path) }))
+    }
+
+    /// The object stored under `digest` by [`DirectoryInfo::store_by_hash`],
+    /// `ErrorKind::NotFound` when there is none
+    pub fn retrieve_by_hash(&self, digest: &str) -> Result<FileInfo> {
+        self.retrieve_by_hash_with(digest, 1)
+    }
+
+    /// Same as [`DirectoryInfo::retrieve_by_hash`], for objects stored with
+    /// [`DirectoryInfo::store_by_hash_with`] at `depth`
+    pub fn retrieve_by_hash_with(&self, digest: &str, depth: usize) -> Result<FileInfo> {
+        FileInfo::open(object_path(self.as_path(), &digest.to_lowercase(), depth)?)
+    }
+}
+
+/// `root/ab/cd/ef…` for `depth` 2, the digest must be a whole hex digest
+fn object_path(root: &Path, digest: &str, depth: usize) -> Result<PathBuf> {
+    let is_hex = digest.bytes().all(|byte| byte.is_ascii_hexdigit());
+    if !is_hex || Algorithm::from_hex_len(digest.len()).is_none() {
+        return Err(invalid_digest(digest));
+    }
+    let depth = depth.min(digest.len() / 2 - 1);
+    let mut path = root.to_path_buf();
+    for level in 0..depth {
+        path.push(&digest[level * 2..level * 2 + 2]);
+    }
+    path.push(&digest[depth * 2..]);
+    Ok(path)
+}
+
+/// Copy `source` to the new file `dest`, returning the digest of what was
+/// written
+fn write_hashed(source: &Path, dest: &Path, algorithm: Algorithm) -> Result<String> {
+    let mut reader = File::open(source)?;
+    let mut writer = BufWriter::new(File::create_new(dest)?);
+    let mut hasher = Hasher::new(algorithm);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let len = reader.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+        writer.write_all(&buf[..len])?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    Ok(hasher.finish())
+}
+
+/// Hash every file on `threads` threads, the results are in the order of
+/// `jobs`. Threads take the largest file left, so a big file is not started
+/// last while the other threads sit idle.
+fn hash_files(jobs: &[(PathBuf, Algorithm)], threads: usize) -> Vec<Result<String>> {
+    let hash = |(file, algorithm): &(PathBuf, Algorithm)| {
+        unsafe { FileInfo::open_uncheck(file) }.hash(*algorithm)
+    };
+    if threads <= 1 || jobs.len() <= 1 {
+        return jobs.iter().map(hash).collect();
+    }
+    let mut order: Vec<_> = jobs
+        .iter()
+        .enumerate()
+        .map(|(index, (file, _))| (fs::metadata(file).map_or(0, |data| data.len()), index))
+        .collect();
+    order.sort_by_key(|&(len, _)| Reverse(len));
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<_> = jobs.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(jobs.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut hashed = Vec::new();
+                    while let Some(&(_, index)) = order.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        hashed.push((index, hash(&jobs[index])));
+                    }
+                    hashed
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (index, result) in worker.join().expect("hashing thread panicked") {
+                results[index] = Some(result);
+            }
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.expect("every file is hashed"))
+        .collect()
+}
+
+/// Accept both the text (`<hex>  <path>`) and binary (`<hex> *<path>`) forms
+fn parse_manifest_line(line: &str) -> Result<(&str, &str)> {
+    let (hash, rest) = line.split_once(' ').ok_or_else(|| invalid_line(line))?;
+    let path = rest
+        .strip_prefix(' ')
+        .or_else(|| rest.strip_prefix('*'))
+        .ok_or_else(|| invalid_line(line))?;
+    Ok((hash, path))
+}
+
+fn invalid_line(line: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid manifest line '{}'", line),
+    )
+}
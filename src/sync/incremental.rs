@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, create_dir_all};
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "unicode-normalization")]
+use crate::convert::{normalize_path_names, Form};
+use crate::error::{into_own_subtree, not_under_base};
+use crate::{fix_path, is_ancestor_of, CopyReport, Size, WalkOptions};
+
+use super::{Action, Algorithm, DirectoryInfo, FileInfo, Info};
+
+/// What a file looked like when it was last copied
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileState {
+    pub len: u64,
+    pub modified: SystemTime,
+    /// Content hash, only recorded when `IncrementalOptions::hash` is set
+    pub hash: Option<String>,
+}
+
+/// Snapshot of a source directory returned by
+/// [`DirectoryInfo::copy_incremental`], to be passed to the next run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CopyState {
+    /// Keyed by the path relative to the source directory
+    pub files: BTreeMap<PathBuf, FileState>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IncrementalOptions {
+    pub walk: WalkOptions,
+    /// Delete destination files whose source was removed since the snapshot.
+    /// Nothing is deleted when the walk was truncated by `max_entries`.
+    pub delete_removed: bool,
+    /// Modification times this close to the snapshot count as unchanged,
+    /// 2 seconds by default to cover FAT timestamps
+    pub mtime_tolerance: Duration,
+    /// Also compare content hashes, so a file whose size or modification
+    /// time changed but whose content did not is still skipped
+    pub hash: Option<Algorithm>,
+    /// Match files with the snapshot by their names normalised to this
+    /// form, so a name that macOS returns decomposed still matches the
+    /// composed spelling recorded in the snapshot. The snapshot keeps the
+    /// names as they are. [`Form::compare_default`] by default.
+    #[cfg(feature = "unicode-normalization")]
+    pub normalize_names: Option<Form>,
+}
+
+impl Default for IncrementalOptions {
+    fn default() -> Self {
+        Self {
+            walk: WalkOptions::default(),
+            delete_removed: false,
+            mtime_tolerance: Duration::from_secs(2),
+            hash: None,
+            #[cfg(feature = "unicode-normalization")]
+            normalize_names: Form::compare_default(),
+        }
+    }
+}
+
+impl IncrementalOptions {
+    fn is_unchanged(&self, previous: &FileState, len: u64, modified: SystemTime) -> bool {
+        let drift = modified
+            .duration_since(previous.modified)
+            .or_else(|_| previous.modified.duration_since(modified))
+            .unwrap_or_default();
+        previous.len == len && drift <= self.mtime_tolerance
+    }
+
+    /// What `relative` is compared by, see `normalize_names`
+    fn key(&self, relative: &Path) -> PathBuf {
+        #[cfg(feature = "unicode-normalization")]
+        if let Some(form) = self.normalize_names {
+            return normalize_path_names(relative, form);
+        }
+        relative.to_path_buf()
+    }
+}
+
+impl DirectoryInfo {
+    /// Copy the directory into `dest`, skipping the files that did not
+    /// change since `state` was taken, see [`DirectoryInfo::copy_incremental_with`]
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_incremental");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// FileInfo::create(root.join("src/same.txt")).unwrap().write("same").unwrap();
+    /// let changed = FileInfo::create(root.join("src/changed.txt")).unwrap();
+    /// changed.write("old").unwrap();
+    /// let src = DirectoryInfo::open(root.join("src")).unwrap();
+    /// let (report, state) = src.copy_incremental(root.join("dest"), None).unwrap();
+    /// assert_eq!((report.files.len(), report.skipped), (2, 0));
+    ///
+    /// changed.write("new content").unwrap();
+    /// let (report, _) = src.copy_incremental(root.join("dest"), Some(&state)).unwrap();
+    /// assert_eq!(report.skipped, 1);
+    /// assert_eq!(report.files, [root.join("dest/changed.txt")]);
+    /// assert_eq!(std::fs::read(root.join("dest/changed.txt")).unwrap(), b"new content");
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn copy_incremental<P: AsRef<Path>>(
+        &self,
+        dest: P,
+        state: Option<&CopyState>,
+    ) -> Result<(CopyReport, CopyState)> {
+        self.copy_incremental_with(dest, state, &IncrementalOptions::default())
+    }
+
+    /// Copy the directory into `dest`, which may already exist. Files that
+    /// match `state` and are still present at the destination are skipped
+    /// and counted in `CopyReport::skipped`, new and changed files
+    /// overwrite the destination. Returns the state to pass to the next run,
+    /// without a previous state every file is copied.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "unicode-normalization")] {
+    /// use fdir::convert::Form;
+    /// use fdir::incremental::{CopyState, IncrementalOptions};
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_incremental_normalized");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// FileInfo::create(root.join("src/cafe\u{301}.txt")).unwrap();
+    /// let src = DirectoryInfo::open(root.join("src")).unwrap();
+    /// let (_, state) = src.copy_incremental(root.join("dest"), None).unwrap();
+    /// // a snapshot recording the composed spelling of the same name
+    /// let state = CopyState {
+    ///     files: state
+    ///         .files
+    ///         .into_values()
+    ///         .map(|file| ("caf\u{e9}.txt".into(), file))
+    ///         .collect(),
+    /// };
+    /// let mut options = IncrementalOptions {
+    ///     normalize_names: Some(Form::Nfc),
+    ///     ..Default::default()
+    /// };
+    /// let (report, _) = src.copy_incremental_with(root.join("dest"), Some(&state), &options).unwrap();
+    /// assert_eq!((report.skipped, report.files.len()), (1, 0));
+    /// options.normalize_names = None;
+    /// let (report, _) = src.copy_incremental_with(root.join("dest"), Some(&state), &options).unwrap();
+    /// assert_eq!((report.skipped, report.files.len()), (0, 1));
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// # }
+    /// ```
+    pub fn copy_incremental_with<P: AsRef<Path>>(
+        &self,
+        dest: P,
+        state: Option<&CopyState>,
+        options: &IncrementalOptions,
+    ) -> Result<(CopyReport, CopyState)> {
+        self.validate()?;
+        let dest = fix_path(dest)?;
+        if is_ancestor_of(self.as_path(), &dest) {
+            return Err(into_own_subtree(self.as_path(), &dest));
+        }
+        let mut report = CopyReport::default();
+        let mut next = CopyState::default();
+        if !dest.is_dir() {
+            create_dir_all(&dest)?;
+            report.directories.push(dest.clone());
+        }
+        let previous_files: BTreeMap<PathBuf, &FileState> = state
+            .iter()
+            .flat_map(|state| &state.files)
+            .map(|(relative, file)| (options.key(relative), file))
+            .collect();
+        let mut walk = self.walk_with(&options.walk);
+        for entry in walk.by_ref() {
+            let entry = entry?;
+            let relative = entry
+                .path()
+                .strip_prefix(self.as_path())
+                .map_err(|_| not_under_base(entry.path(), self.as_path()))?
+                .to_path_buf();
+            let target = dest.join(&relative);
+            if entry.file_type().is_dir() {
+                if !target.is_dir() {
+                    create_dir_all(&target)?;
+                    report.directories.push(target);
+                }
+                continue;
+            }
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let (len, modified) = (metadata.len(), metadata.modified()?);
+            let previous = previous_files.get(&options.key(&relative)).copied();
+            let mut hash = None;
+            let mut unchanged = false;
+            if let Some(previous) = previous {
+                if options.is_unchanged(previous, len, modified) {
+                    unchanged = true;
+                    hash = previous.hash.clone();
+                }
+            }
+            if let (Some(algorithm), None) = (options.hash, &hash) {
+                let file = unsafe { FileInfo::open_uncheck(entry.path()) };
+                let digest = file.hash(algorithm)?;
+                unchanged |= previous.is_some_and(|previous| previous.hash == Some(digest.clone()));
+                hash = Some(digest);
+            }
+            if unchanged && target.is_file() {
+                report.skipped += 1;
+            } else {
+                fs::copy(entry.path(), &target)?;
+                report.bytes += Size(len);
+                report.files.push(target);
+            }
+            next.files.insert(
+                relative,
+                FileState {
+                    len,
+                    modified,
+                    hash,
+                },
+            );
+        }
+        if let (true, false, Some(state)) = (options.delete_removed, walk.is_truncated(), state) {
+            let kept: HashSet<PathBuf> = next.files.keys().map(|path| options.key(path)).collect();
+            for relative in state.files.keys() {
+                let target = dest.join(relative);
+                if !kept.contains(&options.key(relative)) && target.is_file() {
+                    fs::remove_file(&target)?;
+                    report.removed.push(target);
+                }
+            }
+        }
+        Ok((report, next))
+    }
+}
@@ -0,0 +1,250 @@
+//! Opt-in cache of directory listings for repeated scans, see
+//! [`DirectoryInfo::cached_listing`]
+//!
+//! A cached listing can be stale:
+//! - within the TTL the directory is not looked at at all;
+//! - after the TTL only the modification time of the directory is
+//!   compared. It changes when an entry is added, removed or renamed, but not
+//!   when the content or metadata of an entry changes, and some network
+//!   filesystems do not update it reliably;
+//! - changes made through this crate do not invalidate the cache either,
+//!   call [`DirectoryInfo::invalidate`] after modifying a cached directory.
+//!
+//! A directory modified less than a second before it was listed is always
+//! listed again once the TTL expired, as a change within the same
+//! timestamp tick would go unnoticed.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::error::listing_too_large;
+
+use super::{DirectoryInfo, Entry, Info};
+
+/// Modification times closer than this to the listing are not trusted
+const MTIME_GRANULARITY: Duration = Duration::from_secs(1);
+
+/// Entries a listing may collect, see `set_max_listing`
+static MAX_LISTING: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+static CACHE: Mutex<ListingCache> = Mutex::new(ListingCache {
+    capacity: 0,
+    clock: 0,
+    listings: BTreeMap::new(),
+});
+
+struct Listing {
+    entries: Vec<Entry>,
+    /// `None` when too recent to be compared
+    modified: Option<SystemTime>,
+    checked: Instant,
+    used: u64,
+}
+
+struct ListingCache {
+    capacity: usize,
+    /// Incremented on every use, orders the listings for eviction
+    clock: u64,
+    listings: BTreeMap<PathBuf, Listing>,
+}
+
+impl ListingCache {
+    /// The listing of `path` if `is_fresh` accepts it, marked as used
+    fn get(&mut self, path: &Path, is_fresh: impl Fn(&Listing) -> bool) -> Option<Vec<Entry>> {
+        self.clock += 1;
+        let listing = self
+            .listings
+            .get_mut(path)
+            .filter(|listing| is_fresh(listing))?;
+        listing.used = self.clock;
+        Some(listing.entries.clone())
+    }
+
+    fn insert(&mut self, path: PathBuf, listing: Listing) {
+        self.listings.insert(path, listing);
+        self.shrink();
+    }
+
+    /// Evict the least recently used listings beyond the capacity
+    fn shrink(&mut self) {
+        while self.listings.len() > self.capacity {
+            let oldest = self
+                .listings
+                .iter()
+                .min_by_key(|(_, listing)| listing.used)
+                .map(|(path, _)| path.clone());
+            match oldest {
+                Some(path) => self.listings.remove(&path),
+                None => break,
+            };
+        }
+    }
+}
+
+fn cache() -> MutexGuard<'static, ListingCache> {
+    CACHE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Enable the cache with room for the listings of `capacity` directories,
+/// the least recently used one is evicted when it is full. 0, the default,
+/// disables the cache and empties it.
+pub fn set_listing_cache_capacity(capacity: usize) {
+    let mut cache = cache();
+    cache.capacity = capacity;
+    cache.shrink();
+}
+
+/// Forget every cached listing
+pub fn clear_listing_cache() {
+    cache().listings.clear();
+}
+
+/// Make the methods collecting the children of a directory, such as
+/// [`DirectoryInfo::files`], fail with `ErrorKind::ListingTooLarge` on a
+/// directory with more than `limit` entries, before collecting the rest.
+/// Every entry counts, whichever the method keeps. `None`, the default,
+/// removes the limit. [`DirectoryInfo::walk`] reads entries one at a time
+/// and is not limited.
+///
+/// # Examples
+/// ```
+/// use fdir::*;
+/// let dir = std::env::temp_dir().join("fdir_max_listing");
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// for name in ["a", "b", "c"] {
+///     FileInfo::create(dir.join(name)).unwrap();
+/// }
+/// let dir = DirectoryInfo::open(&dir).unwrap();
+/// fdir::set_max_listing(Some(2));
+/// let error = dir.files().unwrap_err();
+/// assert_eq!(fdir::ErrorKind::of(&error), fdir::ErrorKind::ListingTooLarge);
+/// assert!(error.to_string().contains("more than 2 entries"));
+/// assert!(dir.children().is_err() && dir.entries().is_err());
+/// assert_eq!(dir.walk().count(), 3);
+///
+/// fdir::set_max_listing(Some(3));
+/// assert_eq!(dir.files().unwrap().len(), 3);
+/// fdir::set_max_listing(None);
+/// # dir.delete().unwrap();
+/// ```
+pub fn set_max_listing(limit: Option<usize>) {
+    MAX_LISTING.store(limit.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// Counts the entries read from a directory against `set_max_listing`
+pub(crate) struct ListingLimit<'a> {
+    dir: &'a Path,
+    limit: usize,
+    read: usize,
+}
+
+impl<'a> ListingLimit<'a> {
+    pub(crate) fn new(dir: &'a Path) -> Self {
+        Self {
+            dir,
+            limit: MAX_LISTING.load(Ordering::Relaxed),
+            read: 0,
+        }
+    }
+
+    /// Count one more entry, an error once there are too many
+    pub(crate) fn count(&mut self) -> Result<()> {
+        if self.read == self.limit {
+            return Err(listing_too_large(self.dir, self.limit));
+        }
+        self.read += 1;
+        Ok(())
+    }
+}
+
+impl DirectoryInfo {
+    /// Same as [`DirectoryInfo::entries`], served from the listing cache
+    /// when it was enabled with [`set_listing_cache_capacity`].
+    ///
+    /// A listing younger than `ttl` is returned without touching the
+    /// filesystem, an older one after checking that the modification time
+    /// of the directory did not change. See the
+    /// [module documentation](self) for when the result can be stale.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use fdir::{Action, DirectoryInfo, FileInfo, Info};
+    /// let dir = std::env::temp_dir().join("fdir_cached_listing");
+    /// let _ = std::fs::remove_dir_all(&dir);
+    /// FileInfo::create(dir.join("a")).unwrap();
+    /// let dir = DirectoryInfo::open(&dir).unwrap();
+    /// fdir::set_listing_cache_capacity(16);
+    ///
+    /// assert_eq!(dir.cached_listing(Duration::from_secs(60)).unwrap().len(), 1);
+    /// FileInfo::create(dir.as_path().join("b")).unwrap();
+    /// // still within the TTL
+    /// assert_eq!(dir.cached_listing(Duration::from_secs(60)).unwrap().len(), 1);
+    /// dir.invalidate();
+    /// assert_eq!(dir.cached_listing(Duration::from_secs(60)).unwrap().len(), 2);
+    ///
+    /// FileInfo::create(dir.as_path().join("c")).unwrap();
+    /// fdir::clear_listing_cache();
+    /// assert_eq!(dir.cached_listing(Duration::from_secs(60)).unwrap().len(), 3);
+    ///
+    /// fdir::set_listing_cache_capacity(0);
+    /// FileInfo::create(dir.as_path().join("d")).unwrap();
+    /// assert_eq!(dir.cached_listing(Duration::from_secs(60)).unwrap().len(), 4);
+    /// # dir.delete().unwrap();
+    /// ```
+    pub fn cached_listing(&self, ttl: Duration) -> Result<Vec<Entry>> {
+        let path = self.as_path().to_path_buf();
+        {
+            let mut cache = cache();
+            if cache.capacity == 0 {
+                drop(cache);
+                return self.entries();
+            }
+            if let Some(entries) = cache.get(&path, |listing| listing.checked.elapsed() < ttl) {
+                return Ok(entries);
+            }
+        }
+        // the filesystem is never accessed while the cache is locked
+        let modified = fs::metadata(&path)?.modified().ok();
+        {
+            let mut cache = cache();
+            let unchanged = |listing: &Listing| modified.is_some() && listing.modified == modified;
+            if let Some(entries) = cache.get(&path, unchanged) {
+                if let Some(listing) = cache.listings.get_mut(&path) {
+                    listing.checked = Instant::now();
+                }
+                return Ok(entries);
+            }
+        }
+        let checked = Instant::now();
+        let entries = self.entries()?;
+        let modified = modified.filter(|modified| {
+            SystemTime::now()
+                .duration_since(*modified)
+                .is_ok_and(|age| age >= MTIME_GRANULARITY)
+        });
+        let mut cache = cache();
+        if cache.capacity > 0 {
+            let used = cache.clock;
+            let listing = Listing {
+                entries: entries.clone(),
+                modified,
+                checked,
+                used,
+            };
+            cache.insert(path, listing);
+        }
+        Ok(entries)
+    }
+
+    /// Forget the cached listing of the directory, the next
+    /// [`DirectoryInfo::cached_listing`] lists it again
+    pub fn invalidate(&self) {
+        cache().listings.remove(self.as_path());
+    }
+}
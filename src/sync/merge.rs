@@ -0,0 +1,226 @@
+use std::cmp::Reverse;
+use std::fs::{self, FileType};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use crate::error::{already_exist, into_own_subtree};
+use crate::{
+    is_ancestor_of, is_same_file, unique_path, ConflictPolicy, MergeReport, Numbering, Options,
+    UpdateCheck, WalkOptions,
+};
+
+use super::visit::{VisitAction, Visitor};
+use super::walk::WalkEntry;
+use super::{Action, DirectoryInfo, FileInfo, Info};
+
+impl DirectoryInfo {
+    /// Move the content of the directory into the existing directory
+    /// `dest`, as `mv src/* dest/` would, descending into the directories
+    /// found on both sides. The emptied source is removed and `self` then
+    /// points at `dest`, a source still holding skipped entries is kept.
+    ///
+    /// `conflict` applies to the files found on both sides. `Update`
+    /// replaces a file only when the source is newer, see [`UpdateCheck`].
+    /// A file never replaces a directory nor the reverse, such entries are
+    /// skipped unless `conflict` is `Fail`. Links are moved as links.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_merge_into");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for (path, content) in [
+    ///     ("src/a.txt", "new a"),
+    ///     ("src/sub/b.txt", "new b"),
+    ///     ("src/sub/c.txt", "c"),
+    ///     ("src/only/d.txt", "d"),
+    ///     ("dest/a.txt", "old a"),
+    ///     ("dest/sub/b.txt", "old b"),
+    /// ] {
+    ///     let file = FileInfo::create(root.join(path)).unwrap();
+    ///     std::fs::write(file.as_path(), content).unwrap();
+    /// }
+    /// let mut source = DirectoryInfo::open(root.join("src")).unwrap();
+    /// let dest = DirectoryInfo::open(root.join("dest")).unwrap();
+    /// let report = source.merge_into(&dest, ConflictPolicy::Overwrite).unwrap();
+    /// assert_eq!(report.merged, [root.join("dest/sub")]);
+    /// assert_eq!(report.overwritten, [root.join("dest/a.txt"), root.join("dest/sub/b.txt")]);
+    /// assert_eq!(report.moved, [root.join("dest/only"), root.join("dest/sub/c.txt")]);
+    /// assert!(report.skipped.is_empty());
+    /// assert_eq!(std::fs::read_to_string(root.join("dest/sub/b.txt")).unwrap(), "new b");
+    /// assert!(!root.join("src").exists() && source.as_path() == dest.as_path());
+    ///
+    /// // skipped files stay in the source, which is kept
+    /// let mut source = DirectoryInfo::open(root.join("dest/only")).unwrap();
+    /// std::fs::write(root.join("dest/d.txt"), "kept").unwrap();
+    /// let report = source.merge_into(&dest, ConflictPolicy::Skip).unwrap();
+    /// assert_eq!(report.skipped, [root.join("dest/only/d.txt")]);
+    /// assert_eq!(source.as_path(), root.join("dest/only"));
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn merge_into(
+        &mut self,
+        dest: &DirectoryInfo,
+        conflict: ConflictPolicy,
+    ) -> Result<MergeReport> {
+        self.validate()?;
+        dest.validate()?;
+        if is_same_file(self.as_path(), dest.as_path()) {
+            return Ok(MergeReport::default());
+        }
+        if is_ancestor_of(self.as_path(), dest.as_path()) {
+            return Err(into_own_subtree(self.as_path(), dest.as_path()));
+        }
+        let mut visitor = MergeVisitor {
+            conflict,
+            targets: vec![dest.as_path().to_path_buf()],
+            sources: vec![self.as_path().to_path_buf()],
+            report: MergeReport::default(),
+            error: None,
+        };
+        self.visit(&WalkOptions::default(), &mut visitor)?;
+        if let Some(e) = visitor.error {
+            return Err(e);
+        }
+        let MergeVisitor {
+            mut report,
+            sources,
+            ..
+        } = visitor;
+        report.sort();
+        remove_emptied(sources);
+        if !self.as_path().exists() {
+            *self = dest.clone();
+        }
+        Ok(report)
+    }
+}
+
+/// Moves the entries of [`DirectoryInfo::merge_into`] while it visits them
+struct MergeVisitor {
+    conflict: ConflictPolicy,
+    /// Destination of each directory being merged, the innermost last
+    targets: Vec<PathBuf>,
+    /// Source directories to remove once emptied
+    sources: Vec<PathBuf>,
+    report: MergeReport,
+    /// The error that stopped the traversal
+    error: Option<Error>,
+}
+
+impl MergeVisitor {
+    /// Move `source` unless it is a directory to merge, which is returned
+    fn merge(&mut self, source: &Path, file_type: FileType) -> Result<Option<PathBuf>> {
+        let to = self.targets.last().expect("the destination is never left");
+        let target = to.join(source.file_name().unwrap_or_default());
+        match plan(source, file_type, target, self.conflict)? {
+            Step::Merge(target) => {
+                self.report.merged.push(target.clone());
+                self.sources.push(source.to_path_buf());
+                return Ok(Some(target));
+            }
+            Step::Move(target) => {
+                move_entry(source, file_type, &target, false)?;
+                self.report.moved.push(target);
+            }
+            Step::Replace(target) => {
+                move_entry(source, file_type, &target, true)?;
+                self.report.overwritten.push(target);
+            }
+            Step::Skip => self.report.skipped.push(source.to_path_buf()),
+        }
+        Ok(None)
+    }
+}
+
+impl Visitor for MergeVisitor {
+    fn enter_dir(&mut self, dir: &DirectoryInfo) -> VisitAction {
+        let merged = fs::symlink_metadata(dir.as_path())
+            .and_then(|metadata| self.merge(dir.as_path(), metadata.file_type()));
+        match merged {
+            Ok(Some(target)) => {
+                self.targets.push(target);
+                VisitAction::Descend
+            }
+            // moved or left in place
+            Ok(None) => VisitAction::Skip,
+            Err(e) => {
+                self.error = Some(e);
+                VisitAction::Stop
+            }
+        }
+    }
+
+    fn visit_file(&mut self, file: &FileInfo) -> Result<()> {
+        let metadata = fs::symlink_metadata(file.as_path())?;
+        self.merge(file.as_path(), metadata.file_type()).map(drop)
+    }
+
+    fn leave_dir(&mut self, _: &DirectoryInfo) {
+        self.targets.pop();
+    }
+
+    fn visit_other(&mut self, entry: &WalkEntry) -> Result<()> {
+        self.merge(entry.path(), entry.file_type()).map(drop)
+    }
+}
+
+/// What [`DirectoryInfo::merge_into`] does with one entry
+pub(crate) enum Step {
+    /// Descend into the directory found on both sides
+    Merge(PathBuf),
+    /// Move to the free path
+    Move(PathBuf),
+    /// Move over the existing file
+    Replace(PathBuf),
+    /// Leave the entry in the source
+    Skip,
+}
+
+/// Decide what happens to `source` bound for `target` under `conflict`
+pub(crate) fn plan(
+    source: &Path,
+    file_type: FileType,
+    target: PathBuf,
+    conflict: ConflictPolicy,
+) -> Result<Step> {
+    let existing = match fs::symlink_metadata(&target) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Step::Move(target)),
+        Err(e) => return Err(e),
+    };
+    let is_dir = file_type.is_dir();
+    if is_dir && existing.is_dir() {
+        return Ok(Step::Merge(target));
+    }
+    match conflict {
+        ConflictPolicy::Fail => Err(already_exist(&target)),
+        ConflictPolicy::Skip => Ok(Step::Skip),
+        ConflictPolicy::Rename => Ok(Step::Move(unique_path(&target, Numbering::default()))),
+        _ if is_dir || existing.is_dir() => Ok(Step::Skip),
+        ConflictPolicy::Update if UpdateCheck::default().is_up_to_date(source, &target)? => {
+            Ok(Step::Skip)
+        }
+        ConflictPolicy::Overwrite | ConflictPolicy::Update => Ok(Step::Replace(target)),
+    }
+}
+
+/// Remove the merged source directories that were emptied, deepest first
+pub(crate) fn remove_emptied(mut sources: Vec<PathBuf>) {
+    sources.sort_by_key(|path| Reverse(path.components().count()));
+    for source in sources {
+        let _ = fs::remove_dir(source);
+    }
+}
+
+fn move_entry(source: &Path, file_type: FileType, target: &Path, overwrite: bool) -> Result<()> {
+    let options = Options::new().overwrite(overwrite);
+    if file_type.is_dir() {
+        unsafe { DirectoryInfo::open_uncheck(source) }.move_new_with(target, &options)?;
+    } else if file_type.is_symlink() {
+        fs::rename(source, target)?;
+    } else {
+        unsafe { FileInfo::open_uncheck(source) }.move_new_with(target, &options)?;
+    }
+    Ok(())
+}
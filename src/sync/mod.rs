@@ -1,30 +1,328 @@
+#[cfg(all(windows, feature = "acl"))]
+pub mod acl;
+#[cfg(all(windows, feature = "ads"))]
+pub mod ads;
+pub mod background;
+pub mod batch;
+pub mod cached;
+#[cfg(feature = "infer")]
+pub mod detect;
 pub mod dir;
+pub mod dynamic;
+pub mod entry;
 pub mod file;
+pub mod hash;
+pub mod incremental;
+pub mod listing;
+pub mod merge;
+pub mod organize;
+pub mod permissions;
+pub mod quota;
+pub mod readonly;
 pub mod recover;
-pub use self::{dir::DirectoryInfo, file::FileInfo};
-use crate::push_file_name;
+pub mod sample;
+pub mod search;
+pub mod secure;
+pub mod swap;
+pub mod text;
+pub mod tree;
+pub mod visit;
+pub mod walk;
+pub use self::{
+    background::{OperationState, PauseToken, ProgressEvents, ProgressHandle, ProgressSnapshot},
+    cached::CachedFileInfo,
+    dir::{DirectoryInfo, EntryFilter},
+    entry::Entry,
+    file::FileInfo,
+    hash::Algorithm,
+    incremental::{CopyState, IncrementalOptions},
+    listing::{clear_listing_cache, set_listing_cache_capacity, set_max_listing},
+    quota::{Quota, QuotaPolicy},
+    readonly::{ReadOnly, ReadOnlyDirectoryInfo, ReadOnlyFileInfo},
+    search::SearchQuery,
+    secure::SecureDir,
+    tree::TreeOptions,
+    visit::{VisitAction, Visitor},
+    walk::Walk,
+};
+use crate::convert::{QuotedDisplay, RelativeDisplay};
+use crate::error::not_under_base;
+use crate::journal::Operation;
+use crate::{fix_path, push_file_name, renamed, Options, PathPolicy};
 use std::{
+    borrow::Cow,
     ffi::OsStr,
-    fs::{self, metadata, remove_dir_all, remove_file, Metadata, Permissions},
+    fs::{self, metadata, remove_dir_all, remove_file, FileType, Metadata, Permissions},
     io::Result,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use self::recover::TryRecoverResult;
 pub trait Info: Sized {
     fn as_path(&self) -> &Path;
+    /// The path as an owned value, see the `into_path_buf` methods and the
+    /// `From` conversions to avoid the clone. The path is returned exactly as
+    /// it is stored, with no further normalisation.
+    ///
+    /// The types implement `AsRef<Path>` but not `Deref<Target = Path>`:
+    /// `Path` has methods named like those of `Info`, such as `parent`,
+    /// `exists` and `metadata`, that would be called instead whenever `Info`
+    /// is not in scope.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use fdir::*;
+    /// let odd = Path::new("/srv//data/./reports/../notes.txt");
+    /// let file = unsafe { FileInfo::open_uncheck(odd) };
+    /// assert_eq!(file.to_path_buf().as_os_str(), odd.as_os_str());
+    /// assert_eq!(file.parent_path().unwrap().as_os_str(), odd.parent().unwrap().as_os_str());
+    /// assert_eq!(std::fs::metadata(&file).is_ok(), odd.exists());
+    /// assert_eq!(PathBuf::from(file.clone()).as_os_str(), odd.as_os_str());
+    /// assert_eq!(file.into_path_buf().as_os_str(), odd.as_os_str());
+    ///
+    /// // opening normalises once, the conversions keep the result
+    /// let dir = DirectoryInfo::open(std::env::temp_dir().join(".")).unwrap();
+    /// let opened = dir.as_path().to_path_buf();
+    /// assert_eq!(PathBuf::from(dir), opened);
+    /// ```
+    fn to_path_buf(&self) -> PathBuf {
+        self.as_path().to_path_buf()
+    }
     fn file_name(&self) -> Option<&OsStr> {
         self.as_path().file_name()
     }
+    /// The file name as UTF-8. A name that is not valid UTF-8 is an error
+    /// of kind `InvalidInput` carrying an [`InvalidPath`](crate::InvalidPath)
+    /// with a `PathIssue::NotUnicode` holding the raw name, as is a path
+    /// without a name, such as `/`.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let dir = std::env::temp_dir().join("fdir_file_name_str");
+    /// let file = unsafe { FileInfo::open_uncheck(dir.join("café.txt")) };
+    /// assert_eq!(file.file_name_str().unwrap(), "café.txt");
+    /// assert!(file.path_str().unwrap().ends_with("café.txt"));
+    ///
+    /// #[cfg(unix)]
+    /// {
+    ///     use std::ffi::OsStr;
+    ///     use std::os::unix::ffi::OsStrExt;
+    ///     // "café" in Latin-1
+    ///     let raw = OsStr::from_bytes(b"caf\xe9.txt");
+    ///     let file = unsafe { FileInfo::open_uncheck(dir.join(raw)) };
+    ///     let error = file.file_name_str().unwrap_err();
+    ///     assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    ///     let issues = &InvalidPath::find(&error).unwrap().issues;
+    ///     assert_eq!(issues, &[PathIssue::NotUnicode { component: raw.to_owned() }]);
+    ///     assert!(file.path_str().is_err());
+    ///     assert_eq!(file.file_name_lossy(), "caf\u{FFFD}.txt");
+    /// }
+    /// ```
+    fn file_name_str(&self) -> Result<&str> {
+        crate::file_name_str(self.as_path())
+    }
+    /// The whole path as UTF-8, the error carries the first name that is
+    /// not, see [`Info::file_name_str`]
+    fn path_str(&self) -> Result<&str> {
+        crate::path_str(self.as_path())
+    }
+    /// The whole path as a `String`, an error when it is not UTF-8.
+    /// [`Action::from_portable_string`] opens it back, see
+    /// [`Info::to_encoded_string`] for a path that is not UTF-8.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let dir = std::env::temp_dir().join("fdir_portable_string");
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// let file = FileInfo::create(dir.join("100% café.txt")).unwrap();
+    /// let portable = file.to_portable_string().unwrap();
+    /// let back = FileInfo::from_portable_string(&portable).unwrap();
+    /// assert_eq!(back.as_path(), file.as_path());
+    ///
+    /// let encoded = file.to_encoded_string();
+    /// assert!(encoded.ends_with("100%25 café.txt"));
+    /// let back = FileInfo::from_encoded_string(&encoded).unwrap();
+    /// assert_eq!(back.as_path(), file.as_path());
+    /// assert!(DirectoryInfo::from_encoded_string(&encoded).is_err());
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    fn to_portable_string(&self) -> Result<String> {
+        self.path_str().map(str::to_owned)
+    }
+    /// The path as a string that [`Action::from_encoded_string`] opens back
+    /// whatever bytes it holds, see
+    /// [`encode_path`](crate::convert::encode_path) for the scheme
+    fn to_encoded_string(&self) -> String {
+        crate::convert::encode_path(self.as_path())
+    }
+    /// The file name with invalid UTF-8 replaced by `U+FFFD`, empty for a
+    /// path without a name
+    fn file_name_lossy(&self) -> Cow<'_, str> {
+        self.file_name()
+            .map_or(Cow::Borrowed(""), |name| name.to_string_lossy())
+    }
+    /// The file name without its final extension, see [`Path::file_stem`]
+    fn stem(&self) -> Option<&OsStr> {
+        self.as_path().file_stem()
+    }
+    /// The final extension, `None` for dotfiles such as `.bashrc` and names
+    /// ending with a dot
+    fn extension(&self) -> Option<&OsStr> {
+        self.as_path().extension().filter(|ext| !ext.is_empty())
+    }
+    /// Same as [`Info::extension`], lowercased
+    fn extension_lower(&self) -> Option<String> {
+        self.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+    }
+    /// Every extension, `tar.gz` for `archive.tar.gz`
+    fn full_extension(&self) -> Option<&OsStr> {
+        crate::full_extension(self.file_name()?)
+    }
+    /// Whether the name ends with `extension`, ignoring case. `gz` and
+    /// `tar.gz` both match `archive.tar.gz`, `tar` does not.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let archive = unsafe { FileInfo::open_uncheck("/tmp/Archive.TAR.gz") };
+    /// assert!(archive.has_extension("gz") && archive.has_extension(".tar.GZ"));
+    /// assert!(!archive.has_extension("tar"));
+    /// assert_eq!(archive.full_extension().unwrap(), "TAR.gz");
+    /// let dotfile = unsafe { FileInfo::open_uncheck("/home/me/.bashrc") };
+    /// assert!(dotfile.extension().is_none() && !dotfile.has_extension("bashrc"));
+    /// let trailing = unsafe { FileInfo::open_uncheck("/tmp/notes.") };
+    /// assert!(trailing.extension().is_none() && trailing.full_extension().is_none());
+    /// ```
+    fn has_extension(&self, extension: impl AsRef<str>) -> bool {
+        self.file_name()
+            .is_some_and(|name| crate::has_extension(name, extension.as_ref()))
+    }
+    /// The path with its final extension replaced, see [`Path::with_extension`]
+    fn with_extension(&self, extension: impl AsRef<OsStr>) -> PathBuf {
+        self.as_path().with_extension(extension)
+    }
     fn metadata(&self) -> Result<Metadata> {
         metadata(self.as_path())
     }
+    /// Identity of the file or directory, the same for every path naming
+    /// it, see [`FileId`](crate::FileId)
+    fn file_id(&self) -> Result<crate::FileId> {
+        crate::FileId::of(self.as_path())
+    }
+    /// Filesystem holding the file or directory, see [`FsId`](crate::FsId)
+    fn filesystem_id(&self) -> Result<crate::FsId> {
+        crate::FsId::of(self.as_path())
+    }
+    /// Directory the filesystem holding the file or directory is mounted
+    /// on, links are resolved. Read from `/proc/self/mountinfo` on Linux,
+    /// with `statfs` on macOS and `GetVolumePathNameW` on Windows, a
+    /// volume root such as `C:\` there.
+    fn mount_point(&self) -> Result<PathBuf> {
+        crate::mount_point(self.as_path())
+    }
     fn size(&self) -> u64;
-    /// Return None if the path is a root directory
+    /// Same as [`Info::size`], as a [`Size`](crate::Size) that formats
+    /// itself in binary units
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let path = std::env::temp_dir().join("fdir_size_typed.txt");
+    /// let file = FileInfo::create(&path).unwrap();
+    /// file.write(vec![0u8; 1536]).unwrap();
+    /// assert_eq!(file.size_typed(), Size(1536));
+    /// assert_eq!(file.size_typed().to_string(), "1.5 KiB");
+    /// # file.delete().unwrap();
+    /// ```
+    fn size_typed(&self) -> crate::Size {
+        crate::Size(self.size())
+    }
+    fn exists(&self) -> bool {
+        self.as_path().exists()
+    }
+    /// Check that the path still exists and is still the expected kind
+    /// (file or directory)
+    fn validate(&self) -> Result<()>;
+    /// The parent path, `None` for a root directory: `/`, a drive such as
+    /// `C:\` or the root of a share such as `\\server\share`, which has no
+    /// parent even though `\\server` looks like one. Unlike
+    /// [`Info::try_parent`] nothing is read from the filesystem.
+    fn parent_path(&self) -> Option<&Path> {
+        self.as_path().parent()
+    }
+    /// The path of `name` next to this one, in the same directory. `name`
+    /// must be a single file name, see [`Action::rename`].
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let dir = std::env::temp_dir().join("fdir_sibling");
+    /// let file = unsafe { FileInfo::open_uncheck(dir.join("report.csv")) };
+    /// assert_eq!(file.sibling("summary.txt").unwrap(), dir.join("summary.txt"));
+    /// assert!(file.sibling("../summary.txt").is_err());
+    /// assert_eq!(file.with_suffix("-old").unwrap(), dir.join("report-old.csv"));
+    /// let archive = unsafe { FileInfo::open_uncheck(dir.join("logs.tar.gz")) };
+    /// assert_eq!(archive.with_suffix(" (1)").unwrap(), dir.join("logs.tar (1).gz"));
+    /// let dotfile = unsafe { FileInfo::open_uncheck(dir.join(".env")) };
+    /// assert_eq!(dotfile.with_suffix(".bak").unwrap(), dir.join(".env.bak"));
+    /// assert!(file.with_suffix("/old").is_err());
+    /// ```
+    fn sibling(&self, name: impl AsRef<OsStr>) -> Result<PathBuf> {
+        let name = crate::checked_name(name.as_ref())?;
+        Ok(self.as_path().with_file_name(name))
+    }
+    /// The containing directory, `Ok(None)` for a root directory (see
+    /// [`Info::parent_path`]). A parent that cannot be opened, because it
+    /// is gone or not readable, is an error rather than `None`.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = unsafe { DirectoryInfo::open_uncheck("/") };
+    /// assert!(root.try_parent().unwrap().is_none());
+    /// #[cfg(windows)]
+    /// {
+    ///     let share = unsafe { DirectoryInfo::open_uncheck(r"\\server\share") };
+    ///     assert!(share.try_parent().unwrap().is_none());
+    ///     let drive = unsafe { DirectoryInfo::open_uncheck(r"\\?\C:\") };
+    ///     assert!(drive.try_parent().unwrap().is_none());
+    /// }
+    ///
+    /// let dir = std::env::temp_dir().join("fdir_try_parent");
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// let file = FileInfo::create(dir.join("locked/inner/a.txt")).unwrap();
+    /// assert_eq!(file.try_parent().unwrap().unwrap().as_path(), dir.join("locked/inner"));
+    ///
+    /// // a parent below something that is not a directory
+    /// let odd = unsafe { FileInfo::open_uncheck(file.as_path().join("b/c.txt")) };
+    /// assert!(odd.try_parent().is_err());
+    ///
+    /// #[cfg(unix)]
+    /// {
+    ///     use std::os::unix::fs::PermissionsExt;
+    ///     let locked = dir.join("locked");
+    ///     std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+    ///     // permissions are not enforced for a privileged user
+    ///     if std::fs::read_dir(&locked).is_err() {
+    ///         let error = file.try_parent().unwrap_err();
+    ///         assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+    ///     }
+    ///     std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+    /// }
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    fn try_parent(&self) -> Result<Option<DirectoryInfo>> {
+        self.parent_path().map(DirectoryInfo::open).transpose()
+    }
+    /// Return None if the path is a root directory, or if the parent
+    /// cannot be opened
+    #[deprecated(note = "use `try_parent`, which reports why the parent cannot be opened")]
     fn parent(&self) -> Option<DirectoryInfo> {
-        self.as_path()
-            .parent()
-            .and_then(|path| DirectoryInfo::open(path).ok())
+        self.try_parent().ok().flatten()
     }
 
     fn permissions(&self) -> Result<Permissions> {
@@ -34,10 +332,117 @@ pub trait Info: Sized {
     fn read_only(&self) -> Result<bool> {
         self.metadata().map(|data| data.permissions().readonly())
     }
+
+    #[cfg(feature = "url")]
+    fn to_file_url(&self) -> Result<crate::convert::Url> {
+        crate::convert::path_to_file_url(self.as_path())
+    }
+
+    /// Return the path relative to `base`, or an error if the path is not under `base`.
+    /// A trailing separator on `base` makes no difference, the path of
+    /// `base` itself is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// use std::path::Path;
+    /// let root = std::env::temp_dir().join("fdir_relative_to");
+    /// let file = unsafe { FileInfo::open_uncheck(root.join("a/b.txt")) };
+    /// assert_eq!(file.relative_to(&root).unwrap(), Path::new("a/b.txt"));
+    /// let mut base = root.join("a").into_os_string();
+    /// base.push(std::path::MAIN_SEPARATOR_STR);
+    /// assert_eq!(file.relative_to(&base).unwrap(), Path::new("b.txt"));
+    /// assert_eq!(file.relative_to(file.as_path()).unwrap(), Path::new(""));
+    /// let error = file.relative_to(root.join("c")).unwrap_err();
+    /// assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    /// assert!(file.relative_to(root.join("a/b")).is_err());
+    ///
+    /// #[cfg(windows)]
+    /// {
+    ///     let file = unsafe { FileInfo::open_uncheck(r"C:\data\a\b.txt") };
+    ///     assert_eq!(file.relative_to(r"C:\data\").unwrap(), Path::new(r"a\b.txt"));
+    ///     assert!(file.relative_to(r"D:\data").is_err());
+    /// }
+    /// ```
+    fn relative_to<P: AsRef<Path>>(&self, base: P) -> Result<PathBuf> {
+        let base = fix_path(base)?;
+        self.as_path()
+            .strip_prefix(&base)
+            .map(|path| path.to_path_buf())
+            .map_err(|_| not_under_base(self.as_path(), base))
+    }
+
+    /// Display the path relative to `base`, or whole when it is not under
+    /// `base`. `Display` itself shows the whole path, and only the file name
+    /// with `{:#}`.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let file = unsafe { FileInfo::open_uncheck("/srv/www/my site/index.html") };
+    /// assert_eq!(format!("{:#}", file), "index.html");
+    /// assert_eq!(file.display_relative_to("/srv/www").to_string(), "my site/index.html");
+    /// assert_eq!(file.display_relative_to("/home").to_string(), file.to_string());
+    /// # #[cfg(unix)]
+    /// assert_eq!(file.display_quoted().to_string(), "'/srv/www/my site/index.html'");
+    /// ```
+    fn display_relative_to<P: AsRef<Path>>(&self, base: P) -> RelativeDisplay<'_> {
+        RelativeDisplay::new(self.as_path(), base.as_ref())
+    }
+
+    /// Display the path quoted for the shell of the platform, see
+    /// [`quote_path`](crate::convert::quote_path)
+    fn display_quoted(&self) -> QuotedDisplay<'_> {
+        QuotedDisplay::new(self.as_path())
+    }
 }
 
 pub trait Action: Info {
+    /// Open an existing path of the right kind. A missing path fails with
+    /// `ErrorKind::NotFound`, a path of the other kind with
+    /// `ErrorKind::InvalidInput`.
+    ///
+    /// The path is stored as [`normalize`](crate::normalize) makes it, so
+    /// `"some/dir/"` and `"some/dir"` open the same path, `"."` and `".."`
+    /// are resolved against the current directory at the time of the call,
+    /// and the empty path fails with `ErrorKind::InvalidInput`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::ErrorKind;
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_open_kind");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let file = FileInfo::create(root.join("file.txt")).unwrap();
+    ///
+    /// let error = DirectoryInfo::open(file.as_path()).unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    /// assert!(error.to_string().ends_with("exists but is a file"));
+    /// let error = FileInfo::open(&root).unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    /// assert!(error.to_string().ends_with("exists but is a directory"));
+    ///
+    /// let missing = root.join("missing");
+    /// assert_eq!(DirectoryInfo::open(&missing).unwrap_err().kind(), ErrorKind::NotFound);
+    /// assert_eq!(FileInfo::open(&missing).unwrap_err().kind(), ErrorKind::NotFound);
+    ///
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let slashed = DirectoryInfo::open(format!("{}/", root.display())).unwrap();
+    /// assert_eq!(slashed.as_path(), dir.as_path());
+    /// assert_eq!(slashed.to_string(), dir.to_string());
+    /// let cwd = std::env::current_dir().unwrap();
+    /// assert_eq!(DirectoryInfo::open(".").unwrap().as_path(), cwd);
+    /// let parent = cwd.parent().unwrap_or(&cwd);
+    /// assert_eq!(DirectoryInfo::open("..").unwrap().as_path(), parent);
+    /// assert_eq!(DirectoryInfo::open("").unwrap_err().kind(), ErrorKind::InvalidInput);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
     fn open<P: AsRef<Path>>(path: P) -> Result<Self>;
+    /// Same as [`Action::open`], after checking the path against `policy`
+    /// with [`fix_path_strict`](crate::fix_path_strict)
+    fn open_with_policy<P: AsRef<Path>>(path: P, policy: &PathPolicy) -> Result<Self> {
+        Self::open(crate::fix_path_strict(path, policy)?)
+    }
     /// # Safety
     /// This function is unsafe as it does not check or fix the path.
     /// please make sure the path is correct absolute path
@@ -49,45 +454,248 @@ pub trait Action: Info {
     /// assert_eq!(dir.to_string(), ".".to_string())
     /// ```
     unsafe fn open_uncheck<P: AsRef<Path>>(path: P) -> Self;
+    /// Same as [`Action::open`] on a path from [`Info::to_portable_string`]
+    fn from_portable_string(path: &str) -> Result<Self> {
+        Self::open(path)
+    }
+    /// Same as [`Action::open`] on a path from [`Info::to_encoded_string`],
+    /// an error when `encoded` is not a valid encoding
+    fn from_encoded_string(encoded: &str) -> Result<Self> {
+        Self::open(crate::convert::decode_path(encoded)?)
+    }
 
-    /// Rename a file or directory
+    /// Rename a file or directory within its directory. `name` must be a
+    /// single file name: an empty name, `.`, `..` or a name with a path
+    /// separator is refused with `ErrorKind::InvalidInput`, see
+    /// [`Action::move_new`] to move elsewhere.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let dir = std::env::temp_dir().join("fdir_rename_names");
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// let mut file = FileInfo::create(dir.join("a.txt")).unwrap();
+    /// std::fs::create_dir(dir.join("sub")).unwrap();
+    /// let mut refused = vec!["sub/b.txt", "", ".", "..", "../b.txt", "b.txt/"];
+    /// if cfg!(windows) {
+    ///     refused.extend([r"sub\b.txt", r"C:\b.txt", "C:b.txt"]);
+    /// }
+    /// for name in refused {
+    ///     let error = file.rename(name).unwrap_err();
+    ///     assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput, "{name}");
+    /// }
+    /// assert!(file.exists() && !dir.join("sub/b.txt").exists());
+    /// let error = file.change_extension("md/c", &Options::new()).unwrap_err();
+    /// assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    ///
+    /// file.rename("b.txt").unwrap();
+    /// assert_eq!(file.as_path(), dir.join("b.txt"));
+    /// let mut sub = DirectoryInfo::open(dir.join("sub")).unwrap();
+    /// assert!(sub.rename("../elsewhere").is_err());
+    /// sub.rename("renamed").unwrap();
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
     fn rename<T: AsRef<OsStr>>(&mut self, name: T) -> Result<()>;
+    /// Same as [`Action::rename`], recorded in `options.journal`
+    fn rename_with<T: AsRef<OsStr>>(&mut self, name: T, options: &Options) -> Result<()> {
+        let source = self.to_path_buf();
+        let dest = renamed(&source, name.as_ref())?;
+        options.journaled(Operation::Rename, &source, Some(&dest), || {
+            self.rename(name)
+        })
+    }
+    /// Resolve symlinks and relative components, and store the resulting path
+    fn canonicalize(&mut self) -> Result<()> {
+        let path = fs::canonicalize(self.as_path())?;
+        *self = unsafe { Self::open_uncheck(path) };
+        Ok(())
+    }
     fn set_readonly(&self, readonly: bool) -> Result<()> {
         let mut perm = self.metadata()?.permissions();
         perm.set_readonly(readonly);
         self.set_permissions(perm)
     }
     fn set_permissions(&self, perm: Permissions) -> Result<()> {
+        if crate::is_dry_run() {
+            return self.metadata().map(drop);
+        }
         fs::set_permissions(self.as_path(), perm)
     }
+    /// Remove the file or directory. A link is removed itself, its target
+    /// is left alone, and a dangling link is removed too.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(unix)] {
+    /// use fdir::*;
+    /// use std::os::unix::fs::symlink;
+    /// let root = std::env::temp_dir().join("fdir_delete_links_doc");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// std::fs::create_dir_all(root.join("dir")).unwrap();
+    /// std::fs::write(root.join("file"), b"kept").unwrap();
+    /// symlink(root.join("file"), root.join("to_file")).unwrap();
+    /// symlink(root.join("dir"), root.join("to_dir")).unwrap();
+    /// symlink(root.join("missing"), root.join("dangling")).unwrap();
+    /// for link in ["to_file", "to_dir", "dangling"] {
+    ///     unsafe { FileInfo::open_uncheck(root.join(link)) }.delete().unwrap();
+    ///     assert!(std::fs::symlink_metadata(root.join(link)).is_err());
+    /// }
+    /// symlink(root.join("file"), root.join("to_file")).unwrap();
+    /// symlink(root.join("dir"), root.join("to_dir")).unwrap();
+    /// symlink(root.join("missing"), root.join("dangling")).unwrap();
+    /// for link in ["to_file", "to_dir", "dangling"] {
+    ///     unsafe { DirectoryInfo::open_uncheck(root.join(link)) }.delete().unwrap();
+    ///     assert!(std::fs::symlink_metadata(root.join(link)).is_err());
+    /// }
+    /// assert!(root.join("file").is_file() && root.join("dir").is_dir());
+    /// std::fs::remove_dir_all(root).unwrap();
+    /// # }
+    /// ```
     fn delete(self) -> Result<()> {
-        if self.read_only()? {
-            self.set_readonly(false)?;
-        }
-        if self.as_path().is_dir() {
-            remove_dir_all(self.as_path())
-        } else {
-            remove_file(self.as_path())
+        self.delete_with(&Options::default())
+    }
+    /// Same as [`Action::delete`], retried according to `options.retries`.
+    /// In a directory only the entries accepted by `include` and `exclude`
+    /// are removed, the directory itself is kept when anything remains.
+    fn delete_with(self, options: &Options) -> Result<()> {
+        let is_dir = fs::symlink_metadata(self.as_path()).is_ok_and(|data| data.is_dir());
+        if options.has_filters() && is_dir {
+            return dir::_delete_filtered(self.as_path(), options);
         }
+        options.journaled(Operation::Delete, self.as_path(), None, || {
+            options.retry(|| _delete(self.as_path()))
+        })
+    }
+    /// Copy into the existing directory `dest`, keeping the name, and
+    /// return the copy. Prefer it over [`Action::copy_to`], which accepts any
+    /// path and creates missing directories.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_copy_into");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let mut file = FileInfo::create(root.join("src/a.txt")).unwrap();
+    /// let mut dir = DirectoryInfo::open(root.join("src")).unwrap();
+    /// std::fs::create_dir_all(root.join("copies")).unwrap();
+    /// std::fs::create_dir_all(root.join("moved")).unwrap();
+    /// let copies = DirectoryInfo::open(root.join("copies")).unwrap();
+    /// let moved = DirectoryInfo::open(root.join("moved")).unwrap();
+    ///
+    /// let copy = file.copy_into(&copies).unwrap();
+    /// assert_eq!(copy.as_path(), root.join("copies/a.txt"));
+    /// let copy = dir.copy_into(&copies).unwrap();
+    /// assert_eq!(copy.as_path(), root.join("copies/src"));
+    /// assert!(root.join("copies/src/a.txt").is_file());
+    ///
+    /// let moved_file = file.move_into(&moved).unwrap();
+    /// assert_eq!(file.as_path(), root.join("moved/a.txt"));
+    /// assert_eq!(moved_file.as_path(), file.as_path());
+    /// let moved_dir = dir.move_into(&moved).unwrap();
+    /// assert_eq!(moved_dir.as_path(), root.join("moved/src"));
+    /// assert!(!root.join("src").exists());
+    ///
+    /// // the destination must exist
+    /// let missing = unsafe { DirectoryInfo::open_uncheck(root.join("missing")) };
+    /// assert!(copy.copy_into(&missing).is_err());
+    /// assert!(!root.join("missing").exists());
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    fn copy_into(&self, dest: &DirectoryInfo) -> Result<Self> {
+        dest.validate()?;
+        let path = push_file_name(self.as_path(), dest.as_path())?;
+        self.copy_new(&path)?;
+        Ok(unsafe { Self::open_uncheck(path) })
+    }
+    /// Move into the existing directory `dest`, keeping the name, `self`
+    /// and the returned value both point at the new location. Prefer it
+    /// over [`Action::move_to`].
+    fn move_into(&mut self, dest: &DirectoryInfo) -> Result<Self> {
+        dest.validate()?;
+        let path = push_file_name(self.as_path(), dest.as_path())?;
+        self.move_new(&path)?;
+        Ok(unsafe { Self::open_uncheck(self.as_path()) })
     }
-    fn copy_to<P: AsRef<Path>>(&self, path: P) -> TryRecoverResult<()> {
-        let path = push_file_name(self.file_name(), path)?;
+    /// Copy into the directory `path`, see [`Action::copy_into`] for a
+    /// variant that checks the destination
+    fn copy_to<P: AsRef<Path>>(&self, path: P) -> TryRecoverResult<'_, ()> {
+        let path = push_file_name(self.as_path(), path)?;
         self.copy_new(path)
     }
-    fn copy_new<P: AsRef<Path>>(&self, path: P) -> TryRecoverResult<()>;
-    fn move_to<P: AsRef<Path>>(&mut self, path: P) -> TryRecoverResult<()> {
-        let path = push_file_name(self.file_name(), path)?;
+    /// Copying onto itself, however `path` is spelled, fails with
+    /// `ErrorKind::InvalidInput`. On Windows paths longer than `MAX_PATH`
+    /// are given the `\\?\` prefix, `Display` still shows them without it.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(windows)] {
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_long_path");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// let deep: std::path::PathBuf = (0..6).map(|i| format!("{}{}", i, "d".repeat(50))).collect();
+    /// let long = root.join("src").join(&deep).join("file.txt");
+    /// assert!(long.as_os_str().len() > 300);
+    /// let file = FileInfo::create(&long).unwrap();
+    /// assert!(file.as_path().to_string_lossy().starts_with(r"\\?\"));
+    /// assert_eq!(file.to_string(), long.display().to_string());
+    /// let src = DirectoryInfo::open(root.join("src")).unwrap();
+    /// src.copy_new(root.join("copy")).map_err(|e| e.error).unwrap();
+    /// let copy = FileInfo::open(root.join("copy").join(&deep).join("file.txt")).unwrap();
+    /// assert!(!copy.to_string().starts_with(r"\\?\"));
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// # }
+    /// ```
+    fn copy_new<P: AsRef<Path>>(&self, path: P) -> TryRecoverResult<'_, ()>;
+    /// Move into the directory `path`, see [`Action::move_into`]
+    fn move_to<P: AsRef<Path>>(&mut self, path: P) -> TryRecoverResult<'_, ()> {
+        let path = push_file_name(self.as_path(), path)?;
         self.move_new(path)
     }
-    fn move_new<P: AsRef<Path>>(&mut self, path: P) -> TryRecoverResult<()>;
+    /// Moving onto itself, however `path` is spelled, does nothing
+    fn move_new<P: AsRef<Path>>(&mut self, path: P) -> TryRecoverResult<'_, ()>;
 }
+fn _delete(path: &Path) -> Result<()> {
+    // links are removed themselves, even dangling ones
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_symlink() {
+        return remove_link(path, metadata.file_type());
+    }
+    if metadata.permissions().readonly() {
+        unsafe { FileInfo::open_uncheck(path) }.set_readonly(false)?;
+    }
+    if metadata.is_dir() {
+        // read-only files cannot be removed on Windows, failures show up
+        // as the error of `remove_dir_all`
+        #[cfg(windows)]
+        permissions::set_readonly_tree(path, false);
+        remove_dir_all(path)
+    } else {
+        remove_file(path)
+    }
+}
+
+/// Remove the link at `path` without touching its target, on Windows links
+/// to directories and junctions are removed as directories
+fn remove_link(path: &Path, file_type: FileType) -> Result<()> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::FileTypeExt;
+        if file_type.is_symlink_dir() {
+            return fs::remove_dir(path);
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = file_type;
+    remove_file(path)
+}
+
 #[inline]
 fn _delete_file(file: &FileInfo) -> Result<()> {
     file.set_readonly(false)?;
     remove_file(file.as_path())
 }
 
-fn remove_file_any(path: &Path) -> Result<()> {
+pub(crate) fn remove_file_any(path: &Path) -> Result<()> {
     let f = unsafe { FileInfo::open_uncheck(path) };
     f.delete()
 }
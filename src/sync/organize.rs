@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fs::create_dir_all;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use glob::Pattern;
+
+use crate::error::{already_exist, invalid_placeholder};
+use crate::error_kind::vanished;
+use crate::{safe_join, unique_path, ConflictPolicy, Numbering, UpdateCheck};
+
+use super::{Action, DirectoryInfo, FileInfo, Info};
+
+#[derive(Debug, Clone, Default)]
+pub struct OrganizeOptions {
+    pub conflict: ConflictPolicy,
+    /// Names picked by `ConflictPolicy::Rename`
+    pub numbering: Numbering,
+    /// Only report the moves, nothing is created or moved
+    pub dry_run: bool,
+}
+
+/// Result of [`DirectoryInfo::organize_by_extension`] and
+/// [`DirectoryInfo::archive_older_than`]
+///
+/// Files removed by someone else while the directory is organized are
+/// reported in `vanished` instead of failing the operation.
+///
+/// # Examples
+/// ```
+/// use fdir::*;
+/// use fdir::sync::organize::OrganizeOptions;
+/// use glob::Pattern;
+/// let root = std::env::temp_dir().join("fdir_organize_vanished");
+/// let _ = std::fs::remove_dir_all(&root);
+/// let names: Vec<_> = (0..200).map(|i| format!("{}.txt", i)).collect();
+/// for name in &names {
+///     FileInfo::create(root.join(name)).unwrap();
+/// }
+/// let dir = DirectoryInfo::open(&root).unwrap();
+/// // another process removes the files while they are sorted
+/// let cleaner = {
+///     let root = root.clone();
+///     std::thread::spawn(move || {
+///         for name in names.iter().rev() {
+///             let _ = std::fs::remove_file(root.join(name));
+///         }
+///     })
+/// };
+/// let mapping = [(Pattern::new("*.txt").unwrap(), "text")];
+/// let report = dir.organize_by_extension(&mapping, &OrganizeOptions::default()).unwrap();
+/// cleaner.join().unwrap();
+/// assert!(report.moved.len() + report.vanished.len() <= 200);
+/// assert!(report.moved.iter().all(|(_, to)| to.is_file()));
+/// assert!(report.vanished.iter().all(|path| !path.exists()));
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OrganizeReport {
+    /// Source and destination of every move, in listing order
+    pub moved: Vec<(PathBuf, PathBuf)>,
+    /// Files left in place because of `ConflictPolicy::Skip`, or
+    /// `ConflictPolicy::Update` with the default [`UpdateCheck`]
+    pub skipped: Vec<PathBuf>,
+    /// Target folders that were created
+    pub created: Vec<PathBuf>,
+    /// Files removed meanwhile, after the directory was listed
+    pub vanished: Vec<PathBuf>,
+}
+
+impl DirectoryInfo {
+    /// Direct child files grouped by lowercased extension, files without an
+    /// extension are under the empty string
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_group_by_extension");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for name in ["a.TXT", "b.txt", "main.rs", "archive.tar.gz", "Makefile", "sub/c.txt"] {
+    ///     FileInfo::create(root.join(name)).unwrap();
+    /// }
+    /// let groups = DirectoryInfo::open(&root).unwrap().group_by_extension().unwrap();
+    /// let names = |extension: &str| {
+    ///     let mut names: Vec<_> = groups[extension]
+    ///         .iter()
+    ///         .map(|file| file.as_path().file_name().unwrap().to_owned())
+    ///         .collect();
+    ///     names.sort();
+    ///     names
+    /// };
+    /// assert_eq!(groups.keys().collect::<Vec<_>>(), ["", "gz", "rs", "txt"]);
+    /// assert_eq!(names("txt"), ["a.TXT", "b.txt"]);
+    /// assert_eq!(names("gz"), ["archive.tar.gz"]);
+    /// assert_eq!(names(""), ["Makefile"]);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn group_by_extension(&self) -> Result<BTreeMap<String, Vec<FileInfo>>> {
+        let mut groups: BTreeMap<String, Vec<FileInfo>> = BTreeMap::new();
+        for file in self.files()? {
+            let extension = file
+                .as_path()
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            groups.entry(extension).or_default().push(file);
+        }
+        Ok(groups)
+    }
+
+    /// Move the direct child files into subfolders, the first pattern of
+    /// `mapping` matching the file name decides the folder, relative to the
+    /// directory. Files matching no pattern stay in place.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use fdir::*;
+    /// use fdir::sync::organize::OrganizeOptions;
+    /// use glob::Pattern;
+    /// let downloads = DirectoryInfo::open("~/Downloads").unwrap();
+    /// let mapping = [
+    ///     (Pattern::new("*.jpg").unwrap(), "Images"),
+    ///     (Pattern::new("*.pdf").unwrap(), "Documents"),
+    /// ];
+    /// let options = OrganizeOptions { dry_run: true, ..Default::default() };
+    /// let report = downloads.organize_by_extension(&mapping, &options).unwrap();
+    /// ```
+    pub fn organize_by_extension(
+        &self,
+        mapping: &[(Pattern, &str)],
+        options: &OrganizeOptions,
+    ) -> Result<OrganizeReport> {
+        let mut report = OrganizeReport::default();
+        for file in self.files()? {
+            let name = match file.file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            let folder = match mapping.iter().find(|(pattern, _)| pattern.matches(&name)) {
+                Some((_, folder)) => self.as_path().join(folder),
+                None => continue,
+            };
+            let source = file.to_path_buf();
+            let result = place(file, folder.join(&name), options, &mut report);
+            tolerate(result, &source, &mut report)?;
+        }
+        Ok(report)
+    }
+
+    /// Move the direct child files last modified more than `age` ago to
+    /// `pattern`, relative to the directory. `{year}`, `{month}` and `{day}`
+    /// are replaced by the modification date in UTC and `{name}` by the file
+    /// name, which is appended when `pattern` does not use it. Folders are
+    /// created as needed and conflicts follow `options.conflict`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::{Duration, SystemTime};
+    /// use fdir::sync::organize::OrganizeOptions;
+    /// use fdir::*;
+    /// let dir = std::env::temp_dir().join("fdir_archive");
+    /// let _ = std::fs::remove_dir_all(&dir);
+    /// let old = FileInfo::create(dir.join("scan.pdf")).unwrap();
+    /// FileInfo::create(dir.join("new.pdf")).unwrap();
+    /// let january = SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_067_200 + 86_400 * 20);
+    /// let times = std::fs::FileTimes::new().set_modified(january);
+    /// std::fs::File::options().write(true).open(old.as_path()).unwrap().set_times(times).unwrap();
+    ///
+    /// let dir = DirectoryInfo::open(&dir).unwrap();
+    /// let week = Duration::from_secs(7 * 86_400);
+    /// let options = OrganizeOptions::default();
+    /// let report = dir.archive_older_than(week, "archive/{year}/{month}", &options).unwrap();
+    /// let archived = dir.as_path().join("archive/2024/01/scan.pdf");
+    /// assert_eq!(report.moved, [(dir.as_path().join("scan.pdf"), archived.clone())]);
+    /// assert!(archived.is_file() && dir.as_path().join("new.pdf").is_file());
+    /// # dir.delete().unwrap();
+    /// ```
+    pub fn archive_older_than(
+        &self,
+        age: Duration,
+        pattern: &str,
+        options: &OrganizeOptions,
+    ) -> Result<OrganizeReport> {
+        // an invalid pattern fails before anything is moved
+        expand(pattern, (0, 1, 1), OsStr::new(""))?;
+        let cutoff = SystemTime::now().checked_sub(age).unwrap_or(UNIX_EPOCH);
+        let mut report = OrganizeReport::default();
+        for file in self.files()? {
+            let source = file.to_path_buf();
+            let result = self.archive(file, cutoff, pattern, options, &mut report);
+            tolerate(result, &source, &mut report)?;
+        }
+        Ok(report)
+    }
+
+    /// Move `file` to `pattern` if it was last modified before `cutoff`
+    fn archive(
+        &self,
+        file: FileInfo,
+        cutoff: SystemTime,
+        pattern: &str,
+        options: &OrganizeOptions,
+        report: &mut OrganizeReport,
+    ) -> Result<()> {
+        let modified = file.metadata()?.modified()?;
+        let Some(name) = file.file_name().filter(|_| modified < cutoff) else {
+            return Ok(());
+        };
+        let mut relative = PathBuf::from(expand(pattern, civil_date(modified), name)?);
+        if !pattern.contains("{name}") {
+            relative.push(name);
+        }
+        let target = safe_join(self.as_path(), relative)?;
+        place(file, target, options, report)
+    }
+}
+
+/// `result` of organizing `source`, which is reported as vanished when it
+/// was removed meanwhile
+fn tolerate(result: Result<()>, source: &Path, report: &mut OrganizeReport) -> Result<()> {
+    match result {
+        Err(e) if vanished(source, &e) => {
+            report.vanished.push(source.to_path_buf());
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Move `file` to `target` unless `options` say otherwise, creating its folder
+fn place(
+    mut file: FileInfo,
+    mut target: PathBuf,
+    options: &OrganizeOptions,
+    report: &mut OrganizeReport,
+) -> Result<()> {
+    if target.exists() {
+        match options.conflict {
+            ConflictPolicy::Fail => return Err(already_exist(&target)),
+            ConflictPolicy::Skip => {
+                report.skipped.push(file.as_path().to_path_buf());
+                return Ok(());
+            }
+            ConflictPolicy::Rename => target = unique_path(&target, options.numbering),
+            ConflictPolicy::Update
+                if UpdateCheck::default().is_up_to_date(file.as_path(), &target)? =>
+            {
+                report.skipped.push(file.as_path().to_path_buf());
+                return Ok(());
+            }
+            ConflictPolicy::Overwrite | ConflictPolicy::Update => (),
+        }
+    }
+    if let Some(folder) = target.parent() {
+        if !folder.is_dir() && !report.created.iter().any(|created| created == folder) {
+            if !options.dry_run {
+                create_dir_all(folder)?;
+            }
+            report.created.push(folder.to_path_buf());
+        }
+    }
+    let source = file.as_path().to_path_buf();
+    if !options.dry_run {
+        file.move_new(&target).or_else(|op| op.try_recover())?;
+    }
+    report.moved.push((source, target));
+    Ok(())
+}
+
+/// `pattern` with its placeholders replaced by the parts of `date` and `name`
+fn expand(pattern: &str, (year, month, day): (i64, u32, u32), name: &OsStr) -> Result<OsString> {
+    let mut expanded = OsString::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        expanded.push(&rest[..start]);
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => return Err(invalid_placeholder(&rest[start..], pattern)),
+        };
+        match &rest[start + 1..end] {
+            "year" => expanded.push(format!("{:04}", year)),
+            "month" => expanded.push(format!("{:02}", month)),
+            "day" => expanded.push(format!("{:02}", day)),
+            "name" => expanded.push(name),
+            _ => return Err(invalid_placeholder(&rest[start..=end], pattern)),
+        }
+        rest = &rest[end + 1..];
+    }
+    expanded.push(rest);
+    Ok(expanded)
+}
+
+/// Year, month and day of `time` in UTC
+fn civil_date(time: SystemTime) -> (i64, u32, u32) {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs_f64().ceil() as i64),
+    };
+    // `civil_from_days` of Howard Hinnant's date algorithms
+    let days = seconds.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month as u32, day as u32)
+}
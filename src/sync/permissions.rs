@@ -0,0 +1,175 @@
+use std::fs::{self, Metadata, Permissions};
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+
+use crate::{RecursiveResult, WalkOptions};
+
+use super::visit::{VisitAction, Visitor};
+use super::walk::WalkEntry;
+use super::{Action, DirectoryInfo, FileInfo, Info};
+
+impl DirectoryInfo {
+    /// Same as [`Action::set_readonly`](super::Action::set_readonly) for the
+    /// directory and every entry below it. Links are left alone and entries
+    /// that already have the flag are not touched.
+    ///
+    /// Directories are made writable before their content, so that what
+    /// they hold becomes reachable, and read-only after it. An entry that
+    /// fails does not stop the walk, it is listed in the result.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_readonly_recursive");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// FileInfo::create(root.join("a/b.txt")).unwrap();
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let result = dir.set_readonly_recursive(true).unwrap();
+    /// assert!(result.is_ok() && result.changed == 3);
+    /// assert!(FileInfo::open(root.join("a/b.txt")).unwrap().read_only().unwrap());
+    /// let result = dir.set_readonly_recursive(false).unwrap();
+    /// assert!(result.is_ok() && result.changed == 3);
+    /// # dir.delete().unwrap();
+    /// ```
+    pub fn set_readonly_recursive(&self, readonly: bool) -> Result<RecursiveResult> {
+        self.validate()?;
+        Ok(set_readonly_tree(self.as_path(), readonly))
+    }
+
+    /// Give `file_perm` to every file below the directory and `dir_perm` to
+    /// the directory and every directory below it. Directories are changed
+    /// before their content unless `dir_perm` is read-only, otherwise as
+    /// [`DirectoryInfo::set_readonly_recursive`].
+    pub fn set_permissions_recursive(
+        &self,
+        file_perm: Permissions,
+        dir_perm: Permissions,
+    ) -> Result<RecursiveResult> {
+        self.validate()?;
+        let top_down = !dir_perm.readonly();
+        Ok(change_tree(
+            self.as_path(),
+            top_down,
+            &mut |path, metadata| {
+                let perm = if metadata.is_dir() {
+                    &dir_perm
+                } else {
+                    &file_perm
+                };
+                fs::set_permissions(path, perm.clone()).map(|_| true)
+            },
+        ))
+    }
+}
+
+/// Set or clear the read-only flag of `path` and every entry below it, see
+/// [`DirectoryInfo::set_readonly_recursive`]
+pub(crate) fn set_readonly_tree(path: &Path, readonly: bool) -> RecursiveResult {
+    change_tree(path, !readonly, &mut |path, metadata| {
+        let mut perm = metadata.permissions();
+        if perm.readonly() == readonly {
+            return Ok(false);
+        }
+        perm.set_readonly(readonly);
+        fs::set_permissions(path, perm).map(|_| true)
+    })
+}
+
+/// Call `change` for `path` and every entry below it, a directory before its
+/// content when `top_down` and after it otherwise. `change` tells whether it
+/// changed the entry, links are skipped.
+fn change_tree(
+    path: &Path,
+    top_down: bool,
+    change: &mut dyn FnMut(&Path, &Metadata) -> Result<bool>,
+) -> RecursiveResult {
+    let mut visitor = ChangeVisitor {
+        top_down,
+        change,
+        open: vec![path.to_path_buf()],
+        result: RecursiveResult::default(),
+    };
+    // the directory itself may be reached through a link
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            visitor.result.failed.push((path.to_path_buf(), e));
+            return visitor.result;
+        }
+    };
+    if !metadata.is_dir() {
+        visitor.apply(path, &metadata);
+        return visitor.result;
+    }
+    if top_down {
+        visitor.apply(path, &metadata);
+    }
+    let dir = unsafe { DirectoryInfo::open_uncheck(path) };
+    // every error is recorded, the visit itself cannot fail
+    let _ = dir.visit(&WalkOptions::default(), &mut visitor);
+    if !top_down {
+        visitor.apply(path, &metadata);
+    }
+    visitor.result
+}
+
+/// Applies the change of [`change_tree`] to the entries below its root
+struct ChangeVisitor<'a> {
+    top_down: bool,
+    change: &'a mut dyn FnMut(&Path, &Metadata) -> Result<bool>,
+    /// The directories entered, the root first. An error of the walk is
+    /// reported for the innermost one, whose listing failed.
+    open: Vec<PathBuf>,
+    result: RecursiveResult,
+}
+
+impl ChangeVisitor<'_> {
+    fn apply(&mut self, path: &Path, metadata: &Metadata) {
+        match (self.change)(path, metadata) {
+            Ok(changed) => self.result.changed += u64::from(changed),
+            Err(e) => self.result.failed.push((path.to_path_buf(), e)),
+        }
+    }
+
+    fn apply_path(&mut self, path: &Path) {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) => self.apply(path, &metadata),
+            Err(e) => self.result.failed.push((path.to_path_buf(), e)),
+        }
+    }
+}
+
+impl Visitor for ChangeVisitor<'_> {
+    fn enter_dir(&mut self, dir: &DirectoryInfo) -> VisitAction {
+        if self.top_down {
+            self.apply_path(dir.as_path());
+        }
+        self.open.push(dir.to_path_buf());
+        VisitAction::Descend
+    }
+
+    fn visit_file(&mut self, file: &FileInfo) -> Result<()> {
+        self.apply_path(file.as_path());
+        Ok(())
+    }
+
+    fn leave_dir(&mut self, dir: &DirectoryInfo) {
+        self.open.pop();
+        if !self.top_down {
+            self.apply_path(dir.as_path());
+        }
+    }
+
+    fn visit_other(&mut self, entry: &WalkEntry) -> Result<()> {
+        if !entry.file_type().is_symlink() {
+            self.apply_path(entry.path());
+        }
+        Ok(())
+    }
+
+    fn visit_error(&mut self, error: Error) -> Result<()> {
+        let dir = self.open.last().expect("the root is never left");
+        self.result.failed.push((dir.clone(), error));
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::io::Result;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use glob::Pattern;
+
+use crate::error::quota_exceeded;
+use crate::options::matches;
+use crate::{Size, WalkOptions};
+
+use super::{Action, DirectoryInfo, Info};
+
+/// A size limit for the files below a directory
+#[derive(Debug, Clone, Default)]
+pub struct Quota {
+    pub limit: Size,
+    /// Which entries count towards the limit, and may be evicted
+    pub walk: WalkOptions,
+}
+
+impl Quota {
+    pub fn new(limit: impl Into<Size>) -> Self {
+        Self {
+            limit: limit.into(),
+            walk: WalkOptions::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum QuotaPolicy {
+    /// Only report the usage
+    #[default]
+    Reject,
+    /// Delete the least recently modified files, then by path, until the
+    /// usage is under the limit. Files matching `protected` are never
+    /// deleted, see `Options::exclude` for how patterns match.
+    EvictOldest { protected: Vec<Pattern> },
+}
+
+/// Result of [`DirectoryInfo::enforce_quota`]
+#[derive(Debug, Clone, Default)]
+pub struct QuotaReport {
+    pub limit: Size,
+    /// Usage after eviction
+    pub used: Size,
+    /// Deleted files, oldest first
+    pub evicted: Vec<PathBuf>,
+    /// Total length of the deleted files
+    pub freed: Size,
+}
+
+impl QuotaReport {
+    pub fn is_over(&self) -> bool {
+        self.used > self.limit
+    }
+    /// How far the usage is over the limit, 0 when under
+    pub fn excess(&self) -> Size {
+        self.used.saturating_sub(self.limit)
+    }
+    /// How much can still be added before reaching the limit
+    pub fn remaining(&self) -> Size {
+        self.limit.saturating_sub(self.used)
+    }
+}
+
+impl DirectoryInfo {
+    /// Whether adding `incoming` bytes would take the directory over `quota`
+    pub fn would_exceed(&self, quota: &Quota, incoming: impl Into<Size>) -> Result<bool> {
+        let used = self.size_checked(&quota.walk)?.bytes;
+        Ok(used.saturating_add(incoming.into()) > quota.limit)
+    }
+
+    /// Copy `source` into the directory, keeping its name, see
+    /// [`Action::copy_into`]. Fails with `ErrorKind::StorageFull` before
+    /// anything is written if its size would take the directory over `quota`.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::quota::Quota;
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_copy_into_within");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// FileInfo::create(root.join("user/old.bin")).unwrap().write([0; 60]).unwrap();
+    /// let small = FileInfo::create(root.join("small.bin")).unwrap();
+    /// small.write([1; 40]).unwrap();
+    /// let large = FileInfo::create(root.join("large.bin")).unwrap();
+    /// large.write([2; 41]).unwrap();
+    /// let user = DirectoryInfo::open(root.join("user")).unwrap();
+    /// let quota = Quota::new(100);
+    ///
+    /// let error = user.copy_into_within(&large, &quota).unwrap_err();
+    /// assert_eq!(fdir::ErrorKind::of(&error), fdir::ErrorKind::StorageFull);
+    /// assert!(!root.join("user/large.bin").exists());
+    /// assert_eq!(user.size(), 60);
+    ///
+    /// let copy = user.copy_into_within(&small, &quota).unwrap();
+    /// assert_eq!(copy.as_path(), root.join("user/small.bin"));
+    /// assert_eq!(user.size(), 100);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn copy_into_within<T: Action>(&self, source: &T, quota: &Quota) -> Result<T> {
+        let incoming = Size(source.size());
+        if self.would_exceed(quota, incoming)? {
+            return Err(quota_exceeded(self.as_path(), quota.limit, incoming));
+        }
+        source.copy_into(self)
+    }
+
+    /// Measure the usage against `quota` and apply `policy` if it is over
+    pub fn enforce_quota(&self, quota: &Quota, policy: &QuotaPolicy) -> Result<QuotaReport> {
+        let mut report = QuotaReport {
+            limit: quota.limit,
+            ..Default::default()
+        };
+        let mut candidates: Vec<(SystemTime, PathBuf, Size)> = Vec::new();
+        for entry in self.walk_with(&quota.walk) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let len = Size(metadata.len());
+            report.used += len;
+            if let QuotaPolicy::EvictOldest { protected } = policy {
+                let relative = entry
+                    .path()
+                    .strip_prefix(self.as_path())
+                    .unwrap_or(entry.path());
+                if !protected.iter().any(|pattern| matches(pattern, relative)) {
+                    candidates.push((metadata.modified()?, entry.into_path(), len));
+                }
+            }
+        }
+        if !report.is_over() {
+            return Ok(report);
+        }
+        candidates.sort();
+        for (_, path, len) in candidates {
+            if !report.is_over() {
+                break;
+            }
+            fs::remove_file(&path)?;
+            report.used -= len;
+            report.freed += len;
+            report.evicted.push(path);
+        }
+        Ok(report)
+    }
+}
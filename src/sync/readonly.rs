@@ -0,0 +1,267 @@
+use std::fmt::{Display, Write};
+use std::fs::Metadata;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::stats::DirSize;
+use crate::{DirStats, WalkOptions};
+
+use super::hash::VerifyReport;
+use super::search::Match;
+use super::text::{Bom, LineEnding};
+use super::{
+    Action, Algorithm, DirectoryInfo, EntryFilter, FileInfo, Info, SearchQuery, TreeOptions, Walk,
+};
+
+/// A [`FileInfo`] or [`DirectoryInfo`] that only offers reading: it
+/// implements [`Info`] but not [`Action`], so it cannot be deleted, moved,
+/// renamed or written through. Listing a read-only directory gives read-only
+/// entries.
+///
+/// This restricts what code holding the value can call, it does not protect
+/// the path from being opened again with [`Action::open`].
+///
+/// # Examples
+/// ```
+/// use fdir::{Action, FileInfo, Info, ReadOnlyDirectoryInfo};
+/// let dir = std::env::temp_dir().join("fdir_read_only");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// FileInfo::create(dir.join("a.txt")).unwrap().write("hello").unwrap();
+///
+/// let dir = ReadOnlyDirectoryInfo::open(&dir).unwrap();
+/// let files = dir.files().unwrap();
+/// assert_eq!(files[0].read_to_string().unwrap(), "hello");
+/// assert_eq!(files[0].size(), 5);
+/// assert_eq!(dir.stats().unwrap().files, 1);
+/// dir.try_into_writable().unwrap().delete().unwrap();
+/// ```
+///
+/// Nothing that changes the entry compiles:
+/// ```compile_fail
+/// use fdir::{Action, ReadOnlyFileInfo};
+/// let file = ReadOnlyFileInfo::open("/tmp/plugin.txt").unwrap();
+/// file.delete().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReadOnly<T>(T);
+
+pub type ReadOnlyFileInfo = ReadOnly<FileInfo>;
+pub type ReadOnlyDirectoryInfo = ReadOnly<DirectoryInfo>;
+
+impl<T: Display> Display for ReadOnly<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: Action> ReadOnly<T> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        T::open(path).map(ReadOnly)
+    }
+
+    /// The full type, after checking again that the path still exists and
+    /// is still the expected kind
+    pub fn try_into_writable(self) -> Result<T> {
+        T::open(self.0.as_path())
+    }
+}
+
+impl<T: Info> Info for ReadOnly<T> {
+    fn as_path(&self) -> &Path {
+        self.0.as_path()
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        self.0.metadata()
+    }
+
+    fn size(&self) -> u64 {
+        self.0.size()
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.0.validate()
+    }
+}
+
+impl FileInfo {
+    /// Give up the mutating operations, see [`ReadOnly`]
+    pub fn into_read_only(self) -> ReadOnlyFileInfo {
+        ReadOnly(self)
+    }
+}
+
+impl DirectoryInfo {
+    /// Give up the mutating operations, see [`ReadOnly`]
+    pub fn into_read_only(self) -> ReadOnlyDirectoryInfo {
+        ReadOnly(self)
+    }
+}
+
+fn read_only<T>(entries: Vec<T>) -> Vec<ReadOnly<T>> {
+    entries.into_iter().map(ReadOnly).collect()
+}
+
+/// Read operations of [`FileInfo`], see there
+impl ReadOnlyFileInfo {
+    /// The containing directory, read-only as well, `Ok(None)` for a root
+    /// directory. `Info::try_parent` still gives a [`DirectoryInfo`].
+    pub fn try_parent(&self) -> Result<Option<ReadOnlyDirectoryInfo>> {
+        Ok(self.0.try_parent()?.map(ReadOnly))
+    }
+
+    #[deprecated(note = "use `try_parent`, which reports why the parent cannot be opened")]
+    pub fn parent(&self) -> Option<ReadOnlyDirectoryInfo> {
+        self.try_parent().ok().flatten()
+    }
+
+    pub fn read(&self) -> Result<Vec<u8>> {
+        self.0.read()
+    }
+
+    pub fn read_to_string(&self) -> Result<String> {
+        self.0.read_to_string()
+    }
+
+    pub fn hash(&self, algorithm: Algorithm) -> Result<String> {
+        self.0.hash(algorithm)
+    }
+
+    pub fn is_probably_text(&self) -> Result<bool> {
+        self.0.is_probably_text()
+    }
+
+    pub fn detect_bom(&self) -> Result<Option<Bom>> {
+        self.0.detect_bom()
+    }
+
+    pub fn line_endings_to_convert(&self, ending: LineEnding) -> Result<u64> {
+        self.0.line_endings_to_convert(ending)
+    }
+
+    #[cfg(feature = "infer")]
+    pub fn detect_type(&self) -> Result<Option<super::detect::FileKind>> {
+        self.0.detect_type()
+    }
+
+    #[cfg(feature = "infer")]
+    pub fn matches_extension(&self) -> Result<bool> {
+        self.0.matches_extension()
+    }
+}
+
+/// Read operations of [`DirectoryInfo`], see there. Entries are returned
+/// read-only.
+impl ReadOnlyDirectoryInfo {
+    /// The containing directory, read-only as well, `Ok(None)` for a root
+    /// directory. `Info::try_parent` still gives a [`DirectoryInfo`].
+    pub fn try_parent(&self) -> Result<Option<ReadOnlyDirectoryInfo>> {
+        Ok(self.0.try_parent()?.map(ReadOnly))
+    }
+
+    #[deprecated(note = "use `try_parent`, which reports why the parent cannot be opened")]
+    pub fn parent(&self) -> Option<ReadOnlyDirectoryInfo> {
+        self.try_parent().ok().flatten()
+    }
+
+    pub fn children(&self) -> Result<Vec<PathBuf>> {
+        self.0.children()
+    }
+
+    pub fn files(&self) -> Result<Vec<ReadOnlyFileInfo>> {
+        self.0.files().map(read_only)
+    }
+
+    pub fn directories(&self) -> Result<Vec<ReadOnlyDirectoryInfo>> {
+        self.0.directories().map(read_only)
+    }
+
+    pub fn count_entries(&self, filter: EntryFilter, recursive: bool) -> Result<u64> {
+        self.0.count_entries(filter, recursive)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        self.0.is_empty()
+    }
+
+    pub fn find_case_insensitive(&self, name: impl AsRef<std::ffi::OsStr>) -> Result<Vec<PathBuf>> {
+        self.0.find_case_insensitive(name)
+    }
+
+    pub fn walk(&self) -> Walk {
+        self.0.walk()
+    }
+
+    pub fn walk_with(&self, options: &WalkOptions) -> Walk {
+        self.0.walk_with(options)
+    }
+
+    pub fn stats(&self) -> Result<DirStats> {
+        self.0.stats()
+    }
+
+    pub fn stats_with(&self, options: &WalkOptions) -> Result<DirStats> {
+        self.0.stats_with(options)
+    }
+
+    pub fn size_checked(&self, options: &WalkOptions) -> Result<DirSize> {
+        self.0.size_checked(options)
+    }
+
+    pub fn newest_file(&self, recursive: bool) -> Result<Option<ReadOnlyFileInfo>> {
+        self.0.newest_file(recursive).map(|file| file.map(ReadOnly))
+    }
+
+    pub fn oldest_file(&self, recursive: bool) -> Result<Option<ReadOnlyFileInfo>> {
+        self.0.oldest_file(recursive).map(|file| file.map(ReadOnly))
+    }
+
+    pub fn modified_since(
+        &self,
+        since: SystemTime,
+        recursive: bool,
+    ) -> Result<Vec<ReadOnlyFileInfo>> {
+        self.0.modified_since(since, recursive).map(read_only)
+    }
+
+    pub fn tree(&self, options: &TreeOptions) -> Result<String> {
+        self.0.tree(options)
+    }
+
+    pub fn write_tree<W: Write>(&self, w: &mut W, options: &TreeOptions) -> Result<()> {
+        self.0.write_tree(w, options)
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<Match>> {
+        self.0.search(query)
+    }
+
+    pub fn search_count(&self, query: &SearchQuery) -> Result<u64> {
+        self.0.search_count(query)
+    }
+
+    pub fn verify_manifest(&self, manifest: impl AsRef<Path>) -> Result<VerifyReport> {
+        self.0.verify_manifest(manifest)
+    }
+
+    pub fn verify_manifest_parallel(
+        &self,
+        manifest: impl AsRef<Path>,
+        threads: usize,
+    ) -> Result<VerifyReport> {
+        self.0.verify_manifest_parallel(manifest, threads)
+    }
+
+    pub fn find_duplicates(&self, algorithm: Algorithm) -> Result<Vec<Vec<PathBuf>>> {
+        self.0.find_duplicates(algorithm)
+    }
+
+    pub fn find_duplicates_parallel(
+        &self,
+        algorithm: Algorithm,
+        threads: usize,
+    ) -> Result<Vec<Vec<PathBuf>>> {
+        self.0.find_duplicates_parallel(algorithm, threads)
+    }
+}
@@ -1,7 +1,5 @@
-use super::dir::_write_dir;
 use super::{dir::DirectoryInfo, file::FileInfo};
-use super::{remove_file_any, Action, Info};
-use std::fs::{copy, rename};
+use crate::Options;
 use std::io::Result;
 use std::io::{Error, ErrorKind};
 use std::path::PathBuf;
@@ -43,36 +41,22 @@ impl<'a> TryRecover<'a> {
         }
     }
     pub fn try_recover(self) -> Result<()> {
+        self.try_recover_with(&Options::new())
+    }
+    /// Same as [`TryRecover::try_recover`], overwriting with `options`, e.g.
+    /// to record the operations in `options.journal`
+    pub fn try_recover_with(self, options: &Options) -> Result<()> {
         if self.error.kind() == ErrorKind::AlreadyExists {
             let status = match self.status {
                 Some(status) => status,
                 _ => return Err(self.error),
             };
+            let options = options.clone().overwrite(true);
             match status {
-                CopyFile(f, to) => {
-                    remove_file_any(&to)?;
-                    copy(f.as_path(), to)?;
-                    Ok(())
-                }
-                MoveFile(f, to) => {
-                    remove_file_any(&to)?;
-                    if rename(f.as_path(), &to).is_err() {
-                        copy(f.as_path(), &to)?;
-                        remove_file_any(f.as_path())?;
-                        *f = unsafe { FileInfo::open_uncheck(to) };
-                    }
-                    Ok(())
-                }
-                CopyDirectory(dir, to) => {
-                    _write_dir(dir.clone(), &to, true)
-                }
-                MoveDirectory(dir, to) => {
-                    _write_dir(dir.clone(), &to, true)?;
-                    *dir = unsafe {
-                        DirectoryInfo::open_uncheck(to)
-                    };
-                    Ok(())
-                }
+                CopyFile(f, to) => f.copy_new_with(to, &options).map(drop),
+                MoveFile(f, to) => f.move_new_with(to, &options).map(drop),
+                CopyDirectory(dir, to) => dir.copy_new_with(to, &options).map(drop),
+                MoveDirectory(dir, to) => dir.move_new_with(to, &options).map(drop),
             }
         } else {
             Err(self.error)
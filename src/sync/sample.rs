@@ -0,0 +1,175 @@
+use std::collections::BinaryHeap;
+use std::fs::Metadata;
+use std::hash::{BuildHasher, RandomState};
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+use super::dir::file_walk;
+use super::{Action, DirectoryInfo, FileInfo, Info};
+
+/// Decides which files are sampled, see `DirectoryInfo::sample_matching`
+type FilePred<'a> = &'a dyn Fn(&Path, &Metadata) -> bool;
+
+impl DirectoryInfo {
+    /// Up to `n` files picked uniformly at random, in random order. Only
+    /// direct children are considered unless `recursive` is set, symlinks
+    /// are not followed.
+    ///
+    /// The walk is streamed and only the `n` files kept so far are held:
+    /// each file gets a key hashed from `seed` and its path relative to the
+    /// directory, and the `n` lowest keys win. The same seed thus picks the
+    /// same files from the same tree, whatever order the filesystem lists
+    /// them in. Without a seed a random one is used. The keys are not
+    /// cryptographic.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_sample");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for i in 0..20 {
+    ///     FileInfo::create(root.join(format!("sub/{}.jpg", i))).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let names = |files: Vec<FileInfo>| -> Vec<_> {
+    ///     files.iter().map(|file| file.to_path_buf()).collect()
+    /// };
+    /// let sample = names(dir.sample(5, true, Some(42)).unwrap());
+    /// assert_eq!(sample.len(), 5);
+    /// assert_eq!(sample, names(dir.sample(5, true, Some(42)).unwrap()));
+    /// assert!(dir.sample(5, false, None).unwrap().is_empty());
+    /// assert_eq!(dir.sample(50, true, None).unwrap().len(), 20);
+    /// assert!(dir.random_file(true).unwrap().is_some());
+    /// # dir.delete().unwrap();
+    /// ```
+    pub fn sample(&self, n: usize, recursive: bool, seed: Option<u64>) -> Result<Vec<FileInfo>> {
+        self._sample(n, recursive, seed, None)
+    }
+
+    /// Same as [`DirectoryInfo::sample`], among the files accepted by
+    /// `pred`, called with the path and metadata of each file
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_sample_matching");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for (name, len) in [("a.jpg", 10), ("b.JPG", 2000), ("c.png", 3000), ("d.jpg", 4000)] {
+    ///     let file = FileInfo::create(root.join(name)).unwrap();
+    ///     std::fs::write(file.as_path(), vec![0; len]).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let large_jpegs = |path: &Path, metadata: &std::fs::Metadata| {
+    ///     let jpeg = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("jpg"));
+    ///     jpeg && metadata.len() >= 1000
+    /// };
+    /// let sample = dir.sample_matching(5, false, Some(7), large_jpegs).unwrap();
+    /// let mut names: Vec<_> = sample.iter().map(|file| file.file_name_lossy()).collect();
+    /// names.sort();
+    /// assert_eq!(names, ["b.JPG", "d.jpg"]);
+    /// # dir.delete().unwrap();
+    /// ```
+    pub fn sample_matching(
+        &self,
+        n: usize,
+        recursive: bool,
+        seed: Option<u64>,
+        pred: impl Fn(&Path, &Metadata) -> bool,
+    ) -> Result<Vec<FileInfo>> {
+        self._sample(n, recursive, seed, Some(&pred))
+    }
+
+    /// One file picked at random, `None` when there is none, see
+    /// [`DirectoryInfo::sample`]
+    pub fn random_file(&self, recursive: bool) -> Result<Option<FileInfo>> {
+        Ok(self.sample(1, recursive, None)?.pop())
+    }
+
+    fn _sample(
+        &self,
+        n: usize,
+        recursive: bool,
+        seed: Option<u64>,
+        pred: Option<FilePred<'_>>,
+    ) -> Result<Vec<FileInfo>> {
+        let mut reservoir = Reservoir::new(n, seed);
+        for entry in self.walk_with(&file_walk(recursive)) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if let Some(pred) = pred {
+                if !pred(entry.path(), &entry.metadata()?) {
+                    continue;
+                }
+            }
+            let relative = entry
+                .path()
+                .strip_prefix(self.as_path())
+                .unwrap_or(entry.path());
+            reservoir.offer(relative, entry.path());
+        }
+        Ok(reservoir
+            .into_paths()
+            .into_iter()
+            .map(|path| unsafe { FileInfo::open_uncheck(path) })
+            .collect())
+    }
+}
+
+/// The `n` paths with the lowest keys offered so far, see
+/// `DirectoryInfo::sample`
+pub(crate) struct Reservoir {
+    n: usize,
+    seed: u64,
+    /// The greatest key kept on top, to be replaced first
+    kept: BinaryHeap<(u64, PathBuf)>,
+}
+
+impl Reservoir {
+    pub(crate) fn new(n: usize, seed: Option<u64>) -> Self {
+        Self {
+            n,
+            seed: seed.unwrap_or_else(|| RandomState::new().hash_one(0u8)),
+            kept: BinaryHeap::with_capacity(n.saturating_add(1).min(1024)),
+        }
+    }
+
+    /// Consider the file at `path`, `relative` to the sampled directory
+    pub(crate) fn offer(&mut self, relative: &Path, path: &Path) {
+        let key = self.key(relative);
+        if self.kept.len() < self.n {
+            self.kept.push((key, path.to_path_buf()));
+        } else if self
+            .kept
+            .peek()
+            .is_some_and(|(greatest, _)| key < *greatest)
+        {
+            self.kept.pop();
+            self.kept.push((key, path.to_path_buf()));
+        }
+    }
+
+    /// FNV-1a of the path seeded with `seed`, then the SplitMix64 finalizer
+    /// to spread similar paths apart. Unlike `DefaultHasher` it is stable
+    /// across Rust releases.
+    fn key(&self, relative: &Path) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325 ^ self.seed;
+        for &byte in relative.as_os_str().as_encoded_bytes() {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        hash ^ (hash >> 31)
+    }
+
+    /// The kept paths by increasing key, a random order
+    pub(crate) fn into_paths(self) -> Vec<PathBuf> {
+        self.kept
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect()
+    }
+}
@@ -0,0 +1,230 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, Read, Result, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+
+use crate::WalkOptions;
+
+use super::visit::Visitor;
+use super::{DirectoryInfo, FileInfo, Info};
+
+/// Number of leading bytes inspected by the binary-file heuristic
+const BINARY_PROBE: usize = 8 * 1024;
+
+#[derive(Debug, Clone)]
+pub enum Needle {
+    Text(String),
+    #[cfg(feature = "regex")]
+    Regex(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub needle: Needle,
+    pub case_sensitive: bool,
+    /// Only search files whose path relative to the searched directory matches
+    pub include: Option<Pattern>,
+    /// Skip files containing a NUL byte in their first 8 KiB
+    pub skip_binary: bool,
+    /// Skip files larger than this many bytes
+    pub max_file_size: Option<u64>,
+    pub walk: WalkOptions,
+}
+
+impl SearchQuery {
+    pub fn text(needle: impl Into<String>) -> Self {
+        Self::new(Needle::Text(needle.into()))
+    }
+    #[cfg(feature = "regex")]
+    pub fn regex(needle: impl Into<String>) -> Self {
+        Self::new(Needle::Regex(needle.into()))
+    }
+    fn new(needle: Needle) -> Self {
+        Self {
+            needle,
+            case_sensitive: true,
+            include: None,
+            skip_binary: true,
+            max_file_size: None,
+            walk: WalkOptions::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub path: PathBuf,
+    /// 1-based line number
+    pub line: u64,
+    /// The matching line without its line ending
+    pub text: String,
+}
+
+enum Matcher {
+    Text(String),
+    TextIgnoreCase(String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl Matcher {
+    fn new(query: &SearchQuery) -> Result<Self> {
+        Ok(match &query.needle {
+            Needle::Text(text) if query.case_sensitive => Matcher::Text(text.clone()),
+            Needle::Text(text) => Matcher::TextIgnoreCase(text.to_lowercase()),
+            #[cfg(feature = "regex")]
+            Needle::Regex(pattern) => Matcher::Regex(
+                regex::RegexBuilder::new(pattern)
+                    .case_insensitive(!query.case_sensitive)
+                    .build()
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+            ),
+        })
+    }
+    fn is_match(&self, line: &str) -> bool {
+        match self {
+            Matcher::Text(text) => line.contains(text.as_str()),
+            Matcher::TextIgnoreCase(text) => line.to_lowercase().contains(text.as_str()),
+            #[cfg(feature = "regex")]
+            Matcher::Regex(regex) => regex.is_match(line),
+        }
+    }
+}
+
+impl DirectoryInfo {
+    /// Search the content of every file below the directory line by line
+    ///
+    /// Files are streamed, never read into memory as a whole. Files that
+    /// cannot be read are skipped. The tree is traversed according to
+    /// `query.walk` with [`DirectoryInfo::visit`], which is always depth
+    /// first.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_search");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for (name, content) in [("a.txt", "one\nTwo\n"), ("b/c.md", "two\n"), ("d.bin", "two\0")] {
+    ///     let file = FileInfo::create(root.join(name)).unwrap();
+    ///     std::fs::write(file.as_path(), content).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let mut query = SearchQuery::text("two");
+    /// let found = dir.search(&query).unwrap();
+    /// assert_eq!(found.len(), 1);
+    /// assert_eq!((found[0].path.as_path(), found[0].line), (root.join("b/c.md").as_path(), 1));
+    /// query.case_sensitive = false;
+    /// query.include = Some(glob::Pattern::new("*.txt").unwrap());
+    /// let found = dir.search(&query).unwrap();
+    /// assert_eq!((found[0].line, found[0].text.as_str()), (2, "Two"));
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<Match>> {
+        let mut matches = Vec::new();
+        self._search(query, |path, line, text| {
+            matches.push(Match {
+                path: path.to_path_buf(),
+                line,
+                text: text.to_string(),
+            })
+        })?;
+        Ok(matches)
+    }
+
+    /// Count the matching lines without collecting them
+    pub fn search_count(&self, query: &SearchQuery) -> Result<u64> {
+        let mut count = 0;
+        self._search(query, |_, _, _| count += 1)?;
+        Ok(count)
+    }
+
+    fn _search<F>(&self, query: &SearchQuery, mut on_match: F) -> Result<()>
+    where
+        F: FnMut(&Path, u64, &str),
+    {
+        let matcher = Matcher::new(query)?;
+        std::fs::read_dir(self.as_path())?;
+        let mut visitor = SearchVisitor {
+            root: self.as_path(),
+            query,
+            matcher: &matcher,
+            on_match: &mut on_match,
+        };
+        self.visit(&query.walk, &mut visitor)
+    }
+}
+
+/// Searches the files of [`DirectoryInfo::search`]
+struct SearchVisitor<'a, F> {
+    root: &'a Path,
+    query: &'a SearchQuery,
+    matcher: &'a Matcher,
+    on_match: &'a mut F,
+}
+
+impl<F> Visitor for SearchVisitor<'_, F>
+where
+    F: FnMut(&Path, u64, &str),
+{
+    fn visit_file(&mut self, file: &FileInfo) -> Result<()> {
+        if let Some(include) = &self.query.include {
+            let relative = file
+                .as_path()
+                .strip_prefix(self.root)
+                .unwrap_or(file.as_path());
+            if !include.matches_path(relative) {
+                return Ok(());
+            }
+        }
+        let _ = search_file(file.as_path(), self.query, self.matcher, self.on_match);
+        Ok(())
+    }
+
+    // unreadable directories are skipped like unreadable files
+    fn visit_error(&mut self, _: Error) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn search_file<F>(
+    path: &Path,
+    query: &SearchQuery,
+    matcher: &Matcher,
+    on_match: &mut F,
+) -> Result<()>
+where
+    F: FnMut(&Path, u64, &str),
+{
+    let mut file = File::open(path)?;
+    if let Some(max) = query.max_file_size {
+        if file.metadata()?.len() > max {
+            return Ok(());
+        }
+    }
+    if query.skip_binary {
+        let mut probe = Vec::with_capacity(BINARY_PROBE);
+        (&mut file)
+            .take(BINARY_PROBE as u64)
+            .read_to_end(&mut probe)?;
+        if probe.contains(&0) {
+            return Ok(());
+        }
+        file.seek(SeekFrom::Start(0))?;
+    }
+    let mut reader = BufReader::new(file);
+    let mut buf = Vec::new();
+    let mut line = 0;
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            return Ok(());
+        }
+        line += 1;
+        let text = String::from_utf8_lossy(&buf);
+        let text = text.trim_end_matches(['\n', '\r']);
+        if matcher.is_match(text) {
+            on_match(path, line, text);
+        }
+    }
+}
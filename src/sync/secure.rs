@@ -0,0 +1,438 @@
+//! Operations confined to an open directory, see [`SecureDir`]
+//!
+//! A path is resolved again on every use, so a directory of it swapped for
+//! a link between a check and the operation leads elsewhere. A
+//! [`SecureDir`] holds the directory open instead and names its children
+//! relative to it.
+//!
+//! On Linux every operation goes through the descriptor, with `openat`,
+//! `mkdirat`, `unlinkat` and `renameat`, and links are never followed
+//! below it: an operation cannot leave the directory even while its
+//! components are renamed or replaced concurrently. Elsewhere the children
+//! are joined to the path of the directory and checked not to be links
+//! before use, which keeps out links present beforehand but not those
+//! swapped in concurrently, see [`SecureDir::CONFINED`].
+
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::Result;
+use std::path::{Component, Path, PathBuf};
+
+use crate::checked_name;
+use crate::error::invalid_path;
+use crate::ErrorKind;
+
+use super::{DirectoryInfo, Info};
+
+/// A directory held open, whose children are opened, created, renamed and
+/// removed relative to it without following links, see
+/// [`fdir::sync::secure`](crate::sync::secure)
+///
+/// The names given to its methods are single file names, and the relative
+/// paths of [`SecureDir::open_path`] and [`SecureDir::create_path`] must
+/// stay below the directory like those of [`safe_join`](crate::safe_join).
+///
+/// # Examples
+/// ```
+/// use fdir::*;
+/// use std::io::{Read, Write};
+/// let root = std::env::temp_dir().join("fdir_secure");
+/// let _ = std::fs::remove_dir_all(&root);
+/// std::fs::create_dir_all(root.join("extract")).unwrap();
+/// let dir = DirectoryInfo::open(root.join("extract")).unwrap();
+/// let secure = dir.open_secure().unwrap();
+/// secure.create_path("docs/a.txt").unwrap().write_all(b"a").unwrap();
+/// let mut text = String::new();
+/// secure.open_path("docs/a.txt").unwrap().read_to_string(&mut text).unwrap();
+/// assert_eq!(text, "a");
+/// assert!(secure.create_path("../escaped.txt").is_err());
+/// assert_eq!(secure.list().unwrap(), ["docs"]);
+///
+/// # #[cfg(unix)]
+/// # {
+/// // a link planted inside is not followed out of the directory
+/// std::os::unix::fs::symlink(&root, root.join("extract/out")).unwrap();
+/// assert!(secure.create_path("out/escaped.txt").is_err());
+/// assert!(!root.join("escaped.txt").exists());
+/// # }
+/// secure.remove_child("docs").unwrap();
+/// assert!(!root.join("extract/docs").exists());
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SecureDir {
+    path: PathBuf,
+    handle: sys::Handle,
+}
+
+impl DirectoryInfo {
+    /// Hold the directory open for operations confined to it, see
+    /// [`SecureDir`]
+    pub fn open_secure(&self) -> Result<SecureDir> {
+        self.validate()?;
+        Ok(SecureDir {
+            path: self.to_path_buf(),
+            handle: sys::open_root(self.as_path())?,
+        })
+    }
+}
+
+impl SecureDir {
+    /// Whether the operations go through the open directory, which holds
+    /// even against concurrent renames. Only Linux does so far.
+    pub const CONFINED: bool = sys::CONFINED;
+
+    /// The path the directory had when it was opened, it may since have
+    /// been moved
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Open the file `name` for reading, failing on a link
+    pub fn open_child(&self, name: impl AsRef<OsStr>) -> Result<File> {
+        let name = checked_name(name.as_ref())?;
+        sys::open_file(&self.handle, &self.path, name, false)
+    }
+
+    /// Create the file `name` for writing, failing if anything exists there
+    pub fn create_child(&self, name: impl AsRef<OsStr>) -> Result<File> {
+        let name = checked_name(name.as_ref())?;
+        sys::open_file(&self.handle, &self.path, name, true)
+    }
+
+    /// Hold the directory `name` open in turn, failing on a link
+    pub fn open_dir(&self, name: impl AsRef<OsStr>) -> Result<SecureDir> {
+        let name = checked_name(name.as_ref())?;
+        Ok(SecureDir {
+            handle: sys::open_dir(&self.handle, &self.path, name, false)?,
+            path: self.path.join(name),
+        })
+    }
+
+    /// Create the directory `name` and hold it open
+    pub fn create_dir(&self, name: impl AsRef<OsStr>) -> Result<SecureDir> {
+        let name = checked_name(name.as_ref())?;
+        Ok(SecureDir {
+            handle: sys::open_dir(&self.handle, &self.path, name, true)?,
+            path: self.path.join(name),
+        })
+    }
+
+    /// Remove `name`, a directory with its content. A link is removed, not
+    /// what it leads to.
+    pub fn remove_child(&self, name: impl AsRef<OsStr>) -> Result<()> {
+        let name = checked_name(name.as_ref())?;
+        match sys::remove(&self.handle, &self.path, name) {
+            Err(e) if ErrorKind::of(&e) == ErrorKind::DestinationNotEmpty => {
+                let dir = self.open_dir(name)?;
+                for child in dir.list()? {
+                    dir.remove_child(child)?;
+                }
+                sys::remove(&self.handle, &self.path, name)
+            }
+            result => result,
+        }
+    }
+
+    /// Rename `from` to `to` inside the directory, replacing a file at `to`
+    pub fn rename_child(&self, from: impl AsRef<OsStr>, to: impl AsRef<OsStr>) -> Result<()> {
+        let (from, to) = (checked_name(from.as_ref())?, checked_name(to.as_ref())?);
+        sys::rename(&self.handle, &self.path, from, to)
+    }
+
+    /// The names of the entries, in no particular order
+    pub fn list(&self) -> Result<Vec<OsString>> {
+        sys::list(&self.handle, &self.path)
+    }
+
+    /// Open the file at `relative` for reading, each directory on the way
+    /// is opened in turn without following links
+    pub fn open_path(&self, relative: impl AsRef<Path>) -> Result<File> {
+        let (parents, name) = split(relative.as_ref())?;
+        match self.descend(&parents, false)? {
+            Some(dir) => dir.open_child(name),
+            None => self.open_child(name),
+        }
+    }
+
+    /// Create the file at `relative` for writing, with the missing
+    /// directories on the way. Fails if the file exists.
+    pub fn create_path(&self, relative: impl AsRef<Path>) -> Result<File> {
+        let (parents, name) = split(relative.as_ref())?;
+        match self.descend(&parents, true)? {
+            Some(dir) => dir.create_child(name),
+            None => self.create_child(name),
+        }
+    }
+
+    /// The directory below at `names`, `None` for the directory itself
+    fn descend(&self, names: &[&OsStr], create: bool) -> Result<Option<SecureDir>> {
+        let mut current: Option<SecureDir> = None;
+        for name in names {
+            let parent = current.as_ref().unwrap_or(self);
+            let dir = match parent.open_dir(name) {
+                Err(e) if create && ErrorKind::of(&e) == ErrorKind::NotFound => {
+                    parent.create_dir(name)
+                }
+                result => result,
+            }?;
+            current = Some(dir);
+        }
+        Ok(current)
+    }
+}
+
+/// The directories and the file name of `relative`, refused when it does
+/// not stay below the directory it is relative to
+fn split(relative: &Path) -> Result<(Vec<&OsStr>, &OsStr)> {
+    let mut names = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(name) => names.push(name),
+            Component::CurDir => {}
+            _ => {
+                return Err(invalid_path(
+                    relative,
+                    "is not a relative path staying below",
+                ))
+            }
+        }
+    }
+    let name = names
+        .pop()
+        .ok_or_else(|| invalid_path(relative, "has no file name"))?;
+    Ok((names, name))
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64",
+        target_arch = "loongarch64"
+    )
+))]
+mod sys {
+    use std::ffi::{c_char, c_int, c_uint, CString, OsStr, OsString};
+    use std::fs::File;
+    use std::io::{Error, Result};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    use crate::error::exists_as;
+
+    pub(super) const CONFINED: bool = true;
+
+    pub(super) type Handle = OwnedFd;
+
+    const O_RDONLY: c_int = 0;
+    const O_WRONLY: c_int = 0o1;
+    const O_CREAT: c_int = 0o100;
+    const O_EXCL: c_int = 0o200;
+    const O_CLOEXEC: c_int = 0o2000000;
+    #[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+    const O_DIRECTORY: c_int = 0o40000;
+    #[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+    const O_NOFOLLOW: c_int = 0o100000;
+    #[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+    const O_DIRECTORY: c_int = 0o200000;
+    #[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+    const O_NOFOLLOW: c_int = 0o400000;
+    const AT_REMOVEDIR: c_int = 0x200;
+    const EISDIR: i32 = 21;
+    const ELOOP: i32 = 40;
+
+    extern "C" {
+        fn open(path: *const c_char, flags: c_int, ...) -> c_int;
+        fn openat(dir: c_int, path: *const c_char, flags: c_int, ...) -> c_int;
+        fn mkdirat(dir: c_int, path: *const c_char, mode: c_uint) -> c_int;
+        fn unlinkat(dir: c_int, path: *const c_char, flags: c_int) -> c_int;
+        fn renameat(
+            old_dir: c_int,
+            old_path: *const c_char,
+            new_dir: c_int,
+            new_path: *const c_char,
+        ) -> c_int;
+    }
+
+    fn c_name(name: &OsStr) -> Result<CString> {
+        Ok(CString::new(name.as_bytes())?)
+    }
+
+    /// `fd` as owned, the error of the call when it is negative
+    fn owned(fd: c_int, path: &Path) -> Result<OwnedFd> {
+        if fd < 0 {
+            let error = Error::last_os_error();
+            if error.raw_os_error() == Some(ELOOP) {
+                return Err(exists_as(path, "link, which is not followed"));
+            }
+            return Err(error);
+        }
+        // SAFETY: a descriptor just opened and owned by nobody else
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    fn check(result: c_int) -> Result<()> {
+        if result != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn open_root(path: &Path) -> Result<Handle> {
+        let c_path = c_name(path.as_os_str())?;
+        // SAFETY: `c_path` is nul terminated and outlives the call
+        let fd = unsafe { open(c_path.as_ptr(), O_RDONLY | O_DIRECTORY | O_CLOEXEC) };
+        owned(fd, path)
+    }
+
+    pub(super) fn open_file(dir: &Handle, path: &Path, name: &OsStr, create: bool) -> Result<File> {
+        let c_name = c_name(name)?;
+        let flags = if create {
+            O_WRONLY | O_CREAT | O_EXCL
+        } else {
+            O_RDONLY
+        };
+        // SAFETY: `c_name` is nul terminated and the mode is the variadic
+        // argument `O_CREAT` reads
+        let fd = unsafe {
+            openat(
+                dir.as_raw_fd(),
+                c_name.as_ptr(),
+                flags | O_NOFOLLOW | O_CLOEXEC,
+                0o666 as c_uint,
+            )
+        };
+        Ok(File::from(owned(fd, &path.join(name))?))
+    }
+
+    pub(super) fn open_dir(
+        dir: &Handle,
+        path: &Path,
+        name: &OsStr,
+        create: bool,
+    ) -> Result<Handle> {
+        let c_name = c_name(name)?;
+        if create {
+            // SAFETY: `c_name` is nul terminated and outlives the call
+            check(unsafe { mkdirat(dir.as_raw_fd(), c_name.as_ptr(), 0o777) })?;
+        }
+        // SAFETY: as above
+        let fd = unsafe {
+            openat(
+                dir.as_raw_fd(),
+                c_name.as_ptr(),
+                O_RDONLY | O_DIRECTORY | O_NOFOLLOW | O_CLOEXEC,
+            )
+        };
+        owned(fd, &path.join(name))
+    }
+
+    pub(super) fn remove(dir: &Handle, _: &Path, name: &OsStr) -> Result<()> {
+        let c_name = c_name(name)?;
+        // SAFETY: `c_name` is nul terminated and outlives both calls
+        match check(unsafe { unlinkat(dir.as_raw_fd(), c_name.as_ptr(), 0) }) {
+            Err(e) if e.raw_os_error() == Some(EISDIR) => {
+                check(unsafe { unlinkat(dir.as_raw_fd(), c_name.as_ptr(), AT_REMOVEDIR) })
+            }
+            result => result,
+        }
+    }
+
+    pub(super) fn rename(dir: &Handle, _: &Path, from: &OsStr, to: &OsStr) -> Result<()> {
+        let (from, to) = (c_name(from)?, c_name(to)?);
+        let dir = dir.as_raw_fd();
+        // SAFETY: both names are nul terminated and outlive the call
+        check(unsafe { renameat(dir, from.as_ptr(), dir, to.as_ptr()) })
+    }
+
+    /// Read through `/proc/self/fd`, whose entries lead to the directory
+    /// the descriptor holds wherever it was moved
+    pub(super) fn list(dir: &Handle, _: &Path) -> Result<Vec<OsString>> {
+        let path = format!("/proc/self/fd/{}", dir.as_raw_fd());
+        std::fs::read_dir(path)?
+            .map(|entry| Ok(entry?.file_name()))
+            .collect()
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64",
+        target_arch = "loongarch64"
+    )
+)))]
+mod sys {
+    use std::ffi::{OsStr, OsString};
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{ErrorKind, Result};
+    use std::path::Path;
+
+    use crate::error::exists_as;
+
+    pub(super) const CONFINED: bool = false;
+
+    pub(super) type Handle = ();
+
+    /// `path` if it is not a link, when it exists
+    fn no_link(path: &Path) -> Result<&Path> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                Err(exists_as(path, "link, which is not followed"))
+            }
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(path),
+        }
+    }
+
+    pub(super) fn open_root(path: &Path) -> Result<Handle> {
+        fs::read_dir(path).map(|_| ())
+    }
+
+    pub(super) fn open_file(_: &Handle, path: &Path, name: &OsStr, create: bool) -> Result<File> {
+        let path = path.join(name);
+        no_link(&path)?;
+        if create {
+            OpenOptions::new().write(true).create_new(true).open(path)
+        } else {
+            File::open(path)
+        }
+    }
+
+    pub(super) fn open_dir(_: &Handle, path: &Path, name: &OsStr, create: bool) -> Result<Handle> {
+        let path = path.join(name);
+        if create {
+            fs::create_dir(&path)?;
+        }
+        open_root(no_link(&path)?)
+    }
+
+    pub(super) fn remove(_: &Handle, path: &Path, name: &OsStr) -> Result<()> {
+        let path = path.join(name);
+        if fs::symlink_metadata(&path)?.is_dir() {
+            fs::remove_dir(path)
+        } else {
+            crate::sync::remove_file_any(&path)
+        }
+    }
+
+    pub(super) fn rename(_: &Handle, path: &Path, from: &OsStr, to: &OsStr) -> Result<()> {
+        // a link is renamed itself
+        fs::rename(path.join(from), path.join(to))
+    }
+
+    pub(super) fn list(_: &Handle, path: &Path) -> Result<Vec<OsString>> {
+        fs::read_dir(path)?
+            .map(|entry| Ok(entry?.file_name()))
+            .collect()
+    }
+}
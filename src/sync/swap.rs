@@ -0,0 +1,232 @@
+//! Exchanging and replacing whole directories by renames only
+//!
+//! On Linux both [`DirectoryInfo::swap_with`] and
+//! [`DirectoryInfo::replace_with`] exchange the directories in a single
+//! `renameat2(RENAME_EXCHANGE)` call: at every instant each path names
+//! either the old or the new content, even after a crash. This needs glibc
+//! 2.28 or later and a filesystem supporting it, such as ext4, XFS, Btrfs
+//! or tmpfs but not NFS or overlayfs before Linux 6.6.
+//!
+//! Elsewhere, or when the filesystem refuses the exchange, `swap_with`
+//! fails with `ErrorKind::Unsupported` while `replace_with` renames the
+//! current directory aside then the new one into place. The path is then
+//! missing between the two renames, and a failure of the second rename is
+//! rolled back. On Windows a directory cannot be renamed while a file
+//! inside it is open.
+//!
+//! Nothing is ever copied: directories on different filesystems are
+//! refused with `ErrorKind::Unsupported`, use
+//! [`DirectoryInfo::move_new_with`] for those.
+
+use std::fs::rename;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use crate::error::{
+    already_exist, cross_device, into_own_subtree, invalid_path, not_restored, replaced_leftover,
+};
+use crate::{fix_path, is_ancestor_of, is_same_file, FileId, StagingMode};
+
+use super::{Action, DirectoryInfo, Info};
+
+impl DirectoryInfo {
+    /// Exchange the two directories atomically, each value then follows its
+    /// content to the path of the other. Only supported on Linux, see
+    /// [`fdir::sync::swap`](crate::sync::swap).
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_swap_with");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// FileInfo::create(root.join("live/v1")).unwrap();
+    /// FileInfo::create(root.join("next/v2")).unwrap();
+    /// let mut live = DirectoryInfo::open(root.join("live")).unwrap();
+    /// let mut next = DirectoryInfo::open(root.join("next")).unwrap();
+    /// match live.swap_with(&mut next) {
+    ///     Ok(()) => {
+    ///         assert!(root.join("live/v2").exists());
+    ///         assert!(root.join("next/v1").exists());
+    ///         assert_eq!(live.as_path(), root.join("next"));
+    ///     }
+    ///     Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::Unsupported),
+    /// }
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn swap_with(&mut self, other: &mut DirectoryInfo) -> Result<()> {
+        self.validate()?;
+        other.validate()?;
+        if is_same_file(self.as_path(), other.as_path()) {
+            return Ok(());
+        }
+        for (path, to) in [(&*self, &*other), (&*other, &*self)] {
+            if is_ancestor_of(path.as_path(), to.as_path()) {
+                return Err(into_own_subtree(path.as_path(), to.as_path()));
+            }
+        }
+        check_device(self.as_path(), other.as_path())?;
+        exchange(self.as_path(), other.as_path())?;
+        std::mem::swap(self, other);
+        Ok(())
+    }
+
+    /// Put the content of `new` at the path of the directory, its previous
+    /// content is renamed to `backup` or removed. `backup` must not exist,
+    /// its parent must. `self` keeps its path, which now names the new
+    /// content. See [`fdir::sync::swap`](crate::sync::swap) for the
+    /// atomicity on each platform.
+    ///
+    /// When the new directory cannot be renamed into place the previous
+    /// content is renamed back, the error then names where it is left if
+    /// even that fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// let root = std::env::temp_dir().join("fdir_replace_with");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// FileInfo::create(root.join("live/v1")).unwrap();
+    /// FileInfo::create(root.join("build/v2")).unwrap();
+    /// let mut live = DirectoryInfo::open(root.join("live")).unwrap();
+    /// let build = DirectoryInfo::open(root.join("build")).unwrap();
+    /// live.replace_with(build, Some(root.join("previous"))).unwrap();
+    /// assert!(root.join("live/v2").exists());
+    /// assert!(root.join("previous/v1").exists());
+    /// assert!(!root.join("build").exists());
+    ///
+    /// // the new directory cannot follow once its parent is renamed aside,
+    /// // the rename is rolled back
+    /// FileInfo::create(root.join("live/next/v3")).unwrap();
+    /// let next = DirectoryInfo::open(root.join("live/next")).unwrap();
+    /// assert!(live.replace_with(next, None).is_err());
+    /// assert!(root.join("live/v2").exists());
+    /// assert!(root.join("live/next/v3").exists());
+    /// assert_eq!(DirectoryInfo::open(&root).unwrap().children().unwrap().len(), 2);
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn replace_with(&mut self, new: DirectoryInfo, backup: Option<PathBuf>) -> Result<()> {
+        self.validate()?;
+        new.validate()?;
+        if is_same_file(self.as_path(), new.as_path()) {
+            return Ok(());
+        }
+        check_device(new.as_path(), self.as_path())?;
+        let backup = match backup {
+            Some(backup) => {
+                let backup = fix_path(backup)?;
+                if backup.try_exists()? {
+                    return Err(already_exist(&backup));
+                }
+                let Some(parent) = backup.parent() else {
+                    return Err(invalid_path(&backup, "has no parent directory"));
+                };
+                check_device(self.as_path(), parent)?;
+                Some(backup)
+            }
+            None => None,
+        };
+        match exchange(new.as_path(), self.as_path()) {
+            // the previous content is now at the path of `new`
+            Ok(()) => return dispose(self.as_path(), new.as_path(), backup.as_deref()),
+            Err(e) if e.kind() != ErrorKind::Unsupported => return Err(e),
+            Err(_) => {}
+        }
+        let backed_up = backup.is_some();
+        let aside = match backup {
+            Some(backup) => backup,
+            None => StagingMode::HiddenTemp
+                .temp_path(self.as_path())?
+                .unwrap_or_default(),
+        };
+        rename(self.as_path(), &aside)?;
+        if let Err(e) = rename(new.as_path(), self.as_path()) {
+            return match rename(&aside, self.as_path()) {
+                Ok(()) => Err(e),
+                Err(e) => Err(not_restored(self.as_path(), &aside, e)),
+            };
+        }
+        if backed_up {
+            return Ok(());
+        }
+        dispose(self.as_path(), &aside, None)
+    }
+}
+
+/// Move the previous content of `path`, now at `aside`, to `backup` or
+/// remove it
+fn dispose(path: &Path, aside: &Path, backup: Option<&Path>) -> Result<()> {
+    let result = match backup {
+        Some(backup) => rename(aside, backup),
+        None => unsafe { DirectoryInfo::open_uncheck(aside) }.delete(),
+    };
+    result.map_err(|e| replaced_leftover(path, aside, e))
+}
+
+fn check_device(path: &Path, to: &Path) -> Result<()> {
+    if FileId::of(path)?.device() != FileId::of(to)?.device() {
+        return Err(cross_device(path, to));
+    }
+    Ok(())
+}
+
+/// Exchange `a` and `b` in one `renameat2` call, `ErrorKind::Unsupported`
+/// when the kernel or the filesystem cannot
+#[cfg(target_os = "linux")]
+fn exchange(a: &Path, b: &Path) -> Result<()> {
+    use std::ffi::{c_char, c_int, c_uint, CString};
+    use std::os::unix::ffi::OsStrExt;
+
+    const AT_FDCWD: c_int = -100;
+    const RENAME_EXCHANGE: c_uint = 2;
+    const EINVAL: i32 = 22;
+
+    extern "C" {
+        fn renameat2(
+            old_dir: c_int,
+            old_path: *const c_char,
+            new_dir: c_int,
+            new_path: *const c_char,
+            flags: c_uint,
+        ) -> c_int;
+    }
+
+    let (old, new) = (
+        CString::new(a.as_os_str().as_bytes())?,
+        CString::new(b.as_os_str().as_bytes())?,
+    );
+    // SAFETY: both paths are nul terminated and outlive the call
+    if unsafe {
+        renameat2(
+            AT_FDCWD,
+            old.as_ptr(),
+            AT_FDCWD,
+            new.as_ptr(),
+            RENAME_EXCHANGE,
+        )
+    } == 0
+    {
+        return Ok(());
+    }
+    let error = Error::last_os_error();
+    // ENOSYS and EOPNOTSUPP already have the kind, EINVAL is returned by
+    // filesystems without the flag and for a directory inside the other
+    if error.raw_os_error() == Some(EINVAL) {
+        return Err(unsupported(a));
+    }
+    Err(error)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn exchange(a: &Path, _: &Path) -> Result<()> {
+    Err(unsupported(a))
+}
+
+fn unsupported(path: &Path) -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        format!(
+            "The directory '{}' cannot be exchanged atomically on this platform or filesystem",
+            path.display()
+        ),
+    )
+}
@@ -0,0 +1,123 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result, Write};
+
+use super::file::write_atomic;
+use super::{FileInfo, Info};
+
+/// Number of leading bytes inspected by the text heuristic
+const PROBE_LEN: u64 = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bom {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Bom {
+    pub fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            Bom::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Bom::Utf16Le => &[0xFF, 0xFE],
+            Bom::Utf16Be => &[0xFE, 0xFF],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl FileInfo {
+    /// Guess whether the file is text: the first 8 KiB must contain no NUL
+    /// byte and be valid UTF-8 (a sequence cut off by the probe limit is accepted)
+    pub fn is_probably_text(&self) -> Result<bool> {
+        let probe = self.read_prefix(PROBE_LEN)?;
+        if probe.contains(&0) {
+            return Ok(false);
+        }
+        Ok(match std::str::from_utf8(&probe) {
+            Ok(_) => true,
+            Err(e) => e.error_len().is_none(),
+        })
+    }
+
+    pub fn detect_bom(&self) -> Result<Option<Bom>> {
+        let probe = self.read_prefix(3)?;
+        Ok([Bom::Utf8, Bom::Utf16Le, Bom::Utf16Be]
+            .into_iter()
+            .find(|bom| probe.starts_with(bom.as_bytes())))
+    }
+
+    /// Rewrite every line ending to `ending` and return the number of lines changed
+    ///
+    /// The file is replaced atomically and is left untouched when nothing
+    /// needs converting. Files that do not look like text are refused with
+    /// `ErrorKind::InvalidData`.
+    pub fn convert_line_endings(&self, ending: LineEnding) -> Result<u64> {
+        let changed = self.line_endings_to_convert(ending)?;
+        if changed > 0 {
+            write_atomic(self.as_path(), |writer| {
+                for_each_line(self, |line| {
+                    let (content, _) = split_line_ending(line);
+                    writer.write_all(content)?;
+                    if content.len() < line.len() {
+                        writer.write_all(match ending {
+                            LineEnding::Lf => b"\n",
+                            LineEnding::CrLf => b"\r\n",
+                        })?;
+                    }
+                    Ok(())
+                })
+            })?;
+        }
+        Ok(changed)
+    }
+
+    /// Dry run of [`FileInfo::convert_line_endings`], count the lines that would change
+    pub fn line_endings_to_convert(&self, ending: LineEnding) -> Result<u64> {
+        if !self.is_probably_text()? {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("The file '{}' is not a text file", self),
+            ));
+        }
+        let mut changed = 0;
+        for_each_line(self, |line| {
+            match (split_line_ending(line).1, ending) {
+                (Some(LineEnding::CrLf), LineEnding::Lf)
+                | (Some(LineEnding::Lf), LineEnding::CrLf) => changed += 1,
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(changed)
+    }
+}
+
+fn for_each_line<F>(file: &FileInfo, mut f: F) -> Result<()>
+where
+    F: FnMut(&[u8]) -> Result<()>,
+{
+    let mut reader = BufReader::new(File::open(file.as_path())?);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        f(&line)?;
+    }
+}
+
+fn split_line_ending(line: &[u8]) -> (&[u8], Option<LineEnding>) {
+    if let Some(content) = line.strip_suffix(b"\r\n") {
+        (content, Some(LineEnding::CrLf))
+    } else if let Some(content) = line.strip_suffix(b"\n") {
+        (content, Some(LineEnding::Lf))
+    } else {
+        (line, None)
+    }
+}
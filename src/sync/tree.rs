@@ -0,0 +1,267 @@
+use std::ffi::OsString;
+use std::fmt::{self, Write};
+use std::fs::{self, Metadata};
+use std::io::{Error, Result};
+use std::path::Path;
+
+use crate::convert::human_size;
+use crate::{is_hidden, WalkOptions};
+
+use super::visit::{VisitAction, Visitor};
+use super::walk::WalkEntry;
+use super::{DirectoryInfo, FileInfo, Info};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeSort {
+    /// Keep the order returned by the operating system
+    Unsorted,
+    Name,
+    /// Directories before files, each group sorted by name
+    DirectoriesFirst,
+}
+
+#[derive(Debug, Clone)]
+pub struct TreeOptions {
+    /// `None` means unlimited, `Some(0)` prints only the root
+    pub max_depth: Option<usize>,
+    pub show_size: bool,
+    pub show_hidden: bool,
+    pub sort: TreeSort,
+    /// Use `|--` style connectors instead of box-drawing characters
+    pub ascii: bool,
+}
+
+impl Default for TreeOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            show_size: false,
+            show_hidden: false,
+            sort: TreeSort::Name,
+            ascii: false,
+        }
+    }
+}
+
+struct Connectors {
+    branch: &'static str,
+    last: &'static str,
+    pipe: &'static str,
+    blank: &'static str,
+}
+
+const UNICODE: Connectors = Connectors {
+    branch: "├── ",
+    last: "└── ",
+    pipe: "│   ",
+    blank: "    ",
+};
+
+const ASCII: Connectors = Connectors {
+    branch: "|-- ",
+    last: "`-- ",
+    pipe: "|   ",
+    blank: "    ",
+};
+
+impl DirectoryInfo {
+    /// Render the directory like the `tree` command
+    ///
+    /// Subdirectories that cannot be read are annotated inline with
+    /// `[error: ...]` instead of aborting the whole rendering.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// use fdir::tree::TreeSort;
+    /// let root = std::env::temp_dir().join("fdir_tree");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for name in ["b/c/d.txt", "b/a.txt", "z.txt", ".hidden/e.txt"] {
+    ///     FileInfo::create(root.join(name)).unwrap();
+    /// }
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// let options = TreeOptions { ascii: true, ..Default::default() };
+    /// let tree = dir.tree(&options).unwrap();
+    /// let expected = "|-- b\n|   |-- a.txt\n|   `-- c\n|       `-- d.txt\n`-- z.txt\n";
+    /// assert_eq!(tree, format!("{}\n{}", dir, expected));
+    ///
+    /// let options = TreeOptions {
+    ///     max_depth: Some(1),
+    ///     show_hidden: true,
+    ///     sort: TreeSort::DirectoriesFirst,
+    ///     ..Default::default()
+    /// };
+    /// let tree = dir.tree(&options).unwrap();
+    /// assert_eq!(tree, format!("{}\n├── .hidden\n├── b\n└── z.txt\n", dir));
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn tree(&self, options: &TreeOptions) -> Result<String> {
+        let mut buf = String::new();
+        self.write_tree(&mut buf, options)?;
+        Ok(buf)
+    }
+
+    /// Same as [`DirectoryInfo::tree`], but writes into `w` instead of building a `String`
+    pub fn write_tree<W: Write>(&self, w: &mut W, options: &TreeOptions) -> Result<()> {
+        fs::read_dir(self.as_path())?;
+        let mut visitor = TreeVisitor {
+            options,
+            open: vec![Node::new(OsString::new(), true)],
+        };
+        if options.max_depth != Some(0) {
+            let walk = WalkOptions {
+                max_depth: options.max_depth,
+                ..Default::default()
+            };
+            // every error is annotated, the visit itself cannot fail
+            let _ = self.visit(&walk, &mut visitor);
+        }
+        let root = visitor.open.pop().expect("the root is never left");
+        writeln!(w, "{}", self).map_err(fmt_error)?;
+        let connectors = if options.ascii { &ASCII } else { &UNICODE };
+        let mut prefix = String::new();
+        write_entries(w, root.children, &mut prefix, options, connectors).map_err(fmt_error)
+    }
+}
+
+/// An entry of the rendered tree
+struct Node {
+    name: OsString,
+    is_dir: bool,
+    /// Length of the entry when it is not a directory and sizes are shown
+    size: Option<u64>,
+    children: Vec<Node>,
+    /// Why the content of the directory cannot be listed
+    error: Option<Error>,
+}
+
+impl Node {
+    fn new(name: OsString, is_dir: bool) -> Self {
+        Self {
+            name,
+            is_dir,
+            size: None,
+            children: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+/// Builds the [`Node`]s of [`DirectoryInfo::write_tree`]
+struct TreeVisitor<'a> {
+    options: &'a TreeOptions,
+    /// The directories entered, the root first
+    open: Vec<Node>,
+}
+
+impl TreeVisitor<'_> {
+    /// The name of `path` unless it is hidden and left out
+    fn shown(&self, path: &Path) -> Option<OsString> {
+        let name = path.file_name().unwrap_or_default();
+        (self.options.show_hidden || !is_hidden(name)).then(|| name.to_os_string())
+    }
+
+    fn push(&mut self, path: &Path, metadata: impl FnOnce() -> Result<Metadata>) {
+        let Some(name) = self.shown(path) else {
+            return;
+        };
+        let mut node = Node::new(name, false);
+        if self.options.show_size {
+            node.size = Some(metadata().map_or(0, |data| data.len()));
+        }
+        self.parent().children.push(node);
+    }
+
+    fn parent(&mut self) -> &mut Node {
+        self.open.last_mut().expect("the root is never left")
+    }
+}
+
+impl Visitor for TreeVisitor<'_> {
+    fn enter_dir(&mut self, dir: &DirectoryInfo) -> VisitAction {
+        match self.shown(dir.as_path()) {
+            Some(name) => {
+                self.open.push(Node::new(name, true));
+                VisitAction::Descend
+            }
+            None => VisitAction::Skip,
+        }
+    }
+
+    fn visit_file(&mut self, file: &FileInfo) -> Result<()> {
+        self.push(file.as_path(), || fs::symlink_metadata(file.as_path()));
+        Ok(())
+    }
+
+    fn leave_dir(&mut self, _: &DirectoryInfo) {
+        let node = self.open.pop().expect("the root is never left");
+        self.parent().children.push(node);
+    }
+
+    // links are not followed, so linked directories are printed as leaves
+    // and cycles are impossible
+    fn visit_other(&mut self, entry: &WalkEntry) -> Result<()> {
+        self.push(entry.path(), || entry.metadata());
+        Ok(())
+    }
+
+    // the listing of a directory fails before any of its entries
+    fn visit_error(&mut self, error: Error) -> Result<()> {
+        let is_root = self.open.len() == 1;
+        let dir = self.parent();
+        if !is_root && dir.children.is_empty() && dir.error.is_none() {
+            dir.error = Some(error);
+        }
+        Ok(())
+    }
+}
+
+fn write_entries<W: Write>(
+    w: &mut W,
+    mut entries: Vec<Node>,
+    prefix: &mut String,
+    options: &TreeOptions,
+    connectors: &Connectors,
+) -> fmt::Result {
+    match options.sort {
+        TreeSort::Unsorted => (),
+        TreeSort::Name => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+        TreeSort::DirectoriesFirst => {
+            entries.sort_by(|a, b| (!a.is_dir, &a.name).cmp(&(!b.is_dir, &b.name)))
+        }
+    }
+    let count = entries.len();
+    for (i, entry) in entries.into_iter().enumerate() {
+        let is_last = i + 1 == count;
+        let connector = if is_last {
+            connectors.last
+        } else {
+            connectors.branch
+        };
+        write!(w, "{}{}{}", prefix, connector, entry.name.to_string_lossy())?;
+        if let Some(size) = entry.size {
+            write!(w, " [{}]", human_size(size))?;
+        }
+        if let Some(e) = entry.error {
+            writeln!(w, " [error: {}]", e)?;
+            continue;
+        }
+        writeln!(w)?;
+        if entry.children.is_empty() {
+            continue;
+        }
+        let len = prefix.len();
+        prefix.push_str(if is_last {
+            connectors.blank
+        } else {
+            connectors.pipe
+        });
+        write_entries(w, entry.children, prefix, options, connectors)?;
+        prefix.truncate(len);
+    }
+    Ok(())
+}
+
+fn fmt_error(_: fmt::Error) -> Error {
+    Error::other("Failed to write the directory tree")
+}
@@ -0,0 +1,156 @@
+//! The traversal of [`Walk`] driving a [`Visitor`], for consumers that keep
+//! their own state across a tree
+//!
+//! [`Walk`]: super::Walk
+
+use std::io::{Error, Result};
+
+use crate::{TraversalOrder, WalkOptions};
+
+use super::walk::WalkEntry;
+use super::{Action, DirectoryInfo, FileInfo};
+
+/// What [`DirectoryInfo::visit`] does with a directory, see
+/// [`Visitor::enter_dir`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitAction {
+    /// Visit its content, then call [`Visitor::leave_dir`]
+    Descend,
+    /// Leave its content out, `leave_dir` is not called
+    Skip,
+    /// End the traversal, nothing else is called
+    Stop,
+}
+
+/// Receives the entries of [`DirectoryInfo::visit`]
+///
+/// Only [`Visitor::visit_file`] must be implemented. The other methods
+/// descend into every directory, ignore links and special files and stop at
+/// the first error.
+pub trait Visitor {
+    /// Called for each directory below the visited one, before its content
+    fn enter_dir(&mut self, dir: &DirectoryInfo) -> VisitAction {
+        let _ = dir;
+        VisitAction::Descend
+    }
+    /// An error stops the traversal, [`DirectoryInfo::visit`] returns it
+    fn visit_file(&mut self, file: &FileInfo) -> Result<()>;
+    /// Called after the content of a directory entered with
+    /// `VisitAction::Descend`. A directory the walk does not descend into,
+    /// below `max_depth` or on another filesystem, is left at once.
+    fn leave_dir(&mut self, dir: &DirectoryInfo) {
+        let _ = dir;
+    }
+    /// Links that are not followed, dangling ones included, and entries
+    /// that are neither a file nor a directory
+    fn visit_other(&mut self, entry: &WalkEntry) -> Result<()> {
+        let _ = entry;
+        Ok(())
+    }
+    /// An entry or a directory that could not be read. Returning `Ok`
+    /// skips it and goes on.
+    fn visit_error(&mut self, error: Error) -> Result<()> {
+        Err(error)
+    }
+    /// Called when `WalkOptions::max_entries` cut the traversal short
+    fn truncated(&mut self) {}
+}
+
+impl DirectoryInfo {
+    /// Traverse the tree below the directory according to `options`,
+    /// depth first whatever `options.order`, and hand every entry to
+    /// `visitor`. This is the traversal of [`DirectoryInfo::walk_with`],
+    /// with the directories entered and left in order.
+    ///
+    /// # Examples
+    /// ```
+    /// use fdir::*;
+    /// use fdir::visit::{VisitAction, Visitor};
+    /// use std::collections::BTreeMap;
+    /// use std::path::PathBuf;
+    ///
+    /// /// Number of files per directory, without the `.git` directories
+    /// #[derive(Default)]
+    /// struct Index {
+    ///     open: Vec<(PathBuf, usize)>,
+    ///     counts: BTreeMap<PathBuf, usize>,
+    /// }
+    ///
+    /// impl Visitor for Index {
+    ///     fn enter_dir(&mut self, dir: &DirectoryInfo) -> VisitAction {
+    ///         if dir.file_name() == Some(".git".as_ref()) {
+    ///             return VisitAction::Skip;
+    ///         }
+    ///         self.open.push((dir.to_path_buf(), 0));
+    ///         VisitAction::Descend
+    ///     }
+    ///     fn visit_file(&mut self, _: &FileInfo) -> std::io::Result<()> {
+    ///         if let Some((_, count)) = self.open.last_mut() {
+    ///             *count += 1;
+    ///         }
+    ///         Ok(())
+    ///     }
+    ///     fn leave_dir(&mut self, _: &DirectoryInfo) {
+    ///         let (dir, count) = self.open.pop().unwrap();
+    ///         self.counts.insert(dir, count);
+    ///     }
+    /// }
+    ///
+    /// let root = std::env::temp_dir().join("fdir_visit");
+    /// let _ = std::fs::remove_dir_all(&root);
+    /// for name in ["a/1", "a/2", "a/b/3", ".git/HEAD", "4"] {
+    ///     FileInfo::create(root.join(name)).unwrap();
+    /// }
+    /// let mut index = Index::default();
+    /// let dir = DirectoryInfo::open(&root).unwrap();
+    /// dir.visit(&WalkOptions::default(), &mut index).unwrap();
+    /// let counts: Vec<_> = index.counts.values().collect();
+    /// assert_eq!(counts, [&2, &1]);
+    /// assert!(!index.counts.contains_key(&root.join(".git")));
+    /// # std::fs::remove_dir_all(root).unwrap();
+    /// ```
+    pub fn visit(&self, options: &WalkOptions, visitor: &mut impl Visitor) -> Result<()> {
+        let options = WalkOptions {
+            order: TraversalOrder::Dfs,
+            ..options.clone()
+        };
+        let mut walk = self.walk_with(&options);
+        // the entered directories with their depth
+        let mut open: Vec<(DirectoryInfo, usize)> = Vec::new();
+        while let Some(entry) = walk.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    visitor.visit_error(e)?;
+                    continue;
+                }
+            };
+            let depth = entry.depth();
+            // the depths grow along the stack
+            let kept = open.partition_point(|(_, open)| *open < depth);
+            for (dir, _) in open.drain(kept..).rev() {
+                visitor.leave_dir(&dir);
+            }
+            let file_type = entry.file_type();
+            if file_type.is_dir() {
+                let dir = unsafe { DirectoryInfo::open_uncheck(entry.into_path()) };
+                match visitor.enter_dir(&dir) {
+                    VisitAction::Descend => open.push((dir, depth)),
+                    VisitAction::Skip => walk.skip_current_dir(),
+                    VisitAction::Stop => return Ok(()),
+                }
+            } else if file_type.is_file() {
+                visitor.visit_file(&unsafe { FileInfo::open_uncheck(entry.into_path()) })?;
+            } else {
+                visitor.visit_other(&entry)?;
+            }
+        }
+        while let Some((dir, _)) = open.pop() {
+            visitor.leave_dir(&dir);
+        }
+        if walk.is_truncated() {
+            visitor.truncated();
+        }
+        Ok(())
+    }
+}
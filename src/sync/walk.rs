@@ -0,0 +1,453 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, FileType, Metadata, ReadDir};
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "ignore")]
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+#[cfg(feature = "ignore")]
+use ignore::Match;
+use std::sync::Arc;
+
+use crate::error::link_cycle;
+use crate::{CyclePolicy, FileId, FsId, ReadOrder, TraversalOrder, WalkOptions};
+
+use super::{DirectoryInfo, Info};
+
+/// An iterator over every entry below a directory
+///
+/// Entries are produced while the directories are being read, so the
+/// whole tree is never collected in memory. A directory is always yielded
+/// before its content. Symlinks and junctions are yielded but only followed
+/// with `WalkOptions::follow_links`, a link leading back to a directory
+/// containing it is then handled by `WalkOptions::on_cycle`. Errors are
+/// yielded in place so callers can decide whether to skip them or abort.
+///
+/// # Examples
+/// ```
+/// use fdir::{Action, CyclePolicy, DirectoryInfo, WalkOptions};
+/// let dir = std::env::temp_dir().join("fdir_walk_cycle");
+/// let _ = std::fs::remove_dir_all(&dir);
+/// std::fs::create_dir_all(dir.join("a")).unwrap();
+/// # #[cfg(unix)]
+/// std::os::unix::fs::symlink(dir.join("a"), dir.join("a/link")).unwrap();
+/// # #[cfg(windows)]
+/// # std::os::windows::fs::symlink_dir(dir.join("a"), dir.join("a/link")).unwrap();
+/// let a = DirectoryInfo::open(dir.join("a")).unwrap();
+/// let mut options = WalkOptions { follow_links: true, ..Default::default() };
+/// let error = a.walk_with(&options).find_map(Result::err).unwrap();
+/// let cycle = fdir::LinkCycle::find(&error).unwrap();
+/// assert_eq!(cycle.chain, [dir.join("a"), dir.join("a/link")]);
+///
+/// options.on_cycle = CyclePolicy::Skip;
+/// let entries: Vec<_> = a.walk_with(&options).collect::<Result<_, _>>().unwrap();
+/// assert!(entries.len() == 1 && entries[0].file_type().is_symlink());
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+pub struct Walk {
+    options: WalkOptions,
+    /// Directories that have been yielded but not opened yet, in depth-first
+    /// order this holds at most the one directory to descend into next
+    queue: VecDeque<(PathBuf, usize, Ignores, Ancestors)>,
+    /// Directories being read, breadth-first order keeps at most one open
+    stack: Vec<(ReadDir, usize, Ignores, Ancestors)>,
+    /// Whether the last yielded entry was a directory
+    yielded_dir: bool,
+    yielded: u64,
+    truncated: bool,
+    /// Filesystem of the root when `same_filesystem` is set
+    root_device: Option<FsId>,
+    /// Directories descended into so far when `visit_once` is set
+    visited: HashSet<FileId>,
+    /// Directories removed before they were read
+    missing: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    path: PathBuf,
+    depth: usize,
+    file_type: FileType,
+    path_is_symlink: bool,
+    /// Inode number from the directory listing
+    #[cfg(unix)]
+    ino: u64,
+}
+
+impl WalkEntry {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+    /// Depth relative to the walked directory, its direct children have depth 1
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    /// The type of the entry, links are only resolved when they are followed
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+    /// Whether the entry itself is a symlink or junction, even if it was followed
+    pub fn path_is_symlink(&self) -> bool {
+        self.path_is_symlink
+    }
+    /// Metadata of the entry, links are only resolved when they are followed
+    pub fn metadata(&self) -> Result<Metadata> {
+        if self.path_is_symlink && !self.file_type.is_symlink() {
+            fs::metadata(&self.path)
+        } else {
+            fs::symlink_metadata(&self.path)
+        }
+    }
+}
+
+impl Walk {
+    pub(crate) fn new(root: &Path, options: &WalkOptions) -> Self {
+        let mut visited = HashSet::new();
+        let mut ancestors = Ancestors::default();
+        if options.follow_links {
+            if let Ok(id) = FileId::of(root) {
+                ancestors = ancestors.push(id, root);
+                visited.insert(id);
+            }
+        }
+        let mut queue = VecDeque::new();
+        queue.push_back((root.to_path_buf(), 0, Ignores::root(), ancestors));
+        let root_device = if options.same_filesystem {
+            FsId::of(root).ok()
+        } else {
+            None
+        };
+        Self {
+            options: options.clone(),
+            queue,
+            stack: Vec::new(),
+            yielded_dir: false,
+            yielded: 0,
+            truncated: false,
+            root_device,
+            visited,
+            missing: Vec::new(),
+        }
+    }
+
+    /// Whether the walk stopped early because `max_entries` was reached
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Directories yielded but removed before they could be read, left out
+    /// as `WalkOptions::missing_source` allows
+    pub fn missing(&self) -> &[PathBuf] {
+        &self.missing
+    }
+
+    /// Do not descend into the directory that was yielded last,
+    /// does nothing if the last entry was not a directory
+    pub fn skip_current_dir(&mut self) {
+        if self.yielded_dir {
+            self.queue.pop_back();
+            self.yielded_dir = false;
+        }
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.yielded_dir = false;
+        loop {
+            let open_next = match self.options.order {
+                TraversalOrder::Bfs => self.stack.is_empty(),
+                TraversalOrder::Dfs => !self.queue.is_empty(),
+            };
+            if open_next {
+                let (dir, depth, ignores, ancestors) = self.queue.pop_front()?;
+                let ignores = ignores.load(&dir, &self.options);
+                match fs::read_dir(&dir) {
+                    Ok(read_dir) => self.stack.push((read_dir, depth, ignores, ancestors)),
+                    Err(e) if depth > 0 && self.options.missing_source.skips(&dir, &e) => {
+                        self.missing.push(dir);
+                        continue;
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            let (read_dir, depth, ignores, ancestors) = self.stack.last_mut()?;
+            let depth = *depth + 1;
+            match read_dir.next() {
+                Some(Ok(entry)) => {
+                    let mut file_type = match entry.file_type() {
+                        Ok(file_type) => file_type,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    let path = entry.path();
+                    #[cfg(unix)]
+                    let ino = std::os::unix::fs::DirEntryExt::ino(&entry);
+                    let link_type = file_type;
+                    let path_is_symlink = file_type.is_symlink();
+                    if path_is_symlink && self.options.follow_links {
+                        // dangling links stay links
+                        if let Ok(metadata) = fs::metadata(&path) {
+                            file_type = metadata.file_type();
+                        }
+                    }
+                    if ignores.is_ignored(&path, file_type.is_dir()) {
+                        continue;
+                    }
+                    let mut descend = file_type.is_dir()
+                        && self.options.max_depth.is_none_or(|max| depth < max)
+                        && self.root_device.is_none_or(|root| {
+                            FsId::of(&path).ok() == Some(root)
+                        });
+                    let mut ancestors = ancestors.clone();
+                    // only followed links can lead back up the tree
+                    if descend && self.options.follow_links {
+                        if let Ok(id) = FileId::of(&path) {
+                            if let Some(mut chain) = ancestors.chain_from(id) {
+                                if self.options.on_cycle == CyclePolicy::Fail {
+                                    chain.push(path);
+                                    return Some(Err(link_cycle(chain)));
+                                }
+                                file_type = link_type;
+                                descend = false;
+                            } else if self.options.visit_once && !self.visited.insert(id) {
+                                descend = false;
+                            } else {
+                                ancestors = ancestors.push(id, &path);
+                            }
+                        }
+                    }
+                    if self
+                        .options
+                        .max_entries
+                        .is_some_and(|max| self.yielded >= max)
+                    {
+                        self.truncated = true;
+                        self.stack.clear();
+                        self.queue.clear();
+                        return None;
+                    }
+                    self.yielded += 1;
+                    if descend {
+                        self.queue
+                            .push_back((path.clone(), depth, ignores.clone(), ancestors));
+                        self.yielded_dir = true;
+                    }
+                    return Some(Ok(WalkEntry {
+                        path,
+                        depth,
+                        file_type,
+                        path_is_symlink,
+                        #[cfg(unix)]
+                        ino,
+                    }));
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// A walk yielding the files of each directory in `ReadOrder`, after the
+/// other entries of the directory. Files are held back until the walk
+/// leaves their directory, or a run of it in depth-first order.
+pub(crate) struct Ordered {
+    walk: Walk,
+    order: ReadOrder,
+    /// Files of the directory being read
+    held: Vec<WalkEntry>,
+    /// Sorted files to yield before reading on
+    ready: VecDeque<WalkEntry>,
+}
+
+impl Ordered {
+    pub(crate) fn new(walk: Walk, order: ReadOrder) -> Self {
+        Self {
+            walk,
+            order: order.effective(),
+            held: Vec::new(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// See `Walk::missing`
+    pub(crate) fn missing(&self) -> &[PathBuf] {
+        self.walk.missing()
+    }
+
+    /// See `Walk::skip_current_dir`, directories are yielded as soon as
+    /// they are read so this still applies to the last one
+    pub(crate) fn skip_current_dir(&mut self) {
+        self.walk.skip_current_dir();
+    }
+
+    fn release(&mut self) {
+        let mut held = std::mem::take(&mut self.held);
+        match self.order {
+            #[cfg(unix)]
+            ReadOrder::ByInode => held.sort_unstable_by_key(|entry| entry.ino),
+            ReadOrder::ByName => {
+                held.sort_unstable_by(|a, b| a.path.file_name().cmp(&b.path.file_name()))
+            }
+            // `ByInode` only remains on Unix
+            _ => {}
+        }
+        self.ready.extend(held);
+    }
+}
+
+impl Iterator for Ordered {
+    type Item = Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.ready.pop_front() {
+                return Some(Ok(entry));
+            }
+            let entry = match self.walk.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => return Some(Err(e)),
+                None if self.held.is_empty() => return None,
+                None => {
+                    self.release();
+                    continue;
+                }
+            };
+            if self.order == ReadOrder::DirectoryOrder || !entry.file_type.is_file() {
+                return Some(Ok(entry));
+            }
+            if self
+                .held
+                .first()
+                .is_some_and(|held| held.path.parent() != entry.path.parent())
+            {
+                self.release();
+            }
+            self.held.push(entry);
+        }
+    }
+}
+
+impl DirectoryInfo {
+    /// Iterate breadth-first over every entry below the directory, see [`Walk`]
+    pub fn walk(&self) -> Walk {
+        self.walk_with(&WalkOptions::default())
+    }
+
+    /// Iterate over every entry below the directory, see [`Walk`]
+    pub fn walk_with(&self, options: &WalkOptions) -> Walk {
+        Walk::new(self.as_path(), options)
+    }
+}
+
+/// The ids of a directory and all its ancestors up to the walked root, only
+/// tracked when links are followed
+#[derive(Clone, Default)]
+pub(crate) struct Ancestors(Option<Arc<Ancestor>>);
+
+struct Ancestor {
+    parent: Option<Arc<Ancestor>>,
+    id: FileId,
+    path: PathBuf,
+}
+
+impl Ancestors {
+    pub(crate) fn push(self, id: FileId, path: &Path) -> Self {
+        Ancestors(Some(Arc::new(Ancestor {
+            parent: self.0,
+            id,
+            path: path.to_path_buf(),
+        })))
+    }
+
+    /// The paths from the ancestor with `id` down to the innermost one,
+    /// `None` when no ancestor has `id`
+    pub(crate) fn chain_from(&self, id: FileId) -> Option<Vec<PathBuf>> {
+        let ancestors = || std::iter::successors(self.0.as_deref(), |a| a.parent.as_deref());
+        let len = ancestors().position(|ancestor| ancestor.id == id)? + 1;
+        let mut chain: Vec<_> = ancestors()
+            .take(len)
+            .map(|ancestor| ancestor.path.clone())
+            .collect();
+        chain.reverse();
+        Some(chain)
+    }
+}
+
+/// The ignore files of a directory and all its ancestors up to the walked root
+#[cfg(feature = "ignore")]
+#[derive(Clone)]
+struct Ignores(Option<Arc<IgnoreChain>>);
+
+#[cfg(feature = "ignore")]
+struct IgnoreChain {
+    parent: Option<Arc<IgnoreChain>>,
+    matcher: Gitignore,
+}
+
+#[cfg(feature = "ignore")]
+impl Ignores {
+    fn root() -> Self {
+        Ignores(None)
+    }
+
+    /// Extend the chain with the ignore files found in `dir`
+    fn load(self, dir: &Path, options: &WalkOptions) -> Self {
+        if !options.ignore_files {
+            return self;
+        }
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        // patterns added later win, so `.ignore` overrides `.gitignore`
+        for name in [".gitignore", ".ignore"] {
+            let path = dir.join(name);
+            if path.is_file() {
+                found |= builder.add(path).is_none();
+            }
+        }
+        match builder.build() {
+            Ok(matcher) if found => Ignores(Some(Arc::new(IgnoreChain {
+                parent: self.0,
+                matcher,
+            }))),
+            _ => self,
+        }
+    }
+
+    /// The closest ignore file with a matching pattern decides
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut current = self.0.as_deref();
+        while let Some(chain) = current {
+            match chain.matcher.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => current = chain.parent.as_deref(),
+            }
+        }
+        false
+    }
+}
+
+#[cfg(not(feature = "ignore"))]
+#[derive(Clone)]
+struct Ignores;
+
+#[cfg(not(feature = "ignore"))]
+impl Ignores {
+    fn root() -> Self {
+        Ignores
+    }
+    fn load(self, _: &Path, _: &WalkOptions) -> Self {
+        self
+    }
+    fn is_ignored(&self, _: &Path, _: bool) -> bool {
+        false
+    }
+}
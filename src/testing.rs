@@ -0,0 +1,382 @@
+//! Helpers for tests of code working on directory trees, enabled by the
+//! `testing` feature
+//!
+//! # Examples
+//! ```
+//! use fdir::testing::{assert_tree_eq, TreeBuilder};
+//! use fdir::Action;
+//! let root = std::env::temp_dir().join("fdir_testing");
+//! let _ = std::fs::remove_dir_all(&root);
+//! let source = TreeBuilder::new()
+//!     .file("a/b.txt", "hi")
+//!     .dir("empty")
+//!     .build(root.join("source"))
+//!     .unwrap();
+//! source
+//!     .copy_new_with(root.join("copy"), &Default::default())
+//!     .unwrap();
+//! let copy = fdir::DirectoryInfo::open(root.join("copy")).unwrap();
+//! assert_tree_eq(&source, &copy);
+//! # std::fs::remove_dir_all(root).unwrap();
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "unicode-normalization")]
+use crate::convert::{normalize_path_names, Form};
+use crate::{safe_join, Action, DirectoryInfo, Info};
+
+/// Text files up to this length are compared line by line
+const TEXT_DIFF_LEN: usize = 64 * 1024;
+/// Lines of context around each change of a line diff
+const CONTEXT: usize = 2;
+
+/// Declares a tree of files and directories to create in one go
+#[derive(Debug, Clone, Default)]
+pub struct TreeBuilder {
+    /// Relative path and content, `None` for a directory
+    entries: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+impl TreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A file at `path`, relative to the root, its parents are created
+    pub fn file(mut self, path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> Self {
+        let content = content.as_ref().to_vec();
+        self.entries
+            .push((path.as_ref().to_path_buf(), Some(content)));
+        self
+    }
+
+    /// A directory at `path`, relative to the root, its parents are created
+    pub fn dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.entries.push((path.as_ref().to_path_buf(), None));
+        self
+    }
+
+    /// Create the entries below `root`, which is created if needed. A path
+    /// leading outside of `root` fails with `ErrorKind::InvalidInput`.
+    pub fn build(self, root: impl AsRef<Path>) -> Result<DirectoryInfo> {
+        fs::create_dir_all(root.as_ref())?;
+        let root = DirectoryInfo::open(root)?;
+        for (relative, content) in self.entries {
+            let path = safe_join(root.as_path(), relative)?;
+            match content {
+                Some(content) => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(path, content)?;
+                }
+                None => fs::create_dir_all(path)?,
+            }
+        }
+        Ok(root)
+    }
+}
+
+/// An entry of a tree being compared
+#[derive(PartialEq)]
+enum Node {
+    Dir,
+    File(Vec<u8>),
+    Link(PathBuf),
+}
+
+impl Node {
+    fn kind(&self) -> &'static str {
+        match self {
+            Node::Dir => "directory",
+            Node::File(_) => "file",
+            Node::Link(_) => "link",
+        }
+    }
+}
+
+/// How [`tree_diff_with`] compares two trees
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "unicode-normalization"), derive(Default))]
+pub struct DiffOptions {
+    /// Compare names once normalised to this form, so a tree whose names
+    /// macOS returns decomposed equals its composed copy. Paths in the diff
+    /// are then normalised too. [`Form::compare_default`] by default.
+    #[cfg(feature = "unicode-normalization")]
+    pub normalize_names: Option<Form>,
+}
+
+#[cfg(feature = "unicode-normalization")]
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            normalize_names: Form::compare_default(),
+        }
+    }
+}
+
+impl DiffOptions {
+    /// What `relative` is compared by, see `normalize_names`
+    fn key(&self, relative: &Path) -> PathBuf {
+        #[cfg(feature = "unicode-normalization")]
+        if let Some(form) = self.normalize_names {
+            return normalize_path_names(relative, form);
+        }
+        relative.to_path_buf()
+    }
+}
+
+/// Every entry below `dir` by relative path, links are not followed
+fn read_tree(dir: &DirectoryInfo, options: &DiffOptions) -> Result<BTreeMap<PathBuf, Node>> {
+    let mut nodes = BTreeMap::new();
+    for entry in dir.walk() {
+        let entry = entry?;
+        let relative = entry
+            .path()
+            .strip_prefix(dir.as_path())
+            .unwrap_or(entry.path());
+        let file_type = entry.file_type();
+        let node = if file_type.is_symlink() {
+            Node::Link(fs::read_link(entry.path())?)
+        } else if file_type.is_dir() {
+            Node::Dir
+        } else {
+            Node::File(fs::read(entry.path())?)
+        };
+        nodes.insert(options.key(relative), node);
+    }
+    Ok(nodes)
+}
+
+/// Describe how the tree of `right` differs from the tree of `left`, empty
+/// when they hold the same entries with the same content. Metadata is not
+/// compared.
+///
+/// Each difference starts with a line `- path` for an entry missing from
+/// `right`, `+ path` for an extra one and `~ path` for one that differs,
+/// followed by a line diff for small text files or the first differing
+/// byte offset otherwise.
+pub fn tree_diff(left: &DirectoryInfo, right: &DirectoryInfo) -> Result<String> {
+    tree_diff_with(left, right, &DiffOptions::default())
+}
+
+/// [`tree_diff`] comparing as `options` say
+///
+/// # Examples
+/// ```
+/// # #[cfg(feature = "unicode-normalization")] {
+/// use fdir::convert::Form;
+/// use fdir::testing::{tree_diff_with, DiffOptions, TreeBuilder};
+/// let root = std::env::temp_dir().join("fdir_tree_diff_normalized");
+/// let _ = std::fs::remove_dir_all(&root);
+/// let composed = TreeBuilder::new()
+///     .file("caf\u{e9}/menu.txt", "tea")
+///     .build(root.join("composed"))
+///     .unwrap();
+/// let decomposed = TreeBuilder::new()
+///     .file("cafe\u{301}/menu.txt", "tea")
+///     .build(root.join("decomposed"))
+///     .unwrap();
+/// let exact = DiffOptions { normalize_names: None };
+/// let diff = tree_diff_with(&composed, &decomposed, &exact).unwrap();
+/// assert!(diff.contains("+ cafe\u{301}/menu.txt (file)"));
+/// let normalized = DiffOptions { normalize_names: Some(Form::Nfc) };
+/// assert_eq!(tree_diff_with(&composed, &decomposed, &normalized).unwrap(), "");
+/// # std::fs::remove_dir_all(root).unwrap();
+/// # }
+/// ```
+pub fn tree_diff_with(
+    left: &DirectoryInfo,
+    right: &DirectoryInfo,
+    options: &DiffOptions,
+) -> Result<String> {
+    let mut left = read_tree(left, options)?;
+    let right = read_tree(right, options)?;
+    let mut diff = String::new();
+    for (path, node) in right {
+        let path_text = path.display();
+        let Some(old) = left.remove(&path) else {
+            writeln!(diff, "+ {} ({})", path_text, node.kind()).unwrap();
+            continue;
+        };
+        match (old, node) {
+            (old, node) if old == node => (),
+            (Node::File(old), Node::File(new)) => {
+                writeln!(diff, "~ {}", path_text).unwrap();
+                diff.push_str(&content_diff(&old, &new));
+            }
+            (Node::Link(old), Node::Link(new)) => writeln!(
+                diff,
+                "~ {}: link to '{}' instead of '{}'",
+                path_text,
+                new.display(),
+                old.display()
+            )
+            .unwrap(),
+            (old, node) => writeln!(
+                diff,
+                "~ {}: {} instead of {}",
+                path_text,
+                node.kind(),
+                old.kind()
+            )
+            .unwrap(),
+        }
+    }
+    // entries of `left` not found in `right`, listed after the others
+    for (path, node) in left {
+        writeln!(diff, "- {} ({})", path.display(), node.kind()).unwrap();
+    }
+    Ok(diff)
+}
+
+/// Panic with a readable diff unless the trees of `left` and `right` hold the
+/// same entries with the same content, see [`tree_diff`]
+///
+/// # Examples
+/// ```
+/// use fdir::testing::{assert_tree_eq, TreeBuilder};
+/// use fdir::Action;
+/// let root = std::env::temp_dir().join("fdir_assert_tree_eq");
+/// let _ = std::fs::remove_dir_all(&root);
+/// let source = TreeBuilder::new()
+///     .file("docs/readme.md", "# fdir")
+///     .file("bin/data", [0u8, 1, 2])
+///     .dir("logs")
+///     .build(root.join("source"))
+///     .unwrap();
+/// source
+///     .copy_new_with(root.join("copy"), &Default::default())
+///     .unwrap();
+/// let copy = fdir::DirectoryInfo::open(root.join("copy")).unwrap();
+/// assert_tree_eq(&source, &copy);
+/// # std::fs::remove_dir_all(root).unwrap();
+/// ```
+///
+/// A tree whose file differs by one line panics
+/// ```should_panic
+/// use fdir::testing::{assert_tree_eq, TreeBuilder};
+/// let root = std::env::temp_dir().join("fdir_assert_tree_ne");
+/// let _ = std::fs::remove_dir_all(&root);
+/// let left = TreeBuilder::new()
+///     .file("notes.txt", "one\ntwo\n")
+///     .build(root.join("left"))
+///     .unwrap();
+/// let right = TreeBuilder::new()
+///     .file("notes.txt", "one\n2\n")
+///     .build(root.join("right"))
+///     .unwrap();
+/// assert_tree_eq(&left, &right);
+/// ```
+#[track_caller]
+pub fn assert_tree_eq(left: &DirectoryInfo, right: &DirectoryInfo) {
+    let diff = match tree_diff(left, right) {
+        Ok(diff) => diff,
+        Err(e) => panic!("could not compare '{}' and '{}': {}", left, right, e),
+    };
+    if !diff.is_empty() {
+        panic!("the trees differ\n--- {}\n+++ {}\n{}", left, right, diff);
+    }
+}
+
+/// Like `FileInfo::is_probably_text`, text is valid UTF-8 without NUL bytes
+fn as_text(content: &[u8]) -> Option<&str> {
+    if content.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(content).ok()
+}
+
+/// A line diff of small text content, the first differing byte otherwise
+fn content_diff(old: &[u8], new: &[u8]) -> String {
+    match (as_text(old), as_text(new)) {
+        (Some(old), Some(new)) if old.len().max(new.len()) <= TEXT_DIFF_LEN => line_diff(old, new),
+        _ => {
+            let offset = old
+                .iter()
+                .zip(new)
+                .position(|(a, b)| a != b)
+                .unwrap_or(old.len().min(new.len()));
+            format!(
+                "  first difference at byte {} ({} bytes instead of {})\n",
+                offset,
+                new.len(),
+                old.len()
+            )
+        }
+    }
+}
+
+/// Unified diff of the lines of `old` and `new`
+fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<_> = old.lines().collect();
+    let new: Vec<_> = new.lines().collect();
+    // longest common subsequence of the suffixes
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    // (sign, line, index in old, index in new)
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', old[i], i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            ops.push(('-', old[i], i, j));
+            i += 1;
+        } else {
+            ops.push(('+', new[j], i, j));
+            j += 1;
+        }
+    }
+    if ops.iter().all(|op| op.0 == ' ') {
+        // only the line terminators differ
+        return "  the lines are equal, their endings differ\n".to_string();
+    }
+    let mut diff = String::new();
+    let mut start = 0;
+    while let Some(first) = ops[start..].iter().position(|op| op.0 != ' ') {
+        let first = start + first;
+        // a hunk ends after more than twice the context of unchanged lines
+        let mut last = first;
+        while let Some(next) = ops[last + 1..].iter().position(|op| op.0 != ' ') {
+            if next > 2 * CONTEXT {
+                break;
+            }
+            last += 1 + next;
+        }
+        let from = first.saturating_sub(CONTEXT);
+        let to = (last + CONTEXT + 1).min(ops.len());
+        let hunk = &ops[from..to];
+        let old_len = hunk.iter().filter(|op| op.0 != '+').count();
+        let new_len = hunk.iter().filter(|op| op.0 != '-').count();
+        writeln!(
+            diff,
+            "  @@ -{},{} +{},{} @@",
+            hunk[0].2 + 1,
+            old_len,
+            hunk[0].3 + 1,
+            new_len
+        )
+        .unwrap();
+        for (sign, line, _, _) in hunk {
+            writeln!(diff, "  {}{}", sign, line).unwrap();
+        }
+        start = to;
+    }
+    diff
+}
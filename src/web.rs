@@ -0,0 +1,89 @@
+use std::ffi::OsStr;
+use std::path::Path;
+
+#[cfg(feature = "infer")]
+use crate::sync::detect::{FileKind, PROBE_LEN};
+
+/// The MIME type of a file guessed from the extension of `file_name`
+pub(crate) fn content_type(file_name: Option<&str>) -> Option<&'static str> {
+    let extension = Path::new(file_name?).extension()?.to_str()?;
+    let mime = match extension.to_ascii_lowercase().as_str() {
+        "txt" | "log" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "wasm" => "application/wasm",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// An `attachment` disposition for `file_name`, see RFC 6266. `filename*`
+/// carries the raw bytes of the name percent-encoded as RFC 5987 asks,
+/// `filename` an ASCII fallback for clients that ignore it.
+pub(crate) fn content_disposition(file_name: &OsStr) -> String {
+    let bytes = file_name.as_encoded_bytes();
+    let fallback: String = String::from_utf8_lossy(bytes)
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let mut encoded = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        match byte {
+            b'a'..=b'z'
+            | b'A'..=b'Z'
+            | b'0'..=b'9'
+            | b'!'
+            | b'#'
+            | b'$'
+            | b'&'
+            | b'+'
+            | b'-'
+            | b'.'
+            | b'^'
+            | b'_'
+            | b'`'
+            | b'|'
+            | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    )
+}
+
+/// The MIME type detected from the first bytes of `content`, see
+/// `FileInfo::detect_type`
+#[cfg(feature = "infer")]
+pub(crate) fn detected_type(content: &[u8]) -> Option<&'static str> {
+    let probe = &content[..content.len().min(PROBE_LEN as usize)];
+    infer::get(probe).map(|kind| FileKind::from(kind).mime_type())
+}
+
+#[cfg(not(feature = "infer"))]
+pub(crate) fn detected_type(_content: &[u8]) -> Option<&'static str> {
+    None
+}